@@ -0,0 +1,567 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::node::Node;
+use crate::toml;
+use crate::{AppError, Result};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Nesting limit guarding against recursive type definitions.
+const MAX_DEPTH: usize = 64;
+
+/// Integer encoding used when the values were serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntEncoding {
+    /// Fixed-size little-endian integers and `u64` lengths (bincode 1.x
+    /// defaults).
+    Fixint,
+    /// Variable-size integers with zigzag signed values (bincode 2.x
+    /// `config::standard()`).
+    Varint,
+}
+
+/// Field type as written in a schema file, e.g. `vec<option<string>>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Unit,
+    Bool,
+    Unsigned(u8),
+    Signed(u8),
+    F32,
+    F64,
+    Char,
+    String,
+    Bytes,
+    Option(Box<Ty>),
+    Vec(Box<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>, usize),
+    Named(String),
+}
+
+#[derive(Debug, Clone)]
+enum TypeDef {
+    Struct(Vec<(String, Ty)>),
+    Enum(Vec<(String, Ty)>),
+}
+
+/// Struct and enum layouts plus the table-to-type mapping, loaded from a
+/// TOML schema file:
+///
+/// ```toml
+/// encoding = "fixint"
+///
+/// [[struct]]
+/// name = "User"
+/// fields = ["name: string", "age: u32", "tags: vec<string>"]
+///
+/// [[enum]]
+/// name = "Role"
+/// variants = ["Guest", "Member: u64"]
+///
+/// [tables]
+/// users = "User"
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    encoding: IntEncoding,
+    types: HashMap<String, TypeDef>,
+    tables: HashMap<String, Ty>,
+}
+
+impl Schema {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| {
+            AppError::ConfigError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let doc = toml::parse(text).map_err(|e| e.to_string())?;
+        let encoding = match toml::get_str(&doc, "encoding") {
+            None | Some("fixint") => IntEncoding::Fixint,
+            Some("varint") => IntEncoding::Varint,
+            Some(other) => return Err(format!("unknown encoding '{}'", other)),
+        };
+
+        let mut types = HashMap::new();
+        for (section, list_key) in [("struct", "fields"), ("enum", "variants")] {
+            let Some(Node::Array(defs)) = toml::get(&doc, section) else {
+                continue;
+            };
+            for def in defs {
+                let name = toml::get_str(def, "name")
+                    .ok_or_else(|| format!("{} without a name", section))?;
+                let mut members = Vec::new();
+                if let Some(Node::Array(items)) = toml::get(def, list_key) {
+                    for item in items {
+                        let Node::Text(spec) = item else {
+                            return Err(format!(
+                                "{}: {} must be strings",
+                                name, list_key
+                            ));
+                        };
+                        members.push(parse_member(spec)?);
+                    }
+                }
+                let def = if section == "struct" {
+                    TypeDef::Struct(members)
+                } else {
+                    TypeDef::Enum(members)
+                };
+                types.insert(name.to_string(), def);
+            }
+        }
+
+        let mut tables = HashMap::new();
+        if let Some(mapping) = toml::get(&doc, "tables") {
+            for (table, ty) in toml::entries(mapping) {
+                let Node::Text(ty) = ty else {
+                    return Err(format!(
+                        "tables.{} must be a type string",
+                        table
+                    ));
+                };
+                tables.insert(table.to_string(), parse_ty(ty)?);
+            }
+        }
+
+        Ok(Self {
+            encoding,
+            types,
+            tables,
+        })
+    }
+
+    /// Decodes a value of `table`, if the schema maps that table to a type.
+    pub fn decode(
+        &self,
+        table: &str,
+        bytes: &[u8],
+    ) -> Option<DecodeResult<Node>> {
+        let ty = self.tables.get(table)?;
+        let mut reader = Reader {
+            schema: self,
+            bytes,
+            pos: 0,
+        };
+        Some(reader.read(ty, 0).and_then(|node| {
+            if reader.pos == bytes.len() {
+                Ok(node)
+            } else {
+                Err(DecodeError::new(format!(
+                    "{} trailing bytes",
+                    bytes.len() - reader.pos
+                )))
+            }
+        }))
+    }
+}
+
+/// Parses `"name: type"`; a bare name is a unit enum variant.
+fn parse_member(spec: &str) -> std::result::Result<(String, Ty), String> {
+    match spec.split_once(':') {
+        Some((name, ty)) => Ok((name.trim().to_string(), parse_ty(ty)?)),
+        None => Ok((spec.trim().to_string(), Ty::Unit)),
+    }
+}
+
+pub fn parse_ty(spec: &str) -> std::result::Result<Ty, String> {
+    let spec = spec.trim();
+    let generic = |prefix: &str| {
+        spec.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix('>'))
+    };
+    if let Some(inner) = generic("option<") {
+        return Ok(Ty::Option(Box::new(parse_ty(inner)?)));
+    }
+    if let Some(inner) = generic("vec<") {
+        return Ok(Ty::Vec(Box::new(parse_ty(inner)?)));
+    }
+    if let Some(inner) = generic("map<") {
+        let parts = split_top_level(inner);
+        let [key, value] = parts.as_slice() else {
+            return Err(format!("map needs two type arguments: {}", spec));
+        };
+        return Ok(Ty::Map(
+            Box::new(parse_ty(key)?),
+            Box::new(parse_ty(value)?),
+        ));
+    }
+    if let Some(inner) = spec.strip_prefix('(').and_then(|s| s.strip_suffix(')'))
+    {
+        if inner.trim().is_empty() {
+            return Ok(Ty::Unit);
+        }
+        let items = split_top_level(inner)
+            .into_iter()
+            .map(parse_ty)
+            .collect::<std::result::Result<_, _>>()?;
+        return Ok(Ty::Tuple(items));
+    }
+    if let Some(inner) = spec.strip_prefix('[').and_then(|s| s.strip_suffix(']'))
+    {
+        let (ty, len) = inner
+            .rsplit_once(';')
+            .ok_or_else(|| format!("array needs a length: {}", spec))?;
+        let len = len
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid array length: {}", spec))?;
+        return Ok(Ty::Array(Box::new(parse_ty(ty)?), len));
+    }
+    Ok(match spec {
+        "unit" => Ty::Unit,
+        "bool" => Ty::Bool,
+        "u8" => Ty::Unsigned(8),
+        "u16" => Ty::Unsigned(16),
+        "u32" => Ty::Unsigned(32),
+        "u64" | "usize" => Ty::Unsigned(64),
+        "u128" => Ty::Unsigned(128),
+        "i8" => Ty::Signed(8),
+        "i16" => Ty::Signed(16),
+        "i32" => Ty::Signed(32),
+        "i64" | "isize" => Ty::Signed(64),
+        "i128" => Ty::Signed(128),
+        "f32" => Ty::F32,
+        "f64" => Ty::F64,
+        "char" => Ty::Char,
+        "string" => Ty::String,
+        "bytes" => Ty::Bytes,
+        name if !name.is_empty()
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+        {
+            Ty::Named(name.to_string())
+        }
+        _ => return Err(format!("invalid type: {}", spec)),
+    })
+}
+
+/// Splits on commas that are not nested inside `<>`, `()` or `[]`.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+struct Reader<'a> {
+    schema: &'a Schema,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::new("unexpected end of input"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn fixed(&mut self, bits: u8) -> DecodeResult<u128> {
+        let mut buf = [0u8; 16];
+        let len = bits as usize / 8;
+        buf[..len].copy_from_slice(self.take(len)?);
+        Ok(u128::from_le_bytes(buf))
+    }
+
+    fn unsigned(&mut self, bits: u8) -> DecodeResult<u128> {
+        if self.schema.encoding == IntEncoding::Fixint || bits == 8 {
+            return self.fixed(bits);
+        }
+        let value = match self.take(1)?[0] {
+            b @ 0..=250 => b as u128,
+            251 => self.fixed(16)?,
+            252 => self.fixed(32)?,
+            253 => self.fixed(64)?,
+            254 => self.fixed(128)?,
+            _ => return Err(DecodeError::new("invalid varint marker")),
+        };
+        if bits < 128 && value >> bits != 0 {
+            return Err(DecodeError::new(format!("varint overflows u{}", bits)));
+        }
+        Ok(value)
+    }
+
+    fn signed(&mut self, bits: u8) -> DecodeResult<i128> {
+        if self.schema.encoding == IntEncoding::Fixint || bits == 8 {
+            let raw = self.fixed(bits)?;
+            let shift = 128 - bits as u32;
+            return Ok(((raw << shift) as i128) >> shift);
+        }
+        let zigzag = self.unsigned(bits)?;
+        Ok((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128))
+    }
+
+    fn length(&mut self) -> DecodeResult<usize> {
+        let len = self.unsigned(64)?;
+        usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= self.bytes.len() - self.pos)
+            .ok_or_else(|| DecodeError::new("length exceeds input"))
+    }
+
+    fn read(&mut self, ty: &Ty, depth: usize) -> DecodeResult<Node> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::new("nesting too deep"));
+        }
+        Ok(match ty {
+            Ty::Unit => Node::Null,
+            Ty::Bool => match self.take(1)?[0] {
+                0 => Node::Bool(false),
+                1 => Node::Bool(true),
+                b => return Err(DecodeError::new(format!("invalid bool {}", b))),
+            },
+            Ty::Unsigned(bits) => Node::Int(self.unsigned(*bits)? as i128),
+            Ty::Signed(bits) => Node::Int(self.signed(*bits)?),
+            Ty::F32 => Node::Float(f32::from_le_bytes(
+                self.take(4)?.try_into().unwrap(),
+            ) as f64),
+            Ty::F64 => {
+                Node::Float(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+            }
+            Ty::Char => {
+                let width = match self.bytes.get(self.pos) {
+                    Some(b) if *b < 0x80 => 1,
+                    Some(b) if *b >> 5 == 0b110 => 2,
+                    Some(b) if *b >> 4 == 0b1110 => 3,
+                    _ => 4,
+                };
+                let s = std::str::from_utf8(self.take(width)?)
+                    .map_err(|_| DecodeError::new("invalid char"))?;
+                Node::Text(s.to_string())
+            }
+            Ty::String => {
+                let len = self.length()?;
+                let s = std::str::from_utf8(self.take(len)?)
+                    .map_err(|_| DecodeError::new("invalid UTF-8 in string"))?;
+                Node::Text(s.to_string())
+            }
+            Ty::Bytes => {
+                let len = self.length()?;
+                Node::Bytes(self.take(len)?.to_vec())
+            }
+            Ty::Option(inner) => match self.take(1)?[0] {
+                0 => Node::Null,
+                1 => self.read(inner, depth + 1)?,
+                b => {
+                    return Err(DecodeError::new(format!(
+                        "invalid option tag {}",
+                        b
+                    )))
+                }
+            },
+            Ty::Vec(inner) => {
+                let len = self.length()?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.read(inner, depth + 1)?);
+                }
+                Node::Array(items)
+            }
+            Ty::Map(key, value) => {
+                let len = self.length()?;
+                let mut entries = Vec::new();
+                for _ in 0..len {
+                    let k = self.read(key, depth + 1)?;
+                    entries.push((k, self.read(value, depth + 1)?));
+                }
+                Node::Map(entries)
+            }
+            Ty::Tuple(items) => Node::Array(
+                items
+                    .iter()
+                    .map(|ty| self.read(ty, depth + 1))
+                    .collect::<DecodeResult<_>>()?,
+            ),
+            Ty::Array(inner, len) => {
+                let mut items = Vec::new();
+                for _ in 0..*len {
+                    items.push(self.read(inner, depth + 1)?);
+                }
+                Node::Array(items)
+            }
+            Ty::Named(name) => match self.schema.types.get(name) {
+                Some(TypeDef::Struct(fields)) => {
+                    let mut entries = Vec::new();
+                    for (field, ty) in fields {
+                        let value = self.read(ty, depth + 1)?;
+                        entries.push((Node::Text(field.clone()), value));
+                    }
+                    Node::Map(entries)
+                }
+                Some(TypeDef::Enum(variants)) => {
+                    let index = self.unsigned(32)? as usize;
+                    let (variant, ty) = variants.get(index).ok_or_else(|| {
+                        DecodeError::new(format!(
+                            "{} has no variant {}",
+                            name, index
+                        ))
+                    })?;
+                    match ty {
+                        Ty::Unit => Node::Text(variant.clone()),
+                        ty => Node::Map(vec![(
+                            Node::Text(variant.clone()),
+                            self.read(ty, depth + 1)?,
+                        )]),
+                    }
+                }
+                None => {
+                    return Err(DecodeError::new(format!(
+                        "unknown type {}",
+                        name
+                    )))
+                }
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+[[struct]]
+name = "User"
+fields = ["name: string", "age: u32", "tags: vec<string>", "role: Role"]
+
+[[enum]]
+name = "Role"
+variants = ["Guest", "Member: u64"]
+
+[tables]
+users = "User"
+deltas = "(i32, option<i64>, [u8; 2])"
+"#;
+
+    fn schema(encoding: &str) -> Schema {
+        Schema::parse(&format!("encoding = \"{}\"\n{}", encoding, SCHEMA))
+            .unwrap()
+    }
+
+    fn text(s: &str) -> Node {
+        Node::Text(s.to_string())
+    }
+
+    fn user() -> Node {
+        Node::Map(vec![
+            (text("name"), text("Ann")),
+            (text("age"), Node::Int(30)),
+            (text("tags"), Node::Array(vec![text("a")])),
+            (
+                text("role"),
+                Node::Map(vec![(text("Member"), Node::Int(7))]),
+            ),
+        ])
+    }
+
+    /// `User { name: "Ann", age: 30, tags: vec!["a"], role: Member(7) }`
+    /// as bincode 1.x writes it.
+    const USER_FIXINT: &[u8] = &[
+        3, 0, 0, 0, 0, 0, 0, 0, b'A', b'n', b'n', // name
+        30, 0, 0, 0, // age
+        1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, b'a', // tags
+        1, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, // role
+    ];
+
+    /// The same user with bincode 2.x `config::standard()`.
+    const USER_VARINT: &[u8] = &[3, b'A', b'n', b'n', 30, 1, 1, b'a', 1, 7];
+
+    #[test]
+    fn decodes_fixint_and_varint() {
+        let decode = |encoding, bytes| {
+            schema(encoding).decode("users", bytes).unwrap().unwrap()
+        };
+        assert_eq!(decode("fixint", USER_FIXINT), user());
+        assert_eq!(decode("varint", USER_VARINT), user());
+        assert!(schema("varint").decode("other", USER_VARINT).is_none());
+    }
+
+    #[test]
+    fn decodes_zigzag_and_wide_varints() {
+        // -300 zigzags to 599, past a single byte.
+        let bytes = [251, 0x57, 0x02, 1, 3, 9, 8];
+        let decoded = schema("varint").decode("deltas", &bytes).unwrap();
+        assert_eq!(
+            decoded.unwrap(),
+            Node::Array(vec![
+                Node::Int(-300),
+                Node::Int(-2),
+                Node::Array(vec![Node::Int(9), Node::Int(8)]),
+            ])
+        );
+        let fixint = [0xd4, 0xfe, 0xff, 0xff, 0, 1, 2];
+        let decoded = schema("fixint").decode("deltas", &fixint).unwrap();
+        assert_eq!(
+            decoded.unwrap(),
+            Node::Array(vec![
+                Node::Int(-300),
+                Node::Null,
+                Node::Array(vec![Node::Int(1), Node::Int(2)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn truncated_and_trailing_input_are_errors() {
+        for (encoding, bytes) in
+            [("fixint", USER_FIXINT), ("varint", USER_VARINT)]
+        {
+            let schema = schema(encoding);
+            for len in 0..bytes.len() {
+                let decoded = schema.decode("users", &bytes[..len]).unwrap();
+                assert!(decoded.is_err(), "{} bytes of {}", len, encoding);
+            }
+            let mut longer = bytes.to_vec();
+            longer.push(0);
+            assert!(schema.decode("users", &longer).unwrap().is_err());
+        }
+        // A length past the end of the input, and an unknown variant.
+        let varint = schema("varint");
+        assert!(varint.decode("users", &[253, 0xff, 0xff]).unwrap().is_err());
+        let bytes = [0, 0, 0, 2];
+        assert!(varint.decode("users", &bytes).unwrap().is_err());
+    }
+
+    #[test]
+    fn rejects_bad_schemas() {
+        assert!(Schema::parse("encoding = \"zigzag\"").is_err());
+        assert!(parse_ty("map<string>").is_err());
+        assert!(parse_ty("[u8; x]").is_err());
+        assert_eq!(
+            parse_ty("vec<(u8, map<string, bytes>)>").unwrap(),
+            Ty::Vec(Box::new(Ty::Tuple(vec![
+                Ty::Unsigned(8),
+                Ty::Map(Box::new(Ty::String), Box::new(Ty::Bytes)),
+            ])))
+        );
+    }
+
+    #[test]
+    fn recursive_types_stop_at_the_depth_limit() {
+        let schema = Schema::parse(
+            "[[struct]]\nname = \"Node\"\nfields = [\"next: option<Node>\"]\n\
+             [tables]\nlist = \"Node\"",
+        )
+        .unwrap();
+        let bytes = vec![1; MAX_DEPTH * 2];
+        assert!(schema.decode("list", &bytes).unwrap().is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{database, Result};
+use crossterm::event::KeyCode;
+use human_repr::HumanCount;
+use ratatui::widgets::ListState;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Extension of the files listed; other files are left out.
+const EXTENSION: &str = "redb";
+
+/// How many directories down `scan` looks.
+const SCAN_DEPTH: usize = 4;
+
+/// A database found by `scan`.
+pub struct Found {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Its number of tables, or why it couldn't be opened to count them.
+    pub tables: Result<usize>,
+}
+
+/// The redb databases in `dir` and the directories below it, whatever their
+/// file names, told apart from other files by their header. Hidden files
+/// and directories and symlinks are skipped. In path order.
+pub fn scan(dir: &Path) -> io::Result<Vec<Found>> {
+    let mut found = Vec::new();
+    scan_dir(dir, SCAN_DEPTH, &mut found)?;
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+fn scan_dir(dir: &Path, depth: usize, found: &mut Vec<Found>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() && depth > 0 {
+            // One unreadable subdirectory doesn't spoil the scan.
+            let _ = scan_dir(&path, depth - 1, found);
+        } else if file_type.is_file() && database::is_redb_file(&path) {
+            found.push(Found {
+                size: entry.metadata()?.len(),
+                tables: database::count_tables(&path),
+                path,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// A directory listing to pick a redb file from: the parent directory,
+/// the subdirectories, then the `*.redb` files, each in name order.
+/// Hidden files and directories are left out.
+pub struct Browser {
+    pub dir: PathBuf,
+    entries: Vec<Entry>,
+    pub list_state: ListState,
+    /// Why the last directory couldn't be read.
+    pub error: Option<String>,
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    /// Size in bytes, `None` for directories.
+    size: Option<u64>,
+}
+
+/// What a key did in the browser.
+pub enum Outcome {
+    Pick(PathBuf),
+    Cancel,
+}
+
+impl Browser {
+    /// Lists `dir`, or the current directory if it can't be read.
+    pub fn new(dir: &Path) -> Self {
+        let mut browser = Self {
+            dir: PathBuf::new(),
+            entries: Vec::new(),
+            list_state: ListState::default(),
+            error: None,
+        };
+        if let Err(e) = browser.enter(dir) {
+            browser.error = Some(format!("{}: {}", dir.display(), e));
+            if let Err(e) = browser.enter(Path::new(".")) {
+                browser.error = Some(format!("Current directory: {}", e));
+            }
+        }
+        browser
+    }
+
+    /// Lists `dir` in place of the directory shown, selecting the first
+    /// entry.
+    fn enter(&mut self, dir: &Path) -> io::Result<()> {
+        let dir = fs::canonicalize(dir)?;
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            // Follows symlinks, so links to directories can be entered.
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(Entry {
+                    name: format!("{}/", name),
+                    path,
+                    size: None,
+                });
+            } else if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                files.push(Entry {
+                    name,
+                    path,
+                    size: Some(metadata.len()),
+                });
+            }
+        }
+        dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        self.entries = dir
+            .parent()
+            .map(|parent| Entry {
+                name: "../".to_string(),
+                path: parent.to_path_buf(),
+                size: None,
+            })
+            .into_iter()
+            .chain(dirs)
+            .chain(files)
+            .collect();
+        self.dir = dir;
+        self.list_state = ListState::default().with_selected(Some(0));
+        self.error = None;
+        Ok(())
+    }
+
+    /// A line per entry, with the size of files.
+    pub fn items(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| match entry.size {
+                Some(size) => {
+                    format!("{}  {}", entry.name, size.human_count_bytes())
+                }
+                None => entry.name.clone(),
+            })
+            .collect()
+    }
+
+    /// What goes below the listing: the keys, or the last error.
+    pub fn hint(&self) -> String {
+        match &self.error {
+            Some(error) => error.clone(),
+            None => "Enter: open  Backspace: parent directory  Esc: cancel"
+                .to_string(),
+        }
+    }
+
+    /// Moves the selection, enters directories and picks files.
+    pub fn handle_key(&mut self, code: KeyCode) -> Option<Outcome> {
+        let selected = self.list_state.selected().unwrap_or(0);
+        let last = self.entries.len().saturating_sub(1);
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Outcome::Cancel),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.list_state.select(Some(selected.saturating_sub(1)));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.list_state.select(Some((selected + 1).min(last)));
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.list_state.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => {
+                self.list_state.select(Some(last))
+            }
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(parent) = self.dir.parent().map(Path::to_path_buf) {
+                    self.open_dir(&parent);
+                }
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                let entry = self.entries.get(selected)?;
+                if entry.size.is_some() {
+                    return Some(Outcome::Pick(entry.path.clone()));
+                }
+                let dir = entry.path.clone();
+                self.open_dir(&dir);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Lists `dir`, selecting the directory just left when going up.
+    fn open_dir(&mut self, dir: &Path) {
+        let left = self.dir.clone();
+        if let Err(e) = self.enter(dir) {
+            self.error = Some(format!("{}: {}", dir.display(), e));
+            return;
+        }
+        if let Some(i) = self.entries.iter().position(|e| e.path == left) {
+            self.list_state.select(Some(i));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use redb::TableStats;
+
+/// A level of a table's B-tree, from the root down.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    pub pages: u64,
+    pub kind: LevelKind,
+    /// Worked out from the average fan-out rather than counted.
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelKind {
+    Root,
+    Branch,
+    Leaf,
+}
+
+impl LevelKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            LevelKind::Root => "root",
+            LevelKind::Branch => "branch",
+            LevelKind::Leaf => "leaf",
+        }
+    }
+}
+
+/// The levels of the tree `stats` describes. redb counts branch and leaf
+/// pages, not the pages of each level, so only the root, the leaves and a
+/// single level of branches between them are exact; more levels of
+/// branches share the branch pages as the fan-out would have them.
+pub fn levels(stats: &TableStats) -> Vec<Level> {
+    let (height, branches, leaves) = (
+        stats.tree_height(),
+        stats.branch_pages(),
+        stats.leaf_pages(),
+    );
+    let level = |pages, kind, estimated| Level {
+        pages,
+        kind,
+        estimated,
+    };
+    if height <= 1 {
+        // An empty table has no pages, and a tree of one level has a leaf
+        // for its root.
+        return match leaves {
+            0 => Vec::new(),
+            _ => vec![level(leaves, LevelKind::Leaf, false)],
+        };
+    }
+    let mut levels = vec![level(1, LevelKind::Root, false)];
+    let middle = height as usize - 2;
+    let below_root = branches.saturating_sub(1);
+    if middle == 1 {
+        levels.push(level(below_root, LevelKind::Branch, false));
+    } else if middle > 1 {
+        let fanout = fanout(stats).unwrap_or(1.0);
+        let shares: Vec<f64> = (1..=middle)
+            .map(|depth| fanout.powi(depth as i32))
+            .collect();
+        let scale = below_root as f64 / shares.iter().sum::<f64>();
+        levels.extend(shares.iter().map(|share| {
+            let pages = ((share * scale).round() as u64).max(1);
+            level(pages, LevelKind::Branch, true)
+        }));
+    }
+    levels.push(level(leaves, LevelKind::Leaf, false));
+    levels
+}
+
+/// Average children of a branch page: every page but the root has a
+/// branch page for a parent.
+pub fn fanout(stats: &TableStats) -> Option<f64> {
+    let branches = stats.branch_pages();
+    (branches > 0)
+        .then(|| (branches - 1 + stats.leaf_pages()) as f64 / branches as f64)
+}
@@ -0,0 +1,204 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::node::Node;
+
+/// Nesting limit guarding against maliciously deep payloads.
+const MAX_DEPTH: usize = 128;
+
+/// Decodes a single CBOR data item (RFC 8949) that spans the whole input.
+pub fn decode(bytes: &[u8]) -> DecodeResult<Node> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let node = reader.item(0)?;
+    if reader.pos != bytes.len() {
+        return Err(DecodeError::new(format!(
+            "{} trailing bytes",
+            bytes.len() - reader.pos
+        )));
+    }
+    Ok(node)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::new("unexpected end of input"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> DecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads the argument of a head byte; `None` means indefinite length.
+    fn argument(&mut self, info: u8) -> DecodeResult<Option<u64>> {
+        let value = match info {
+            0..=23 => info as u64,
+            24 => self.byte()? as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            31 => return Ok(None),
+            _ => {
+                return Err(DecodeError::new(format!(
+                    "reserved additional info {}",
+                    info
+                )))
+            }
+        };
+        Ok(Some(value))
+    }
+
+    fn length(&mut self, info: u8) -> DecodeResult<Option<usize>> {
+        self.argument(info)?
+            .map(|n| {
+                usize::try_from(n)
+                    .ok()
+                    .filter(|n| *n <= self.bytes.len())
+                    .ok_or_else(|| DecodeError::new("length exceeds input"))
+            })
+            .transpose()
+    }
+
+    fn is_break(&self) -> bool {
+        self.bytes.get(self.pos) == Some(&0xff)
+    }
+
+    fn item(&mut self, depth: usize) -> DecodeResult<Node> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::new("nesting too deep"));
+        }
+        let head = self.byte()?;
+        let (major, info) = (head >> 5, head & 0x1f);
+        match major {
+            0 => Ok(Node::Int(self.definite(info)? as i128)),
+            1 => Ok(Node::Int(-1 - self.definite(info)? as i128)),
+            2 | 3 => {
+                let bytes = match self.length(info)? {
+                    Some(len) => self.take(len)?.to_vec(),
+                    None => {
+                        let mut bytes = Vec::new();
+                        while !self.is_break() {
+                            let chunk_head = self.byte()?;
+                            if chunk_head >> 5 != major {
+                                return Err(DecodeError::new(
+                                    "invalid chunk in indefinite string",
+                                ));
+                            }
+                            let len = self
+                                .length(chunk_head & 0x1f)?
+                                .ok_or_else(|| {
+                                    DecodeError::new("nested indefinite string")
+                                })?;
+                            bytes.extend_from_slice(self.take(len)?);
+                        }
+                        self.pos += 1;
+                        bytes
+                    }
+                };
+                if major == 2 {
+                    Ok(Node::Bytes(bytes))
+                } else {
+                    String::from_utf8(bytes).map(Node::Text).map_err(|_| {
+                        DecodeError::new("invalid UTF-8 in text string")
+                    })
+                }
+            }
+            4 => {
+                let mut items = Vec::new();
+                match self.length(info)? {
+                    Some(len) => {
+                        for _ in 0..len {
+                            items.push(self.item(depth + 1)?);
+                        }
+                    }
+                    None => {
+                        while !self.is_break() {
+                            items.push(self.item(depth + 1)?);
+                        }
+                        self.pos += 1;
+                    }
+                }
+                Ok(Node::Array(items))
+            }
+            5 => {
+                let mut entries = Vec::new();
+                match self.length(info)? {
+                    Some(len) => {
+                        for _ in 0..len {
+                            let key = self.item(depth + 1)?;
+                            entries.push((key, self.item(depth + 1)?));
+                        }
+                    }
+                    None => {
+                        while !self.is_break() {
+                            let key = self.item(depth + 1)?;
+                            entries.push((key, self.item(depth + 1)?));
+                        }
+                        self.pos += 1;
+                    }
+                }
+                Ok(Node::Map(entries))
+            }
+            6 => {
+                let tag = self.definite(info)?;
+                Ok(Node::Tagged(tag, Box::new(self.item(depth + 1)?)))
+            }
+            _ => self.simple(info),
+        }
+    }
+
+    fn definite(&mut self, info: u8) -> DecodeResult<u64> {
+        self.argument(info)?
+            .ok_or_else(|| DecodeError::new("unexpected indefinite length"))
+    }
+
+    fn simple(&mut self, info: u8) -> DecodeResult<Node> {
+        match info {
+            20 => Ok(Node::Bool(false)),
+            21 => Ok(Node::Bool(true)),
+            22 => Ok(Node::Null),
+            23 => Ok(Node::Undefined),
+            24 => Ok(Node::Int(self.byte()? as i128)),
+            25 => {
+                let half = u16::from_be_bytes(self.take(2)?.try_into().unwrap());
+                Ok(Node::Float(f16_to_f64(half)))
+            }
+            26 => {
+                let bits = u32::from_be_bytes(self.take(4)?.try_into().unwrap());
+                Ok(Node::Float(f32::from_bits(bits) as f64))
+            }
+            27 => {
+                let bits = u64::from_be_bytes(self.take(8)?.try_into().unwrap());
+                Ok(Node::Float(f64::from_bits(bits)))
+            }
+            31 => Err(DecodeError::new("unexpected break")),
+            0..=19 => Ok(Node::Int(info as i128)),
+            _ => Err(DecodeError::new(format!("reserved simple value {}", info))),
+        }
+    }
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent as i32 - 15),
+    };
+    if half & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
@@ -0,0 +1,11 @@
+use crate::decoder::base64;
+use std::io::{self, Write};
+
+/// Asks the terminal to put `text` on the system clipboard with the OSC 52
+/// escape sequence. Unlike a clipboard library this works over SSH, and in
+/// tmux with `set-clipboard on`; terminals without support ignore it.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
@@ -0,0 +1,168 @@
+use crate::commands::{Encoding, ExportFormat, ExportOptions};
+use crate::decoder::Decoder;
+use clap::ValueEnum;
+use std::path::PathBuf;
+
+/// A command typed at the `:` prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Select the table with this name.
+    Table(String),
+    /// Export the entries of the selected table in view to a file.
+    Export {
+        options: ExportOptions,
+        path: PathBuf,
+    },
+    /// Narrow the selected table down to a key range as typed at `p`; empty
+    /// to clear it.
+    KeyRange(String),
+    /// Jump to a key of the selected table.
+    Goto(String),
+    /// Search the entries of the selected table.
+    Search(String),
+    /// Use `decoder` for the keys, or the values, of the selected table.
+    Decoder {
+        key: bool,
+        decoder: Decoder,
+    },
+    /// Compare the database with the one at this path in the diff view.
+    Diff(PathBuf),
+    /// Show a table, or the selected one, of both databases side by side.
+    Compare(Option<String>),
+    /// Open the database at this path in a new tab, or pick one in a file
+    /// browser.
+    Open(Option<PathBuf>),
+    /// Close the database on screen.
+    Close,
+    /// Copy the selected table into the open database with this number or
+    /// file name, under `name` or its own name.
+    CopyTable {
+        database: String,
+        name: Option<String>,
+    },
+    Help,
+    Quit,
+}
+
+/// Usage and description of each command, for the help and for errors.
+pub const COMMANDS: &[(&str, &str)] = &[
+    (":table NAME", "Select a table"),
+    (
+        ":export json|csv|jsonl|sqlite [key=ENC] [value=ENC] PATH",
+        "Export the entries in view; ENC is decoded, utf8, hex or base64",
+    ),
+    (
+        ":filter prefix PREFIX",
+        "Narrow down to keys starting with PREFIX",
+    ),
+    (
+        ":filter range RANGE",
+        "Narrow down to a key range like 10..20",
+    ),
+    (":filter clear", "Show all keys again"),
+    (":goto KEY", "Jump to a key"),
+    (":search TEXT", "Search keys and values"),
+    (":decoder key|value NAME", "Set the key or value decoder"),
+    (":diff PATH", "Compare with another database"),
+    (
+        ":compare [TABLE]",
+        "Show a table of both databases side by side",
+    ),
+    (
+        ":open [PATH]",
+        "Open another database in a new tab, or browse for one",
+    ),
+    (":close", "Close the database on screen"),
+    (
+        ":copy DATABASE [NAME]",
+        "Copy the selected table into an open database, by number or name",
+    ),
+    (":help", "Show the help"),
+    (":quit", "Quit"),
+];
+
+impl Command {
+    /// Parses a command line, without the leading `:`.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let (first, second) = rest.split_once(' ').unwrap_or((rest, ""));
+        let second = second.trim();
+        let usage = |name: &str| {
+            let usages: Vec<_> = COMMANDS
+                .iter()
+                .filter(|(usage, _)| usage[1..].split(' ').next() == Some(name))
+                .map(|(usage, _)| *usage)
+                .collect();
+            format!("Usage: {}", usages.join(", "))
+        };
+        let command = match name {
+            "table" | "t" if !rest.is_empty() => Command::Table(rest.to_string()),
+            "export" if !second.is_empty() => {
+                let mut options = ExportOptions {
+                    format: ExportFormat::from_str(first, true)
+                        .map_err(|_| usage("export"))?,
+                    key: Encoding::default(),
+                    value: Encoding::default(),
+                };
+                let mut path = second;
+                while let Some((option, rest)) = path.split_once(' ') {
+                    let (encoding, name) = match option.split_once('=') {
+                        Some(("key", name)) => (&mut options.key, name),
+                        Some(("value", name)) => (&mut options.value, name),
+                        _ => break,
+                    };
+                    *encoding = Encoding::from_str(name, true)
+                        .map_err(|_| format!("Unknown encoding '{}'", name))?;
+                    path = rest.trim_start();
+                }
+                Command::Export {
+                    options,
+                    path: PathBuf::from(path),
+                }
+            }
+            "filter" => match (first, second) {
+                ("prefix", prefix) if !prefix.is_empty() => {
+                    Command::KeyRange(format!("{}*", prefix))
+                }
+                ("range", range) if !range.is_empty() => {
+                    Command::KeyRange(range.to_string())
+                }
+                ("clear", "") => Command::KeyRange(String::new()),
+                _ => return Err(usage("filter")),
+            },
+            "goto" if !rest.is_empty() => Command::Goto(rest.to_string()),
+            "search" if !rest.is_empty() => Command::Search(rest.to_string()),
+            "decoder" => {
+                let key = match first {
+                    "key" => true,
+                    "value" => false,
+                    _ => return Err(usage("decoder")),
+                };
+                let decoder = Decoder::from_name(second)
+                    .ok_or_else(|| format!("Unknown decoder '{}'", second))?;
+                Command::Decoder { key, decoder }
+            }
+            "diff" if !rest.is_empty() => Command::Diff(PathBuf::from(rest)),
+            "open" | "o" => {
+                Command::Open((!rest.is_empty()).then(|| PathBuf::from(rest)))
+            }
+            "close" => Command::Close,
+            "copy" if !first.is_empty() => Command::CopyTable {
+                database: first.to_string(),
+                name: (!second.is_empty()).then(|| second.to_string()),
+            },
+            "compare" => {
+                Command::Compare((!rest.is_empty()).then(|| rest.to_string()))
+            }
+            "help" | "h" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            "table" | "t" | "export" | "goto" | "search" | "diff" | "copy" => {
+                return Err(usage(name))
+            }
+            _ => return Err(format!("Unknown command '{}'", name)),
+        };
+        Ok(command)
+    }
+}
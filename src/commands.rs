@@ -0,0 +1,1665 @@
+use crate::database::{
+    self, CopyOptions, KeyRange, OnConflict, RawEntry, RawKey, RawType, RawValue,
+    TableTypes, WriteDurability,
+};
+use crate::decoder::{self, DecodeContext, Decoder, Schemas};
+#[cfg(feature = "lmdb")]
+use crate::lmdb;
+use crate::node::Node;
+use crate::registry::Registry;
+use crate::search::Query;
+use crate::{csv, diff, layout, sqlite};
+use crate::{json, AppError, Result};
+use log::info;
+use redb::{
+    Database, ReadableTableMetadata, Table, TableHandle, WriteTransaction,
+};
+use std::cell::Cell;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::ops::Bound;
+use std::path::Path;
+use std::rc::Rc;
+
+/// How a key was given on the command line.
+pub enum KeyArg<'a> {
+    /// Text in the display form of the table's key type.
+    Text(&'a str),
+    /// Hex-encoded key bytes.
+    Hex(&'a str),
+}
+
+/// What to print for a found value.
+pub enum Output {
+    /// The value bytes exactly as stored.
+    Raw,
+    /// The value rendered by the given decoder, or by the one the schema
+    /// sidecar declares for the table.
+    Decoded(Option<Decoder>),
+}
+
+/// Opens the database, reporting the progress of a repair on stderr when it
+/// is a terminal.
+fn open_database(db_path: &Path) -> Result<Database> {
+    let show_progress = io::stderr().is_terminal();
+    let repairing = Rc::new(Cell::new(false));
+    let db = database::open(db_path, {
+        let repairing = repairing.clone();
+        move |progress| {
+            repairing.set(true);
+            if show_progress {
+                eprint!("\rRepairing database: {:.0}%", progress * 100.0);
+            }
+            true
+        }
+    });
+    if show_progress && repairing.get() {
+        eprintln!("\rRepairing database: done");
+    }
+    db
+}
+
+/// Prints the value stored under `key` in `table` to stdout.
+pub fn get(
+    db_path: &Path,
+    mut schemas: Schemas,
+    table: &str,
+    key: KeyArg,
+    output: Output,
+) -> Result<()> {
+    info!("Looking up {:?} in table {}", db_path, table);
+    let db = open_database(db_path)?;
+    let txn = db.begin_read()?;
+    let (raw_table, types) = database::open_raw_table(&txn, table)?;
+    let key_bytes = match key {
+        KeyArg::Text(text) => decoder::encode_native(&types.key.name, text),
+        KeyArg::Hex(text) => decoder::parse_hex(text),
+    }
+    .map_err(AppError::InvalidKey)?;
+
+    let Some(value) = raw_table.get(key_bytes.as_slice())? else {
+        let key = match key {
+            KeyArg::Text(text) | KeyArg::Hex(text) => text,
+        };
+        return Err(AppError::KeyNotFound(key.to_string()));
+    };
+    let value = value.value();
+
+    let mut stdout = io::stdout().lock();
+    match output {
+        Output::Raw => stdout.write_all(value)?,
+        Output::Decoded(decoder) => {
+            let registry = Registry::load(&Registry::sidecar_path(db_path))?;
+            let (_, sidecar_decoder) =
+                table_decoders(&registry, &mut schemas, table);
+            let decoder = decoder.unwrap_or(sidecar_decoder);
+            let ctx = schemas.context(table);
+            writeln!(
+                stdout,
+                "{}",
+                decoder.decode(&ctx, &types.value.name, value)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Key and value decoders the schema sidecar declares for `table`, falling
+/// back to `Decoder::Auto`. Also registers the table's protobuf message
+/// unless one was given on the command line.
+fn table_decoders(
+    registry: &Registry,
+    schemas: &mut Schemas,
+    table: &str,
+) -> (Decoder, Decoder) {
+    let schema = registry.tables.get(table);
+    if let Some(message) = schema.and_then(|s| s.message.clone()) {
+        schemas
+            .proto_messages
+            .entry(table.to_string())
+            .or_insert(message);
+    }
+    (
+        schema.and_then(|s| s.key).unwrap_or(Decoder::Auto),
+        schema.and_then(|s| s.value).unwrap_or(Decoder::Auto),
+    )
+}
+
+/// File format of `export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// `{"table": [{"key": ..., "value": ...}, ...], ...}`
+    Json,
+    /// `table,key,value` rows with a header line
+    Csv,
+    /// One `{"table", "key", "value", "key_encoding", "value_encoding",
+    /// "key_type", "value_type"}` object per line
+    Jsonl,
+    /// SQL statements creating a SQLite table per table, for `sqlite3`
+    Sqlite,
+}
+
+/// How `export` writes keys or values, and `import` reads them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    /// Rendered by the table's decoder, as in the value pane; imports take
+    /// the display form of the stored type
+    #[default]
+    Decoded,
+    /// The bytes as UTF-8 text, invalid sequences replaced
+    Utf8,
+    /// The bytes as lowercase hex
+    Hex,
+    /// The bytes as standard base64
+    Base64,
+}
+
+impl Encoding {
+    /// Name of the encoding on the command line and in JSON Lines records.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Decoded => "decoded",
+            Encoding::Utf8 => "utf8",
+            Encoding::Hex => "hex",
+            Encoding::Base64 => "base64",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        <Self as clap::ValueEnum>::value_variants()
+            .iter()
+            .copied()
+            .find(|encoding| encoding.name() == name)
+    }
+
+    fn encode(
+        self,
+        decoder: Decoder,
+        ctx: &DecodeContext,
+        type_name: &str,
+        bytes: &[u8],
+    ) -> Node {
+        match self {
+            Encoding::Decoded => decoder.decode_node(ctx, type_name, bytes),
+            Encoding::Utf8 => {
+                Node::Text(String::from_utf8_lossy(bytes).into_owned())
+            }
+            Encoding::Hex => Node::Text(decoder::hex(bytes)),
+            Encoding::Base64 => Node::Text(decoder::base64(bytes)),
+        }
+    }
+
+    /// Turns an imported field back into the bytes stored as `type_name`.
+    fn parse(
+        self,
+        type_name: &str,
+        node: &Node,
+    ) -> std::result::Result<Vec<u8>, String> {
+        let text = match (self, node) {
+            (Encoding::Decoded, node) => return encode_node(type_name, node),
+            (_, Node::Text(text)) => text,
+            (_, other) => {
+                return Err(format!(
+                    "expected a {} string, got {}",
+                    self.name(),
+                    json::to_string(other)
+                ))
+            }
+        };
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Hex => decoder::parse_hex(text),
+            _ => decoder::parse_base64(text),
+        }
+    }
+}
+
+/// How `export` writes its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub key: Encoding,
+    pub value: Encoding,
+}
+
+/// A table to export, with the decoders of its keys and values.
+#[derive(Debug, Clone)]
+pub struct ExportTable {
+    pub name: String,
+    pub key_decoder: Decoder,
+    pub value_decoder: Decoder,
+    /// Keys to export; the whole table when `None`.
+    pub range: Option<KeyRange>,
+    /// Only these keys are exported, when given; missing ones are skipped.
+    pub keys: Option<Vec<Vec<u8>>>,
+    /// Only entries whose decoded key or value matches are exported.
+    pub search: Option<Query>,
+}
+
+impl ExportTable {
+    pub fn new(
+        name: String,
+        key_decoder: Decoder,
+        value_decoder: Decoder,
+    ) -> Self {
+        Self {
+            name,
+            key_decoder,
+            value_decoder,
+            range: None,
+            keys: None,
+            search: None,
+        }
+    }
+}
+
+/// Entries written between progress updates of `export`.
+const EXPORT_PROGRESS_INTERVAL: u64 = 1000;
+
+/// Writes the entries of `table`, or of every table, to `output` or stdout.
+/// Keys and values are decoded with the decoders of the schema sidecar.
+/// Progress is reported on stderr when it is a terminal that the export
+/// isn't written to.
+pub fn export(
+    db_path: &Path,
+    mut schemas: Schemas,
+    table: Option<&str>,
+    options: ExportOptions,
+    output: Option<&Path>,
+) -> Result<()> {
+    info!("Exporting {:?} as {:?}", db_path, options.format);
+    let db = open_database(db_path)?;
+    let registry = Registry::load(&Registry::sidecar_path(db_path))?;
+    let tables = match table {
+        Some(table) => vec![table.to_string()],
+        None => database::get_table_names(&db)?,
+    };
+    let show_progress = io::stderr().is_terminal()
+        && (output.is_some() || !io::stdout().is_terminal());
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let tables: Vec<_> = tables
+        .into_iter()
+        .map(|table| {
+            let (key, value) = table_decoders(&registry, &mut schemas, &table);
+            ExportTable::new(table, key, value)
+        })
+        .collect();
+    write_export(
+        &db,
+        &schemas,
+        &tables,
+        options,
+        &mut out,
+        |table, done, total| {
+            if show_progress {
+                eprint!("\r{}: {}", table, layout::progress_label(done, total));
+                if done == total {
+                    eprintln!();
+                }
+            }
+        },
+    )
+    .map(|_| ())
+}
+
+/// Writes the entries of `tables` to `out` from a single read transaction,
+/// so they are consistent with each other even while the database is
+/// written to. `progress` is called with the table, the entries read and
+/// the table's length every thousand entries and once a table is done.
+/// Returns the number of entries written.
+pub fn write_export(
+    db: &Database,
+    schemas: &Schemas,
+    tables: &[ExportTable],
+    options: ExportOptions,
+    out: &mut dyn Write,
+    mut progress: impl FnMut(&str, u64, u64),
+) -> Result<u64> {
+    let format = options.format;
+    let txn = db.begin_read()?;
+    match format {
+        ExportFormat::Json => write!(out, "{{")?,
+        ExportFormat::Csv => writeln!(out, "table,key,value")?,
+        ExportFormat::Jsonl => {}
+        ExportFormat::Sqlite => writeln!(out, "BEGIN TRANSACTION;")?,
+    }
+    let mut written = 0;
+    for (i, export) in tables.iter().enumerate() {
+        let table = &export.name;
+        let ctx = schemas.context(table);
+        let (raw_table, types) = database::open_raw_table(&txn, table)?;
+        let total = raw_table.len()?;
+        if format == ExportFormat::Json {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "\n{}:[", json::to_string(&Node::Text(table.clone())))?;
+        }
+        // Decoded columns only say something for known types or decoders.
+        let decoded = (
+            !database::is_opaque_type(&types.key.name)
+                || export.key_decoder != Decoder::Auto,
+            !database::is_opaque_type(&types.value.name)
+                || export.value_decoder != Decoder::Auto,
+        );
+        if format == ExportFormat::Sqlite {
+            let name = sql_identifier(table);
+            let mut columns = "key BLOB PRIMARY KEY, value BLOB".to_string();
+            if decoded.0 {
+                columns.push_str(", key_decoded");
+            }
+            if decoded.1 {
+                columns.push_str(", value_decoded");
+            }
+            writeln!(out, "DROP TABLE IF EXISTS {};", name)?;
+            writeln!(out, "CREATE TABLE {} ({});", name, columns)?;
+        }
+        let range = export
+            .range
+            .as_ref()
+            .map_or((Bound::Unbounded, Bound::Unbounded), KeyRange::bounds);
+        let entries: Box<dyn Iterator<Item = Result<RawEntry>>> = match &export
+            .keys
+        {
+            Some(keys) => {
+                let mut keys = keys.clone();
+                keys.sort_by(|a, b| database::compare_as(&types.key.name, a, b));
+                let mut found = Vec::with_capacity(keys.len());
+                for key in keys {
+                    if let Some(value) = raw_table.get(key.as_slice())? {
+                        let value = value.value().to_vec();
+                        found.push(Ok((key, value)));
+                    }
+                }
+                Box::new(found.into_iter())
+            }
+            None => Box::new(raw_table.range::<&[u8]>(range)?.map(|entry| {
+                let (key, value) = entry?;
+                Ok((key.value().to_vec(), value.value().to_vec()))
+            })),
+        };
+        let mut first = true;
+        for (j, entry) in entries.enumerate() {
+            let done = j as u64 + 1;
+            if done < total && done.is_multiple_of(EXPORT_PROGRESS_INTERVAL) {
+                progress(table, done, total);
+            }
+            let (key, value) = entry?;
+            let (key, value) = (key.as_slice(), value.as_slice());
+            let raw = (key, value);
+            if let Some(query) = &export.search {
+                let key = export.key_decoder.decode(&ctx, &types.key.name, key);
+                let value =
+                    export.value_decoder.decode(&ctx, &types.value.name, value);
+                if !query.matches(&key) && !query.matches(&value) {
+                    continue;
+                }
+            }
+            let key = options.key.encode(
+                export.key_decoder,
+                &ctx,
+                &types.key.name,
+                key,
+            );
+            let value = options.value.encode(
+                export.value_decoder,
+                &ctx,
+                &types.value.name,
+                value,
+            );
+            written += 1;
+            match format {
+                ExportFormat::Json => {
+                    let entry = Node::Map(vec![
+                        (Node::Text("key".to_string()), key),
+                        (Node::Text("value".to_string()), value),
+                    ]);
+                    if !first {
+                        write!(out, ",")?;
+                    }
+                    first = false;
+                    write!(out, "\n  {}", json::to_string(&entry))?;
+                }
+                ExportFormat::Jsonl => {
+                    let text = |text: &str| Node::Text(text.to_string());
+                    let (key_encoding, key) =
+                        restorable(options.key, &types.key.name, raw.0, key);
+                    let (value_encoding, value) = restorable(
+                        options.value,
+                        &types.value.name,
+                        raw.1,
+                        value,
+                    );
+                    let record = Node::Map(vec![
+                        (text("table"), Node::Text(table.clone())),
+                        (text("key"), key),
+                        (text("value"), value),
+                        (text("key_encoding"), text(key_encoding.name())),
+                        (text("value_encoding"), text(value_encoding.name())),
+                        (text("key_type"), text(&types.key.name)),
+                        (text("value_type"), text(&types.value.name)),
+                    ]);
+                    writeln!(out, "{}", json::to_string(&record))?;
+                }
+                ExportFormat::Csv => writeln!(
+                    out,
+                    "{},{},{}",
+                    csv::quote(table),
+                    csv::quote(&json::to_text(&key)),
+                    csv::quote(&json::to_text(&value))
+                )?,
+                ExportFormat::Sqlite => {
+                    let mut values =
+                        format!("{}, {}", sql_blob(raw.0), sql_blob(raw.1));
+                    if decoded.0 {
+                        values.push_str(&format!(", {}", sql_literal(&key)));
+                    }
+                    if decoded.1 {
+                        values.push_str(&format!(", {}", sql_literal(&value)));
+                    }
+                    writeln!(
+                        out,
+                        "INSERT INTO {} VALUES ({});",
+                        sql_identifier(table),
+                        values
+                    )?;
+                }
+            }
+        }
+        progress(table, total, total);
+        if format == ExportFormat::Json {
+            write!(out, "]")?;
+        }
+    }
+    match format {
+        ExportFormat::Json => writeln!(out, "\n}}")?,
+        ExportFormat::Sqlite => writeln!(out, "COMMIT;")?,
+        ExportFormat::Csv | ExportFormat::Jsonl => {}
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+/// A SQL identifier in double quotes.
+fn sql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A SQLite blob literal, `X'00ff'`.
+fn sql_blob(bytes: &[u8]) -> String {
+    format!("X'{}'", decoder::hex(bytes))
+}
+
+/// A decoded key or value as a SQL literal. Integers that fit SQLite's
+/// 64 bits and floats stay numbers; documents are stored as JSON text.
+fn sql_literal(node: &Node) -> String {
+    let text = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    match node {
+        Node::Null | Node::Undefined => "NULL".to_string(),
+        Node::Bool(b) => (*b as u8).to_string(),
+        Node::Int(i) if i64::try_from(*i).is_ok() => i.to_string(),
+        Node::Float(f) if f.is_finite() => format!("{:?}", f),
+        Node::Text(s) => text(s),
+        Node::Bytes(bytes) => sql_blob(bytes),
+        other => text(&json::to_string(other)),
+    }
+}
+
+/// File format of `import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// One `{"key": ..., "value": ...}` object per line
+    Jsonl,
+    /// Rows with a header line naming the key and value columns
+    Csv,
+    /// Tables of a SQLite database file
+    Sqlite,
+    /// Databases of an LMDB environment
+    #[cfg(feature = "lmdb")]
+    Lmdb,
+}
+
+/// Table the unnamed database of an LMDB environment is imported into.
+#[cfg(feature = "lmdb")]
+const LMDB_MAIN_TABLE: &str = "main";
+
+/// How `import` reads its input.
+#[derive(Debug, Clone, PartialEq, Eq, clap::Args)]
+pub struct ImportOptions {
+    #[arg(short, long, value_enum, default_value = "jsonl")]
+    pub format: ImportFormat,
+    /// How keys are read
+    #[arg(
+        long = "key-encoding",
+        value_name = "ENCODING",
+        value_enum,
+        default_value = "decoded"
+    )]
+    pub key: Encoding,
+    /// How values are read
+    #[arg(
+        long = "value-encoding",
+        value_name = "ENCODING",
+        value_enum,
+        default_value = "decoded"
+    )]
+    pub value: Encoding,
+    /// CSV or SQLite column holding the keys, by name or 1-based number
+    #[arg(long, default_value = "key")]
+    pub key_column: String,
+    /// CSV or SQLite column holding the values, by name or 1-based number
+    #[arg(long, default_value = "value")]
+    pub value_column: String,
+    /// SQLite table to import; by default every table with key and value
+    /// columns, or two columns, each into the table of its name
+    #[arg(long)]
+    pub sqlite_table: Option<String>,
+    /// LMDB database to import; by default every one, each into the table
+    /// of its name
+    #[cfg(feature = "lmdb")]
+    #[arg(long)]
+    pub lmdb_database: Option<String>,
+    /// Entries inserted per write transaction
+    #[arg(
+        long,
+        default_value_t = 10_000,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub batch_size: usize,
+}
+
+/// An entry read from an import file.
+struct ImportRecord {
+    /// Line the entry is on, or its position in a SQLite table or LMDB
+    /// database, for errors.
+    line: usize,
+    /// Table named by the record, as in dumps of several tables.
+    table: Option<String>,
+    key: Node,
+    value: Node,
+    /// Encodings named by the record, overriding those of the options.
+    key_encoding: Option<Encoding>,
+    value_encoding: Option<Encoding>,
+    /// Stored types of the table named by the record, as in dumps.
+    key_type: Option<String>,
+    value_type: Option<String>,
+}
+
+/// Inserts the entries of `input` into `table`, or into the tables the
+/// records name, committing a write transaction every `batch_size` entries
+/// so big files don't build up one huge transaction. Progress is reported
+/// on stderr when it is a terminal, as a percentage when the input says
+/// how many entries it holds.
+pub fn import(
+    db_path: &Path,
+    table: Option<&str>,
+    options: &ImportOptions,
+    input: &Path,
+    new_types: &TableTypes,
+) -> Result<()> {
+    info!(
+        "Importing {:?} into {} as {:?}",
+        input,
+        table.unwrap_or("the tables of the records"),
+        options.format
+    );
+    // Line 0 stands for the file as a whole.
+    let error = |line: usize, message: String| {
+        AppError::ImportError(match line {
+            0 => format!("{}: {}", input.display(), message),
+            line => format!("{}:{}: {}", input.display(), line, message),
+        })
+    };
+    let open = || -> Result<_> { Ok(BufReader::new(File::open(input)?)) };
+    let sqlite;
+    #[cfg(feature = "lmdb")]
+    let lmdb;
+    // The records, and how many there are in all when the input says.
+    let (expected, mut records): (
+        Option<u64>,
+        Box<dyn Iterator<Item = Result<ImportRecord>> + '_>,
+    ) = match options.format {
+        ImportFormat::Jsonl => (None, Box::new(jsonl_records(open()?, &error))),
+        ImportFormat::Csv => {
+            (None, Box::new(csv_records(open()?, options, &error)?))
+        }
+        ImportFormat::Sqlite => {
+            sqlite = sqlite::Database::load(input)?;
+            (None, Box::new(sqlite_records(&sqlite, options, &error)?))
+        }
+        #[cfg(feature = "lmdb")]
+        ImportFormat::Lmdb => {
+            lmdb = lmdb::Environment::load(input)?;
+            let (total, records) = lmdb_records(&lmdb, options, &error)?;
+            (Some(total), Box::new(records))
+        }
+    };
+    // Seeding a fresh database is the common case, so create it if needed.
+    let db = Database::create(db_path)?;
+    let show_progress = io::stderr().is_terminal();
+    // Entries per table, in the order the tables came up.
+    let mut counts: Vec<(String, usize)> =
+        table.map(|t| (t.to_string(), 0)).into_iter().collect();
+    let mut total = 0;
+    loop {
+        let txn = db.begin_write()?;
+        let mut batch = 0;
+        {
+            let mut tables = HashMap::new();
+            for record in records.by_ref() {
+                let record = record?;
+                let line = record.line;
+                let name = match (table, record.table.clone()) {
+                    (Some(table), _) => table.to_string(),
+                    (None, Some(table)) => table,
+                    (None, None) => {
+                        return Err(error(
+                            line,
+                            "no table; pass --table or give each record one"
+                                .to_string(),
+                        ))
+                    }
+                };
+                let (raw_table, types) = match tables.entry(name.clone()) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(open_for_record(
+                        &txn, &record, &name, new_types,
+                    )?),
+                };
+                let key = record
+                    .key_encoding
+                    .unwrap_or(options.key)
+                    .parse(&types.key.name, &record.key)
+                    .map_err(|e| error(line, e))?;
+                let value = record
+                    .value_encoding
+                    .unwrap_or(options.value)
+                    .parse(&types.value.name, &record.value)
+                    .map_err(|e| error(line, e))?;
+                database::check_entry(types, &key, &value)
+                    .map_err(|e| error(line, e.to_string()))?;
+                raw_table.insert(key.as_slice(), value.as_slice())?;
+                match counts.iter_mut().find(|(table, _)| *table == name) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((name, 1)),
+                }
+                batch += 1;
+                if batch == options.batch_size {
+                    break;
+                }
+            }
+        }
+        txn.commit()?;
+        total += batch;
+        if show_progress {
+            let done = layout::group_thousands(total as u64);
+            match expected {
+                Some(expected) if expected > 0 => eprint!(
+                    "\rImported {} of {} entries ({}%)",
+                    done,
+                    layout::group_thousands(expected),
+                    (total as u64 * 100 / expected).min(100)
+                ),
+                _ => eprint!("\rImported {} entries", done),
+            }
+        }
+        if batch < options.batch_size {
+            break;
+        }
+    }
+    if show_progress {
+        eprintln!();
+    }
+    for (table, count) in counts {
+        println!("Imported {} entries into {}", count, table);
+    }
+    Ok(())
+}
+
+/// Opens table `name` to import `record` into. A table the database lacks is
+/// created with the types the record names, as dumps do, or else with
+/// `new_types`; one it has must store the types the record names.
+fn open_for_record<'txn>(
+    txn: &'txn WriteTransaction,
+    record: &ImportRecord,
+    name: &str,
+    new_types: &TableTypes,
+) -> Result<(Table<'txn, RawKey, RawValue>, TableTypes)> {
+    let recorded = |recorded: &Option<String>, default: &RawType| {
+        let Some(type_name) = recorded else {
+            return Ok(default.clone());
+        };
+        database::builtin_type(type_name).ok_or_else(|| {
+            AppError::UnsupportedTable(format!(
+                "{}: can't create a table of {}; create it first",
+                name, type_name
+            ))
+        })
+    };
+    if !txn.list_tables()?.any(|t| t.name() == name) {
+        let types = TableTypes {
+            key: recorded(&record.key_type, &new_types.key)?,
+            value: recorded(&record.value_type, &new_types.value)?,
+        };
+        return database::open_raw_table_mut(txn, name, &types);
+    }
+    let (table, types) = database::open_raw_table_mut(txn, name, new_types)?;
+    let differs = |recorded: &Option<String>, stored: &RawType| {
+        recorded.as_ref().is_some_and(|name| *name != stored.name)
+    };
+    if differs(&record.key_type, &types.key)
+        || differs(&record.value_type, &types.value)
+    {
+        return Err(AppError::TableTypesDiffer(format!(
+            "{} stores {} -> {}, the records {} -> {}",
+            name,
+            types.key.name,
+            types.value.name,
+            record.key_type.as_deref().unwrap_or(&types.key.name),
+            record.value_type.as_deref().unwrap_or(&types.value.name)
+        )));
+    }
+    Ok((table, types))
+}
+
+/// Entries of a JSON Lines file: `key` and `value`, and optionally the
+/// `table`, encodings and types written by a `jsonl` export.
+fn jsonl_records<'a>(
+    reader: impl BufRead + 'a,
+    error: &'a impl Fn(usize, String) -> AppError,
+) -> impl Iterator<Item = Result<ImportRecord>> + 'a {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+        .map(|(i, line)| {
+            let line_number = i + 1;
+            let record = json::parse(&line?)
+                .map_err(|e| error(line_number, e.to_string()))?;
+            let field = |name: &str| {
+                record.get(name).cloned().ok_or_else(|| {
+                    error(line_number, format!("missing \"{}\"", name))
+                })
+            };
+            let text = |name: &str| match record.get(name) {
+                None | Some(Node::Null) => Ok(None),
+                Some(Node::Text(text)) => Ok(Some(text.clone())),
+                Some(_) => Err(error(
+                    line_number,
+                    format!("\"{}\" is not a string", name),
+                )),
+            };
+            let encoding = |name: &str| {
+                text(name)?
+                    .map(|text| {
+                        Encoding::from_name(&text).ok_or_else(|| {
+                            error(
+                                line_number,
+                                format!("unknown {} '{}'", name, text),
+                            )
+                        })
+                    })
+                    .transpose()
+            };
+            Ok(ImportRecord {
+                line: line_number,
+                table: text("table")?,
+                key: field("key")?,
+                value: field("value")?,
+                key_encoding: encoding("key_encoding")?,
+                value_encoding: encoding("value_encoding")?,
+                key_type: text("key_type")?,
+                value_type: text("value_type")?,
+            })
+        })
+}
+
+/// Entries of a CSV file, from the columns named in its header, and the
+/// table from a `table` column if there is one.
+fn csv_records<'a>(
+    reader: impl BufRead + 'a,
+    options: &ImportOptions,
+    error: &'a impl Fn(usize, String) -> AppError,
+) -> Result<impl Iterator<Item = Result<ImportRecord>> + 'a> {
+    let mut reader = csv::Reader::new(reader);
+    let (line, header) = reader
+        .next_record()?
+        .ok_or_else(|| error(1, "no header line".to_string()))?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h == name)
+            .or_else(|| {
+                name.parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=header.len()).contains(n))
+                    .map(|n| n - 1)
+            })
+            .ok_or_else(|| error(line, format!("no column \"{}\"", name)))
+    };
+    let key = column(&options.key_column)?;
+    let value = column(&options.value_column)?;
+    let table = header.iter().position(|h| h == "table");
+    Ok(std::iter::from_fn(move || match reader.next_record() {
+        Ok(record) => record.map(Ok),
+        Err(e) => Some(Err(error(reader.line(), e.to_string()))),
+    })
+    .map(move |record| {
+        let (line, mut fields) = record?;
+        if fields.len() <= key.max(value) {
+            return Err(error(
+                line,
+                format!(
+                    "expected {} fields, got {}",
+                    key.max(value) + 1,
+                    fields.len()
+                ),
+            ));
+        }
+        Ok(ImportRecord {
+            line,
+            table: table.and_then(|i| fields.get(i).cloned()),
+            key: Node::Text(std::mem::take(&mut fields[key])),
+            value: Node::Text(std::mem::take(&mut fields[value])),
+            key_encoding: None,
+            value_encoding: None,
+            key_type: None,
+            value_type: None,
+        })
+    }))
+}
+
+/// Entries of the tables of a SQLite database, from the columns named by
+/// the options, or from the two columns of two-column tables. Tables
+/// without either are skipped unless asked for by name.
+fn sqlite_records<'a>(
+    db: &'a sqlite::Database,
+    options: &ImportOptions,
+    error: &'a impl Fn(usize, String) -> AppError,
+) -> Result<impl Iterator<Item = Result<ImportRecord>> + 'a> {
+    let tables = db.tables().map_err(|e| error(0, e.to_string()))?;
+    if let Some(name) = &options.sqlite_table {
+        if !tables.iter().any(|t| t.name == *name) {
+            return Err(error(0, format!("no table \"{}\"", name)));
+        }
+    }
+    let mut selected = Vec::new();
+    for table in tables {
+        if options
+            .sqlite_table
+            .as_ref()
+            .is_some_and(|n| *n != table.name)
+        {
+            continue;
+        }
+        let names: Vec<&str> =
+            table.columns.iter().map(|c| c.name.as_str()).collect();
+        let column = |name: &str, fallback: usize| {
+            names
+                .iter()
+                .position(|n| n.eq_ignore_ascii_case(name))
+                .or_else(|| {
+                    name.parse::<usize>()
+                        .ok()
+                        .filter(|n| (1..=names.len()).contains(n))
+                        .map(|n| n - 1)
+                })
+                .or((names.len() == 2).then_some(fallback))
+        };
+        match (
+            column(&options.key_column, 0),
+            column(&options.value_column, 1),
+        ) {
+            (Some(key), Some(value)) => selected.push((table, key, value)),
+            _ if options.sqlite_table.is_some() => {
+                return Err(error(
+                    0,
+                    format!(
+                        "table \"{}\" has no \"{}\" and \"{}\" columns",
+                        table.name, options.key_column, options.value_column
+                    ),
+                ))
+            }
+            _ => {
+                info!(
+                    "Skipping SQLite table {}: no key/value columns",
+                    table.name
+                );
+                eprintln!("Skipping {}: no key and value columns", table.name);
+            }
+        }
+    }
+    Ok(selected.into_iter().flat_map(move |(table, key, value)| {
+        // The rows borrow the table, so collect them per table as read.
+        let rows: Vec<_> = db.rows(&table).enumerate().collect();
+        rows.into_iter().map(move |(i, row)| {
+            let mut row = row.map_err(|e| {
+                error(0, format!("table \"{}\": {}", table.name, e))
+            })?;
+            Ok(ImportRecord {
+                line: i + 1,
+                table: Some(table.name.clone()),
+                key: std::mem::replace(&mut row[key], Node::Null),
+                value: std::mem::replace(&mut row[value], Node::Null),
+                key_encoding: None,
+                value_encoding: None,
+                key_type: None,
+                value_type: None,
+            })
+        })
+    }))
+}
+
+/// Entries of the databases of an LMDB environment, each for the table of
+/// its name, and how many there are in all. Databases with sorted
+/// duplicates are skipped, since a table keeps a single value per key.
+#[cfg(feature = "lmdb")]
+fn lmdb_records<'a>(
+    env: &'a lmdb::Environment,
+    options: &ImportOptions,
+    error: &'a impl Fn(usize, String) -> AppError,
+) -> Result<(u64, impl Iterator<Item = Result<ImportRecord>> + 'a)> {
+    let name = |db: &lmdb::Database| {
+        db.name
+            .clone()
+            .unwrap_or_else(|| LMDB_MAIN_TABLE.to_string())
+    };
+    let databases = env.databases().map_err(|e| error(0, e.to_string()))?;
+    if let Some(wanted) = &options.lmdb_database {
+        if !databases.iter().any(|db| name(db) == *wanted) {
+            return Err(error(0, format!("no database \"{}\"", wanted)));
+        }
+    }
+    let mut selected = Vec::new();
+    for db in databases {
+        let table = name(&db);
+        if options.lmdb_database.as_ref().is_some_and(|n| *n != table) {
+            continue;
+        }
+        if db.dup_sort {
+            if options.lmdb_database.is_some() {
+                return Err(error(
+                    0,
+                    format!("database \"{}\" has sorted duplicates", table),
+                ));
+            }
+            info!("Skipping LMDB database {}: sorted duplicates", table);
+            eprintln!("Skipping {}: sorted duplicates", table);
+            continue;
+        }
+        selected.push((table, db));
+    }
+    let total = selected.iter().map(|(_, db)| db.entries).sum();
+    let records = selected.into_iter().flat_map(move |(table, db)| {
+        env.entries(&db).enumerate().map(move |(i, entry)| {
+            let (key, value) = entry.map_err(|e| {
+                error(0, format!("database \"{}\": {}", table, e))
+            })?;
+            Ok(ImportRecord {
+                line: i + 1,
+                table: Some(table.clone()),
+                key: Node::Bytes(key),
+                value: Node::Bytes(value),
+                key_encoding: None,
+                value_encoding: None,
+                key_type: None,
+                value_type: None,
+            })
+        })
+    });
+    Ok((total, records))
+}
+
+/// How a `jsonl` dump writes `bytes`, given as `node` in `encoding`: as is
+/// when importing the record gives the bytes back, as base64 otherwise.
+/// Decoded renderings of opaque bytes, like a msgpack value shown as JSON,
+/// and UTF-8 with invalid sequences replaced don't.
+fn restorable(
+    encoding: Encoding,
+    type_name: &str,
+    bytes: &[u8],
+    node: Node,
+) -> (Encoding, Node) {
+    let read_back = json::parse(&json::to_string(&node))
+        .map_err(|e| e.to_string())
+        .and_then(|node| encoding.parse(type_name, &node));
+    match read_back {
+        Ok(read_back) if read_back == bytes => (encoding, node),
+        _ => (Encoding::Base64, Node::Text(decoder::base64(bytes))),
+    }
+}
+
+/// Encodes an imported JSON value as the stored type `type_name`. Opaque
+/// types take bytes as they are, strings as UTF-8 and anything else as
+/// JSON text. Other types take bytes as they are when they are valid:
+/// UTF-8 for strings, or as wide as fixed-width types.
+fn encode_node(
+    type_name: &str,
+    node: &Node,
+) -> std::result::Result<Vec<u8>, String> {
+    if database::is_opaque_type(type_name) {
+        if let Node::Bytes(bytes) = node {
+            return Ok(bytes.clone());
+        }
+        return Ok(json::to_text(node).into_bytes());
+    }
+    if let Node::Bytes(bytes) = node {
+        let width = database::builtin_type(type_name).and_then(|t| t.fixed_width);
+        let hex = decoder::hex(bytes);
+        return match (type_name, width) {
+            ("&str" | "String", _) => std::str::from_utf8(bytes)
+                .map(|_| bytes.clone())
+                .map_err(|_| format!("{} is not UTF-8", hex)),
+            (_, Some(width)) if width == bytes.len() => Ok(bytes.clone()),
+            _ => Err(format!("{} is not a {}", hex, type_name)),
+        };
+    }
+    let text = match node {
+        Node::Null if type_name.starts_with("Option<") => "None".to_string(),
+        other => json::to_text(other),
+    };
+    decoder::encode_native(type_name, &text)
+}
+
+/// Prints database and per-table storage stats as a JSON object. Fields
+/// that need a write transaction are left out in read-only mode, which
+/// reads a snapshot so as not to write to the file in any way.
+pub fn stats(db_path: &Path, read_only: bool) -> Result<()> {
+    let db = match read_only {
+        true => database::open_snapshot(db_path, |_| true)?,
+        false => open_database(db_path)?,
+    };
+    let int = |n: u64| Node::Int(n as i128);
+    let field = |name: &str, value: Node| (Node::Text(name.to_string()), value);
+
+    let mut tables = Vec::new();
+    for name in database::get_table_names(&db)? {
+        let (len, stats, types) = database::get_table_stats(&db, &name)?;
+        tables.push(Node::Map(vec![
+            field("name", Node::Text(name)),
+            field("key_type", Node::Text(types.key.name)),
+            field("value_type", Node::Text(types.value.name)),
+            field("entries", int(len)),
+            field("tree_height", int(stats.tree_height() as u64)),
+            field("leaf_pages", int(stats.leaf_pages())),
+            field("branch_pages", int(stats.branch_pages())),
+            field("stored_bytes", int(stats.stored_bytes())),
+            field("metadata_bytes", int(stats.metadata_bytes())),
+            field("fragmented_bytes", int(stats.fragmented_bytes())),
+        ]));
+    }
+
+    let stats = database::get_storage_summary(&db, read_only)?;
+    let mut fields =
+        vec![field("file_size", int(std::fs::metadata(db_path)?.len()))];
+    if let Some(page_size) = stats.page_size {
+        fields.push(field("page_size", int(page_size as u64)));
+    }
+    fields.push(field("tree_height", int(stats.tree_height as u64)));
+    if let Some(pages) = stats.allocated_pages {
+        fields.push(field("allocated_pages", int(pages)));
+    }
+    fields.extend([
+        field("leaf_pages", int(stats.leaf_pages)),
+        field("branch_pages", int(stats.branch_pages)),
+        field("stored_bytes", int(stats.stored_bytes)),
+        field("metadata_bytes", int(stats.metadata_bytes)),
+        field("fragmented_bytes", int(stats.fragmented_bytes)),
+        field("read_only", Node::Bool(read_only)),
+        field("tables", Node::Array(tables)),
+    ]);
+    let doc = Node::Map(fields);
+    println!("{}", json::to_string(&doc));
+    Ok(())
+}
+
+/// Checks the database file for damage and repairs it, printing which it
+/// was.
+pub fn check(db_path: &Path) -> Result<()> {
+    let mut db = open_database(db_path)?;
+    let show_progress = io::stderr().is_terminal();
+    if show_progress {
+        eprint!("Checking {}...", db_path.display());
+    }
+    let intact = database::check_integrity(&mut db, db_path)?;
+    if show_progress {
+        eprintln!();
+    }
+    match intact {
+        true => println!("No damage found"),
+        false => println!("Found damage and repaired it"),
+    }
+    Ok(())
+}
+
+/// Compacts the database, printing the file size and fragmentation before
+/// and after.
+pub fn compact(db_path: &Path) -> Result<()> {
+    let mut db = open_database(db_path)?;
+    let show_progress = io::stderr().is_terminal();
+    if show_progress {
+        eprint!("Compacting {}...", db_path.display());
+    }
+    let compaction = database::compact(&mut db, db_path)?;
+    if show_progress {
+        eprintln!();
+    }
+    println!("Compacted: {}", compaction);
+    Ok(())
+}
+
+/// Renames a table, reporting progress on stderr when it is a terminal.
+pub fn rename(db_path: &Path, from: &str, to: &str) -> Result<()> {
+    info!("Renaming table {} to {}", from, to);
+    let db = open_database(db_path)?;
+    let show_progress = io::stderr().is_terminal();
+    let durability = WriteDurability::Immediate;
+    let count =
+        database::rename_table(&db, from, to, durability, |done, total| {
+            if show_progress {
+                eprint!("\rCopied {}", layout::progress_label(done, total));
+            }
+        })?;
+    if show_progress {
+        eprintln!();
+    }
+    println!("Renamed {} to {} ({} entries)", from, to, count);
+    Ok(())
+}
+
+/// Copies `table` from the database at `from` into the one at `to`, created
+/// if needed, under the same name or `target_table`. A table the target
+/// already has is refused unless `overwrite` is set. Progress is reported
+/// on stderr when it is a terminal.
+pub fn copy_table(
+    from: &Path,
+    to: &Path,
+    table: &str,
+    target_table: Option<&str>,
+    overwrite: bool,
+    batch_size: usize,
+) -> Result<()> {
+    let target_table = target_table.unwrap_or(table);
+    info!(
+        "Copying table {} of {:?} to {} of {:?}",
+        table, from, target_table, to
+    );
+    let source = open_database(from)?;
+    let target = open_copy_target(from, to)?;
+    if target.is_none() && table == target_table {
+        return Err(AppError::TableExists(table.to_string()));
+    }
+    let target_tables =
+        database::get_table_names(target.as_ref().unwrap_or(&source))?;
+    if !overwrite && target_tables.iter().any(|t| t == target_table) {
+        return Err(AppError::TableExists(format!(
+            "{} in {} (pass --overwrite to copy into it)",
+            target_table,
+            to.display()
+        )));
+    }
+    let show_progress = io::stderr().is_terminal();
+    let options = CopyOptions {
+        on_conflict: OnConflict::Overwrite,
+        batch_size,
+        durability: WriteDurability::Immediate,
+    };
+    let counts = database::copy_table(
+        &source,
+        target.as_ref().unwrap_or(&source),
+        table,
+        target_table,
+        &options,
+        |done, total| {
+            if show_progress {
+                eprint!("\rCopied {}", layout::progress_label(done, total));
+            }
+        },
+    )?;
+    if show_progress {
+        eprintln!();
+    }
+    println!(
+        "Copied {} entries of {} into {} in {}",
+        counts.copied,
+        table,
+        target_table,
+        to.display()
+    );
+    Ok(())
+}
+
+/// Copies every table of the database at `from` into the one at `to`,
+/// created if needed, handling keys both have as `on_conflict` says. All
+/// tables are checked before anything is written, so mismatched types, or
+/// a shared key with `OnConflict::Fail`, leave the target as it was.
+pub fn merge(
+    from: &Path,
+    to: &Path,
+    on_conflict: OnConflict,
+    batch_size: usize,
+) -> Result<()> {
+    info!("Merging {:?} into {:?} ({:?})", from, to, on_conflict);
+    let source = open_database(from)?;
+    let target = open_copy_target(from, to)?;
+    let target = target.as_ref().unwrap_or(&source);
+    let tables = database::get_table_names(&source)?;
+    for table in &tables {
+        database::check_copy(&source, target, table, table, on_conflict)?;
+    }
+    let options = CopyOptions {
+        on_conflict,
+        batch_size,
+        durability: WriteDurability::Immediate,
+    };
+    let show_progress = io::stderr().is_terminal();
+    for table in &tables {
+        let counts = database::copy_table(
+            &source,
+            target,
+            table,
+            table,
+            &options,
+            |done, total| {
+                if show_progress {
+                    eprint!(
+                        "\r{}: {}",
+                        table,
+                        layout::progress_label(done, total)
+                    );
+                }
+            },
+        )?;
+        if show_progress {
+            eprintln!();
+        }
+        println!(
+            "{}: {} entries merged, {} skipped",
+            table, counts.copied, counts.skipped
+        );
+    }
+    Ok(())
+}
+
+/// Opens the database at `to` for copying tables from the one at `from`,
+/// creating it if needed; `None` when both are the same file, which redb
+/// opens only once, so the copy goes through the source's handle.
+fn open_copy_target(from: &Path, to: &Path) -> Result<Option<Database>> {
+    if same_file(from, to)? {
+        return Ok(None);
+    }
+    Ok(Some(Database::create(to)?))
+}
+
+fn same_file(a: &Path, b: &Path) -> Result<bool> {
+    Ok(b.exists() && fs::canonicalize(a)? == fs::canonicalize(b)?)
+}
+
+/// Output format of `diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// Changed tables and entries marked with `+`, `-` and `~`
+    Text,
+    /// One `{"table", "change", ...}` object per changed table or entry
+    Jsonl,
+}
+
+/// Prints how the database at `new_path` differs from the one at
+/// `old_path`: the tables added, removed or stored with other types, and
+/// the entries added, removed or changed in the others. Keys and values
+/// are written as `key_encoding` and `value_encoding` say, decoded with
+/// the decoders of the second database's schema sidecar.
+pub fn diff(
+    old_path: &Path,
+    new_path: &Path,
+    mut schemas: Schemas,
+    table: Option<&str>,
+    format: DiffFormat,
+    key_encoding: Encoding,
+    value_encoding: Encoding,
+) -> Result<()> {
+    info!("Comparing {:?} with {:?}", new_path, old_path);
+    let old = open_database(old_path)?;
+    let new = (!same_file(old_path, new_path)?)
+        .then(|| open_database(new_path))
+        .transpose()?;
+    let new = new.as_ref().unwrap_or(&old);
+    let registry = Registry::load(&Registry::sidecar_path(new_path))?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    let text = |text: &str| Node::Text(text.to_string());
+    let types_node = |types: &TableTypes| {
+        Node::Map(vec![
+            (text("key"), text(&types.key.name)),
+            (text("value"), text(&types.value.name)),
+        ])
+    };
+    for table in diff::tables(&old, new, table)? {
+        let name = &table.name;
+        match (table.change(), format) {
+            (None, _) => {}
+            (Some(change), DiffFormat::Text) => {
+                let detail = match (&table.old, &table.new) {
+                    (Some(old), Some(new)) => format!(
+                        "{} -> {}, was {} -> {}",
+                        new.key.name,
+                        new.value.name,
+                        old.key.name,
+                        old.value.name
+                    ),
+                    (None, _) => format!("{} entries", table.new_len),
+                    (_, None) => format!("{} entries", table.old_len),
+                };
+                writeln!(out, "{} table {} ({})", change.sign(), name, detail)?;
+                continue;
+            }
+            (Some(change), DiffFormat::Jsonl) => {
+                let mut record = vec![
+                    (text("table"), text(name)),
+                    (text("change"), text(&format!("table_{}", change.name()))),
+                ];
+                if let Some(types) = &table.old {
+                    record.push((text("old_types"), types_node(types)));
+                    record.push((
+                        text("old_entries"),
+                        Node::Int(table.old_len.into()),
+                    ));
+                }
+                if let Some(types) = &table.new {
+                    record.push((text("new_types"), types_node(types)));
+                    record.push((
+                        text("new_entries"),
+                        Node::Int(table.new_len.into()),
+                    ));
+                }
+                writeln!(out, "{}", json::to_string(&Node::Map(record)))?;
+                continue;
+            }
+        }
+        let Some(types) = &table.new else {
+            continue;
+        };
+        let (key_decoder, value_decoder) =
+            table_decoders(&registry, &mut schemas, name);
+        let ctx = schemas.context(name);
+        let key_node = |bytes: &[u8]| {
+            key_encoding.encode(key_decoder, &ctx, &types.key.name, bytes)
+        };
+        let value_node = |bytes: &Option<Vec<u8>>| match bytes {
+            Some(bytes) => value_encoding.encode(
+                value_decoder,
+                &ctx,
+                &types.value.name,
+                bytes,
+            ),
+            None => Node::Null,
+        };
+        let counts = diff::entries(&old, new, &table, |entry| {
+            let key = key_node(&entry.key);
+            let (old_value, new_value) =
+                (value_node(&entry.old), value_node(&entry.new));
+            match format {
+                DiffFormat::Text => {
+                    let value = match entry.change {
+                        diff::Change::Added => json::to_text(&new_value),
+                        diff::Change::Removed => json::to_text(&old_value),
+                        diff::Change::Changed => format!(
+                            "{} -> {}",
+                            json::to_text(&old_value),
+                            json::to_text(&new_value)
+                        ),
+                    };
+                    writeln!(
+                        out,
+                        "{} {} {}: {}",
+                        entry.change.sign(),
+                        name,
+                        json::to_text(&key),
+                        value
+                    )?;
+                }
+                DiffFormat::Jsonl => {
+                    let mut record = vec![
+                        (text("table"), text(name)),
+                        (text("change"), text(entry.change.name())),
+                        (text("key"), key),
+                    ];
+                    if entry.old.is_some() {
+                        record.push((text("old"), old_value));
+                    }
+                    if entry.new.is_some() {
+                        record.push((text("new"), new_value));
+                    }
+                    writeln!(out, "{}", json::to_string(&Node::Map(record)))?;
+                }
+            }
+            Ok(true)
+        })?;
+        info!(
+            "{}: {} added, {} removed, {} changed",
+            name, counts.added, counts.removed, counts.changed
+        );
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Prints one table name per line, optionally followed by the entry count
+/// and the stored key/value types, separated by tabs.
+pub fn tables(db_path: &Path, counts: bool, types: bool) -> Result<()> {
+    let db = open_database(db_path)?;
+    let mut stdout = io::stdout().lock();
+    for name in database::get_table_names(&db)? {
+        let mut line = name.clone();
+        if counts || types {
+            let (len, _, table_types) = database::get_table_stats(&db, &name)?;
+            if counts {
+                line.push_str(&format!("\t{}", len));
+            }
+            if types {
+                line.push_str(&format!(
+                    "\t{}\t{}",
+                    table_types.key.name, table_types.value.name
+                ));
+            }
+        }
+        writeln!(stdout, "{}", line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Args, FromArgMatches};
+    use redb::{ReadableTable, TableDefinition};
+    use std::path::PathBuf;
+
+    const BLOBS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("blobs");
+    const NUMS: TableDefinition<u64, &str> = TableDefinition::new("nums");
+
+    /// A path of its own in the temp directory, removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "redb-tui-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn import_options(args: &[&str]) -> ImportOptions {
+        let command = ImportOptions::augment_args(clap::Command::new("import"));
+        let matches = command
+            .try_get_matches_from(
+                std::iter::once("import").chain(args.iter().copied()),
+            )
+            .unwrap();
+        ImportOptions::from_arg_matches(&matches).unwrap()
+    }
+
+    /// Bytes no decoded rendering gives back: msgpack, binary, JSON with
+    /// its own spacing, and a key that isn't UTF-8.
+    fn sample(path: &Path) {
+        let db = Database::create(path).unwrap();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut blobs = txn.open_table(BLOBS).unwrap();
+            let msgpack = decoder::parse_hex("82a16101a162cb3ff0000000000000");
+            blobs
+                .insert(&b"msgpack"[..], &msgpack.unwrap()[..])
+                .unwrap();
+            let binary = [0x00, 0x9f, 0x92, 0x96, 0xff, 0x01, 0x02];
+            blobs.insert(&b"binary"[..], &binary[..]).unwrap();
+            let json = br#"{"a": 1,   "b": 2}"#;
+            blobs.insert(&b"json"[..], &json[..]).unwrap();
+            blobs.insert(&[0xff, 0x00][..], &b"text"[..]).unwrap();
+            let mut nums = txn.open_table(NUMS).unwrap();
+            nums.insert(1, "one").unwrap();
+            nums.insert(300, "three hundred").unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    /// Every entry of every table, as stored.
+    fn contents(path: &Path) -> Vec<(String, Vec<RawEntry>)> {
+        let db = Database::open(path).unwrap();
+        let txn = db.begin_read().unwrap();
+        database::get_table_names(&db)
+            .unwrap()
+            .into_iter()
+            .map(|name| {
+                let (table, _) = database::open_raw_table(&txn, &name).unwrap();
+                let entries = table
+                    .iter()
+                    .unwrap()
+                    .map(|entry| {
+                        let (key, value) = entry.unwrap();
+                        (key.value().to_vec(), value.value().to_vec())
+                    })
+                    .collect();
+                (name, entries)
+            })
+            .collect()
+    }
+
+    fn dump(path: &Path, key: Encoding, value: Encoding, to: &Path) {
+        let db = Database::open(path).unwrap();
+        let tables: Vec<_> = database::get_table_names(&db)
+            .unwrap()
+            .into_iter()
+            .map(|name| ExportTable::new(name, Decoder::Auto, Decoder::Auto))
+            .collect();
+        let options = ExportOptions {
+            format: ExportFormat::Jsonl,
+            key,
+            value,
+        };
+        let mut out = Vec::new();
+        write_export(
+            &db,
+            &Schemas::default(),
+            &tables,
+            options,
+            &mut out,
+            |_, _, _| {},
+        )
+        .unwrap();
+        fs::write(to, out).unwrap();
+    }
+
+    #[test]
+    fn dumps_restore_the_bytes_they_were_taken_from() {
+        let source = TempPath::new("dump-source.redb");
+        let target = TempPath::new("dump-target.redb");
+        let dumped = TempPath::new("dump.jsonl");
+        sample(&source.0);
+        for value in [Encoding::Decoded, Encoding::Base64, Encoding::Utf8] {
+            dump(&source.0, Encoding::Decoded, value, &dumped.0);
+            fs::copy(&source.0, &target.0).unwrap();
+            import(
+                &target.0,
+                None,
+                &import_options(&[]),
+                &dumped.0,
+                &default_types(),
+            )
+            .unwrap();
+            assert_eq!(contents(&target.0), contents(&source.0), "{:?}", value);
+        }
+        // What decodes faithfully stays readable.
+        dump(&source.0, Encoding::Decoded, Encoding::Decoded, &dumped.0);
+        let text = fs::read_to_string(&dumped.0).unwrap();
+        assert!(
+            text.contains(r#""key":300,"value":"three hundred""#),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn dumps_create_their_tables_with_the_stored_types() {
+        let source = TempPath::new("typed-source.redb");
+        let target = TempPath::new("typed-target.redb");
+        let dumped = TempPath::new("typed.jsonl");
+        sample(&source.0);
+        dump(&source.0, Encoding::Decoded, Encoding::Decoded, &dumped.0);
+        let options = import_options(&[]);
+        import(&target.0, None, &options, &dumped.0, &default_types()).unwrap();
+        assert_eq!(contents(&target.0), contents(&source.0));
+        let db = Database::open(&target.0).unwrap();
+        let txn = db.begin_read().unwrap();
+        let (_, types) = database::open_raw_table(&txn, "nums").unwrap();
+        assert_eq!(
+            (types.key.name.as_str(), types.value.name.as_str()),
+            ("u64", "&str")
+        );
+        drop((txn, db));
+
+        // A table of other types isn't written to.
+        let other = TempPath::new("typed-other.redb");
+        let db = Database::create(&other.0).unwrap();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(TableDefinition::<&str, &str>::new("nums"))
+            .unwrap();
+        txn.commit().unwrap();
+        drop(db);
+        let result =
+            import(&other.0, None, &options, &dumped.0, &default_types());
+        assert!(matches!(result, Err(AppError::TableTypesDiffer(_))));
+
+        // Nor is a table created of types only a `Value` impl could make.
+        fs::write(
+            &dumped.0,
+            concat!(
+                r#"{"table":"t","key":"0a0b","value":"","#,
+                r#""key_encoding":"hex","key_type":"(u8, u8)"}"#
+            ),
+        )
+        .unwrap();
+        let result =
+            import(&target.0, None, &options, &dumped.0, &default_types());
+        assert!(matches!(result, Err(AppError::UnsupportedTable(_))));
+    }
+
+    fn default_types() -> TableTypes {
+        TableTypes {
+            key: database::builtin_type("&str").unwrap(),
+            value: database::builtin_type("&[u8]").unwrap(),
+        }
+    }
+}
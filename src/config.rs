@@ -0,0 +1,107 @@
+use crate::database::WriteDurability;
+use crate::keymap::KeyMap;
+use crate::layout::LayoutMode;
+use crate::node::Node;
+use crate::state;
+use crate::theme::Theme;
+use crate::{toml, AppError, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// User settings from `config.toml`, see `Config::default_path`.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub keymap: KeyMap,
+    pub theme: Theme,
+    /// Share of the screen taken by the table list, in percent, from
+    /// `split` under `[layout]`.
+    pub split: Option<u16>,
+    /// Arrangement of the panes, from `mode` under `[layout]`.
+    pub layout: Option<LayoutMode>,
+    /// How writes are committed, from `durability` under `[writes]`.
+    pub durability: WriteDurability,
+    /// Interval of watch mode, from `--watch`.
+    pub watch: Option<Duration>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/redb-tui/config.toml`, falling back to
+    /// `~/.config/redb-tui/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".config"))
+            })?;
+        Some(dir.join("redb-tui").join("config.toml"))
+    }
+
+    /// Reads the config file; a missing file yields the defaults.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| {
+            AppError::ConfigError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let doc = toml::parse(text).map_err(|e| e.to_string())?;
+        let keymap = match toml::get(&doc, "keys") {
+            Some(section) => KeyMap::parse(section)?,
+            None => KeyMap::default(),
+        };
+        let theme = match toml::get(&doc, "theme") {
+            Some(section) => Theme::parse(section)?,
+            None => Theme::default(),
+        };
+        let section = toml::get(&doc, "layout");
+        let split = match section.and_then(|section| toml::get(section, "split"))
+        {
+            Some(split) => Some(
+                state::parse_split(split)
+                    .map_err(|e| format!("layout.split: {}", e))?,
+            ),
+            None => None,
+        };
+        let layout = match section.and_then(|section| toml::get(section, "mode"))
+        {
+            Some(mode) => Some(
+                state::parse_layout(mode)
+                    .map_err(|e| format!("layout.mode: {}", e))?,
+            ),
+            None => None,
+        };
+        let durability = match toml::get(&doc, "writes")
+            .and_then(|section| toml::get(section, "durability"))
+        {
+            Some(durability) => parse_durability(durability)
+                .map_err(|e| format!("writes.durability: {}", e))?,
+            None => WriteDurability::default(),
+        };
+        Ok(Self {
+            keymap,
+            theme,
+            split,
+            layout,
+            durability,
+            watch: None,
+        })
+    }
+}
+
+/// Reads a durability, `immediate` or `eventual`.
+fn parse_durability(node: &Node) -> std::result::Result<WriteDurability, String> {
+    match node {
+        Node::Text(name) => WriteDurability::from_name(name).ok_or_else(|| {
+            format!("expected immediate or eventual, got '{}'", name)
+        }),
+        _ => Err("expected a string".to_string()),
+    }
+}
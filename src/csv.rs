@@ -0,0 +1,81 @@
+use std::io::{self, BufRead};
+
+/// Reads CSV records: fields separated by commas, quoted with `"` when they
+/// hold commas, quotes or line breaks, with `""` for a quote inside quotes.
+pub struct Reader<R> {
+    input: R,
+    /// Lines read so far.
+    line: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(input: R) -> Self {
+        Self { input, line: 0 }
+    }
+
+    /// The last line read.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The fields of the next record and the line it starts on, or `None`
+    /// at the end of the input. Blank lines are skipped.
+    pub fn next_record(&mut self) -> io::Result<Option<(usize, Vec<String>)>> {
+        let mut text = String::new();
+        let mut start = None;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            text.clear();
+            if self.input.read_line(&mut text)? == 0 {
+                break;
+            }
+            self.line += 1;
+            if start.is_none() {
+                if text.trim_end_matches(['\r', '\n']).is_empty() {
+                    continue;
+                }
+                start = Some(self.line);
+            }
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (c, quoted) {
+                    ('"', false) if field.is_empty() => quoted = true,
+                    ('"', true) if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    ('"', true) => quoted = false,
+                    (',', false) => fields.push(std::mem::take(&mut field)),
+                    ('\r' | '\n', false) => {}
+                    (c, _) => field.push(c),
+                }
+            }
+            // A quoted field goes on past the line break.
+            if !quoted {
+                break;
+            }
+        }
+        let Some(start) = start else {
+            return Ok(None);
+        };
+        if quoted {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("quoted field starting on line {} never ends", start),
+            ));
+        }
+        fields.push(field);
+        Ok(Some((start, fields)))
+    }
+}
+
+/// Quotes a field when it contains a separator, quote or line break.
+pub fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,16 +1,1289 @@
-use crate::Result;
-use redb::{Database, DatabaseStats, TableDefinition, TableHandle};
-use std::path::PathBuf;
+use crate::keyorder::KeyOrder;
+use crate::{cbor, decoder, json, msgpack, AppError, Result};
+use human_repr::HumanCount;
+use log::info;
+use redb::StorageBackend;
+use redb::{
+    backends::InMemoryBackend, Database, DatabaseError, DatabaseStats,
+    Durability, Key, ReadOnlyTable, ReadTransaction, ReadableTable,
+    ReadableTableMetadata, RepairSession, Savepoint, StorageError, Table,
+    TableDefinition, TableError, TableHandle, TableStats, TypeName, Value,
+    WriteTransaction,
+};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    fmt,
+    fs::{self, File},
+    io::Read,
+    ops::Bound,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+    thread,
+    time::Duration,
+};
+
+/// What redb database files start with.
+const MAGIC: [u8; 9] = [b'r', b'e', b'd', b'b', 0x1A, 0x0A, 0xA9, 0x0D, 0x0A];
 
 const USERS: TableDefinition<&str, u32> = TableDefinition::new("users");
 const PRODUCTS: TableDefinition<u32, &str> = TableDefinition::new("products");
 
+/// Number of attempts to discover the stored key/value types of a table.
+const TYPE_PROBE_ATTEMPTS: usize = 4;
+
+/// Attempts at opening a database locked by another process, and the wait
+/// before the second one, doubled after each.
+const OPEN_ATTEMPTS: u32 = 5;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Size of the largest file `open_snapshot` reads into memory unless told
+/// otherwise with `set_snapshot_limit`.
+pub const DEFAULT_SNAPSHOT_LIMIT: u64 = 1 << 30;
+
+static SNAPSHOT_LIMIT: AtomicU64 = AtomicU64::new(DEFAULT_SNAPSHOT_LIMIT);
+
 #[derive(Debug)]
 pub struct DbProperties {
     pub file_size: u64,
     pub num_tables: usize,
 }
 
+/// A key/value pair as stored on disk.
+pub type RawEntry = (Vec<u8>, Vec<u8>);
+
+/// Stored type of a table key or value, as recorded by redb.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawType {
+    pub type_name: TypeName,
+    pub name: String,
+    pub fixed_width: Option<usize>,
+    /// How redb encodes and orders the type, for the builtin types modelled
+    /// by `KeyOrder`.
+    pub order: Option<KeyOrder>,
+}
+
+impl RawType {
+    fn new(type_name: TypeName, fixed_width: Option<usize>) -> Self {
+        let name = type_name_str(&type_name);
+        // User defined types may be called anything, builtin names among
+        // them, and order themselves however they like.
+        let order = is_internal(&type_name)
+            .then(|| KeyOrder::parse(&name))
+            .flatten();
+        Self {
+            type_name,
+            name,
+            fixed_width,
+            order,
+        }
+    }
+
+    /// Checks that `data` is a key redb could have encoded for the type.
+    pub fn check_key(&self, data: &[u8]) -> Result<()> {
+        self.mismatch(data)
+            .map_or(Ok(()), |e| Err(AppError::InvalidKey(e)))
+    }
+
+    /// Checks that `data` is a value redb could have encoded for the type.
+    pub fn check_value(&self, data: &[u8]) -> Result<()> {
+        self.mismatch(data)
+            .map_or(Ok(()), |e| Err(AppError::InvalidValue(e)))
+    }
+
+    /// Why `data` can't be of the type: a length other than its fixed
+    /// width, or bytes a modelled type never has.
+    fn mismatch(&self, data: &[u8]) -> Option<String> {
+        match (self.fixed_width, &self.order) {
+            (Some(width), _) if data.len() != width => Some(format!(
+                "{} bytes where {} takes {}",
+                data.len(),
+                self.name,
+                width
+            )),
+            (_, Some(order)) if !order.check(data) => {
+                Some(format!("not encoded as a {}", self.name))
+            }
+            _ => None,
+        }
+    }
+
+    /// Placeholder type used before the real one is discovered. It never
+    /// matches a stored table, so redb reports the actual types back.
+    fn probe() -> Self {
+        Self::new(TypeName::new("redb-tui::probe"), None)
+    }
+}
+
+/// Key and value types of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableTypes {
+    pub key: RawType,
+    pub value: RawType,
+}
+
+// NOTE: redb checks the requested types against the stored table definition
+// through static trait methods, so the raw types below read their identity
+// from thread-local state. Only one raw table layout can be open per thread.
+thread_local! {
+    static RAW_KEY_TYPE: RefCell<RawType> = RefCell::new(RawType::probe());
+    static RAW_VALUE_TYPE: RefCell<RawType> = RefCell::new(RawType::probe());
+}
+
+macro_rules! raw_value {
+    ($name:ident, $state:ident) => {
+        /// Byte view over a table column of any stored type.
+        #[derive(Debug)]
+        pub struct $name;
+
+        impl Value for $name {
+            type SelfType<'a> = &'a [u8];
+            type AsBytes<'a> = &'a [u8];
+
+            fn fixed_width() -> Option<usize> {
+                $state.with(|t| t.borrow().fixed_width)
+            }
+
+            fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+            where
+                Self: 'a,
+            {
+                data
+            }
+
+            fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+            where
+                Self: 'b,
+            {
+                value
+            }
+
+            fn type_name() -> TypeName {
+                $state.with(|t| t.borrow().type_name.clone())
+            }
+        }
+    };
+}
+
+raw_value!(RawKey, RAW_KEY_TYPE);
+raw_value!(RawValue, RAW_VALUE_TYPE);
+
+impl Key for RawKey {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        RAW_KEY_TYPE.with(|t| match &t.borrow().order {
+            Some(order) => order.compare(data1, data2),
+            // Only read: tables of other key types aren't written to.
+            None => data1.cmp(data2),
+        })
+    }
+}
+
+/// Orders encoded keys the same way redb does for the builtin types. Unknown
+/// types fall back to byte order.
+pub fn compare_as(type_name: &str, data1: &[u8], data2: &[u8]) -> Ordering {
+    match KeyOrder::parse(type_name) {
+        Some(order) => order.compare(data1, data2),
+        None => data1.cmp(data2),
+    }
+}
+
+/// Extracts the plain type name, e.g. `&str`, from a redb `TypeName`.
+pub fn type_name_str(type_name: &TypeName) -> String {
+    // TypeName keeps its name private; its Debug output is the only accessor.
+    let debug = format!("{:?}", type_name);
+    debug
+        .split_once("name: \"")
+        .and_then(|(_, rest)| rest.rsplit_once('"'))
+        .map(|(name, _)| name.replace("\\\"", "\"").replace("\\\\", "\\"))
+        .unwrap_or(debug)
+}
+
+/// Whether redb itself defined the type, as opposed to a `Value` impl.
+fn is_internal(type_name: &TypeName) -> bool {
+    // The classification comes first in the Debug output, before a name
+    // that may hold anything.
+    format!("{:?}", type_name).starts_with("TypeName { classification: Internal,")
+}
+
+fn set_table_types(types: &TableTypes) {
+    RAW_KEY_TYPE.with(|t| *t.borrow_mut() = types.key.clone());
+    RAW_VALUE_TYPE.with(|t| *t.borrow_mut() = types.value.clone());
+}
+
+fn current_table_types() -> TableTypes {
+    TableTypes {
+        key: RAW_KEY_TYPE.with(|t| t.borrow().clone()),
+        value: RAW_VALUE_TYPE.with(|t| t.borrow().clone()),
+    }
+}
+
+fn raw_definition(name: &str) -> TableDefinition<'_, RawKey, RawValue> {
+    TableDefinition::new(name)
+}
+
+/// Opens a table through the raw types, adjusting them to whatever redb
+/// reports as the stored definition until the open succeeds.
+fn open_raw<T>(
+    name: &str,
+    mut open: impl FnMut() -> std::result::Result<T, TableError>,
+) -> Result<(T, TableTypes)> {
+    for _ in 0..TYPE_PROBE_ATTEMPTS {
+        match open() {
+            Ok(table) => return Ok((table, current_table_types())),
+            Err(TableError::TableTypeMismatch { key, value, .. }) => {
+                let mut types = current_table_types();
+                types.key = RawType::new(key, types.key.fixed_width);
+                types.value = RawType::new(value, types.value.fixed_width);
+                set_table_types(&types);
+            }
+            Err(TableError::TypeDefinitionChanged {
+                name: type_name,
+                width,
+                ..
+            }) => {
+                let mut types = current_table_types();
+                if types.key.type_name == type_name {
+                    types.key.fixed_width = width;
+                }
+                if types.value.type_name == type_name {
+                    types.value.fixed_width = width;
+                }
+                set_table_types(&types);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(AppError::UnsupportedTable(name.to_string()))
+}
+
+/// Opens any table for reading, regardless of its stored key/value types.
+pub fn open_raw_table(
+    txn: &ReadTransaction,
+    name: &str,
+) -> Result<(ReadOnlyTable<RawKey, RawValue>, TableTypes)> {
+    open_raw(name, || txn.open_table(raw_definition(name)))
+}
+
+/// Opens a table for writing. Existing tables keep their stored types;
+/// missing tables are created with `new_types`.
+pub fn open_raw_table_mut<'txn>(
+    txn: &'txn WriteTransaction,
+    name: &str,
+    new_types: &TableTypes,
+) -> Result<(Table<'txn, RawKey, RawValue>, TableTypes)> {
+    if txn.list_tables()?.any(|t| t.name() == name) {
+        return open_existing_table_mut(txn, name);
+    }
+    check_writable(name, new_types)?;
+    set_table_types(new_types);
+    let table = txn.open_table(raw_definition(name))?;
+    Ok((table, new_types.clone()))
+}
+
+/// Refuses tables whose key order `KeyOrder` doesn't model: redb places
+/// each key written with `RawKey::compare`, and any order but the stored
+/// type's own would corrupt the table.
+pub fn check_writable(name: &str, types: &TableTypes) -> Result<()> {
+    match types.key.order {
+        Some(_) => Ok(()),
+        None => Err(AppError::UnorderedKeys(format!(
+            "{} ({})",
+            name, types.key.name
+        ))),
+    }
+}
+
+/// Checks that `key` and `value` are encoded as `types` store them, before
+/// they are written.
+pub fn check_entry(types: &TableTypes, key: &[u8], value: &[u8]) -> Result<()> {
+    types.key.check_key(key)?;
+    types.value.check_value(value)
+}
+
+/// A change to the database requested from the TUI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOp {
+    /// Inserts an entry, replacing any value stored under the key.
+    Insert {
+        table: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// Removes the entry stored under the key.
+    Remove { table: String, key: Vec<u8> },
+    /// Writes several entries of a table at once, storing each key with its
+    /// value or removing it when it has none.
+    Batch {
+        table: String,
+        entries: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    },
+    /// Creates an empty table with the given key and value types.
+    CreateTable { table: String, types: TableTypes },
+    /// Deletes a table with all of its entries.
+    DropTable { table: String },
+    /// Removes all entries of a table, keeping the table.
+    Truncate { table: String },
+}
+
+/// How durably the TUI commits its writes; see `redb::Durability`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteDurability {
+    /// Each commit is on disk once it returns.
+    #[default]
+    Immediate,
+    /// Commits reach the disk a little later, which is quicker for many
+    /// small writes, but a crash can lose the latest ones.
+    Eventual,
+}
+
+impl WriteDurability {
+    pub fn name(self) -> &'static str {
+        match self {
+            WriteDurability::Immediate => "immediate",
+            WriteDurability::Eventual => "eventual",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "immediate" => Some(WriteDurability::Immediate),
+            "eventual" => Some(WriteDurability::Eventual),
+            _ => None,
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            WriteDurability::Immediate => WriteDurability::Eventual,
+            WriteDurability::Eventual => WriteDurability::Immediate,
+        }
+    }
+}
+
+/// Starts a write transaction committed with `durability`.
+fn begin_write(
+    db: &Database,
+    durability: WriteDurability,
+) -> Result<WriteTransaction> {
+    let mut txn = db.begin_write()?;
+    txn.set_durability(match durability {
+        WriteDurability::Immediate => Durability::Immediate,
+        WriteDurability::Eventual => Durability::Eventual,
+    });
+    Ok(txn)
+}
+
+impl WriteOp {
+    /// Whether applying the op changes the set of tables.
+    pub fn changes_tables(&self) -> bool {
+        matches!(
+            self,
+            WriteOp::CreateTable { .. } | WriteOp::DropTable { .. }
+        )
+    }
+}
+
+/// Opens an existing table for writing. Unlike `open_raw_table_mut`, never
+/// creates the table.
+fn open_existing_table_mut<'txn>(
+    txn: &'txn WriteTransaction,
+    name: &str,
+) -> Result<(Table<'txn, RawKey, RawValue>, TableTypes)> {
+    if !txn.list_tables()?.any(|t| t.name() == name) {
+        return Err(AppError::TableNotFound(name.to_string()));
+    }
+    let (table, types) = open_raw(name, || txn.open_table(raw_definition(name)))?;
+    check_writable(name, &types)?;
+    Ok((table, types))
+}
+
+/// Applies `op` in its own write transaction. Returns the values previously
+/// stored under the keys written, in order, `None` where there was none.
+pub fn apply_write(
+    db: &Database,
+    op: &WriteOp,
+    durability: WriteDurability,
+) -> Result<Vec<Option<Vec<u8>>>> {
+    let txn = begin_write(db, durability)?;
+    let previous = match op {
+        WriteOp::Insert { table, key, value } => {
+            let (mut table, types) = open_existing_table_mut(&txn, table)?;
+            check_entry(&types, key, value)?;
+            let previous = table.insert(key.as_slice(), value.as_slice())?;
+            vec![previous.map(|v| v.value().to_vec())]
+        }
+        WriteOp::Remove { table, key } => {
+            let (mut table, types) = open_existing_table_mut(&txn, table)?;
+            types.key.check_key(key)?;
+            let previous = table.remove(key.as_slice())?;
+            vec![previous.map(|v| v.value().to_vec())]
+        }
+        WriteOp::Batch { table, entries } => {
+            let (mut table, types) = open_existing_table_mut(&txn, table)?;
+            let mut previous = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                let old = match value {
+                    Some(value) => {
+                        check_entry(&types, key, value)?;
+                        table.insert(key.as_slice(), value.as_slice())?
+                    }
+                    None => {
+                        types.key.check_key(key)?;
+                        table.remove(key.as_slice())?
+                    }
+                };
+                previous.push(old.map(|v| v.value().to_vec()));
+            }
+            previous
+        }
+        WriteOp::CreateTable { table, types } => {
+            if txn.list_tables()?.any(|t| t.name() == table) {
+                return Err(AppError::TableExists(table.clone()));
+            }
+            open_raw_table_mut(&txn, table, types)?;
+            Vec::new()
+        }
+        WriteOp::Truncate { table } => {
+            let (mut table, _) = open_existing_table_mut(&txn, table)?;
+            table.retain(|_, _| false)?;
+            Vec::new()
+        }
+        WriteOp::DropTable { table } => {
+            if !txn.delete_table(raw_definition(table))? {
+                return Err(AppError::TableNotFound(table.clone()));
+            }
+            Vec::new()
+        }
+    };
+    txn.commit()?;
+    Ok(previous)
+}
+
+/// Entries copied between calls of a rename's progress callback.
+const RENAME_PROGRESS_INTERVAL: u64 = 1000;
+
+/// Renames a table by copying its entries into a new table and dropping the
+/// old one, all in one write transaction. `progress` is called with the
+/// number of entries copied so far and the total. Returns the entry count.
+pub fn rename_table(
+    db: &Database,
+    from: &str,
+    to: &str,
+    durability: WriteDurability,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<u64> {
+    let txn = begin_write(db, durability)?;
+    if txn.list_tables()?.any(|t| t.name() == to) {
+        return Err(AppError::TableExists(to.to_string()));
+    }
+    let mut copied = 0;
+    {
+        let (source, types) = open_existing_table_mut(&txn, from)?;
+        let (mut target, _) = open_raw_table_mut(&txn, to, &types)?;
+        let total = source.len()?;
+        progress(0, total);
+        for entry in source.iter()? {
+            let (key, value) = entry?;
+            target.insert(key.value(), value.value())?;
+            copied += 1;
+            if copied % RENAME_PROGRESS_INTERVAL == 0 {
+                progress(copied, total);
+            }
+        }
+        progress(copied, total);
+    }
+    txn.delete_table(raw_definition(from))?;
+    txn.commit()?;
+    Ok(copied)
+}
+
+/// What copying a table does with an entry whose key the target table
+/// already has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+    /// Keeps the target's entry.
+    Skip,
+    /// Replaces the target's entry.
+    Overwrite,
+    /// Stops with an error.
+    #[default]
+    Fail,
+}
+
+impl OnConflict {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(OnConflict::Skip),
+            "overwrite" => Some(OnConflict::Overwrite),
+            "fail" => Some(OnConflict::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// How `copy_table` writes the entries.
+#[derive(Debug, Clone, Copy)]
+pub struct CopyOptions {
+    pub on_conflict: OnConflict,
+    /// Entries written per write transaction.
+    pub batch_size: usize,
+    pub durability: WriteDurability,
+}
+
+/// Entries written and skipped by `copy_table`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Copied {
+    pub copied: u64,
+    pub skipped: u64,
+}
+
+/// Checks that table `from` of `source` can be copied into table `to` of
+/// `target` before anything is written: its keys must be of an order redb-tui
+/// can write, and the target table, if it exists, must have the same types
+/// and, with `OnConflict::Fail`, none of the keys.
+pub fn check_copy(
+    source: &Database,
+    target: &Database,
+    from: &str,
+    to: &str,
+    on_conflict: OnConflict,
+) -> Result<()> {
+    let read = source.begin_read()?;
+    if !read.list_tables()?.any(|t| t.name() == from) {
+        return Err(AppError::TableNotFound(from.to_string()));
+    }
+    let (table, types) = open_raw_table(&read, from)?;
+    check_writable(to, &types)?;
+    let target_read = target.begin_read()?;
+    if !target_read.list_tables()?.any(|t| t.name() == to) {
+        return Ok(());
+    }
+    let (target_table, target_types) = open_raw_table(&target_read, to)?;
+    if target_types != types {
+        return Err(AppError::TableTypesDiffer(format!(
+            "{} stores {} -> {}, {} stores {} -> {}",
+            from,
+            types.key.name,
+            types.value.name,
+            to,
+            target_types.key.name,
+            target_types.value.name
+        )));
+    }
+    if on_conflict == OnConflict::Fail {
+        for entry in table.iter()? {
+            let (key, _) = entry?;
+            if target_table.get(key.value())?.is_some() {
+                return Err(AppError::KeyExists(format!(
+                    "{} in {}",
+                    decoder::hex(key.value()),
+                    to
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copies table `from` of `source` into table `to` of `target`, reading the
+/// entries untyped and committing a write transaction on `target` every
+/// batch of entries. A missing target table is created with the types
+/// of the source one; an existing one must have the same types and, with
+/// `OnConflict::Fail`, none of the keys, as checked by `check_copy` before
+/// the first batch is written. `progress` is called with the number of
+/// entries copied or skipped so far and the total.
+pub fn copy_table(
+    source: &Database,
+    target: &Database,
+    from: &str,
+    to: &str,
+    options: &CopyOptions,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<Copied> {
+    let CopyOptions {
+        on_conflict,
+        batch_size,
+        durability,
+    } = *options;
+    check_copy(source, target, from, to, on_conflict)?;
+    let read = source.begin_read()?;
+    let (table, types) = open_raw_table(&read, from)?;
+    let total = table.len()?;
+    progress(0, total);
+    let mut entries = table.iter()?;
+    let mut counts = Copied::default();
+    loop {
+        let txn = begin_write(target, durability)?;
+        let mut batch = 0;
+        {
+            let (mut target, _) = open_raw_table_mut(&txn, to, &types)?;
+            for entry in entries.by_ref() {
+                let (key, value) = entry?;
+                batch += 1;
+                if on_conflict == OnConflict::Skip
+                    && target.get(key.value())?.is_some()
+                {
+                    counts.skipped += 1;
+                } else if target.insert(key.value(), value.value())?.is_some()
+                    && on_conflict == OnConflict::Fail
+                {
+                    return Err(AppError::KeyExists(format!(
+                        "{} in {}",
+                        decoder::hex(key.value()),
+                        to
+                    )));
+                } else {
+                    counts.copied += 1;
+                }
+                if batch == batch_size {
+                    break;
+                }
+            }
+        }
+        txn.commit()?;
+        progress(counts.copied + counts.skipped, total);
+        if batch < batch_size {
+            break;
+        }
+    }
+    Ok(counts)
+}
+
+/// Stored type of the builtin redb type called `name`, e.g. `u64` or `&str`.
+pub fn builtin_type(name: &str) -> Option<RawType> {
+    macro_rules! of {
+        ($t:ty) => {
+            RawType::new(<$t>::type_name(), <$t>::fixed_width())
+        };
+    }
+    let raw = match name {
+        "u8" => of!(u8),
+        "u16" => of!(u16),
+        "u32" => of!(u32),
+        "u64" => of!(u64),
+        "u128" => of!(u128),
+        "i8" => of!(i8),
+        "i16" => of!(i16),
+        "i32" => of!(i32),
+        "i64" => of!(i64),
+        "i128" => of!(i128),
+        "f32" => of!(f32),
+        "f64" => of!(f64),
+        "bool" => of!(bool),
+        "char" => of!(char),
+        "()" => of!(()),
+        "&str" | "str" => of!(&str),
+        "String" => of!(String),
+        "&[u8]" | "bytes" => of!(&[u8]),
+        _ => return None,
+    };
+    Some(raw)
+}
+
+/// Range of raw keys to read from a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRange {
+    pub start: Bound<Vec<u8>>,
+    pub end: Bound<Vec<u8>>,
+}
+
+impl KeyRange {
+    /// Parses `PREFIX*` or `START..END` (`..=` for an inclusive end, either
+    /// side may be empty). Keys are given in the display form of `key_type`,
+    /// or as hex with a `0x` prefix. Prefixes need byte-ordered keys.
+    pub fn parse(
+        text: &str,
+        key_type: &str,
+    ) -> std::result::Result<Self, String> {
+        let encode = |text: &str| match text.strip_prefix("0x") {
+            Some(hex) => decoder::parse_hex(hex),
+            None => decoder::encode_native(key_type, text),
+        };
+        if let Some(prefix) = text.strip_suffix('*') {
+            if !is_byte_ordered(key_type) {
+                return Err(format!(
+                    "{} keys aren't ordered by bytes; use START..END",
+                    key_type
+                ));
+            }
+            return Ok(Self::prefix(encode(prefix)?));
+        }
+        let Some((start, end)) = text.split_once("..") else {
+            return Err("expected PREFIX* or START..END".to_string());
+        };
+        let (end, inclusive) = match end.strip_prefix('=') {
+            Some(end) => (end, true),
+            None => (end, false),
+        };
+        let start = match start {
+            "" => Bound::Unbounded,
+            start => Bound::Included(encode(start)?),
+        };
+        let end = match (end, inclusive) {
+            ("", _) => Bound::Unbounded,
+            (end, true) => Bound::Included(encode(end)?),
+            (end, false) => Bound::Excluded(encode(end)?),
+        };
+        if let (
+            Bound::Included(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) = (&start, &end)
+        {
+            if compare_as(key_type, start, end) == Ordering::Greater {
+                return Err("range start is after its end".to_string());
+            }
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Keys starting with `prefix`.
+    fn prefix(prefix: Vec<u8>) -> Self {
+        // The first key past the prefix: drop trailing 0xff bytes and
+        // increment the last remaining one.
+        let mut end = prefix.clone();
+        while end.last() == Some(&0xff) {
+            end.pop();
+        }
+        let end = match end.last_mut() {
+            Some(last) => {
+                *last += 1;
+                Bound::Excluded(end)
+            }
+            None => Bound::Unbounded,
+        };
+        Self {
+            start: Bound::Included(prefix),
+            end,
+        }
+    }
+
+    /// The range as borrowed bounds.
+    pub fn bounds(&self) -> (Bound<&[u8]>, Bound<&[u8]>) {
+        (as_slice(&self.start), as_slice(&self.end))
+    }
+}
+
+fn as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Whether redb orders keys of a stored type by their bytes, which is what
+/// makes prefix ranges possible.
+pub fn is_byte_ordered(type_name: &str) -> bool {
+    matches!(type_name, "&str" | "String" | "&[u8]" | "Vec<u8>")
+        || type_name.starts_with("[u8;")
+}
+
+/// Reads up to `limit` entries of a table as raw key/value bytes within
+/// `range`, from the last key down when `reverse` is set, and hands them to
+/// `batch` in chunks of `batch_size` along with the table's types. The last
+/// chunk may be empty, so `batch` is called at least once. Reading stops
+/// early when `batch` returns false. Returns the number of entries read.
+/// Seeking to a key is a ranged read, not a scan.
+pub fn scan_table_entries(
+    db: &Database,
+    name: &str,
+    range: (Bound<&[u8]>, Bound<&[u8]>),
+    reverse: bool,
+    limit: usize,
+    batch_size: usize,
+    mut batch: impl FnMut(&TableTypes, Vec<RawEntry>) -> bool,
+) -> Result<usize> {
+    let txn = db.begin_read()?;
+    let (table, types) = open_raw_table(&txn, name)?;
+    let range = table.range::<&[u8]>(range)?;
+    let iter: Box<dyn Iterator<Item = _>> = if reverse {
+        Box::new(range.rev())
+    } else {
+        Box::new(range)
+    };
+    let mut count = 0;
+    let mut entries = Vec::with_capacity(batch_size);
+    for entry in iter.take(limit) {
+        let (key, value) = entry?;
+        entries.push((key.value().to_vec(), value.value().to_vec()));
+        count += 1;
+        if entries.len() == batch_size
+            && !batch(&types, std::mem::take(&mut entries))
+        {
+            return Ok(count);
+        }
+    }
+    batch(&types, entries);
+    Ok(count)
+}
+
+/// Byte order of a detected fixed-width integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Best guess at what an opaque byte value holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedType {
+    Utf8,
+    Json,
+    MsgPack,
+    Cbor,
+    U32(Endian),
+    U64(Endian),
+    I64(Endian),
+    Uuid,
+    Binary,
+}
+
+impl DetectedType {
+    pub fn label(self) -> &'static str {
+        match self {
+            DetectedType::Utf8 => "utf8",
+            DetectedType::Json => "json",
+            DetectedType::MsgPack => "msgpack",
+            DetectedType::Cbor => "cbor",
+            DetectedType::U32(Endian::Little) => "u32le",
+            DetectedType::U32(Endian::Big) => "u32be",
+            DetectedType::U64(Endian::Little) => "u64le",
+            DetectedType::U64(Endian::Big) => "u64be",
+            DetectedType::I64(Endian::Little) => "i64le",
+            DetectedType::I64(Endian::Big) => "i64be",
+            DetectedType::Uuid => "uuid",
+            DetectedType::Binary => "bin",
+        }
+    }
+}
+
+/// Whether values of a stored redb type are opaque bytes whose meaning has
+/// to be guessed, as opposed to a builtin type with a known encoding.
+pub fn is_opaque_type(type_name: &str) -> bool {
+    !type_name.starts_with("Option<")
+        && !matches!(
+            type_name,
+            "&str"
+                | "String"
+                | "u8"
+                | "u16"
+                | "u32"
+                | "u64"
+                | "u128"
+                | "i8"
+                | "i16"
+                | "i32"
+                | "i64"
+                | "i128"
+                | "f32"
+                | "f64"
+                | "bool"
+                | "char"
+                | "()"
+        )
+}
+
+/// Guesses the encoding of `bytes`: readable UTF-8 (and JSON documents
+/// within it), then 4/8-byte integers and 16-byte UUIDs, then
+/// MessagePack/CBOR containers.
+pub fn detect_type(bytes: &[u8]) -> DetectedType {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        if !bytes.is_empty()
+            && !text.chars().any(|c| c.is_control() && !c.is_whitespace())
+        {
+            let trimmed = text.trim_start();
+            if (trimmed.starts_with('{') || trimmed.starts_with('['))
+                && json::parse(text).is_ok()
+            {
+                return DetectedType::Json;
+            }
+            return DetectedType::Utf8;
+        }
+    }
+    match bytes.len() {
+        4 => {
+            let le = u32::from_le_bytes(bytes.try_into().unwrap());
+            let be = u32::from_be_bytes(bytes.try_into().unwrap());
+            // The smaller reading is the likelier one: counters and ids
+            // rarely fill the high bytes.
+            return DetectedType::U32(if be < le {
+                Endian::Big
+            } else {
+                Endian::Little
+            });
+        }
+        8 => {
+            let le = u64::from_le_bytes(bytes.try_into().unwrap());
+            let be = u64::from_be_bytes(bytes.try_into().unwrap());
+            let candidates = [
+                (DetectedType::U64(Endian::Little), le),
+                (DetectedType::U64(Endian::Big), be),
+                (
+                    DetectedType::I64(Endian::Little),
+                    (le as i64).unsigned_abs(),
+                ),
+                (DetectedType::I64(Endian::Big), (be as i64).unsigned_abs()),
+            ];
+            return candidates
+                .into_iter()
+                .min_by_key(|(_, magnitude)| *magnitude)
+                .map(|(detected, _)| detected)
+                .unwrap();
+        }
+        16 => return DetectedType::Uuid,
+        _ => {}
+    }
+    match bytes.first() {
+        Some(0x80..=0x9f | 0xdc..=0xdf) if msgpack::decode(bytes).is_ok() => {
+            DetectedType::MsgPack
+        }
+        Some(0x80..=0xbf) if cbor::decode(bytes).is_ok() => DetectedType::Cbor,
+        _ => DetectedType::Binary,
+    }
+}
+
+/// How a value no decoder reads was likely packed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packing {
+    Gzip,
+    Zlib,
+    Zstd,
+    Lz4,
+    Bzip2,
+    Xz,
+    Snappy,
+    Zip,
+    /// Bytes close to random: compressed without a header, or encrypted.
+    Random,
+}
+
+impl Packing {
+    pub fn label(self) -> &'static str {
+        match self {
+            Packing::Gzip => "gzip",
+            Packing::Zlib => "zlib",
+            Packing::Zstd => "zstd",
+            Packing::Lz4 => "lz4",
+            Packing::Bzip2 => "bzip2",
+            Packing::Xz => "xz",
+            Packing::Snappy => "snappy",
+            Packing::Zip => "zip",
+            Packing::Random => "random",
+        }
+    }
+}
+
+/// Leading bytes of the compressed formats `detect_packing` knows.
+const PACKING_MAGIC: &[(&[u8], Packing)] = &[
+    (&[0x1f, 0x8b], Packing::Gzip),
+    (&[0x28, 0xb5, 0x2f, 0xfd], Packing::Zstd),
+    (&[0x04, 0x22, 0x4d, 0x18], Packing::Lz4),
+    (b"BZh", Packing::Bzip2),
+    (b"\xfd7zXZ\x00", Packing::Xz),
+    (b"\xff\x06\x00\x00sNaPpY", Packing::Snappy),
+    (b"PK\x03\x04", Packing::Zip),
+];
+
+/// Bytes a value needs for its entropy to tell random bytes apart.
+const MIN_ENTROPY_BYTES: usize = 32;
+
+/// Share of the most entropy bytes of a length can have above which they
+/// count as random.
+const RANDOM_ENTROPY: f64 = 0.9;
+
+/// Recognizes values that are compressed, by the magic bytes of common
+/// formats, or compressed without a header or encrypted, by their entropy.
+/// Text is never taken for either.
+pub fn detect_packing(bytes: &[u8]) -> Option<Packing> {
+    let detected = detect_type(bytes);
+    if matches!(detected, DetectedType::Utf8 | DetectedType::Json) {
+        return None;
+    }
+    if let Some((_, packing)) = PACKING_MAGIC
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+    {
+        return Some(*packing);
+    }
+    // zlib starts with a deflate method byte and a header checksum.
+    if let [cmf @ 0x78, flg, ..] = bytes {
+        if (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 {
+            return Some(Packing::Zlib);
+        }
+    }
+    // Short values can't reach 8 bits per byte, so the bar is relative to
+    // what their length allows.
+    let most = (bytes.len() as f64).log2().min(8.0);
+    (detected == DetectedType::Binary
+        && bytes.len() >= MIN_ENTROPY_BYTES
+        && entropy(bytes) >= RANDOM_ENTROPY * most)
+        .then_some(Packing::Random)
+}
+
+/// Shannon entropy of `bytes`, in bits per byte.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in bytes {
+        counts[*b as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Opens the database at `path`. A lock held by another process is waited
+/// on for about a second and a half, in case it is about to be released,
+/// before giving up with `AppError::DatabaseLocked`.
+///
+/// A database that was not closed cleanly is repaired first, which takes a
+/// while for big ones. `on_repair` is called along the way with the progress
+/// from 0 to 1, and stops the repair by returning false.
+pub fn open(
+    path: &Path,
+    on_repair: impl Fn(f64) -> bool + 'static,
+) -> Result<Database> {
+    let on_repair = Rc::new(on_repair);
+    let mut delay = OPEN_RETRY_DELAY;
+    for attempt in 1..=OPEN_ATTEMPTS {
+        let mut builder = Database::builder();
+        builder.set_repair_callback(repair_callback(path, on_repair.clone()));
+        let result = catch_corruption(path, || builder.open(path))?;
+        match result {
+            Err(DatabaseError::DatabaseAlreadyOpen)
+                if attempt < OPEN_ATTEMPTS =>
+            {
+                info!("{:?} is locked, retrying in {:?}", path, delay);
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(DatabaseError::DatabaseAlreadyOpen) => break,
+            result => return result.map_err(|e| open_error(path, e)),
+        }
+    }
+    Err(AppError::DatabaseLocked(path.to_path_buf()))
+}
+
+/// Opens a copy of the database at `path` held in memory, for browsing a
+/// database that another process keeps locked. Transactions the other
+/// process has not committed yet are rolled back in the copy, which takes a
+/// repair reported to `on_repair` as by `open`. Writes to it are lost.
+/// Files bigger than the snapshot limit are refused rather than read whole.
+pub fn open_snapshot(
+    path: &Path,
+    on_repair: impl Fn(f64) -> bool + 'static,
+) -> Result<Database> {
+    info!("Opening a snapshot of {:?}", path);
+    let limit = SNAPSHOT_LIMIT.load(AtomicOrdering::Relaxed);
+    let bytes = read_within(path, limit)?;
+    let backend = InMemoryBackend::new();
+    backend.set_len(bytes.len() as u64)?;
+    backend.write(0, &bytes)?;
+    let mut builder = Database::builder();
+    builder.set_repair_callback(repair_callback(path, Rc::new(on_repair)));
+    catch_corruption(path, || builder.create_with_backend(backend))?
+        .map_err(|e| open_error(path, e))
+}
+
+/// The contents of the file at `path`, unless it is bigger than `limit`
+/// bytes, 0 standing for no limit.
+fn read_within(path: &Path, limit: u64) -> Result<Vec<u8>> {
+    let size = fs::metadata(path)?.len();
+    if limit > 0 && size > limit {
+        return Err(AppError::SnapshotTooLarge(path.to_path_buf(), size, limit));
+    }
+    Ok(fs::read(path)?)
+}
+
+/// Sets the size of the largest file `open_snapshot` reads into memory, 0
+/// for no limit. Called once at startup, from `--snapshot-limit`.
+pub fn set_snapshot_limit(limit: u64) {
+    SNAPSHOT_LIMIT.store(limit, AtomicOrdering::Relaxed);
+}
+
+thread_local! {
+    /// Whether `catch_corruption` is running on this thread, whose panics
+    /// are caught and only logged.
+    static CATCHING_CORRUPTION: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs the panic hook that only logs the panics `catch_corruption`
+/// catches, handing any other to the hook installed before. Called once
+/// at startup; the hook is never swapped, as other threads may panic at
+/// any time.
+pub fn install_panic_hook() {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| match panic_is_caught() {
+        true => info!("redb panicked: {}", info),
+        false => hook(info),
+    }));
+}
+
+/// Whether a panic on this thread is one `catch_corruption` catches. Hooks
+/// installed over the one of `install_panic_hook` leave those alone.
+pub fn panic_is_caught() -> bool {
+    CATCHING_CORRUPTION.with(Cell::get)
+}
+
+/// Runs `open`, turning a panic, which is how redb reacts to some damage
+/// of the file, into `AppError::DatabaseCorrupted`. The panic is only
+/// logged, see `install_panic_hook`.
+fn catch_corruption<T>(path: &Path, open: impl FnOnce() -> T) -> Result<T> {
+    let catching = CATCHING_CORRUPTION.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(open));
+    CATCHING_CORRUPTION.set(catching);
+    result.map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "redb panicked".to_string());
+        AppError::DatabaseCorrupted(path.to_path_buf(), message)
+    })
+}
+
+fn repair_callback(
+    path: &Path,
+    on_repair: Rc<impl Fn(f64) -> bool + 'static>,
+) -> impl Fn(&mut RepairSession) + 'static {
+    let path = path.to_path_buf();
+    let started = Cell::new(false);
+    move |session| {
+        if !started.replace(true) {
+            info!("Repairing {:?}", path);
+        }
+        if !on_repair(session.progress()) {
+            info!("Aborting the repair of {:?}", path);
+            session.abort();
+        }
+    }
+}
+
+/// Checks the whole database file, repairing it if needed; see
+/// `Database::check_integrity`. Returns whether it was intact.
+pub fn check_integrity(db: &mut Database, path: &Path) -> Result<bool> {
+    info!("Checking the integrity of {:?}", path);
+    db.check_integrity().map_err(|e| open_error(path, e))
+}
+
+/// File size and fragmentation of a database before and after `compact`.
+#[derive(Debug, Clone, Copy)]
+pub struct Compaction {
+    pub file_size: (u64, u64),
+    pub fragmented_bytes: (u64, u64),
+}
+
+impl fmt::Display for Compaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}, fragmented {} -> {}",
+            self.file_size.0.human_count_bytes(),
+            self.file_size.1.human_count_bytes(),
+            self.fragmented_bytes.0.human_count_bytes(),
+            self.fragmented_bytes.1.human_count_bytes(),
+        )
+    }
+}
+
+/// Moves data to the start of the file and shrinks it, freeing the space
+/// left by deleted entries; see `Database::compact`.
+pub fn compact(db: &mut Database, path: &Path) -> Result<Compaction> {
+    info!("Compacting {:?}", path);
+    let before = (
+        fs::metadata(path)?.len(),
+        get_database_stats(db)?.fragmented_bytes(),
+    );
+    while db.compact()? {}
+    let after = (
+        fs::metadata(path)?.len(),
+        get_database_stats(db)?.fragmented_bytes(),
+    );
+    Ok(Compaction {
+        file_size: (before.0, after.0),
+        fragmented_bytes: (before.1, after.1),
+    })
+}
+
+/// Ids of the persistent savepoints stored in the database, oldest first.
+pub fn list_savepoints(db: &Database) -> Result<Vec<u64>> {
+    // Savepoints are only listed by write transactions; this one is
+    // dropped without changing anything.
+    let txn = db.begin_write()?;
+    let ids = txn.list_persistent_savepoints()?.collect();
+    txn.abort()?;
+    Ok(ids)
+}
+
+/// Stores a savepoint of the current state in the file and returns its id.
+pub fn create_persistent_savepoint(db: &Database) -> Result<u64> {
+    let txn = db.begin_write()?;
+    let id = txn.persistent_savepoint()?;
+    txn.commit()?;
+    info!("Created persistent savepoint {}", id);
+    Ok(id)
+}
+
+/// Takes a savepoint of the current state, kept as long as the returned
+/// `Savepoint` lives.
+pub fn create_ephemeral_savepoint(db: &Database) -> Result<Savepoint> {
+    let txn = db.begin_write()?;
+    let savepoint = txn.ephemeral_savepoint()?;
+    txn.abort()?;
+    info!("Created ephemeral savepoint");
+    Ok(savepoint)
+}
+
+/// Rolls the database back to `savepoint`. Savepoints taken after it
+/// become invalid.
+pub fn restore_savepoint(db: &Database, savepoint: &Savepoint) -> Result<()> {
+    let mut txn = db.begin_write()?;
+    txn.restore_savepoint(savepoint)?;
+    txn.commit()?;
+    info!("Restored ephemeral savepoint");
+    Ok(())
+}
+
+/// Rolls the database back to the persistent savepoint `id`, as
+/// `restore_savepoint` does.
+pub fn restore_persistent_savepoint(db: &Database, id: u64) -> Result<()> {
+    let mut txn = db.begin_write()?;
+    let savepoint = txn.get_persistent_savepoint(id)?;
+    txn.restore_savepoint(&savepoint)?;
+    txn.commit()?;
+    info!("Restored persistent savepoint {}", id);
+    Ok(())
+}
+
+/// Deletes the persistent savepoint `id`.
+pub fn delete_savepoint(db: &Database, id: u64) -> Result<()> {
+    let txn = db.begin_write()?;
+    let deleted = txn.delete_persistent_savepoint(id)?;
+    txn.commit()?;
+    if !deleted {
+        return Err(AppError::SavepointNotFound(id));
+    }
+    info!("Deleted persistent savepoint {}", id);
+    Ok(())
+}
+
+/// Tells a database beyond repair apart from other errors.
+fn open_error(path: &Path, e: DatabaseError) -> AppError {
+    match e {
+        DatabaseError::Storage(StorageError::Corrupted(message)) => {
+            AppError::DatabaseCorrupted(path.to_path_buf(), message)
+        }
+        e => e.into(),
+    }
+}
+
 pub fn create_dummy_database(path: &PathBuf) -> Result<()> {
     let db = Database::create(path)?;
     let write_txn = db.begin_write()?;
@@ -36,14 +1309,579 @@ pub fn create_dummy_database(path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Whether the file at `path` starts like a redb database.
+pub fn is_redb_file(path: &Path) -> bool {
+    let mut header = [0; MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| header == MAGIC)
+}
+
+/// Number of tables of the database at `path`, opened just for counting
+/// them. Unlike `open`, it neither waits for another process to close the
+/// database nor repairs it, failing with `DatabaseLocked` and
+/// `DatabaseError::RepairAborted` instead.
+pub fn count_tables(path: &Path) -> Result<usize> {
+    let mut builder = Database::builder();
+    builder.set_repair_callback(|session| session.abort());
+    let db = match catch_corruption(path, || builder.open(path))? {
+        Ok(db) => db,
+        Err(DatabaseError::DatabaseAlreadyOpen) => {
+            return Err(AppError::DatabaseLocked(path.to_path_buf()))
+        }
+        Err(e) => return Err(open_error(path, e)),
+    };
+    Ok(get_table_names(&db)?.len())
+}
+
 pub fn get_table_names(db: &Database) -> Result<Vec<String>> {
     let read_txn = db.begin_read()?;
     let tables = read_txn.list_tables()?;
     Ok(tables.into_iter().map(|t| t.name().to_string()).collect())
 }
 
-pub fn get_database_stats(db: &Database) -> DatabaseStats {
-    let txn = db.begin_write().unwrap();
-    let stats = txn.stats().unwrap();
-    stats
+pub fn get_database_stats(db: &Database) -> Result<DatabaseStats> {
+    let txn = db.begin_write()?;
+    Ok(txn.stats()?)
+}
+
+/// Storage usage of the whole database.
+#[derive(Debug, Clone, Default)]
+pub struct StorageSummary {
+    pub tree_height: u32,
+    /// Only known to redb's `DatabaseStats`, which needs a write transaction.
+    pub allocated_pages: Option<u64>,
+    pub page_size: Option<usize>,
+    pub leaf_pages: u64,
+    pub branch_pages: u64,
+    pub stored_bytes: u64,
+    pub metadata_bytes: u64,
+    pub fragmented_bytes: u64,
+}
+
+/// Summarizes storage usage. In read-only mode no write transaction is
+/// taken and the figures are summed over the user tables instead, which
+/// leaves out redb's internal tables and free pages.
+pub fn get_storage_summary(
+    db: &Database,
+    read_only: bool,
+) -> Result<StorageSummary> {
+    if !read_only {
+        let stats = get_database_stats(db)?;
+        return Ok(StorageSummary {
+            tree_height: stats.tree_height(),
+            allocated_pages: Some(stats.allocated_pages()),
+            page_size: Some(stats.page_size()),
+            leaf_pages: stats.leaf_pages(),
+            branch_pages: stats.branch_pages(),
+            stored_bytes: stats.stored_bytes(),
+            metadata_bytes: stats.metadata_bytes(),
+            fragmented_bytes: stats.fragmented_bytes(),
+        });
+    }
+    let txn = db.begin_read()?;
+    let mut summary = StorageSummary::default();
+    for handle in txn.list_tables()? {
+        let (table, _) = open_raw_table(&txn, handle.name())?;
+        let stats = table.stats()?;
+        summary.tree_height = summary.tree_height.max(stats.tree_height());
+        summary.leaf_pages += stats.leaf_pages();
+        summary.branch_pages += stats.branch_pages();
+        summary.stored_bytes += stats.stored_bytes();
+        summary.metadata_bytes += stats.metadata_bytes();
+        summary.fragmented_bytes += stats.fragmented_bytes();
+    }
+    Ok(summary)
+}
+
+/// Number of entries in a table.
+pub fn table_len(db: &Database, name: &str) -> Result<u64> {
+    let txn = db.begin_read()?;
+    let (table, _) = open_raw_table(&txn, name)?;
+    Ok(table.len()?)
+}
+
+/// The key `n` entries before the end of `range` in table `name`, where
+/// the last `n` entries start; `None` when the range holds fewer.
+pub fn tail_start(
+    db: &Database,
+    name: &str,
+    range: (Bound<&[u8]>, Bound<&[u8]>),
+    n: usize,
+) -> Result<Option<Vec<u8>>> {
+    let txn = db.begin_read()?;
+    let (table, _) = open_raw_table(&txn, name)?;
+    let mut entries = table.range::<&[u8]>(range)?.rev();
+    match entries.nth(n.saturating_sub(1)) {
+        Some(entry) => Ok(Some(entry?.0.value().to_vec())),
+        None => Ok(None),
+    }
+}
+
+/// The stored types of table `name` and the value stored under `key`, if
+/// any.
+pub fn get_raw_value(
+    db: &Database,
+    name: &str,
+    key: &[u8],
+) -> Result<(TableTypes, Option<Vec<u8>>)> {
+    let txn = db.begin_read()?;
+    let (table, types) = open_raw_table(&txn, name)?;
+    let value = table.get(key)?.map(|v| v.value().to_vec());
+    Ok((types, value))
+}
+
+/// The entries of table `name` stored under `keys`, in the order of the
+/// keys; keys not stored are left out.
+pub fn get_raw_entries<'a>(
+    db: &Database,
+    name: &str,
+    keys: impl IntoIterator<Item = &'a [u8]>,
+) -> Result<Vec<RawEntry>> {
+    let txn = db.begin_read()?;
+    let (table, _) = open_raw_table(&txn, name)?;
+    let mut entries = Vec::new();
+    for key in keys {
+        if let Some(value) = table.get(key)? {
+            entries.push((key.to_vec(), value.value().to_vec()));
+        }
+    }
+    Ok(entries)
+}
+
+/// A key under a prefix, or a group of the keys going on past a separator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyChild {
+    /// Keys starting with this prefix, which ends with the separator.
+    Group(Vec<u8>),
+    Key(Vec<u8>),
+}
+
+/// The keys of table `name` starting with `prefix`, in key order, with the
+/// keys folded into a group per part `group_len` finds at the start of
+/// what follows the prefix. Keys must be ordered by bytes. Groups are
+/// skipped over by ranged reads, not read through. Stops at `limit`
+/// children, returning whether there were more.
+pub fn key_children(
+    db: &Database,
+    name: &str,
+    prefix: &[u8],
+    group_len: impl Fn(&[u8]) -> Option<usize>,
+    limit: usize,
+) -> Result<(Vec<KeyChild>, bool)> {
+    let txn = db.begin_read()?;
+    let (table, _) = open_raw_table(&txn, name)?;
+    let KeyRange { mut start, end } = KeyRange::prefix(prefix.to_vec());
+    let mut children = Vec::new();
+    loop {
+        let mut skip_to = None;
+        for entry in table.range::<&[u8]>((as_slice(&start), as_slice(&end)))? {
+            let key = entry?.0.value().to_vec();
+            if children.len() == limit {
+                return Ok((children, true));
+            }
+            match group_len(&key[prefix.len()..]) {
+                Some(len) => {
+                    let group = key[..prefix.len() + len].to_vec();
+                    skip_to = Some(KeyRange::prefix(group.clone()).end);
+                    children.push(KeyChild::Group(group));
+                    break;
+                }
+                None => children.push(KeyChild::Key(key)),
+            }
+        }
+        // Go on from the first key past the group, if any.
+        match skip_to {
+            Some(Bound::Excluded(next)) => start = Bound::Included(next),
+            _ => return Ok((children, false)),
+        }
+    }
+}
+
+/// Entry count, storage stats and stored types of a table.
+pub fn get_table_stats(
+    db: &Database,
+    name: &str,
+) -> Result<(u64, TableStats, TableTypes)> {
+    let txn = db.begin_read()?;
+    let (table, types) = open_raw_table(&txn, name)?;
+    Ok((table.len()?, table.stats()?, types))
+}
+
+/// Entry count, storage stats and stored types of a table, for the stats
+/// and schema views.
+#[derive(Debug)]
+pub struct TableSummary {
+    pub name: String,
+    pub entries: u64,
+    pub stats: TableStats,
+    pub types: TableTypes,
+}
+
+/// Summaries of all tables, in name order. Tables that can't be read are
+/// left out, and logged.
+pub fn get_table_summaries(db: &Database) -> Result<Vec<TableSummary>> {
+    let mut summaries = Vec::new();
+    for name in get_table_names(db)? {
+        match get_table_stats(db, &name) {
+            Ok((entries, stats, types)) => summaries.push(TableSummary {
+                name,
+                entries,
+                stats,
+                types,
+            }),
+            Err(e) => info!("Failed to read stats of {}: {}", name, e),
+        }
+    }
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAIRS: TableDefinition<(u64, u64), u64> = TableDefinition::new("pairs");
+
+    fn memory_database() -> Database {
+        Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap()
+    }
+
+    fn insert(
+        db: &Database,
+        table: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        let op = WriteOp::Insert {
+            table: table.to_string(),
+            key: key.to_vec(),
+            value: value.to_vec(),
+        };
+        apply_write(db, &op, WriteDurability::Immediate).map(|_| ())
+    }
+
+    #[test]
+    fn raw_writes_keep_tuple_keys_in_order() {
+        let db = memory_database();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(PAIRS).unwrap();
+            for i in 0..2000u64 {
+                table.insert((i, 0), i).unwrap();
+            }
+        }
+        txn.commit().unwrap();
+
+        let key = <(u64, u64)>::as_bytes(&(1500, 0));
+        insert(&db, "pairs", &key, &7u64.to_le_bytes()).unwrap();
+        let key = <(u64, u64)>::as_bytes(&(255, 1));
+        insert(&db, "pairs", &key, &8u64.to_le_bytes()).unwrap();
+
+        let txn = db.begin_read().unwrap();
+        let table = txn.open_table(PAIRS).unwrap();
+        assert_eq!(table.len().unwrap(), 2001);
+        assert_eq!(table.get((1500, 0)).unwrap().unwrap().value(), 7);
+        assert_eq!(table.get((255, 1)).unwrap().unwrap().value(), 8);
+        let keys: Vec<(u64, u64)> = table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value())
+            .collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn refuses_values_of_the_wrong_width() {
+        let db = memory_database();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(PAIRS).unwrap();
+        txn.commit().unwrap();
+        let key = <(u64, u64)>::as_bytes(&(1, 2));
+        assert!(matches!(
+            insert(&db, "pairs", &key, &[1]),
+            Err(AppError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            insert(&db, "pairs", &key[..8], &[0; 8]),
+            Err(AppError::InvalidKey(_))
+        ));
+    }
+
+    /// A key type of an order only its own `compare` knows.
+    #[derive(Debug)]
+    struct Reversed;
+
+    impl Value for Reversed {
+        type SelfType<'a> = u8;
+        type AsBytes<'a> = [u8; 1];
+
+        fn fixed_width() -> Option<usize> {
+            Some(1)
+        }
+
+        fn from_bytes<'a>(data: &'a [u8]) -> u8
+        where
+            Self: 'a,
+        {
+            data[0]
+        }
+
+        fn as_bytes<'a, 'b: 'a>(value: &'a u8) -> [u8; 1]
+        where
+            Self: 'b,
+        {
+            [*value]
+        }
+
+        fn type_name() -> TypeName {
+            // Named like a builtin type, which must not fool the writes.
+            TypeName::new("u8")
+        }
+    }
+
+    impl Key for Reversed {
+        fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+            data2.cmp(data1)
+        }
+    }
+
+    #[test]
+    fn refuses_writes_to_user_defined_keys() {
+        const REVERSED: TableDefinition<Reversed, u8> =
+            TableDefinition::new("reversed");
+        let db = memory_database();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(REVERSED).unwrap().insert(1, 1).unwrap();
+        txn.commit().unwrap();
+        assert!(matches!(
+            insert(&db, "reversed", &[2], &[2]),
+            Err(AppError::UnorderedKeys(_))
+        ));
+        let op = WriteOp::Remove {
+            table: "reversed".to_string(),
+            key: vec![1],
+        };
+        assert!(matches!(
+            apply_write(&db, &op, WriteDurability::Immediate),
+            Err(AppError::UnorderedKeys(_))
+        ));
+    }
+
+    #[test]
+    fn snapshots_refuse_files_past_the_limit() {
+        let path = std::env::temp_dir()
+            .join(format!("redb-tui-snapshot-{}.redb", std::process::id()));
+        fs::write(&path, [0; 2048]).unwrap();
+        let too_large = read_within(&path, 1024);
+        let unlimited = read_within(&path, 0).map(|bytes| bytes.len());
+        let within = read_within(&path, 2048).map(|bytes| bytes.len());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            too_large,
+            Err(AppError::SnapshotTooLarge(_, 2048, 1024))
+        ));
+        assert_eq!(unlimited.unwrap(), 2048);
+        assert_eq!(within.unwrap(), 2048);
+    }
+
+    #[test]
+    fn only_redb_types_are_internal() {
+        assert!(is_internal(&<(u64, &str)>::type_name()));
+        let user = TypeName::new("classification: Internal, name: \"u64\"");
+        assert!(!is_internal(&user));
+        assert!(!is_internal(&Reversed::type_name()));
+    }
+
+    #[test]
+    fn checks_copies_can_be_written_before_writing() {
+        const REVERSED: TableDefinition<Reversed, u8> =
+            TableDefinition::new("reversed");
+        let source = memory_database();
+        let txn = source.begin_write().unwrap();
+        txn.open_table(REVERSED).unwrap().insert(1, 1).unwrap();
+        txn.commit().unwrap();
+        let target = memory_database();
+        for on_conflict in [OnConflict::Skip, OnConflict::Fail] {
+            assert!(matches!(
+                check_copy(&source, &target, "reversed", "copy", on_conflict),
+                Err(AppError::UnorderedKeys(_))
+            ));
+        }
+        assert!(get_table_names(&target).unwrap().is_empty());
+    }
+
+    fn pair_keys(db: &Database, name: &str) -> Vec<(u64, u64)> {
+        let definition: TableDefinition<(u64, u64), u64> =
+            TableDefinition::new(name);
+        let txn = db.begin_read().unwrap();
+        let table = txn.open_table(definition).unwrap();
+        table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value())
+            .collect()
+    }
+
+    #[test]
+    fn every_write_path_keeps_tuple_keys_in_order() {
+        let db = memory_database();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(PAIRS).unwrap();
+            for i in 0..500u64 {
+                table.insert((i, 0), i).unwrap();
+            }
+        }
+        txn.commit().unwrap();
+        let key = |a: u64, b: u64| <(u64, u64)>::as_bytes(&(a, b));
+        let value = 1u64.to_le_bytes().to_vec();
+
+        // Deletions and undo go through batches and removals.
+        let op = WriteOp::Batch {
+            table: "pairs".to_string(),
+            entries: vec![
+                (key(300, 0), None),
+                (key(256, 1), Some(value.clone())),
+                (key(1, 256), Some(value.clone())),
+            ],
+        };
+        apply_write(&db, &op, WriteDurability::Immediate).unwrap();
+        let op = WriteOp::Remove {
+            table: "pairs".to_string(),
+            key: key(255, 0),
+        };
+        apply_write(&db, &op, WriteDurability::Immediate).unwrap();
+        let mut expected: Vec<(u64, u64)> = (0..500)
+            .filter(|i| ![255, 300].contains(i))
+            .map(|i| (i, 0))
+            .chain([(256, 1), (1, 256)])
+            .collect();
+        expected.sort();
+        assert_eq!(pair_keys(&db, "pairs"), expected);
+
+        rename_table(
+            &db,
+            "pairs",
+            "renamed",
+            WriteDurability::Immediate,
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(pair_keys(&db, "renamed"), expected);
+
+        let target = memory_database();
+        let options = CopyOptions {
+            on_conflict: OnConflict::Fail,
+            batch_size: 64,
+            durability: WriteDurability::Immediate,
+        };
+        copy_table(&db, &target, "renamed", "copy", &options, |_, _| {}).unwrap();
+        assert_eq!(pair_keys(&target, "copy"), expected);
+    }
+
+    #[test]
+    fn every_write_path_refuses_user_defined_keys() {
+        const REVERSED: TableDefinition<Reversed, u8> =
+            TableDefinition::new("reversed");
+        let db = memory_database();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(REVERSED).unwrap().insert(1, 1).unwrap();
+        txn.commit().unwrap();
+        fn unordered<T>(result: Result<T>) {
+            assert!(matches!(result, Err(AppError::UnorderedKeys(_))))
+        }
+        let durability = WriteDurability::Immediate;
+        let op = WriteOp::Batch {
+            table: "reversed".to_string(),
+            entries: vec![(vec![2], Some(vec![2]))],
+        };
+        unordered(apply_write(&db, &op, durability));
+        let op = WriteOp::Truncate {
+            table: "reversed".to_string(),
+        };
+        unordered(apply_write(&db, &op, durability));
+        unordered(rename_table(&db, "reversed", "to", durability, |_, _| {}));
+        let options = CopyOptions {
+            on_conflict: OnConflict::Overwrite,
+            batch_size: 64,
+            durability,
+        };
+        let target = memory_database();
+        unordered(copy_table(
+            &db,
+            &target,
+            "reversed",
+            "to",
+            &options,
+            |_, _| {},
+        ));
+        assert_eq!(get_table_names(&db).unwrap(), ["reversed"]);
+        assert!(get_table_names(&target).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_copies_write_nothing() {
+        let (source, target) = (memory_database(), memory_database());
+        for (db, keys) in [(&source, 0..100u64), (&target, 99..100)] {
+            let txn = db.begin_write().unwrap();
+            {
+                let mut table = txn.open_table(PAIRS).unwrap();
+                for i in keys {
+                    table.insert((i, 0), i).unwrap();
+                }
+            }
+            txn.commit().unwrap();
+        }
+        let options = CopyOptions {
+            on_conflict: OnConflict::Fail,
+            batch_size: 10,
+            durability: WriteDurability::Immediate,
+        };
+        assert!(matches!(
+            copy_table(&source, &target, "pairs", "pairs", &options, |_, _| {}),
+            Err(AppError::KeyExists(_))
+        ));
+        assert_eq!(pair_keys(&target, "pairs"), [(99, 0)]);
+    }
+
+    /// Set in the process `only_caught_panics_are_kept_from_other_hooks`
+    /// runs itself in.
+    const OWN_PROCESS: &str = "REDB_TUI_PANIC_HOOK_TEST";
+
+    #[test]
+    fn only_caught_panics_are_kept_from_other_hooks() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        // The hook is global: swapping it while other tests run would
+        // swallow their panics, so the test reruns alone in a process of
+        // its own.
+        if std::env::var_os(OWN_PROCESS).is_none() {
+            let name =
+                "database::tests::only_caught_panics_are_kept_from_other_hooks";
+            let output =
+                std::process::Command::new(std::env::current_exe().unwrap())
+                    .args([name, "--exact", "--test-threads=1"])
+                    .env(OWN_PROCESS, "1")
+                    .output()
+                    .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "{}", stdout);
+            assert!(stdout.contains("1 passed"), "{}", stdout);
+            return;
+        }
+        static PASSED_ON: AtomicUsize = AtomicUsize::new(0);
+        panic::set_hook(Box::new(|_| {
+            PASSED_ON.fetch_add(1, SeqCst);
+        }));
+        install_panic_hook();
+        let caught = catch_corruption(Path::new("damaged.redb"), || {
+            // Another thread panicking meanwhile isn't redb's doing.
+            assert!(thread::spawn(|| panic!("elsewhere")).join().is_err());
+            panic!("damaged")
+        });
+        let _ = panic::take_hook();
+        assert!(matches!(caught, Err(AppError::DatabaseCorrupted(..))));
+        assert_eq!(PASSED_ON.load(SeqCst), 1);
+    }
 }
@@ -0,0 +1,856 @@
+use crate::database::{self, DetectedType, Endian, Packing, RawType};
+use crate::keyorder::KeyOrder;
+use crate::node::Node;
+use crate::protobuf::{self, DescriptorPool};
+use crate::{bincode, cbor, inflate, json, lz4, msgpack, timestamp, zstd};
+use std::collections::HashMap;
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Failure to interpret bytes with a particular decoder.
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct DecodeError(String);
+
+impl DecodeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+pub type DecodeResult<T> = std::result::Result<T, DecodeError>;
+
+/// User-supplied type information for formats that don't describe
+/// themselves.
+#[derive(Debug, Clone, Default)]
+pub struct Schemas {
+    pub bincode: Option<bincode::Schema>,
+    pub protobuf: Option<DescriptorPool>,
+    /// Protobuf message type chosen for each table.
+    pub proto_messages: HashMap<String, String>,
+    /// Whether compressed values are unpacked before they are decoded.
+    pub decompress: bool,
+}
+
+impl Schemas {
+    pub fn context<'a>(&'a self, table: &'a str) -> DecodeContext<'a> {
+        DecodeContext {
+            table,
+            bincode: self.bincode.as_ref(),
+            protobuf: self.protobuf.as_ref(),
+            proto_message: self.proto_messages.get(table).map(String::as_str),
+            decompress: self.decompress,
+        }
+    }
+}
+
+/// Table-specific inputs some decoders need.
+pub struct DecodeContext<'a> {
+    pub table: &'a str,
+    pub bincode: Option<&'a bincode::Schema>,
+    pub protobuf: Option<&'a DescriptorPool>,
+    pub proto_message: Option<&'a str>,
+    pub decompress: bool,
+}
+
+/// How raw key/value bytes are turned into text in the value pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoder {
+    /// Native decoding for builtin redb types, detection for opaque bytes.
+    Auto,
+    /// Decode according to the redb type the table was created with.
+    Native,
+    Utf8,
+    Hex,
+    /// Fixed-width integers; bytes of another width are decoded as with
+    /// `Decoder::Auto`.
+    U32(Endian),
+    U64(Endian),
+    I64(Endian),
+    /// Canonical hyphenated form of 16-byte values.
+    Uuid,
+    /// 4/8-byte integers as unix seconds, milliseconds, micros or nanos.
+    Timestamp,
+    Json,
+    MsgPack,
+    Cbor,
+    /// Decode with the struct layouts of a user-supplied schema file.
+    Bincode,
+    /// Decode as the table's protobuf message, or schemaless by field number.
+    Protobuf,
+}
+
+impl Decoder {
+    /// Value decoders, in selection order.
+    pub const VALUES: [Decoder; 11] = [
+        Decoder::Auto,
+        Decoder::Native,
+        Decoder::Utf8,
+        Decoder::Hex,
+        Decoder::Uuid,
+        Decoder::Timestamp,
+        Decoder::Json,
+        Decoder::MsgPack,
+        Decoder::Cbor,
+        Decoder::Bincode,
+        Decoder::Protobuf,
+    ];
+
+    /// Key decoders, in selection order.
+    pub const KEYS: [Decoder; 11] = [
+        Decoder::Auto,
+        Decoder::Utf8,
+        Decoder::U32(Endian::Little),
+        Decoder::U32(Endian::Big),
+        Decoder::U64(Endian::Little),
+        Decoder::U64(Endian::Big),
+        Decoder::I64(Endian::Little),
+        Decoder::I64(Endian::Big),
+        Decoder::Timestamp,
+        Decoder::Uuid,
+        Decoder::Hex,
+    ];
+
+    /// The decoder following this one in `cycle`.
+    pub fn next(self, cycle: &[Decoder]) -> Self {
+        let i = cycle.iter().position(|d| *d == self).unwrap_or(0);
+        cycle[(i + 1) % cycle.len()]
+    }
+
+    /// Looks up a decoder by its label or by one of the type names accepted
+    /// in schema files (`str`, `u64`, `timestamp`, ...).
+    pub fn from_name(name: &str) -> Option<Self> {
+        let decoder = match name {
+            "str" | "string" => Decoder::Utf8,
+            "u32" => Decoder::U32(Endian::Little),
+            "u64" => Decoder::U64(Endian::Little),
+            "i64" => Decoder::I64(Endian::Little),
+            "timestamp" => Decoder::Timestamp,
+            _ => *Self::VALUES
+                .iter()
+                .chain(Self::KEYS.iter())
+                .find(|d| d.label() == name)?,
+        };
+        Some(decoder)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Decoder::Auto => "auto",
+            Decoder::Native => "native",
+            Decoder::Utf8 => "utf8",
+            Decoder::Hex => "hex",
+            Decoder::U32(endian) => DetectedType::U32(endian).label(),
+            Decoder::U64(endian) => DetectedType::U64(endian).label(),
+            Decoder::I64(endian) => DetectedType::I64(endian).label(),
+            Decoder::Uuid => "uuid",
+            Decoder::Timestamp => "time",
+            Decoder::Json => "json",
+            Decoder::MsgPack => "msgpack",
+            Decoder::Cbor => "cbor",
+            Decoder::Bincode => "bincode",
+            Decoder::Protobuf => "protobuf",
+        }
+    }
+
+    /// Renders `bytes` stored under the redb type `type_name` as one line,
+    /// unpacking them first when the context asks to and they are
+    /// compressed.
+    pub fn decode(
+        self,
+        ctx: &DecodeContext,
+        type_name: &str,
+        bytes: &[u8],
+    ) -> String {
+        let unpacked = (ctx.decompress && database::is_opaque_type(type_name))
+            .then(|| decompress(bytes))
+            .flatten();
+        match unpacked {
+            Some((_, Ok(unpacked))) => {
+                self.decode_bytes(ctx, type_name, &unpacked)
+            }
+            Some((packing, Err(e))) => invalid(packing.label(), &e, bytes),
+            None => self.decode_bytes(ctx, type_name, bytes),
+        }
+    }
+
+    fn decode_bytes(
+        self,
+        ctx: &DecodeContext,
+        type_name: &str,
+        bytes: &[u8],
+    ) -> String {
+        match self {
+            Decoder::Auto if database::is_opaque_type(type_name) => {
+                let detected = database::detect_type(bytes);
+                format!(
+                    "[{}] {}",
+                    detected.label(),
+                    decode_detected(detected, bytes)
+                )
+            }
+            Decoder::Auto | Decoder::Native => decode_native(type_name, bytes),
+            Decoder::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Decoder::Hex => hex(bytes),
+            Decoder::U32(endian) if bytes.len() == 4 => {
+                decode_detected(DetectedType::U32(endian), bytes)
+            }
+            Decoder::U64(endian) if bytes.len() == 8 => {
+                decode_detected(DetectedType::U64(endian), bytes)
+            }
+            Decoder::I64(endian) if bytes.len() == 8 => {
+                decode_detected(DetectedType::I64(endian), bytes)
+            }
+            // Already unpacked, if at all.
+            Decoder::U32(_) | Decoder::U64(_) | Decoder::I64(_) => {
+                Decoder::Auto.decode_bytes(ctx, type_name, bytes)
+            }
+            Decoder::Uuid => match uuid(bytes) {
+                Some(uuid) => uuid,
+                None => invalid(
+                    "uuid",
+                    &DecodeError::new(format!("{} bytes", bytes.len())),
+                    bytes,
+                ),
+            },
+            Decoder::Timestamp => match timestamp::decode(type_name, bytes) {
+                Ok(time) => time,
+                Err(e) => invalid("timestamp", &e, bytes),
+            },
+            Decoder::Json => match std::str::from_utf8(bytes)
+                .map_err(|_| DecodeError::new("invalid UTF-8"))
+                .and_then(json::parse)
+            {
+                Ok(node) => node.to_string(),
+                Err(e) => invalid("json", &e, bytes),
+            },
+            Decoder::MsgPack => match msgpack::decode(bytes) {
+                Ok(node) => node.to_string(),
+                Err(e) => invalid("msgpack", &e, bytes),
+            },
+            Decoder::Cbor => match cbor::decode(bytes) {
+                Ok(node) => node.to_string(),
+                Err(e) => invalid("cbor", &e, bytes),
+            },
+            // Without a layout for this table there is nothing to go on.
+            Decoder::Bincode => {
+                match ctx.bincode.and_then(|s| s.decode(ctx.table, bytes)) {
+                    Some(Ok(node)) => node.to_string(),
+                    Some(Err(e)) => invalid("bincode", &e, bytes),
+                    None => hex(bytes),
+                }
+            }
+            Decoder::Protobuf => {
+                let decoded = match (ctx.protobuf, ctx.proto_message) {
+                    (Some(pool), Some(message)) => pool.decode(message, bytes),
+                    _ => protobuf::decode_raw(bytes),
+                };
+                match decoded {
+                    Ok(node) => node.to_string(),
+                    Err(e) => invalid("protobuf", &e, bytes),
+                }
+            }
+        }
+    }
+
+    /// Decodes `bytes` into a structured value for export. Numbers and
+    /// documents keep their structure; everything else becomes the text
+    /// `decode` would show, without detection labels.
+    pub fn decode_node(
+        self,
+        ctx: &DecodeContext,
+        type_name: &str,
+        bytes: &[u8],
+    ) -> Node {
+        let decoded = match self {
+            Decoder::Auto if database::is_opaque_type(type_name) => {
+                let detected = database::detect_type(bytes);
+                return detected_node(detected, bytes).unwrap_or_else(|| {
+                    Node::Text(decode_detected(detected, bytes))
+                });
+            }
+            Decoder::Auto | Decoder::Native => native_node(type_name, bytes),
+            Decoder::U32(endian) if bytes.len() == 4 => {
+                detected_node(DetectedType::U32(endian), bytes)
+            }
+            Decoder::U64(endian) if bytes.len() == 8 => {
+                detected_node(DetectedType::U64(endian), bytes)
+            }
+            Decoder::I64(endian) if bytes.len() == 8 => {
+                detected_node(DetectedType::I64(endian), bytes)
+            }
+            Decoder::Json => std::str::from_utf8(bytes)
+                .ok()
+                .and_then(|text| json::parse(text).ok()),
+            Decoder::MsgPack => msgpack::decode(bytes).ok(),
+            Decoder::Cbor => cbor::decode(bytes).ok(),
+            Decoder::Bincode => ctx
+                .bincode
+                .and_then(|s| s.decode(ctx.table, bytes))
+                .and_then(|r| r.ok()),
+            Decoder::Protobuf => match (ctx.protobuf, ctx.proto_message) {
+                (Some(pool), Some(message)) => pool.decode(message, bytes).ok(),
+                _ => protobuf::decode_raw(bytes).ok(),
+            },
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| Node::Text(self.decode(ctx, type_name, bytes)))
+    }
+}
+
+/// Text form a value is edited in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditFormat {
+    /// The display form of a builtin redb type.
+    Native,
+    /// UTF-8 text.
+    Text,
+    /// UTF-8 text that must be a valid JSON document.
+    Json,
+    /// Hex-encoded bytes, for anything without a text form.
+    Hex,
+}
+
+impl EditFormat {
+    /// Picks the format for editing `bytes` shown with `decoder`.
+    pub fn for_value(decoder: Decoder, type_name: &str, bytes: &[u8]) -> Self {
+        if !database::is_opaque_type(type_name) {
+            return match decoder {
+                Decoder::Hex => EditFormat::Hex,
+                _ => EditFormat::Native,
+            };
+        }
+        match decoder {
+            Decoder::Utf8 => EditFormat::Text,
+            Decoder::Json => EditFormat::Json,
+            Decoder::Auto => match database::detect_type(bytes) {
+                DetectedType::Utf8 => EditFormat::Text,
+                DetectedType::Json => EditFormat::Json,
+                _ => EditFormat::Hex,
+            },
+            _ => EditFormat::Hex,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EditFormat::Native => "native",
+            EditFormat::Text => "text",
+            EditFormat::Json => "json",
+            EditFormat::Hex => "hex",
+        }
+    }
+
+    /// File extension for editing in this format with an external editor.
+    pub fn extension(self) -> &'static str {
+        match self {
+            EditFormat::Json => "json",
+            EditFormat::Hex => "hex",
+            EditFormat::Native | EditFormat::Text => "txt",
+        }
+    }
+
+    /// Initial editor text for `bytes`.
+    pub fn render(self, type_name: &str, bytes: &[u8]) -> String {
+        match self {
+            EditFormat::Native => decode_native(type_name, bytes),
+            EditFormat::Text | EditFormat::Json => {
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            EditFormat::Hex => hex(bytes),
+        }
+    }
+
+    /// Turns edited text back into the bytes to store as a value of
+    /// `value_type`, refusing bytes the type can't hold, e.g. text of the
+    /// wrong length for a fixed width type.
+    pub fn encode(
+        self,
+        value_type: &RawType,
+        text: &str,
+    ) -> std::result::Result<Vec<u8>, String> {
+        let bytes = match self {
+            EditFormat::Native => encode_native(&value_type.name, text),
+            EditFormat::Text => Ok(text.as_bytes().to_vec()),
+            EditFormat::Json => json::parse(text)
+                .map(|_| text.as_bytes().to_vec())
+                .map_err(|e| format!("invalid JSON: {}", e)),
+            EditFormat::Hex => parse_hex(text),
+        }?;
+        value_type.check_value(&bytes).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+}
+
+/// Structured form of detected numbers and documents.
+fn detected_node(detected: DetectedType, bytes: &[u8]) -> Option<Node> {
+    macro_rules! int {
+        ($t:ty, $endian:expr) => {{
+            let b = bytes.try_into().ok()?;
+            Some(Node::Int(match $endian {
+                Endian::Little => <$t>::from_le_bytes(b) as i128,
+                Endian::Big => <$t>::from_be_bytes(b) as i128,
+            }))
+        }};
+    }
+    match detected {
+        DetectedType::U32(endian) => int!(u32, endian),
+        DetectedType::U64(endian) => int!(u64, endian),
+        DetectedType::I64(endian) => int!(i64, endian),
+        DetectedType::Json => std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|text| json::parse(text).ok()),
+        DetectedType::MsgPack => msgpack::decode(bytes).ok(),
+        DetectedType::Cbor => cbor::decode(bytes).ok(),
+        DetectedType::Utf8 | DetectedType::Uuid | DetectedType::Binary => None,
+    }
+}
+
+/// Structured form of redb's builtin numeric and boolean types.
+fn native_node(type_name: &str, bytes: &[u8]) -> Option<Node> {
+    macro_rules! le {
+        ($t:ty) => {
+            Some(Node::Int(
+                <$t>::from_le_bytes(bytes.try_into().ok()?) as i128
+            ))
+        };
+    }
+    match type_name {
+        "u8" => le!(u8),
+        "u16" => le!(u16),
+        "u32" => le!(u32),
+        "u64" => le!(u64),
+        "u128" => bytes
+            .try_into()
+            .ok()
+            .map(u128::from_le_bytes)
+            .and_then(|v| i128::try_from(v).ok())
+            .map(Node::Int),
+        "i8" => le!(i8),
+        "i16" => le!(i16),
+        "i32" => le!(i32),
+        "i64" => le!(i64),
+        "i128" => le!(i128),
+        "f32" => Some(Node::Float(
+            f32::from_le_bytes(bytes.try_into().ok()?) as f64
+        )),
+        "f64" => Some(Node::Float(f64::from_le_bytes(bytes.try_into().ok()?))),
+        "bool" => match bytes {
+            [0] => Some(Node::Bool(false)),
+            [1] => Some(Node::Bool(true)),
+            _ => None,
+        },
+        "()" => Some(Node::Null),
+        _ => None,
+    }
+}
+
+/// Renders bytes according to the outcome of type detection.
+fn decode_detected(detected: DetectedType, bytes: &[u8]) -> String {
+    macro_rules! int {
+        ($t:ty, $endian:expr) => {{
+            let b = bytes.try_into().unwrap();
+            match $endian {
+                Endian::Little => <$t>::from_le_bytes(b).to_string(),
+                Endian::Big => <$t>::from_be_bytes(b).to_string(),
+            }
+        }};
+    }
+    let decoded = match detected {
+        DetectedType::Utf8 => return String::from_utf8_lossy(bytes).into_owned(),
+        DetectedType::U32(endian) => return int!(u32, endian),
+        DetectedType::U64(endian) => return int!(u64, endian),
+        DetectedType::I64(endian) => return int!(i64, endian),
+        DetectedType::Uuid => return uuid(bytes).unwrap_or_else(|| hex(bytes)),
+        DetectedType::Binary => return hex(bytes),
+        DetectedType::Json => std::str::from_utf8(bytes)
+            .map_err(|_| DecodeError::new("invalid UTF-8"))
+            .and_then(json::parse),
+        DetectedType::MsgPack => msgpack::decode(bytes),
+        DetectedType::Cbor => cbor::decode(bytes),
+    };
+    decoded
+        .map(|node| node.to_string())
+        .unwrap_or_else(|_| hex(bytes))
+}
+
+/// Most bytes a compressed value is unpacked to.
+const MAX_DECOMPRESSED: usize = 16 << 20;
+
+/// Unpacks values compressed with gzip, zlib, zstd or LZ4, along with
+/// which one it was. `None` for other values.
+pub fn decompress(bytes: &[u8]) -> Option<(Packing, DecodeResult<Vec<u8>>)> {
+    let packing = database::detect_packing(bytes)?;
+    let unpacked = match packing {
+        Packing::Gzip => inflate::gunzip(bytes, MAX_DECOMPRESSED),
+        Packing::Zlib => inflate::zlib(bytes, MAX_DECOMPRESSED),
+        Packing::Zstd => zstd::decode(bytes, MAX_DECOMPRESSED),
+        Packing::Lz4 => lz4::decode(bytes, MAX_DECOMPRESSED),
+        _ => return None,
+    };
+    Some((packing, unpacked))
+}
+
+fn invalid(format: &str, error: &DecodeError, bytes: &[u8]) -> String {
+    format!("<invalid {}: {}> {}", format, error, escape_bytes(bytes))
+}
+
+/// Decodes the encodings of redb's builtin `Value` implementations. Types
+/// redb-tui doesn't know (tuples, user-defined types) are shown as bytes.
+pub fn decode_native(type_name: &str, bytes: &[u8]) -> String {
+    macro_rules! le {
+        ($t:ty) => {
+            match bytes.try_into() {
+                Ok(b) => <$t>::from_le_bytes(b).to_string(),
+                Err(_) => escape_bytes(bytes),
+            }
+        };
+    }
+    match type_name {
+        "&str" | "String" => String::from_utf8_lossy(bytes).into_owned(),
+        "u8" => le!(u8),
+        "u16" => le!(u16),
+        "u32" => le!(u32),
+        "u64" => le!(u64),
+        "u128" => le!(u128),
+        "i8" => le!(i8),
+        "i16" => le!(i16),
+        "i32" => le!(i32),
+        "i64" => le!(i64),
+        "i128" => le!(i128),
+        "f32" => le!(f32),
+        "f64" => le!(f64),
+        "()" => "()".to_string(),
+        "bool" => match bytes {
+            [0] => "false".to_string(),
+            [1] => "true".to_string(),
+            _ => escape_bytes(bytes),
+        },
+        "char" => match bytes {
+            [a, b, c] => char::from_u32(u32::from_le_bytes([*a, *b, *c, 0]))
+                .map(|c| format!("{:?}", c))
+                .unwrap_or_else(|| escape_bytes(bytes)),
+            _ => escape_bytes(bytes),
+        },
+        _ => {
+            if let Some(inner) = type_name
+                .strip_prefix("Option<")
+                .and_then(|t| t.strip_suffix('>'))
+            {
+                return match bytes.split_first() {
+                    Some((0, _)) => "None".to_string(),
+                    Some((1, rest)) => {
+                        format!("Some({})", decode_native(inner, rest))
+                    }
+                    _ => escape_bytes(bytes),
+                };
+            }
+            escape_bytes(bytes)
+        }
+    }
+}
+
+/// Encodes `text` the way redb stores values of the builtin `type_name`;
+/// the inverse of `decode_native`. Other types take the UTF-8 bytes of the
+/// text.
+pub fn encode_native(
+    type_name: &str,
+    text: &str,
+) -> std::result::Result<Vec<u8>, String> {
+    macro_rules! le {
+        ($t:ty) => {
+            text.parse::<$t>()
+                .map(|v| v.to_le_bytes().to_vec())
+                .map_err(|e| format!("'{}' is not a {}: {}", text, type_name, e))
+        };
+    }
+    match type_name {
+        "u8" => le!(u8),
+        "u16" => le!(u16),
+        "u32" => le!(u32),
+        "u64" => le!(u64),
+        "u128" => le!(u128),
+        "i8" => le!(i8),
+        "i16" => le!(i16),
+        "i32" => le!(i32),
+        "i64" => le!(i64),
+        "i128" => le!(i128),
+        "f32" => le!(f32),
+        "f64" => le!(f64),
+        "()" => Ok(Vec::new()),
+        "bool" => match text {
+            "false" => Ok(vec![0]),
+            "true" => Ok(vec![1]),
+            _ => Err(format!("'{}' is not a bool", text)),
+        },
+        "char" => {
+            // Accept the quoted form `decode_native` displays.
+            let text = text
+                .strip_prefix('\'')
+                .and_then(|t| t.strip_suffix('\''))
+                .filter(|t| !t.is_empty())
+                .unwrap_or(text);
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok((c as u32).to_le_bytes()[..3].to_vec()),
+                _ => Err(format!("'{}' is not a single char", text)),
+            }
+        }
+        _ => {
+            if let Some(inner) = type_name
+                .strip_prefix("Option<")
+                .and_then(|t| t.strip_suffix('>'))
+            {
+                if text == "None" {
+                    // Padded to the width of the type, as redb does.
+                    let mut bytes = vec![0];
+                    bytes.resize(1 + native_width(inner).unwrap_or(0), 0);
+                    return Ok(bytes);
+                }
+                let value = text
+                    .strip_prefix("Some(")
+                    .and_then(|t| t.strip_suffix(')'))
+                    .unwrap_or(text);
+                let mut bytes = vec![1];
+                bytes.extend(encode_native(inner, value)?);
+                return Ok(bytes);
+            }
+            Ok(text.as_bytes().to_vec())
+        }
+    }
+}
+
+/// Length of every encoded value of the builtin `type_name`, when it's
+/// fixed.
+fn native_width(type_name: &str) -> Option<usize> {
+    match type_name {
+        "f32" => Some(4),
+        "f64" => Some(8),
+        _ => KeyOrder::parse(type_name)?.fixed_width(),
+    }
+}
+
+/// Parses a hex string such as `00ff10`, ignoring whitespace and an
+/// optional `0x` prefix.
+pub fn parse_hex(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let digits: String = text
+        .trim()
+        .trim_start_matches("0x")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    // Checked first: slicing below assumes one byte per character.
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not valid hex", text));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("'{}' has an odd number of hex digits", text));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("'{}' is not valid hex", text))
+        })
+        .collect()
+}
+
+/// Lowercase hex dump without separators.
+pub fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(out, "{:02x}", b);
+    }
+    out
+}
+
+/// Standard base64 with padding.
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Parses standard base64, padded or not, ignoring whitespace.
+pub fn parse_base64(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for c in text
+        .trim_end_matches(|c: char| c == '=' || c.is_whitespace())
+        .chars()
+    {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            c if c.is_whitespace() => continue,
+            _ => return Err(format!("'{}' is not valid base64", text)),
+        };
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    if bits >= 6 {
+        return Err(format!("'{}' is cut off", text));
+    }
+    Ok(out)
+}
+
+/// Hex dump in lines of 16 bytes, each with its offset and the bytes as
+/// ASCII, `.` standing in for anything unprintable:
+/// `00000000  68 69 0a                                          |hi.|`
+pub fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut line = format!("{:08x} ", i * 16);
+            for (j, b) in chunk.iter().enumerate() {
+                // An extra space between the two halves of the line.
+                if j == 8 {
+                    line.push(' ');
+                }
+                let _ = write!(line, " {:02x}", b);
+            }
+            let missing = 16 - chunk.len();
+            line.push_str(&" ".repeat(missing * 3 + usize::from(missing >= 8)));
+            line.push_str("  |");
+            line.extend(chunk.iter().map(|&b| match b {
+                0x20..=0x7e => b as char,
+                _ => '.',
+            }));
+            line.push('|');
+            line
+        })
+        .collect()
+}
+
+/// Canonical `8-4-4-4-12` UUID string, if `bytes` is exactly 16 bytes long.
+pub fn uuid(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 16 {
+        return None;
+    }
+    let hex = hex(bytes);
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+/// Byte-string literal with printable ASCII kept and the rest escaped.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut out = String::from("b\"");
+    for b in bytes {
+        out.extend(std::ascii::escape_default(*b).map(char::from));
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_none_padded_to_the_inner_width() {
+        use redb::Value;
+        let none = <Option<u64>>::as_bytes(&None);
+        assert_eq!(encode_native("Option<u64>", "None").unwrap(), none);
+        let some = <Option<u64>>::as_bytes(&Some(7));
+        assert_eq!(encode_native("Option<u64>", "Some(7)").unwrap(), some);
+        assert_eq!(encode_native("Option<&str>", "None").unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn edits_keep_to_the_fixed_width() {
+        let u64_type = database::builtin_type("u64").unwrap();
+        assert_eq!(
+            EditFormat::Native.encode(&u64_type, "5").unwrap(),
+            5u64.to_le_bytes()
+        );
+        assert!(EditFormat::Text.encode(&u64_type, "5").is_err());
+        assert!(EditFormat::Hex.encode(&u64_type, "0500").is_err());
+        let str_type = database::builtin_type("&str").unwrap();
+        assert!(EditFormat::Hex.encode(&str_type, "ff").is_err());
+    }
+
+    #[test]
+    fn parses_hex_and_refuses_other_characters() {
+        assert_eq!(parse_hex(" 0x00 ff10 ").unwrap(), [0x00, 0xff, 0x10]);
+        assert!(parse_hex("abc").is_err());
+        for text in ["aé0", "éa", "0g", "+1"] {
+            assert!(parse_hex(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn hex_dump_lines_up_short_last_lines() {
+        let bytes: Vec<u8> = (b'a'..).take(24).collect();
+        let lines = hex_dump(&bytes);
+        assert_eq!(
+            lines,
+            [
+                "00000000  61 62 63 64 65 66 67 68  69 6a 6b 6c 6d 6e 6f 70  \
+                 |abcdefghijklmnop|",
+                "00000010  71 72 73 74 75 76 77 78                           \
+                 |qrstuvwx|",
+            ]
+        );
+        for len in 1..=16 {
+            let line = &hex_dump(&bytes[..len])[0];
+            assert_eq!(line.find('|'), lines[0].find('|'), "{} bytes", len);
+        }
+    }
+
+    /// An LZ4 frame holding `data` in an uncompressed block.
+    fn lz4_stored(data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x04, 0x22, 0x4d, 0x18, 0x60, 0x40, 0x82];
+        frame.extend((data.len() as u32 | 0x8000_0000).to_le_bytes());
+        frame.extend(data);
+        frame.extend([0; 4]);
+        frame
+    }
+
+    #[test]
+    fn integer_fallbacks_unpack_once() {
+        let ctx = DecodeContext {
+            table: "t",
+            bincode: None,
+            protobuf: None,
+            proto_message: None,
+            decompress: true,
+        };
+        let inner = lz4_stored(b"hello, world");
+        let outer = lz4_stored(&inner);
+        let decoded = Decoder::U32(Endian::Little).decode(&ctx, "&[u8]", &outer);
+        assert_eq!(decoded, Decoder::Auto.decode_bytes(&ctx, "&[u8]", &inner));
+        assert_ne!(
+            decoded,
+            Decoder::Auto.decode_bytes(&ctx, "&[u8]", b"hello, world")
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::database::{self, RawEntry, TableTypes};
+use crate::{AppError, Result};
+use redb::{Database, ReadableTableMetadata};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+/// How a table or an entry differs going from the first database to the
+/// second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Change {
+    /// Name of the change in machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        }
+    }
+
+    /// Mark of the change in the text output and the diff view.
+    pub fn sign(self) -> &'static str {
+        match self {
+            Change::Added => "+",
+            Change::Removed => "-",
+            Change::Changed => "~",
+        }
+    }
+}
+
+/// A table of either database.
+#[derive(Debug, Clone)]
+pub struct TableDiff {
+    pub name: String,
+    /// Types in the first database, if the table is there.
+    pub old: Option<TableTypes>,
+    /// Types in the second database, if the table is there.
+    pub new: Option<TableTypes>,
+    /// Entry counts in the first and second database.
+    pub old_len: u64,
+    pub new_len: u64,
+}
+
+impl TableDiff {
+    /// How the table itself changed: added, removed, or its types changed.
+    /// `None` for tables in both with the same types, whose entries are
+    /// compared instead.
+    pub fn change(&self) -> Option<Change> {
+        match (&self.old, &self.new) {
+            (None, _) => Some(Change::Added),
+            (_, None) => Some(Change::Removed),
+            (Some(old), Some(new)) if old != new => Some(Change::Changed),
+            _ => None,
+        }
+    }
+}
+
+/// An entry that differs, with its value in each database it is in.
+#[derive(Debug, Clone)]
+pub struct EntryDiff {
+    pub change: Change,
+    pub key: Vec<u8>,
+    pub old: Option<Vec<u8>>,
+    pub new: Option<Vec<u8>>,
+}
+
+/// A key of either database, with its value in each database it is in.
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub key: Vec<u8>,
+    pub old: Option<Vec<u8>>,
+    pub new: Option<Vec<u8>>,
+}
+
+impl Row {
+    /// How the entry changed, `None` if it is the same in both.
+    pub fn change(&self) -> Option<Change> {
+        match (&self.old, &self.new) {
+            (None, _) => Some(Change::Added),
+            (_, None) => Some(Change::Removed),
+            (Some(old), Some(new)) if old != new => Some(Change::Changed),
+            _ => None,
+        }
+    }
+}
+
+/// Entries added, removed and changed in a table.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub added: u64,
+    pub removed: u64,
+    pub changed: u64,
+}
+
+impl Counts {
+    pub fn add(&mut self, change: Change) {
+        match change {
+            Change::Added => self.added += 1,
+            Change::Removed => self.removed += 1,
+            Change::Changed => self.changed += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.added + self.removed + self.changed
+    }
+}
+
+/// The tables of both databases in name order, or just the one named
+/// `only`.
+pub fn tables(
+    old: &Database,
+    new: &Database,
+    only: Option<&str>,
+) -> Result<Vec<TableDiff>> {
+    let old_txn = old.begin_read()?;
+    let new_txn = new.begin_read()?;
+    let old_names = database::get_table_names(old)?;
+    let new_names = database::get_table_names(new)?;
+    let names: BTreeSet<&String> = old_names.iter().chain(&new_names).collect();
+    if let Some(only) =
+        only.filter(|only| !names.iter().any(|name| name.as_str() == *only))
+    {
+        return Err(AppError::TableNotFound(only.to_string()));
+    }
+    let mut tables = Vec::new();
+    for name in names {
+        if only.is_some_and(|only| only != name) {
+            continue;
+        }
+        let mut table = TableDiff {
+            name: name.clone(),
+            old: None,
+            new: None,
+            old_len: 0,
+            new_len: 0,
+        };
+        if old_names.contains(name) {
+            let (raw_table, types) = database::open_raw_table(&old_txn, name)?;
+            table.old_len = raw_table.len()?;
+            table.old = Some(types);
+        }
+        if new_names.contains(name) {
+            let (raw_table, types) = database::open_raw_table(&new_txn, name)?;
+            table.new_len = raw_table.len()?;
+            table.new = Some(types);
+        }
+        tables.push(table);
+    }
+    Ok(tables)
+}
+
+/// Calls `f` with each entry of `table` that differs between the databases,
+/// in key order, until it returns false. Both databases need to have the
+/// table with the same types. Returns the changes seen.
+pub fn entries(
+    old: &Database,
+    new: &Database,
+    table: &TableDiff,
+    mut f: impl FnMut(EntryDiff) -> Result<bool>,
+) -> Result<Counts> {
+    let mut counts = Counts::default();
+    rows(old, new, table, Bound::Unbounded, |row| {
+        let Some(change) = row.change() else {
+            return Ok(true);
+        };
+        counts.add(change);
+        f(EntryDiff {
+            change,
+            key: row.key,
+            old: row.old,
+            new: row.new,
+        })
+    })?;
+    Ok(counts)
+}
+
+/// Calls `f` with each key of `table` in either database from `start` on,
+/// in key order, until it returns false. Both databases need to have the
+/// table with the same types.
+pub fn rows(
+    old: &Database,
+    new: &Database,
+    table: &TableDiff,
+    start: Bound<&[u8]>,
+    mut f: impl FnMut(Row) -> Result<bool>,
+) -> Result<()> {
+    let (Some(types), None) = (&table.new, table.change()) else {
+        return Ok(());
+    };
+    let old_txn = old.begin_read()?;
+    let new_txn = new.begin_read()?;
+    let (old_table, _) = database::open_raw_table(&old_txn, &table.name)?;
+    let (new_table, _) = database::open_raw_table(&new_txn, &table.name)?;
+    let range = (start, Bound::Unbounded);
+    let mut old_entries = old_table.range::<&[u8]>(range)?.map(|entry| {
+        entry.map(|(k, v)| -> RawEntry {
+            (k.value().to_vec(), v.value().to_vec())
+        })
+    });
+    let mut new_entries = new_table.range::<&[u8]>(range)?.map(|entry| {
+        entry.map(|(k, v)| -> RawEntry {
+            (k.value().to_vec(), v.value().to_vec())
+        })
+    });
+    let mut old_entry = old_entries.next().transpose()?;
+    let mut new_entry = new_entries.next().transpose()?;
+    loop {
+        let order = match (&old_entry, &new_entry) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((old_key, _)), Some((new_key, _))) => {
+                database::compare_as(&types.key.name, old_key, new_key)
+            }
+        };
+        let row = match order {
+            Ordering::Less => {
+                let (key, value) = old_entry.take().unwrap_or_default();
+                old_entry = old_entries.next().transpose()?;
+                Row {
+                    key,
+                    old: Some(value),
+                    new: None,
+                }
+            }
+            Ordering::Greater => {
+                let (key, value) = new_entry.take().unwrap_or_default();
+                new_entry = new_entries.next().transpose()?;
+                Row {
+                    key,
+                    old: None,
+                    new: Some(value),
+                }
+            }
+            Ordering::Equal => {
+                let (key, old_value) = old_entry.take().unwrap_or_default();
+                let (_, new_value) = new_entry.take().unwrap_or_default();
+                old_entry = old_entries.next().transpose()?;
+                new_entry = new_entries.next().transpose()?;
+                Row {
+                    key,
+                    old: Some(old_value),
+                    new: Some(new_value),
+                }
+            }
+        };
+        if !f(row)? {
+            break;
+        }
+    }
+    Ok(())
+}
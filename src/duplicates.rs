@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Keys kept as examples of each value.
+const EXAMPLE_KEYS: usize = 3;
+
+/// A value, told apart by its hash and length, and the keys storing it.
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    /// Keys storing the value.
+    pub count: u64,
+    /// Length of the value in bytes.
+    pub size: usize,
+    /// The first keys storing it, in the order read.
+    pub keys: Vec<Vec<u8>>,
+}
+
+impl Duplicate {
+    /// Bytes taken by the copies beyond the first.
+    pub fn wasted(&self) -> u64 {
+        (self.count - 1) * self.size as u64
+    }
+}
+
+/// The values of a table counted by hash, to find those stored under more
+/// than one key.
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    values: HashMap<(u64, usize), Duplicate>,
+    entries: u64,
+}
+
+/// The values stored more than once, see `Duplicates::report`.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Entries read.
+    pub entries: u64,
+    /// Values stored more than once.
+    pub duplicated: usize,
+    /// Bytes taken by copies of values, beyond the first of each.
+    pub wasted: u64,
+    /// The values wasting the most bytes, most first.
+    pub top: Vec<Duplicate>,
+}
+
+impl Duplicates {
+    pub fn add(&mut self, key: &[u8], value: &[u8]) {
+        self.entries += 1;
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let duplicate = self
+            .values
+            .entry((hasher.finish(), value.len()))
+            .or_insert_with(|| Duplicate {
+                count: 0,
+                size: value.len(),
+                keys: Vec::new(),
+            });
+        duplicate.count += 1;
+        if duplicate.keys.len() < EXAMPLE_KEYS {
+            duplicate.keys.push(key.to_vec());
+        }
+    }
+
+    /// Entries added so far.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// The totals of the values stored more than once, with the `limit`
+    /// wasting the most bytes; ties go to the value stored more often.
+    pub fn report(self, limit: usize) -> Report {
+        let mut top: Vec<Duplicate> = self
+            .values
+            .into_values()
+            .filter(|duplicate| duplicate.count > 1)
+            .collect();
+        let wasted = top.iter().map(Duplicate::wasted).sum();
+        let duplicated = top.len();
+        top.sort_by(|a, b| {
+            b.wasted()
+                .cmp(&a.wasted())
+                .then(b.count.cmp(&a.count))
+                .then_with(|| a.keys.cmp(&b.keys))
+        });
+        top.truncate(limit);
+        Report {
+            entries: self.entries,
+            duplicated,
+            wasted,
+            top,
+        }
+    }
+}
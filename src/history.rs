@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+
+/// Places kept to go back to.
+const MAX_PLACES: usize = 100;
+
+/// Places visited, to go back and forward through like in a browser.
+/// Going somewhere new after going back drops the places ahead.
+#[derive(Debug)]
+pub struct History<T> {
+    back: VecDeque<T>,
+    forward: Vec<T>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            back: VecDeque::new(),
+            forward: Vec::new(),
+        }
+    }
+}
+
+impl<T: PartialEq> History<T> {
+    /// Records `left` as the place just left for somewhere new. The oldest
+    /// place goes once there are `MAX_PLACES`.
+    pub fn visit(&mut self, left: T) {
+        self.forward.clear();
+        if self.back.back() == Some(&left) {
+            return;
+        }
+        if self.back.len() == MAX_PLACES {
+            self.back.pop_front();
+        }
+        self.back.push_back(left);
+    }
+
+    /// The place before `here`, which is kept to come forward to again.
+    pub fn back(&mut self, here: T) -> Option<T> {
+        let place = self.back.pop_back()?;
+        self.forward.push(here);
+        Some(place)
+    }
+
+    /// Keeps only the places, behind and ahead, that `f` is true for.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.back.retain(&mut f);
+        self.forward.retain(f);
+    }
+
+    /// The place `back` left, with `here` kept to go back to again.
+    pub fn forward(&mut self, here: T) -> Option<T> {
+        let place = self.forward.pop()?;
+        self.back.push_back(here);
+        Some(place)
+    }
+}
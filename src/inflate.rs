@@ -0,0 +1,470 @@
+use crate::decoder::{DecodeError, DecodeResult};
+
+/// Longest code of the deflate Huffman codes.
+const MAX_BITS: usize = 15;
+
+/// Base lengths and extra bits of length codes 257 to 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67,
+    83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5,
+    5, 5, 0,
+];
+
+/// Base distances and extra bits of distance codes 0 to 29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+    769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11,
+    11, 12, 12, 13, 13,
+];
+
+/// Order the code length code lengths of a dynamic block come in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses a gzip member (RFC 1952), checking its CRC and size.
+/// Output past `limit` bytes is refused.
+pub fn gunzip(bytes: &[u8], limit: usize) -> DecodeResult<Vec<u8>> {
+    let header = bytes
+        .get(..10)
+        .ok_or_else(|| DecodeError::new("truncated gzip header"))?;
+    if header[..2] != [0x1f, 0x8b] || header[2] != 8 {
+        return Err(DecodeError::new("not a deflate gzip member"));
+    }
+    let flags = header[3];
+    let mut pos = 10;
+    let skip_to = |pos: usize| {
+        Some(pos)
+            .filter(|pos| *pos <= bytes.len())
+            .ok_or_else(|| DecodeError::new("truncated gzip header"))
+    };
+    if flags & 0x04 != 0 {
+        let len = bytes
+            .get(pos..pos + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| DecodeError::new("truncated gzip header"))?;
+        pos = skip_to(pos + 2 + len)?;
+    }
+    // The file name and the comment end with a zero byte.
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let len = bytes[pos..]
+                .iter()
+                .position(|b| *b == 0)
+                .ok_or_else(|| DecodeError::new("truncated gzip header"))?;
+            pos += len + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos = skip_to(pos + 2)?;
+    }
+    let (out, read) = inflate(&bytes[pos..], limit)?;
+    let trailer = bytes
+        .get(pos + read..pos + read + 8)
+        .ok_or_else(|| DecodeError::new("truncated gzip trailer"))?;
+    let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+    let size = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err(DecodeError::new("gzip checksum mismatch"));
+    }
+    Ok(out)
+}
+
+/// Decompresses a zlib stream (RFC 1950), checking its Adler-32. Output
+/// past `limit` bytes is refused.
+pub fn zlib(bytes: &[u8], limit: usize) -> DecodeResult<Vec<u8>> {
+    let [cmf, flg, ..] = *bytes else {
+        return Err(DecodeError::new("truncated zlib header"));
+    };
+    if cmf & 0x0f != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(DecodeError::new("not a deflate zlib stream"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(DecodeError::new(
+            "zlib preset dictionaries aren't supported",
+        ));
+    }
+    let (out, read) = inflate(&bytes[2..], limit)?;
+    let trailer = bytes
+        .get(2 + read..2 + read + 4)
+        .ok_or_else(|| DecodeError::new("truncated zlib trailer"))?;
+    if u32::from_be_bytes(trailer.try_into().unwrap()) != adler32(&out) {
+        return Err(DecodeError::new("zlib checksum mismatch"));
+    }
+    Ok(out)
+}
+
+/// Decompresses a raw deflate stream (RFC 1951). Returns the output and
+/// the number of bytes the stream took.
+pub fn inflate(bytes: &[u8], limit: usize) -> DecodeResult<(Vec<u8>, usize)> {
+    let mut bits = Bits {
+        bytes,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => stored(&mut bits, &mut out, limit)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                codes(&mut bits, &mut out, &lengths, &distances, limit)?
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, &lengths, &distances, limit)?
+            }
+            _ => return Err(DecodeError::new("invalid deflate block type")),
+        }
+        if last {
+            return Ok((out, bits.pos));
+        }
+    }
+}
+
+/// Reads the bits of a deflate stream, least significant first.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> DecodeResult<u32> {
+        while self.count < n {
+            let byte = *self.bytes.get(self.pos).ok_or_else(|| {
+                DecodeError::new("unexpected end of deflate data")
+            })?;
+            self.pos += 1;
+            self.buf |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops the bits left of the current byte; fewer than 8 are ever
+    /// buffered.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+
+    /// Decodes a symbol of `code`, reading a bit at a time.
+    fn decode(&mut self, code: &Huffman) -> DecodeResult<u16> {
+        let (mut value, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            value |= self.bits(1)? as i32;
+            let count = i32::from(code.counts[len]);
+            if value - first < count {
+                return Ok(code.symbols[(index + value - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            value <<= 1;
+        }
+        Err(DecodeError::new("invalid deflate code"))
+    }
+}
+
+/// A canonical Huffman code: how many codes of each length, and the symbols
+/// in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> DecodeResult<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+        // More codes than lengths allow can't be decoded; fewer can.
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - i32::from(*count);
+            if left < 0 {
+                return Err(DecodeError::new("over-subscribed deflate code"));
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>, limit: usize) -> DecodeResult<()> {
+    bits.align();
+    let header = bits
+        .bytes
+        .get(bits.pos..bits.pos + 4)
+        .ok_or_else(|| DecodeError::new("truncated stored block"))?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(DecodeError::new("stored block length mismatch"));
+    }
+    let start = bits.pos + 4;
+    let data = bits
+        .bytes
+        .get(start..start + len as usize)
+        .ok_or_else(|| DecodeError::new("truncated stored block"))?;
+    check_limit(out.len() + data.len(), limit)?;
+    out.extend_from_slice(data);
+    bits.pos = start + len as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> DecodeResult<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> DecodeResult<(Huffman, Huffman)> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(DecodeError::new("too many deflate codes"));
+    }
+    let mut lengths = [0u8; 19];
+    for index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[*index] = bits.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (len, repeat) = match bits.decode(&code)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or_else(|| {
+                    DecodeError::new("deflate length repeat with no length")
+                })?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > literals + distances {
+            return Err(DecodeError::new("too many deflate code lengths"));
+        }
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        return Err(DecodeError::new("deflate block without an end code"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+/// Decodes the literals and copies of a compressed block.
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> DecodeResult<()> {
+    loop {
+        let symbol = bits.decode(lengths)? as usize;
+        match symbol {
+            0..=255 => {
+                check_limit(out.len() + 1, limit)?;
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(DecodeError::new("invalid deflate length code"));
+                }
+                let len = LENGTH_BASE[index] as usize
+                    + bits.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+                let index = bits.decode(distances)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(DecodeError::new(
+                        "invalid deflate distance code",
+                    ));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + bits.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+                if distance > out.len() {
+                    return Err(DecodeError::new(
+                        "deflate distance too far back",
+                    ));
+                }
+                check_limit(out.len() + len, limit)?;
+                // Copies may overlap what they produce.
+                let start = out.len() - distance;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Refuses output growing past `limit` bytes.
+pub fn check_limit(len: usize, limit: usize) -> DecodeResult<()> {
+    if len > limit {
+        return Err(DecodeError::new(format!(
+            "decompresses to more than {} bytes",
+            limit
+        )));
+    }
+    Ok(())
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::parse_hex;
+
+    const LIMIT: usize = 1 << 20;
+
+    /// `b"hello hello hello world\n"` as one fixed-code block, from zlib with
+    /// `Z_FIXED`.
+    const FIXED: &str = "cb48cdc9c957c84022cbf38b7252b800";
+
+    /// `dynamic_text()` as one dynamic-code block, from zlib at level 9.
+    const DYNAMIC: &str = "\
+        15cfcb0d00200803d0857ae05b60ffc5ac074c8ca6af18e6fce0ec864704c830cc56\
+        a07617336cb048dc393c6b309d85eb6a4caec12d0f1d9a6d03ef1a792c7074742ae3\
+        b612eeca8968acb7fe9e8f328cd8cf973795118570a76445662e3f5f6a283eef4244\
+        e9a1530d4d0d99b37019885fe0b255f1ce50d40cfdef20221b41f9212ad952ce136b\
+        969f1ff4df7e3f4feabada6f37fe068e07";
+
+    /// `b"hello world\n"` from Python's `gzip.compress(.., mtime=0)` and
+    /// `zlib.compress`.
+    const GZIP: &str =
+        "1f8b0800000000000203cb48cdc9c95728cf2fca49e102002d3b08af0c000000";
+    const ZLIB: &str = "789ccb48cdc9c95728cf2fca49e102001e720467";
+
+    /// `b"hi\n"` gzipped with the file name `a.txt`.
+    const GZIP_NAMED: &str =
+        "1f8b08080000000002ff612e74787400cbc8e402007a7a6fed03000000";
+
+    fn dynamic_text() -> Vec<u8> {
+        (0..60u64)
+            .flat_map(|i| format!("{},", i * i * 7919 % 10007).into_bytes())
+            .collect()
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        parse_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn inflates_stored_fixed_and_dynamic_blocks() {
+        // A stored block followed by a final fixed block holding nothing.
+        let stored = [0x00, 5, 0, !5, !0, b'h', b'e', b'l', b'l', b'o', 0x03, 0];
+        assert_eq!(inflate(&stored, LIMIT).unwrap(), (b"hello".to_vec(), 12));
+
+        let fixed = bytes(FIXED);
+        assert_eq!(fixed[0] >> 1 & 3, 1);
+        let (out, read) = inflate(&fixed, LIMIT).unwrap();
+        assert_eq!(
+            (out.as_slice(), read),
+            (&b"hello hello hello world\n"[..], 16)
+        );
+
+        let dynamic = bytes(DYNAMIC);
+        assert_eq!(dynamic[0] >> 1 & 3, 2);
+        assert_eq!(inflate(&dynamic, LIMIT).unwrap().0, dynamic_text());
+    }
+
+    #[test]
+    fn unwraps_gzip_and_zlib() {
+        assert_eq!(gunzip(&bytes(GZIP), LIMIT).unwrap(), b"hello world\n");
+        assert_eq!(gunzip(&bytes(GZIP_NAMED), LIMIT).unwrap(), b"hi\n");
+        assert_eq!(zlib(&bytes(ZLIB), LIMIT).unwrap(), b"hello world\n");
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn refuses_bad_checksums_and_output_past_the_limit() {
+        let mut gzip = bytes(GZIP);
+        let crc = gzip.len() - 8;
+        gzip[crc] ^= 1;
+        assert!(gunzip(&gzip, LIMIT).is_err());
+        let mut stream = bytes(ZLIB);
+        *stream.last_mut().unwrap() ^= 1;
+        assert!(zlib(&stream, LIMIT).is_err());
+        assert!(inflate(&bytes(DYNAMIC), 100).is_err());
+        assert!(inflate(&[0x07], LIMIT).is_err());
+        // A stored length that doesn't match its complement.
+        assert!(inflate(&[0x01, 5, 0, 5, 0], LIMIT).is_err());
+    }
+
+    #[test]
+    fn truncated_streams_are_errors() {
+        for hex in [FIXED, DYNAMIC] {
+            let stream = bytes(hex);
+            for len in 0..stream.len() {
+                assert!(inflate(&stream[..len], LIMIT).is_err(), "{}", len);
+            }
+        }
+        for (hex, unwrap) in [
+            (GZIP, gunzip as fn(&[u8], usize) -> DecodeResult<Vec<u8>>),
+            (GZIP_NAMED, gunzip),
+            (ZLIB, zlib),
+        ] {
+            let stream = bytes(hex);
+            for len in 0..stream.len() {
+                assert!(unwrap(&stream[..len], LIMIT).is_err(), "{}", len);
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::decoder;
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::node::{write_quoted, Node};
+use std::fmt::Write;
+
+/// Nesting limit guarding against maliciously deep documents.
+const MAX_DEPTH: usize = 128;
+
+/// Parses a complete JSON document.
+pub fn parse(text: &str) -> DecodeResult<Node> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+    parser.skip_whitespace();
+    let node = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(node)
+}
+
+/// Serializes a node as compact JSON. Values JSON has no counterpart for
+/// are mapped onto the closest one: bytes become hex strings, non-finite
+/// floats and `undefined` become `null`, tags are dropped and non-string map
+/// keys are rendered as text.
+pub fn to_string(node: &Node) -> String {
+    let mut out = String::new();
+    write_node(&mut out, node);
+    out
+}
+
+/// Plain text of a node: strings as-is, anything else in JSON.
+pub fn to_text(node: &Node) -> String {
+    match node {
+        Node::Text(s) => s.clone(),
+        other => to_string(other),
+    }
+}
+
+fn write_node(out: &mut String, node: &Node) {
+    match node {
+        Node::Null | Node::Undefined => out.push_str("null"),
+        Node::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Node::Int(i) => {
+            let _ = write!(out, "{}", i);
+        }
+        Node::Float(x) if x.is_finite() => {
+            let _ = write!(out, "{:?}", x);
+        }
+        Node::Float(_) => out.push_str("null"),
+        Node::Text(s) => {
+            let _ = write_quoted(out, s);
+        }
+        Node::Bytes(bytes) => {
+            let _ = write_quoted(out, &decoder::hex(bytes));
+        }
+        Node::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_node(out, item);
+            }
+            out.push(']');
+        }
+        Node::Map(entries) => {
+            out.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let key = match key {
+                    Node::Text(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                let _ = write_quoted(out, &key);
+                out.push(':');
+                write_node(out, value);
+            }
+            out.push('}');
+        }
+        Node::Tagged(_, inner) => write_node(out, inner),
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> DecodeError {
+        DecodeError::new(format!("{} at offset {}", message, self.pos))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> DecodeResult<()> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, node: Node) -> DecodeResult<Node> {
+        if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(node)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> DecodeResult<Node> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Node::Map(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = Node::Text(self.string()?);
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                    self.skip_whitespace();
+                    entries.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Node::Map(entries));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Node::Array(items));
+                }
+                loop {
+                    self.skip_whitespace();
+                    items.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Node::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'"') => Ok(Node::Text(self.string()?)),
+            Some(b't') => self.literal("true", Node::Bool(true)),
+            Some(b'f') => self.literal("false", Node::Bool(false)),
+            Some(b'n') => self.literal("null", Node::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn number(&mut self) -> DecodeResult<Node> {
+        let start = self.pos;
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.pos])
+            .map_err(|_| self.error("invalid number"))?;
+        if let Ok(i) = text.parse::<i128>() {
+            return Ok(Node::Int(i));
+        }
+        text.parse::<f64>()
+            .map(Node::Float)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> DecodeResult<String> {
+        self.expect(b'"')?;
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(out)
+                        .map_err(|_| self.error("invalid UTF-8"));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let c = self.unicode_escape()?;
+                            let mut buf = [0; 4];
+                            out.extend_from_slice(
+                                c.encode_utf8(&mut buf).as_bytes(),
+                            );
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    out.push(escaped as u8);
+                }
+                Some(b) if b < 0x20 => {
+                    return Err(self.error("control character in string"))
+                }
+                Some(b) => {
+                    self.pos += 1;
+                    out.push(b);
+                }
+            }
+        }
+    }
+
+    /// Reads the four hex digits after `\u`, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> DecodeResult<char> {
+        let high = self.hex4()?;
+        if (0xd800..0xdc00).contains(&high) {
+            if self.bytes[self.pos..].starts_with(b"\\u") {
+                self.pos += 2;
+                let low = self.hex4()?;
+                if (0xdc00..0xe000).contains(&low) {
+                    let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                    return char::from_u32(code)
+                        .ok_or_else(|| self.error("invalid surrogate pair"));
+                }
+            }
+            return Err(self.error("unpaired surrogate"));
+        }
+        char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> DecodeResult<u32> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+}
@@ -0,0 +1,544 @@
+use crate::node::Node;
+use crate::toml;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Something a key does while no prompt or popup is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    First,
+    Last,
+    ToggleFocus,
+    FocusTables,
+    FocusEntries,
+    ToggleTables,
+    FilterTables,
+    Search,
+    SearchAll,
+    GotoKey,
+    KeyRange,
+    KeyTree,
+    PinKey,
+    Bookmark,
+    Bookmarks,
+    Back,
+    Forward,
+    Mark,
+    MarkRange,
+    Follow,
+    Reverse,
+    Duplicates,
+    ValueSizes,
+    BTree,
+    Cancel,
+    ShowEntry,
+    Zoom,
+    Edit,
+    EditExternally,
+    CopyKey,
+    CopyValue,
+    Delete,
+    Undo,
+    Redo,
+    NewTable,
+    Rename,
+    Truncate,
+    NextValueDecoder,
+    NextKeyDecoder,
+    NextProtoMessage,
+    Decompress,
+    RefreshStats,
+    SaveDecoders,
+    CheckIntegrity,
+    Compact,
+    Savepoint,
+    PersistentSavepoint,
+    ToggleTheme,
+    ShrinkTables,
+    GrowTables,
+    ToggleLayout,
+    ToggleDurability,
+    ToggleWatch,
+    DataTab,
+    StatsTab,
+    SchemaTab,
+    LogsTab,
+    SavepointsTab,
+    DiffTab,
+    NextDatabase,
+    PreviousDatabase,
+    CommandLine,
+    Help,
+    Quit,
+}
+
+impl Action {
+    /// Name of the action in the `[keys]` section of the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::ToggleFocus => "toggle_focus",
+            Action::FocusTables => "focus_tables",
+            Action::FocusEntries => "focus_entries",
+            Action::ToggleTables => "toggle_tables",
+            Action::FilterTables => "filter_tables",
+            Action::Search => "search",
+            Action::SearchAll => "search_all",
+            Action::GotoKey => "goto_key",
+            Action::KeyRange => "key_range",
+            Action::KeyTree => "key_tree",
+            Action::PinKey => "pin_key",
+            Action::Bookmark => "bookmark",
+            Action::Bookmarks => "bookmarks",
+            Action::Back => "back",
+            Action::Forward => "forward",
+            Action::Mark => "mark",
+            Action::MarkRange => "mark_range",
+            Action::Follow => "follow",
+            Action::Reverse => "reverse",
+            Action::Duplicates => "duplicates",
+            Action::ValueSizes => "value_sizes",
+            Action::BTree => "btree",
+            Action::Cancel => "cancel",
+            Action::ShowEntry => "show_entry",
+            Action::Zoom => "zoom",
+            Action::Edit => "edit",
+            Action::EditExternally => "edit_externally",
+            Action::CopyKey => "copy_key",
+            Action::CopyValue => "copy_value",
+            Action::Delete => "delete",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::NewTable => "new_table",
+            Action::Rename => "rename",
+            Action::Truncate => "truncate",
+            Action::NextValueDecoder => "next_value_decoder",
+            Action::NextKeyDecoder => "next_key_decoder",
+            Action::NextProtoMessage => "next_proto_message",
+            Action::Decompress => "decompress",
+            Action::RefreshStats => "refresh_stats",
+            Action::SaveDecoders => "save_decoders",
+            Action::CheckIntegrity => "check_integrity",
+            Action::Compact => "compact",
+            Action::Savepoint => "savepoint",
+            Action::PersistentSavepoint => "persistent_savepoint",
+            Action::ToggleTheme => "toggle_theme",
+            Action::ShrinkTables => "shrink_tables",
+            Action::GrowTables => "grow_tables",
+            Action::ToggleLayout => "toggle_layout",
+            Action::ToggleDurability => "toggle_durability",
+            Action::ToggleWatch => "toggle_watch",
+            Action::DataTab => "data_tab",
+            Action::StatsTab => "stats_tab",
+            Action::SchemaTab => "schema_tab",
+            Action::LogsTab => "logs_tab",
+            Action::SavepointsTab => "savepoints_tab",
+            Action::DiffTab => "diff_tab",
+            Action::NextDatabase => "next_database",
+            Action::PreviousDatabase => "previous_database",
+            Action::CommandLine => "command_line",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, _)| *action)
+            .find(|action| action.name() == name)
+    }
+
+    /// What the action does, for the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Down => "Select the next table, or entry when focused",
+            Action::Up => "Select the previous table, or entry when focused",
+            Action::PageDown => "Move down a page of entries",
+            Action::PageUp => "Move up a page of entries",
+            Action::HalfPageDown => "Move down half a page of entries",
+            Action::HalfPageUp => "Move up half a page of entries",
+            Action::First => "Select the first table, or entry when focused",
+            Action::Last => "Select the last table, or entry when focused",
+            Action::ToggleFocus => "Switch focus between tables and entries",
+            Action::FocusTables => "Focus the table list",
+            Action::FocusEntries => "Focus the entries pane",
+            Action::ToggleTables => "Hide or show the table list",
+            Action::FilterTables => "Filter the table list",
+            Action::Search => "Search keys and values",
+            Action::SearchAll => "Search keys and values of every table",
+            Action::GotoKey => "Jump to a key",
+            Action::KeyRange => "Narrow down to a key prefix or range",
+            Action::KeyTree => "Browse the keys grouped by prefix",
+            Action::PinKey => "Keep the selected key's value in view, or unpin",
+            Action::Bookmark => {
+                "Bookmark the selected key, or remove its bookmark"
+            }
+            Action::Bookmarks => "List the bookmarks to jump to one",
+            Action::Back => "Go back to the table or key left last",
+            Action::Forward => "Go forward again to where back came from",
+            Action::Mark => "Mark the selected entry, or unmark it",
+            Action::MarkRange => {
+                "Start a range of entries to mark, or mark up to here"
+            }
+            Action::Follow => "Follow the end of the table as entries are added",
+            Action::Reverse => "Reverse the listing order",
+            Action::Duplicates => "Find values stored under more than one key",
+            Action::ValueSizes => "Show how the value sizes of the table spread",
+            Action::BTree => "Show the shape of the table's B-tree",
+            Action::Cancel => {
+                "Stop loading entries, or clear the marks or key range"
+            }
+            Action::ShowEntry => {
+                "Show the selected entry in full, or restore the savepoint"
+            }
+            Action::Zoom => "Zoom the entries pane or entry view",
+            Action::Edit => "Edit the selected value",
+            Action::EditExternally => "Edit the selected value in $EDITOR",
+            Action::CopyKey => "Copy the selected key",
+            Action::CopyValue => "Copy the selected value",
+            Action::Delete => {
+                "Delete the entry or savepoint, or drop the focused table"
+            }
+            Action::Undo => "Undo the last edit or deletion of an entry",
+            Action::Redo => "Redo the last write undone",
+            Action::NewTable => "Create a table",
+            Action::Rename => "Rename the selected table",
+            Action::Truncate => "Clear all entries of the selected table",
+            Action::NextValueDecoder => "Cycle the value decoder",
+            Action::NextKeyDecoder => "Cycle the key decoder",
+            Action::NextProtoMessage => "Cycle the protobuf message type",
+            Action::Decompress => "Unpack compressed values before decoding them",
+            Action::RefreshStats => {
+                "Refresh the stats, or reload a database changed on disk"
+            }
+            Action::SaveDecoders => {
+                "Save the decoders and key groupings to the schema sidecar"
+            }
+            Action::CheckIntegrity => "Check the database file for damage",
+            Action::Compact => "Compact the database file",
+            Action::Savepoint => "Take a savepoint kept until you quit",
+            Action::PersistentSavepoint => {
+                "Store a savepoint in the database file"
+            }
+            Action::ToggleTheme => "Switch between light and dark colors",
+            Action::ShrinkTables => "Give the table list less room",
+            Action::GrowTables => "Give the table list more room",
+            Action::ToggleLayout => {
+                "Put the table list beside or above the entries"
+            }
+            Action::ToggleDurability => {
+                "Switch writes between immediate and eventual durability"
+            }
+            Action::ToggleWatch => "Re-read the database every few seconds",
+            Action::DataTab => "Show the tables and entries",
+            Action::StatsTab => "Show the database and table stats",
+            Action::SchemaTab => "Show the stored types and decoders of tables",
+            Action::LogsTab => "Show the log",
+            Action::SavepointsTab => "Show the savepoints",
+            Action::DiffTab => "Show the differences from another database",
+            Action::NextDatabase => "Show the next open database",
+            Action::PreviousDatabase => "Show the previous open database",
+            Action::CommandLine => "Type a command, listed below",
+            Action::Help => "Show this help",
+            Action::Quit => "Quit",
+        }
+    }
+}
+
+/// Keys of every action, in the order the help lists them. Keys are
+/// written as the characters they type, or by name (`Up`, `PageDown`,
+/// `Enter`), with `Ctrl-` for control chords; `gg` is `g` pressed twice.
+pub const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::Down, &["Down", "j"]),
+    (Action::Up, &["Up", "k"]),
+    (Action::PageDown, &["PageDown"]),
+    (Action::PageUp, &["PageUp"]),
+    (Action::HalfPageDown, &["Ctrl-d"]),
+    (Action::HalfPageUp, &["Ctrl-u"]),
+    (Action::First, &["gg", "Home"]),
+    (Action::Last, &["G", "End"]),
+    (Action::ToggleFocus, &["Tab"]),
+    (Action::FocusTables, &["h"]),
+    (Action::FocusEntries, &["l"]),
+    (Action::ToggleTables, &["b"]),
+    (Action::FilterTables, &["f"]),
+    (Action::Search, &["/"]),
+    (Action::SearchAll, &["Ctrl-f"]),
+    (Action::GotoKey, &["J"]),
+    (Action::KeyRange, &["p"]),
+    (Action::KeyTree, &["t"]),
+    (Action::PinKey, &["P"]),
+    (Action::Bookmark, &["m"]),
+    (Action::Bookmarks, &["'"]),
+    (Action::Back, &["Backspace", "Ctrl-o"]),
+    (Action::Forward, &["Ctrl-i", "Ctrl-n"]),
+    (Action::Mark, &["Space"]),
+    (Action::MarkRange, &["V"]),
+    (Action::Follow, &["F"]),
+    (Action::Reverse, &["o"]),
+    (Action::Duplicates, &["A"]),
+    (Action::ValueSizes, &["H"]),
+    (Action::BTree, &["B"]),
+    (Action::Cancel, &["Esc"]),
+    (Action::ShowEntry, &["Enter"]),
+    (Action::Zoom, &["z"]),
+    (Action::Edit, &["e"]),
+    (Action::EditExternally, &["E"]),
+    (Action::CopyKey, &["y"]),
+    (Action::CopyValue, &["Y"]),
+    (Action::Delete, &["d"]),
+    (Action::Undo, &["u"]),
+    (Action::Redo, &["Ctrl-r"]),
+    (Action::NewTable, &["n"]),
+    (Action::Rename, &["R"]),
+    (Action::Truncate, &["c"]),
+    (Action::NextValueDecoder, &["v"]),
+    (Action::NextKeyDecoder, &["K"]),
+    (Action::NextProtoMessage, &["M"]),
+    (Action::Decompress, &["U"]),
+    (Action::RefreshStats, &["r"]),
+    (Action::SaveDecoders, &["w"]),
+    (Action::CheckIntegrity, &["I"]),
+    (Action::Compact, &["C"]),
+    (Action::Savepoint, &["s"]),
+    (Action::PersistentSavepoint, &["S"]),
+    (Action::ToggleTheme, &["T"]),
+    (Action::ShrinkTables, &["<"]),
+    (Action::GrowTables, &[">"]),
+    (Action::ToggleLayout, &["L"]),
+    (Action::ToggleDurability, &["D"]),
+    (Action::ToggleWatch, &["W"]),
+    (Action::DataTab, &["1"]),
+    (Action::StatsTab, &["2"]),
+    (Action::SchemaTab, &["3"]),
+    (Action::LogsTab, &["4"]),
+    (Action::SavepointsTab, &["5"]),
+    (Action::DiffTab, &["6"]),
+    (Action::NextDatabase, &["]"]),
+    (Action::PreviousDatabase, &["["]),
+    (Action::CommandLine, &[":"]),
+    (Action::Help, &["?"]),
+    (Action::Quit, &["q"]),
+];
+
+/// A key press, with Ctrl held down or not. Shift is part of the character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+/// Keys written by name, as in `DEFAULT_BINDINGS`.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl Key {
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self {
+            code: event.code,
+            ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+        }
+    }
+
+    /// Parses a single key: a character, a name from `KEY_NAMES`, `F1` to
+    /// `F12`, each optionally prefixed with `Ctrl-`.
+    fn parse(text: &str) -> Option<Self> {
+        let (ctrl, name) = match text.strip_prefix("Ctrl-") {
+            Some(name) => (true, name),
+            None => (false, text),
+        };
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => KEY_NAMES
+                .iter()
+                .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+                .map(|(_, code)| *code)
+                .or_else(|| {
+                    let n = name.strip_prefix('F')?.parse().ok()?;
+                    (1..=12).contains(&n).then_some(KeyCode::F(n))
+                })?,
+        };
+        Some(Self { code, ctrl })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// Parses a binding: one key, or a sequence of plain characters pressed
+/// one after the other such as `gg`.
+fn parse_binding(text: &str) -> Option<Vec<Key>> {
+    if let Some(key) = Key::parse(text) {
+        return Some(vec![key]);
+    }
+    if text.chars().count() == 2 && !text.contains(char::is_whitespace) {
+        return Some(
+            text.chars()
+                .filter_map(|c| Key::parse(&c.to_string()))
+                .collect(),
+        );
+    }
+    None
+}
+
+fn binding_label(keys: &[Key]) -> String {
+    keys.iter().map(Key::to_string).collect()
+}
+
+/// Bindings of the actions: the defaults, with the actions listed in the
+/// `[keys]` section of the config file rebound.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    /// Keys of every action, in the order of `DEFAULT_BINDINGS`.
+    bindings: Vec<(Action, Vec<Vec<Key>>)>,
+    lookup: HashMap<Vec<Key>, Action>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, keys)| {
+                let keys = keys
+                    .iter()
+                    .map(|k| parse_binding(k).expect("valid default binding"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+        Self::from_bindings(bindings)
+    }
+}
+
+impl KeyMap {
+    fn from_bindings(bindings: Vec<(Action, Vec<Vec<Key>>)>) -> Self {
+        let lookup = bindings
+            .iter()
+            .flat_map(|(action, keys)| keys.iter().map(|k| (k.clone(), *action)))
+            .collect();
+        Self { bindings, lookup }
+    }
+
+    /// Reads the `[keys]` section of the config file, which maps action
+    /// names to a key or a list of keys:
+    ///
+    /// ```toml
+    /// [keys]
+    /// search = "s"
+    /// down = ["j", "Down", "Ctrl-n"]
+    /// ```
+    ///
+    /// Listed actions lose their default keys, and keys taken by them are
+    /// unbound from other actions.
+    pub fn parse(section: &Node) -> Result<Self, String> {
+        let mut bindings = Self::default().bindings;
+        for (name, keys) in toml::entries(section) {
+            let action = Action::from_name(name)
+                .ok_or_else(|| format!("keys.{}: unknown action", name))?;
+            let texts: Vec<&str> = match keys {
+                Node::Text(key) => vec![key.as_str()],
+                Node::Array(keys) => keys
+                    .iter()
+                    .map(|key| match key {
+                        Node::Text(key) => Ok(key.as_str()),
+                        _ => Err(format!("keys.{}: expected a string", name)),
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => {
+                    return Err(format!(
+                        "keys.{}: expected a string or array",
+                        name
+                    ))
+                }
+            };
+            let keys = texts
+                .iter()
+                .map(|text| {
+                    parse_binding(text).ok_or_else(|| {
+                        format!("keys.{}: unknown key '{}'", name, text)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for (other, other_keys) in bindings.iter_mut() {
+                if *other == action {
+                    other_keys.clone_from(&keys);
+                } else {
+                    other_keys.retain(|k| !keys.contains(k));
+                }
+            }
+        }
+        Ok(Self::from_bindings(bindings))
+    }
+
+    /// Action bound to `keys`, which is one key or a sequence.
+    pub fn action(&self, keys: &[Key]) -> Option<Action> {
+        self.lookup.get(keys).copied()
+    }
+
+    /// Whether `key` starts a sequence such as `gg`.
+    pub fn is_prefix(&self, key: Key) -> bool {
+        self.lookup
+            .keys()
+            .any(|keys| keys.len() > 1 && keys[0] == key)
+    }
+
+    /// Rows of the help as `(keys, description)`, in `DEFAULT_BINDINGS`
+    /// order, leaving out actions without keys.
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        self.bindings
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(action, keys)| {
+                let keys: Vec<_> =
+                    keys.iter().map(|k| binding_label(k)).collect();
+                (keys.join(", "), action.description())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,400 @@
+use std::cmp::Ordering;
+
+/// How redb encodes and orders the keys of one of its builtin types, worked
+/// out from the stored type name. Raw keys written through `RawKey` must
+/// land where redb's own `Key::compare` would put them, so only the types
+/// modelled here can be written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyOrder {
+    Unit,
+    Bool,
+    /// A `char`, as three little-endian bytes.
+    Char,
+    /// A little-endian integer of `width` bytes.
+    Int {
+        width: usize,
+        signed: bool,
+    },
+    /// `&[u8]`, in byte order.
+    Bytes,
+    /// `&str` and `String`: UTF-8, which orders the same as its bytes.
+    Str,
+    /// A flag byte, then the value or, for `None` of a fixed width type,
+    /// that many zero bytes.
+    Option(Box<KeyOrder>),
+    /// `[T;N]`, including `[u8;N]`.
+    Array(Box<KeyOrder>, usize),
+    /// Tuples of up to twelve elements, compared element by element.
+    Tuple(Vec<KeyOrder>),
+}
+
+impl KeyOrder {
+    /// The order of the builtin type called `name`, e.g. `(u64,&str)`.
+    /// `None` for types redb-tui doesn't model, user defined ones among
+    /// them.
+    pub fn parse(name: &str) -> Option<Self> {
+        let int = |width, signed| Some(KeyOrder::Int { width, signed });
+        match name {
+            "()" => return Some(KeyOrder::Unit),
+            "bool" => return Some(KeyOrder::Bool),
+            "char" => return Some(KeyOrder::Char),
+            "u8" => return int(1, false),
+            "u16" => return int(2, false),
+            "u32" => return int(4, false),
+            "u64" => return int(8, false),
+            "u128" => return int(16, false),
+            "i8" => return int(1, true),
+            "i16" => return int(2, true),
+            "i32" => return int(4, true),
+            "i64" => return int(8, true),
+            "i128" => return int(16, true),
+            "&[u8]" => return Some(KeyOrder::Bytes),
+            "&str" | "String" => return Some(KeyOrder::Str),
+            _ => {}
+        }
+        if let Some(inner) = name
+            .strip_prefix("Option<")
+            .and_then(|n| n.strip_suffix('>'))
+        {
+            return Some(KeyOrder::Option(Box::new(Self::parse(inner)?)));
+        }
+        if let Some(inner) =
+            name.strip_prefix('[').and_then(|n| n.strip_suffix(']'))
+        {
+            let (element, len) = inner.rsplit_once(';')?;
+            let len = len.parse().ok()?;
+            return Some(KeyOrder::Array(Box::new(Self::parse(element)?), len));
+        }
+        let inner = name.strip_prefix('(')?.strip_suffix(')')?;
+        let mut elements = split_top_level(inner)?;
+        // A tuple of one is written `(T,)`.
+        if elements.len() == 2 && elements[1].is_empty() {
+            elements.pop();
+        }
+        if elements.len() < 2 && !inner.ends_with(',') {
+            return None;
+        }
+        elements
+            .into_iter()
+            .map(Self::parse)
+            .collect::<Option<_>>()
+            .map(KeyOrder::Tuple)
+    }
+
+    /// Length of every encoded key, when it's the same for all of them.
+    pub fn fixed_width(&self) -> Option<usize> {
+        match self {
+            KeyOrder::Unit => Some(0),
+            KeyOrder::Bool => Some(1),
+            KeyOrder::Char => Some(3),
+            KeyOrder::Int { width, .. } => Some(*width),
+            KeyOrder::Bytes | KeyOrder::Str => None,
+            KeyOrder::Option(inner) => inner.fixed_width().map(|w| w + 1),
+            KeyOrder::Array(element, len) => {
+                element.fixed_width().map(|w| w * len)
+            }
+            KeyOrder::Tuple(elements) => {
+                elements.iter().map(KeyOrder::fixed_width).sum()
+            }
+        }
+    }
+
+    /// Whether `data` is a key redb could have encoded. redb panics on
+    /// keys it can't decode, so nothing else may be written.
+    pub fn check(&self, data: &[u8]) -> bool {
+        match self {
+            KeyOrder::Unit => data.is_empty(),
+            KeyOrder::Bool => matches!(data, [0 | 1]),
+            KeyOrder::Char => match data {
+                [a, b, c] => {
+                    char::from_u32(u32::from_le_bytes([*a, *b, *c, 0])).is_some()
+                }
+                _ => false,
+            },
+            KeyOrder::Int { width, .. } => data.len() == *width,
+            KeyOrder::Bytes => true,
+            KeyOrder::Str => std::str::from_utf8(data).is_ok(),
+            KeyOrder::Option(inner) => match data.split_first() {
+                Some((0, rest)) => {
+                    rest.len() == inner.fixed_width().unwrap_or(0)
+                        && rest.iter().all(|b| *b == 0)
+                }
+                Some((1, rest)) => inner.check(rest),
+                _ => false,
+            },
+            KeyOrder::Array(element, len) => self
+                .check_parts(element_parts(element, *len, data), || {
+                    std::iter::repeat(element.as_ref())
+                }),
+            KeyOrder::Tuple(elements) => {
+                self.check_parts(tuple_parts(elements, data), || elements.iter())
+            }
+        }
+    }
+
+    fn check_parts<'a, I: Iterator<Item = &'a KeyOrder>>(
+        &self,
+        parts: Option<Vec<&[u8]>>,
+        orders: impl FnOnce() -> I,
+    ) -> bool {
+        parts.is_some_and(|parts| {
+            orders().zip(&parts).all(|(order, part)| order.check(part))
+        })
+    }
+
+    /// Orders two encoded keys as redb does. Keys that don't decode, which
+    /// `check` keeps out of the tables written to, fall back to byte order.
+    pub fn compare(&self, data1: &[u8], data2: &[u8]) -> Ordering {
+        self.try_compare(data1, data2)
+            .unwrap_or_else(|| data1.cmp(data2))
+    }
+
+    fn try_compare(&self, data1: &[u8], data2: &[u8]) -> Option<Ordering> {
+        let order = match self {
+            KeyOrder::Unit => Ordering::Equal,
+            KeyOrder::Bool | KeyOrder::Bytes | KeyOrder::Str => data1.cmp(data2),
+            KeyOrder::Char => char_value(data1)?.cmp(&char_value(data2)?),
+            KeyOrder::Int {
+                width,
+                signed: false,
+            } => unsigned(data1, *width)?.cmp(&unsigned(data2, *width)?),
+            KeyOrder::Int {
+                width,
+                signed: true,
+            } => signed(data1, *width)?.cmp(&signed(data2, *width)?),
+            KeyOrder::Option(inner) => {
+                let ((flag1, rest1), (flag2, rest2)) =
+                    (data1.split_first()?, data2.split_first()?);
+                match (flag1, flag2) {
+                    (0, 0) => Ordering::Equal,
+                    (0, _) => Ordering::Less,
+                    (_, 0) => Ordering::Greater,
+                    _ => inner.try_compare(rest1, rest2)?,
+                }
+            }
+            KeyOrder::Array(element, len) => compare_parts(
+                std::iter::repeat(element.as_ref()),
+                element_parts(element, *len, data1)?,
+                element_parts(element, *len, data2)?,
+            )?,
+            KeyOrder::Tuple(elements) => compare_parts(
+                elements.iter(),
+                tuple_parts(elements, data1)?,
+                tuple_parts(elements, data2)?,
+            )?,
+        };
+        Some(order)
+    }
+}
+
+/// Splits `text` at the commas outside any brackets.
+fn split_top_level(text: &str) -> Option<Vec<&str>> {
+    let (mut parts, mut depth, mut start) = (Vec::new(), 0usize, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    (depth == 0).then_some(parts)
+}
+
+/// The elements of a tuple: one after another when they all have a fixed
+/// width, otherwise after the lengths of all but the last as `u32`s.
+fn tuple_parts<'a>(
+    elements: &[KeyOrder],
+    data: &'a [u8],
+) -> Option<Vec<&'a [u8]>> {
+    let widths: Option<Vec<usize>> =
+        elements.iter().map(KeyOrder::fixed_width).collect();
+    if let Some(widths) = widths {
+        return fixed_parts(&widths, data);
+    }
+    let (last, lens) = elements.len().checked_sub(1).map(|n| (n, n * 4))?;
+    let mut offset = lens;
+    let mut parts = Vec::with_capacity(elements.len());
+    for i in 0..last {
+        let len = read_u32(data, i * 4)?;
+        parts.push(data.get(offset..offset.checked_add(len)?)?);
+        offset += len;
+    }
+    parts.push(data.get(offset..)?);
+    Some(parts)
+}
+
+/// The elements of an array: one after another for a fixed width element,
+/// otherwise after the end offsets of all of them as `u32`s.
+fn element_parts<'a>(
+    element: &KeyOrder,
+    len: usize,
+    data: &'a [u8],
+) -> Option<Vec<&'a [u8]>> {
+    if let Some(width) = element.fixed_width() {
+        return fixed_parts(&vec![width; len], data);
+    }
+    let mut start = len.checked_mul(4)?;
+    let mut parts = Vec::with_capacity(len);
+    for i in 0..len {
+        let end = read_u32(data, i * 4)?;
+        parts.push(data.get(start..end)?);
+        start = end;
+    }
+    (start == data.len()).then_some(parts)
+}
+
+fn fixed_parts<'a>(widths: &[usize], data: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+    if widths.iter().sum::<usize>() != data.len() {
+        return None;
+    }
+    let mut offset = 0;
+    Some(
+        widths
+            .iter()
+            .map(|width| {
+                offset += width;
+                &data[offset - width..offset]
+            })
+            .collect(),
+    )
+}
+
+fn compare_parts<'a>(
+    orders: impl Iterator<Item = &'a KeyOrder>,
+    parts1: Vec<&[u8]>,
+    parts2: Vec<&[u8]>,
+) -> Option<Ordering> {
+    for ((order, part1), part2) in orders.zip(parts1).zip(parts2) {
+        match order.try_compare(part1, part2)? {
+            Ordering::Equal => {}
+            other => return Some(other),
+        }
+    }
+    Some(Ordering::Equal)
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<usize> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+}
+
+fn char_value(data: &[u8]) -> Option<u32> {
+    match data {
+        [a, b, c] => Some(u32::from_le_bytes([*a, *b, *c, 0])),
+        _ => None,
+    }
+}
+
+fn unsigned(data: &[u8], width: usize) -> Option<u128> {
+    if data.len() != width {
+        return None;
+    }
+    let mut bytes = [0; 16];
+    bytes[..width].copy_from_slice(data);
+    Some(u128::from_le_bytes(bytes))
+}
+
+fn signed(data: &[u8], width: usize) -> Option<i128> {
+    let negative = *data.last()? >= 0x80;
+    let mut bytes = [if negative { 0xff } else { 0 }; 16];
+    if data.len() != width {
+        return None;
+    }
+    bytes[..width].copy_from_slice(data);
+    Some(i128::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> KeyOrder {
+        KeyOrder::parse(name).unwrap()
+    }
+
+    #[test]
+    fn parses_builtin_names() {
+        assert_eq!(
+            parse("(u64,&str)"),
+            KeyOrder::Tuple(vec![
+                KeyOrder::Int {
+                    width: 8,
+                    signed: false
+                },
+                KeyOrder::Str
+            ])
+        );
+        assert_eq!(parse("(u8,)"), KeyOrder::Tuple(vec![parse("u8")]));
+        assert_eq!(
+            parse("Option<[(i32,bool);2]>").fixed_width(),
+            Some(1 + 2 * 5)
+        );
+        assert_eq!(parse("[u8;16]").fixed_width(), Some(16));
+        for name in ["f64", "MyKey", "(u8", "(u8,MyKey)", "Option<u8", "()x"] {
+            assert_eq!(KeyOrder::parse(name), None, "{}", name);
+        }
+    }
+
+    /// Checks `order` against redb's own comparison of the keys.
+    fn same_as_redb(
+        name: &str,
+        keys: &[Vec<u8>],
+        compare: fn(&[u8], &[u8]) -> Ordering,
+    ) {
+        let order = parse(name);
+        for a in keys {
+            assert!(order.check(a), "{} {:?}", name, a);
+            for b in keys {
+                assert_eq!(order.compare(a, b), compare(a, b), "{}", name);
+            }
+        }
+    }
+
+    macro_rules! same_as_redb {
+        ($name:expr, $t:ty, [$($key:expr),+ $(,)?]) => {{
+            use redb::{Key, Value};
+            let keys = vec![
+                $(AsRef::<[u8]>::as_ref(&<$t>::as_bytes(&$key)).to_vec()),+
+            ];
+            same_as_redb($name, &keys, <$t>::compare);
+        }};
+    }
+
+    #[test]
+    fn orders_as_redb() {
+        same_as_redb!("i16", i16, [-300i16, -1, 0, 1, 255, 256, i16::MAX]);
+        same_as_redb!("u64", u64, [0u64, 255, 256, 1 << 40, u64::MAX]);
+        same_as_redb!("char", char, ['a', 'é', '\u{10ffff}', '\0']);
+        same_as_redb!(
+            "(u64,u64)",
+            (u64, u64),
+            [(1500u64, 0u64), (0, 1500), (255, 256), (256, 255)]
+        );
+        same_as_redb!("Option<u32>", Option<u32>, [None, Some(0u32), Some(9)]);
+        same_as_redb!(
+            "(i8,&str,u16)",
+            (i8, &str, u16),
+            [(-1i8, "b", 2u16), (-1, "a", 3), (1, "", 0), (-1, "b", 1)]
+        );
+        same_as_redb!("[&str;2]", [&str; 2], [["b", "a"], ["a", "b"], ["", "z"]]);
+        same_as_redb!("Option<&str>", Option<&str>, [None, Some(""), Some("x")]);
+    }
+
+    #[test]
+    fn rejects_what_redb_couldnt_decode() {
+        assert!(!parse("u64").check(&[0; 7]));
+        assert!(!parse("bool").check(&[2]));
+        assert!(!parse("&str").check(&[0xff]));
+        assert!(!parse("Option<u64>").check(&[0]));
+        assert!(parse("Option<u64>").check(&[0; 9]));
+        assert!(!parse("(u64,&str)").check(&[0; 4]));
+        // Tuple element lengths past the end of the key.
+        assert!(!parse("(&str,&str)").check(&[9, 0, 0, 0, b'a']));
+        assert!(!parse("[&str;2]").check(&[9, 0, 0, 0, 8, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::database::KeyChild;
+use crate::decoder;
+use crate::node::Node;
+
+/// Characters keys are grouped by.
+pub const SEPARATORS: &[u8] = b":/.|#";
+
+/// How the keys of a table are grouped in the key tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    /// By the part of a key up to a separator, which it ends with.
+    Separator(u8),
+    /// By every so many bytes, for keys made of fixed-width parts.
+    Bytes(usize),
+}
+
+impl Grouping {
+    /// Reads a grouping of the schema sidecar: a separator character, or a
+    /// number of bytes.
+    pub fn from_node(node: &Node) -> std::result::Result<Self, String> {
+        match node {
+            Node::Text(text) => match text.as_bytes() {
+                [separator] => Ok(Grouping::Separator(*separator)),
+                _ => Err(format!("expected one ASCII character, got '{}'", text)),
+            },
+            Node::Int(bytes) if *bytes > 0 => usize::try_from(*bytes)
+                .map(Grouping::Bytes)
+                .map_err(|_| format!("{} bytes is too many", bytes)),
+            _ => Err("expected a separator or a number of bytes".to_string()),
+        }
+    }
+
+    pub fn to_node(self) -> Node {
+        match self {
+            Grouping::Separator(separator) => {
+                Node::Text(char::from(separator).to_string())
+            }
+            Grouping::Bytes(bytes) => Node::Int(bytes as i128),
+        }
+    }
+
+    /// Length of the group the part `rest` of a key starts with, if the key
+    /// goes on past it.
+    pub fn group_len(self, rest: &[u8]) -> Option<usize> {
+        match self {
+            Grouping::Separator(separator) => {
+                rest.iter().position(|b| *b == separator).map(|at| at + 1)
+            }
+            Grouping::Bytes(bytes) => (rest.len() > bytes).then_some(bytes),
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Grouping::Separator(separator) => {
+                format!("'{}'", char::from(separator))
+            }
+            Grouping::Bytes(1) => "1 byte".to_string(),
+            Grouping::Bytes(bytes) => format!("{} bytes", bytes),
+        }
+    }
+}
+
+/// The separator found in the most of `keys`, the earlier one in
+/// `SEPARATORS` on a tie; `:` when keys have none.
+pub fn detect_separator<'a>(keys: impl IntoIterator<Item = &'a [u8]>) -> u8 {
+    let mut counts = [0usize; SEPARATORS.len()];
+    for key in keys {
+        for (count, separator) in counts.iter_mut().zip(SEPARATORS) {
+            if key.contains(separator) {
+                *count += 1;
+            }
+        }
+    }
+    SEPARATORS
+        .iter()
+        .zip(counts)
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .filter(|(_, count)| *count > 0)
+        .map_or(b':', |(separator, _)| *separator)
+}
+
+/// The keys of a table as a tree of prefixes, as the rows shown with the
+/// groups opened so far.
+#[derive(Debug)]
+pub struct KeyTree {
+    pub table: String,
+    pub grouping: Grouping,
+    pub rows: Vec<TreeRow>,
+    pub selected: usize,
+}
+
+#[derive(Debug)]
+pub struct TreeRow {
+    /// The prefix of a group, or a key.
+    pub key: Vec<u8>,
+    /// Length of the prefix of the group the row is in.
+    pub parent_len: usize,
+    pub depth: usize,
+    pub kind: RowKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+    Group {
+        open: bool,
+    },
+    Key,
+    /// Stands for the children past the limit read.
+    More,
+}
+
+impl KeyTree {
+    /// A tree of `children`, the top-level keys and groups.
+    pub fn new(
+        table: String,
+        grouping: Grouping,
+        children: Vec<KeyChild>,
+        more: bool,
+    ) -> Self {
+        Self {
+            table,
+            grouping,
+            rows: rows(children, more, 0, 0),
+            selected: 0,
+        }
+    }
+
+    /// The selected row, if there is one.
+    pub fn selected_row(&self) -> Option<&TreeRow> {
+        self.rows.get(self.selected)
+    }
+
+    /// Shows `children` under the group at `row`.
+    pub fn open(&mut self, row: usize, children: Vec<KeyChild>, more: bool) {
+        let Some(group) = self.rows.get_mut(row) else {
+            return;
+        };
+        group.kind = RowKind::Group { open: true };
+        let (parent_len, depth) = (group.key.len(), group.depth + 1);
+        let rows = rows(children, more, parent_len, depth);
+        self.rows.splice(row + 1..row + 1, rows);
+    }
+
+    /// Hides the rows under the group at `row`.
+    pub fn close(&mut self, row: usize) {
+        let Some(group) = self.rows.get_mut(row) else {
+            return;
+        };
+        group.kind = RowKind::Group { open: false };
+        let depth = group.depth;
+        let end = self.rows[row + 1..]
+            .iter()
+            .position(|r| r.depth <= depth)
+            .map_or(self.rows.len(), |n| row + 1 + n);
+        self.rows.drain(row + 1..end);
+        if self.selected >= end {
+            self.selected -= end - row - 1;
+        } else if self.selected > row {
+            self.selected = row;
+        }
+    }
+
+    /// The row of the group `row` is in.
+    pub fn parent(&self, row: usize) -> Option<usize> {
+        let depth = self.rows.get(row)?.depth;
+        self.rows[..row].iter().rposition(|r| r.depth < depth)
+    }
+
+    /// The lines of the rows: the part of each key past its group's
+    /// prefix, as hex unless it is text, indented by depth, with groups
+    /// marked open or closed.
+    pub fn lines(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let part = &row.key[row.parent_len..];
+                let part = match std::str::from_utf8(part) {
+                    Ok(text) if !text.chars().any(char::is_control) => {
+                        text.to_string()
+                    }
+                    _ => format!("0x{}", decoder::hex(part)),
+                };
+                let marker = match row.kind {
+                    RowKind::Group { open: true } => "▾ ",
+                    RowKind::Group { open: false } => "▸ ",
+                    RowKind::Key => "  ",
+                    RowKind::More => "  …",
+                };
+                format!("{}{}{}", "  ".repeat(row.depth), marker, part)
+            })
+            .collect()
+    }
+}
+
+fn rows(
+    children: Vec<KeyChild>,
+    more: bool,
+    parent_len: usize,
+    depth: usize,
+) -> Vec<TreeRow> {
+    let row = |key, kind| TreeRow {
+        key,
+        parent_len,
+        depth,
+        kind,
+    };
+    let mut rows: Vec<TreeRow> = children
+        .into_iter()
+        .map(|child| match child {
+            KeyChild::Group(key) => row(key, RowKind::Group { open: false }),
+            KeyChild::Key(key) => row(key, RowKind::Key),
+        })
+        .collect();
+    if more {
+        rows.push(TreeRow {
+            key: Vec::new(),
+            parent_len: 0,
+            depth,
+            kind: RowKind::More,
+        });
+    }
+    rows
+}
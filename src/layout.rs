@@ -1,19 +1,86 @@
+use crate::decoder;
+use crate::input::Input;
+use crate::search::Query;
+use crate::theme::Theme;
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
+    symbols::{border, scrollbar},
+    text::{Line, Span},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Borders, Clear, Gauge, List, ListItem,
+        ListState, Paragraph, Row, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Table, Tabs, Wrap,
+    },
     Frame,
 };
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
-pub fn get_layout(size: Rect) -> (Rect, Rect, Rect) {
+/// Share of the screen taken by the table list, in percent, unless
+/// configured otherwise.
+pub const DEFAULT_SPLIT: u16 = 30;
+
+/// Smallest and largest share of the screen the table list can take.
+pub const SPLIT_RANGE: RangeInclusive<u16> = 10..=90;
+
+/// How the table list and the entries pane are arranged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayoutMode {
+    /// Side by side, the table list on the left.
+    #[default]
+    Horizontal,
+    /// The table list above the entries pane, for narrow terminals.
+    Vertical,
+}
+
+impl LayoutMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LayoutMode::Horizontal => "horizontal",
+            LayoutMode::Vertical => "vertical",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "horizontal" => Some(LayoutMode::Horizontal),
+            "vertical" => Some(LayoutMode::Vertical),
+            _ => None,
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            LayoutMode::Horizontal => LayoutMode::Vertical,
+            LayoutMode::Vertical => LayoutMode::Horizontal,
+        }
+    }
+}
+
+/// Splits the screen into the table list, taking `split` percent of the
+/// width or, in vertical mode, the height, the entries pane and the status
+/// bar.
+pub fn get_layout(
+    size: Rect,
+    mode: LayoutMode,
+    split: u16,
+) -> (Rect, Rect, Rect) {
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(3), Constraint::Length(4)])
         .split(size);
 
+    let direction = match mode {
+        LayoutMode::Horizontal => Direction::Horizontal,
+        LayoutMode::Vertical => Direction::Vertical,
+    };
     let top_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .direction(direction)
+        .constraints([
+            Constraint::Percentage(split),
+            Constraint::Percentage(100 - split),
+        ])
         .split(main_layout[0]);
 
     (top_layout[0], top_layout[1], main_layout[1])
@@ -21,7 +88,9 @@ pub fn get_layout(size: Rect) -> (Rect, Rect, Rect) {
 
 pub fn render_table_list(
     frame: &mut Frame,
+    theme: &Theme,
     area: Rect,
+    title: &str,
     table_names: &[String],
     list_state: &mut ListState,
 ) {
@@ -31,42 +100,1078 @@ pub fn render_table_list(
         .collect();
 
     let list = List::new(items)
-        .block(Block::default().title("ReDB Tables").borders(Borders::ALL))
-        .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().bg(Color::LightGreen).fg(Color::Black));
+        .block(bordered(theme).title(title))
+        .style(Style::default().fg(theme.text))
+        .highlight_style(
+            Style::default().bg(theme.selected_bg).fg(theme.selected_fg),
+        );
 
     frame.render_stateful_widget(list, area, list_state);
+    render_scrollbar(
+        frame,
+        theme,
+        area,
+        table_names.len(),
+        list_state.selected().unwrap_or(0),
+    );
+}
+
+/// Box-drawing characters replaced by `--ascii`.
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+const ASCII_SCROLLBAR: scrollbar::Set = scrollbar::Set {
+    track: "|",
+    thumb: "#",
+    begin: "^",
+    end: "v",
+};
+
+/// Block with borders all around, drawn in ASCII if the theme says so.
+fn bordered(theme: &Theme) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL);
+    if theme.ascii {
+        block.border_set(ASCII_BORDER)
+    } else {
+        block
+    }
+}
+
+/// Scrollbar on the right border of `area` at `position` out of `len`
+/// rows, shown only when they don't all fit.
+fn render_scrollbar(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    len: usize,
+    position: usize,
+) {
+    let inner = area.inner(Margin {
+        vertical: 1,
+        horizontal: 0,
+    });
+    if len <= inner.height as usize {
+        return;
+    }
+    let mut state = ScrollbarState::new(len).position(position);
+    let mut scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight);
+    if theme.ascii {
+        scrollbar = scrollbar.symbols(ASCII_SCROLLBAR);
+    }
+    frame.render_stateful_widget(scrollbar, inner, &mut state);
+}
+
+/// Filter input at the top of `area`, above the table list. Returns the
+/// area left for the list.
+pub fn render_filter_box(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    input: &Input,
+    editing: bool,
+) -> Rect {
+    let [filter_area, list_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(area);
+    let (text, cursor) =
+        input.visible(filter_area.width.saturating_sub(2) as usize);
+    let style = if editing {
+        Style::default().fg(theme.accent)
+    } else {
+        Style::default()
+    };
+    let filter = Paragraph::new(text)
+        .block(bordered(theme).title("Filter"))
+        .style(style);
+    frame.render_widget(filter, filter_area);
+    if editing {
+        frame.set_cursor_position((
+            filter_area.x + 1 + cursor as u16,
+            filter_area.y + 1,
+        ));
+    }
+    list_area
+}
+
+/// What the entries pane shows.
+pub struct EntriesPane<'a> {
+    pub table: &'a str,
+    /// Key range the entries are narrowed to, as typed.
+    pub range: Option<&'a str>,
+    /// Whether entries are listed from the last key down.
+    pub reverse: bool,
+    /// Label of the decoders in use.
+    pub decoder: &'a str,
+    pub pairs: &'a [(String, String)],
+    /// Whether each of `pairs` is marked for export.
+    pub marked: &'a [bool],
+    /// How the value of each of `pairs` looks packed, if it does.
+    pub packings: &'a [Option<&'a str>],
+    /// Entries marked, including those not in view.
+    pub marked_count: usize,
+    /// Whether the pane receives navigation keys.
+    pub focused: bool,
+    pub search: Option<&'a Query>,
+    /// Matches of the search in the whole listing so far, and whether they
+    /// are all counted.
+    pub matches: Option<(usize, bool)>,
+    /// Entries in the listing including those not loaded yet, when known.
+    pub total: Option<usize>,
+    /// How long entries have been loading, while they are.
+    pub loading: Option<Duration>,
+    /// Whether new entries at the end are read as they are added.
+    pub following: bool,
 }
 
 pub fn render_key_value_pairs(
     frame: &mut Frame,
+    theme: &Theme,
     area: Rect,
-    selected_table: &str,
-    key_value_pairs: &[(String, String)],
+    pane: &EntriesPane,
+    entry_state: &mut ListState,
 ) {
-    let content = key_value_pairs
+    let items: Vec<ListItem> = pane
+        .pairs
         .iter()
-        .map(|(k, v)| format!("{}: {}", k, v))
-        .collect::<Vec<String>>()
-        .join("\n");
-
-    let paragraph = Paragraph::new(content).block(
-        Block::default()
-            .title(format!("Table: {}", selected_table))
-            .borders(Borders::ALL),
+        .zip(pane.marked)
+        .zip(pane.packings)
+        .map(|(((k, v), &marked), packing)| {
+            let text = format!("{}: {}", k, v);
+            let mut line = match pane.search {
+                Some(query) => highlight_matches(theme, &text, query),
+                None => Line::raw(text),
+            };
+            if let Some(packing) = packing {
+                let badge = format!("[{}] ", packing);
+                line.spans.insert(
+                    0,
+                    Span::styled(badge, Style::default().fg(theme.accent)),
+                );
+            }
+            if marked {
+                line.spans.insert(0, Span::raw("+ "));
+                line = line.style(Style::default().fg(theme.accent));
+            }
+            ListItem::new(line)
+        })
+        .collect();
+
+    // The selected entry is only highlighted while the pane has focus.
+    let highlight = if pane.focused {
+        Style::default().bg(theme.selected_bg).fg(theme.selected_fg)
+    } else {
+        Style::default()
+    };
+    let list = List::new(items)
+        .block(bordered(theme).title(entries_title(pane)))
+        .highlight_style(highlight);
+
+    frame.render_stateful_widget(list, area, entry_state);
+    render_scrollbar(
+        frame,
+        theme,
+        area,
+        pane.total.unwrap_or(0).max(pane.pairs.len()),
+        entry_state.selected().unwrap_or(0),
     );
+}
 
-    frame.render_widget(paragraph, area);
+fn entries_title(pane: &EntriesPane) -> String {
+    let mut title = format!("Table: {} [{}]", pane.table, pane.decoder);
+    if pane.reverse {
+        title.push_str(" reversed");
+    }
+    if let Some(range) = pane.range {
+        title.push_str(&format!(" range {}", range));
+    }
+    match pane.search {
+        // Patterns are written between slashes already.
+        Some(query) if query.is_regex() => {
+            title.push_str(&format!(" {}", query.text()))
+        }
+        Some(query) => title.push_str(&format!(" /{}", query.text())),
+        None => {}
+    }
+    match pane.matches {
+        Some((1, true)) => title.push_str(" 1 match"),
+        Some((n, true)) => title.push_str(&format!(" {} matches", n)),
+        Some((n, false)) => title.push_str(&format!(" {}+ matches", n)),
+        None => {}
+    }
+    if pane.marked_count > 0 {
+        title.push_str(&format!(" {} marked", pane.marked_count));
+    }
+    if pane.following {
+        title.push_str(" following");
+    }
+    if let Some(elapsed) = pane.loading {
+        title.push_str(&format!(" {} loading", spinner(elapsed)));
+    }
+    title
 }
 
-pub fn render_bottom_status(frame: &mut Frame, area: Rect, status: &str) {
-    let status_widget = Paragraph::new(status)
+/// Frame of a spinner that has been turning for `elapsed`.
+fn spinner(elapsed: Duration) -> char {
+    const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+    FRAMES[(elapsed.as_millis() / 250) as usize % FRAMES.len()]
+}
+
+/// `text` with the matches of `query` emphasized.
+fn highlight_matches(theme: &Theme, text: &str, query: &Query) -> Line<'static> {
+    let style = Style::default()
+        .fg(theme.accent)
+        .add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for range in query.find(text) {
+        spans.push(Span::raw(text[end..range.start].to_string()));
+        spans.push(Span::styled(text[range.clone()].to_string(), style));
+        end = range.end;
+    }
+    spans.push(Span::raw(text[end..].to_string()));
+    Line::from(spans)
+}
+
+/// `n` with thousands separators, e.g. `1,204`.
+pub fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `done` out of an expected `total` entries, with an estimate of what is
+/// left, e.g. `1,000/20,000 entries, ~19,000 left`.
+pub fn progress_label(done: u64, total: u64) -> String {
+    format!(
+        "{}/{} entries, ~{} left",
+        group_thousands(done),
+        group_thousands(total),
+        group_thousands(total.saturating_sub(done))
+    )
+}
+
+/// Area of `width` x `height` cells centered in `area`.
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Popup with a single-line text input and an optional error below it.
+pub fn render_input_popup(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    input: &Input,
+    error: Option<&str>,
+) {
+    let area = centered_rect(frame.area(), frame.area().width * 4 / 5, 4);
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let (text, cursor) = input.visible(inner_width);
+    let mut lines = vec![Line::raw(text)];
+    if let Some(error) = error {
+        lines.push(Line::styled(error, Style::default().fg(theme.error)));
+    }
+    let popup = Paragraph::new(lines).block(
+        bordered(theme)
+            .title(format!("{} (Enter to apply, Esc to cancel)", title)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+    frame.set_cursor_position((area.x + 1 + cursor as u16, area.y + 1));
+}
+
+/// Everything shown about one entry in the detail view.
+pub struct EntryDetail<'a> {
+    pub table: &'a str,
+    pub key_type: &'a str,
+    pub value_type: &'a str,
+    /// Labels of the decoders in use.
+    pub key_decoder: &'a str,
+    pub value_decoder: &'a str,
+    /// The key and value as decoded.
+    pub key: &'a str,
+    pub value: &'a str,
+    pub key_bytes: &'a [u8],
+    pub value_bytes: &'a [u8],
+    /// How the value looks packed, if it does.
+    pub value_packing: Option<&'a str>,
+}
+
+/// Full key and value of an entry with their sizes and hex dumps, scrolled
+/// down by `scroll` lines, which is kept short of the end.
+pub fn render_entry_detail(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    detail: &EntryDetail,
+    scroll: &mut u16,
+) {
+    let separator = if theme.ascii { "-" } else { "·" };
+    let heading = |name: &str, type_name: &str, len: usize, decoder: &str| {
+        Line::styled(
+            format!(
+                "{name} {sep} {} {sep} {} bytes {sep} {}",
+                type_name,
+                group_thousands(len as u64),
+                decoder,
+                name = name,
+                sep = separator,
+            ),
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD),
+        )
+    };
+    let mut lines = vec![heading(
+        "Key",
+        detail.key_type,
+        detail.key_bytes.len(),
+        detail.key_decoder,
+    )];
+    lines.extend(detail.key.lines().map(Line::raw));
+    lines.push(Line::default());
+    let value_decoder = match detail.value_packing {
+        Some(packing) => {
+            format!("{} {} {}", detail.value_decoder, separator, packing)
+        }
+        None => detail.value_decoder.to_string(),
+    };
+    lines.push(heading(
+        "Value",
+        detail.value_type,
+        detail.value_bytes.len(),
+        &value_decoder,
+    ));
+    lines.extend(detail.value.lines().map(Line::raw));
+    for (name, bytes) in
+        [("Key", detail.key_bytes), ("Value", detail.value_bytes)]
+    {
+        lines.push(Line::default());
+        lines.push(Line::styled(
+            format!("{} bytes", name),
+            Style::default().fg(theme.accent),
+        ));
+        lines.extend(decoder::hex_dump(bytes).into_iter().map(Line::raw));
+    }
+    // Wrapped lines aren't counted, so this may stop a little early.
+    let last = lines
+        .len()
+        .saturating_sub(area.height.saturating_sub(2) as usize);
+    *scroll = (*scroll).min(last as u16);
+
+    let paragraph = Paragraph::new(lines)
         .block(
-            Block::default()
-                .title("Database Info")
-                .borders(Borders::ALL),
+            bordered(theme)
+                .title(format!("Entry of {} (Esc to close)", detail.table)),
         )
-        .style(Style::default().fg(Color::Yellow));
+        .wrap(Wrap { trim: false })
+        .scroll((*scroll, 0));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Popup listing the key bindings as `(keys, description)` and the
+/// commands as `(usage, description)` under the current session state as
+/// `(name, value)`, scrolled down by `scroll` lines, which is kept short of
+/// the end.
+pub fn render_help(
+    frame: &mut Frame,
+    theme: &Theme,
+    state: &[(String, String)],
+    bindings: &[(String, &str)],
+    commands: &[(&str, &str)],
+    scroll: &mut u16,
+) {
+    let area = centered_rect(
+        frame.area(),
+        frame.area().width * 4 / 5,
+        frame.area().height.saturating_sub(2),
+    );
+    let heading = Style::default()
+        .fg(theme.accent)
+        .add_modifier(Modifier::BOLD);
+    let state_width = state
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut lines = vec![Line::styled("Session", heading)];
+    lines.extend(state.iter().map(|(name, value)| {
+        Line::raw(format!("{:width$}  {}", name, value, width = state_width))
+    }));
+    lines.push(Line::default());
+    lines.push(Line::styled("Keys", heading));
+    let keys_width = bindings
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .chain(commands.iter().map(|(usage, _)| usage.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let row = |keys: &str, description: &str| {
+        Line::raw(format!(
+            "{:width$}  {}",
+            keys,
+            description,
+            width = keys_width
+        ))
+    };
+    lines.extend(
+        bindings
+            .iter()
+            .map(|(keys, description)| row(keys, description)),
+    );
+    lines.push(Line::default());
+    lines.push(Line::styled("Commands", heading));
+    lines.extend(
+        commands
+            .iter()
+            .map(|(usage, description)| row(usage, description)),
+    );
+
+    let last = lines
+        .len()
+        .saturating_sub(area.height.saturating_sub(2) as usize);
+    *scroll = (*scroll).min(last as u16);
+    let paragraph = Paragraph::new(lines)
+        .block(bordered(theme).title("Help (Esc to close)"))
+        .scroll((*scroll, 0));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Box in the middle of the screen with `lines` of text, for notices shown
+/// before there is anything else to draw.
+pub fn render_notice(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    lines: &[&str],
+) {
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let area =
+        centered_rect(frame.area(), width as u16 + 4, lines.len() as u16 + 2);
+    let notice = Paragraph::new(
+        lines
+            .iter()
+            .map(|line| Line::raw(*line))
+            .collect::<Vec<_>>(),
+    )
+    .block(bordered(theme).title(title))
+    .style(Style::default().fg(theme.accent));
+    frame.render_widget(notice, area);
+}
+
+/// Popup asking to confirm `message` with `y` or `n`.
+pub fn render_confirm_popup(frame: &mut Frame, theme: &Theme, message: &str) {
+    let width = (message.chars().count() as u16 + 4).max(30);
+    let area = centered_rect(frame.area(), width, 4);
+    let popup =
+        Paragraph::new(vec![Line::raw(message), Line::raw("[y]es / [n]o")])
+            .block(bordered(theme).title("Confirm"))
+            .style(Style::default().fg(theme.accent));
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Popup showing an error `message`, wrapped to fit, until a key is pressed.
+pub fn render_error_popup(frame: &mut Frame, theme: &Theme, message: &str) {
+    const HINT: &str = "Press any key to continue";
+    let width = message
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(HINT.len()) as u16
+        + 4;
+    let width = width.min(72).min(frame.area().width);
+    let inner = width.saturating_sub(2).max(1) as usize;
+    // Rows of the message once wrapped, roughly as `Wrap` does it.
+    let rows: usize = message
+        .lines()
+        .map(|line| line.chars().count().div_ceil(inner).max(1))
+        .sum();
+    let area = centered_rect(frame.area(), width, rows as u16 + 4);
+    let mut lines: Vec<Line> = message.lines().map(Line::raw).collect();
+    lines.push(Line::raw(""));
+    lines.push(Line::styled(HINT, Style::default().fg(theme.accent)));
+    let popup = Paragraph::new(lines)
+        .block(bordered(theme).title("Error"))
+        .style(Style::default().fg(theme.error))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Popup with a gauge of `done` out of `total` entries.
+pub fn render_progress_popup(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    done: u64,
+    total: u64,
+) {
+    let area = centered_rect(frame.area(), frame.area().width * 3 / 5, 3);
+    let label = progress_label(done, total);
+    render_progress_gauge(frame, theme, area, title, ratio(done, total), &label);
+}
+
+/// Popup with a gauge at `percent`, for progress not counted in entries.
+pub fn render_percent_popup(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    percent: f64,
+) {
+    let area = centered_rect(frame.area(), frame.area().width * 3 / 5, 3);
+    let label = format!("{:.0}%", percent);
+    render_progress_gauge(frame, theme, area, title, percent / 100.0, &label);
+}
+
+/// Popup for work of unknown length, with a spinner and the time it has
+/// taken so far.
+pub fn render_busy_popup(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    elapsed: Duration,
+) {
+    let width = (title.chars().count() as u16 + 4).max(30);
+    let area = centered_rect(frame.area(), width, 3);
+    let text = format!("{} {}s", spinner(elapsed), elapsed.as_secs());
+    let popup = Paragraph::new(text)
+        .block(bordered(theme).title(title))
+        .style(Style::default().fg(theme.accent));
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Gauge of `done` out of `total` entries over the bottom of `area`.
+pub fn render_progress_bar(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    title: &str,
+    done: u64,
+    total: u64,
+) {
+    let height = area.height.min(3);
+    let area = Rect {
+        y: area.y + area.height - height,
+        height,
+        ..area
+    };
+    let label = progress_label(done, total);
+    render_progress_gauge(frame, theme, area, title, ratio(done, total), &label);
+}
+
+fn ratio(done: u64, total: u64) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    }
+}
+
+fn render_progress_gauge(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    title: &str,
+    ratio: f64,
+    label: &str,
+) {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let block = bordered(theme).title(title);
+    frame.render_widget(Clear, area);
+    if theme.ascii {
+        // Gauge fills with block characters; draw `[###---] label` instead.
+        let width = (block.inner(area).width as usize)
+            .saturating_sub(label.chars().count() + 3);
+        let filled = (width as f64 * ratio) as usize;
+        let bar = format!(
+            "[{}{}] {}",
+            "#".repeat(filled),
+            "-".repeat(width - filled),
+            label
+        );
+        let bar = Paragraph::new(bar)
+            .block(block)
+            .style(Style::default().fg(theme.gauge));
+        frame.render_widget(bar, area);
+        return;
+    }
+    let gauge = Gauge::default()
+        .block(block)
+        .gauge_style(Style::default().fg(theme.gauge))
+        .ratio(ratio)
+        .label(label);
+    frame.render_widget(gauge, area);
+}
+
+/// Symbols filling the parts of an allocation bar, in turn, and their
+/// plain ASCII stand-ins.
+const ALLOCATION_SYMBOLS: [char; 5] = ['█', '▓', '▒', '░', ' '];
+const ASCII_ALLOCATION_SYMBOLS: [char; 5] = ['#', '=', '+', '-', '.'];
+
+/// Height of the allocation bar with its legend and border.
+pub const ALLOCATION_HEIGHT: u16 = 4;
+
+/// A bar split into `parts` by their sizes, each filled with a symbol of
+/// its own, above a legend of their labels.
+pub fn render_allocation(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    title: &str,
+    parts: &[(String, u64)],
+) {
+    let block = bordered(theme).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let symbols = if theme.ascii {
+        ASCII_ALLOCATION_SYMBOLS
+    } else {
+        ALLOCATION_SYMBOLS
+    };
+    let total: u64 = parts.iter().map(|(_, size)| size).sum();
+    let width = inner.width as u64;
+    // Cells go by the running total, so rounding never leaves a gap.
+    let mut bar = String::new();
+    let mut sum = 0;
+    for (i, (_, size)) in parts.iter().enumerate() {
+        let start = (sum * width).checked_div(total).unwrap_or(0);
+        sum += size;
+        let end = (sum * width).checked_div(total).unwrap_or(0);
+        let symbol = symbols[i % symbols.len()];
+        bar.extend(std::iter::repeat_n(symbol, (end - start) as usize));
+    }
+    let legend: Vec<Span> = parts
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (label, _))| {
+            let symbol = symbols[i % symbols.len()];
+            [
+                Span::styled(
+                    format!("[{}]", symbol),
+                    Style::default().fg(theme.gauge),
+                ),
+                Span::raw(format!(" {}  ", label)),
+            ]
+        })
+        .collect();
+    let lines = vec![
+        Line::styled(bar, Style::default().fg(theme.gauge)),
+        Line::from(legend),
+    ];
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+pub fn render_bottom_status(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    status: &str,
+) {
+    let status_widget = Paragraph::new(status)
+        .block(bordered(theme).title("Database Info"))
+        .style(Style::default().fg(theme.accent));
 
     frame.render_widget(status_widget, area);
 }
+
+/// Tab bar with `titles`, `selected` highlighted.
+pub fn render_tabs(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    titles: &[&str],
+    selected: usize,
+) {
+    let divider = if theme.ascii { "|" } else { "│" };
+    let tabs = Tabs::new(
+        titles
+            .iter()
+            .enumerate()
+            .map(|(i, title)| format!("{} {}", i + 1, title)),
+    )
+    .select(selected)
+    .divider(divider)
+    .highlight_style(
+        Style::default()
+            .fg(theme.accent)
+            .add_modifier(Modifier::BOLD),
+    );
+    frame.render_widget(tabs, area);
+}
+
+/// A pinned key: its value, followed by `note` in the accent color.
+pub fn render_pin(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    title: &str,
+    value: &str,
+    note: &str,
+) {
+    let line = Line::from(vec![
+        Span::raw(value.to_string()),
+        Span::styled(format!("  {}", note), Style::default().fg(theme.accent)),
+    ]);
+    let pin = Paragraph::new(line).block(bordered(theme).title(title));
+    frame.render_widget(pin, area);
+}
+
+/// Coarse length of `duration`, e.g. `5s` or `3m`.
+pub fn age(duration: Duration) -> String {
+    match duration.as_secs() {
+        secs @ 0..60 => format!("{}s", secs),
+        secs @ 60..3600 => format!("{}m", secs / 60),
+        secs => format!("{}h", secs / 3600),
+    }
+}
+
+/// `text` at the right end of `area`, standing out from what's around it.
+pub fn render_banner(frame: &mut Frame, theme: &Theme, area: Rect, text: &str) {
+    let banner = Paragraph::new(format!(" {} ", text)).style(
+        Style::default()
+            .fg(theme.error)
+            .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+    );
+    let width = (text.chars().count() as u16 + 2).min(area.width);
+    let area = Rect {
+        x: area.right() - width,
+        width,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+    frame.render_widget(banner, area);
+}
+
+/// What the stats, schema and savepoints views show.
+pub struct Report<'a> {
+    pub title: &'a str,
+    /// Lines as `(name, value)` above the table.
+    pub summary: &'a [(String, String)],
+    pub header: &'a [&'a str],
+    pub rows: &'a [Vec<String>],
+    /// Row highlighted as selected, if rows can be selected.
+    pub selected: Option<usize>,
+}
+
+/// The summary of `report` above its table, scrolled down by `scroll`
+/// rows, which is kept short of the end and around the selected row.
+pub fn render_report(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    report: &Report,
+    scroll: &mut u16,
+) {
+    let Report {
+        title,
+        summary,
+        header,
+        rows,
+        selected,
+    } = *report;
+    let block = bordered(theme).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let summary_height = match summary.len() {
+        0 => 0,
+        n => n as u16 + 1,
+    };
+    let [summary_area, table_area] = Layout::vertical([
+        Constraint::Length(summary_height),
+        Constraint::Min(0),
+    ])
+    .areas(inner);
+    frame.render_widget(
+        Paragraph::new(summary_lines(theme, summary)),
+        summary_area,
+    );
+
+    // One line goes to the header.
+    let last = rows
+        .len()
+        .saturating_sub(table_area.height.saturating_sub(1) as usize);
+    *scroll = (*scroll).min(last as u16);
+    if let Some(selected) = selected {
+        let height = table_area.height.saturating_sub(1).max(1) as usize;
+        let first = selected.saturating_sub(height - 1);
+        *scroll = (*scroll).clamp(first as u16, selected as u16);
+    }
+    let highlight = Style::default().bg(theme.selected_bg).fg(theme.selected_fg);
+    let widths: Vec<Constraint> = (0..header.len())
+        .map(|i| {
+            let width = rows
+                .iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.chars().count())
+                .chain([header[i].chars().count()])
+                .max()
+                .unwrap_or(0);
+            Constraint::Length(width as u16)
+        })
+        .collect();
+    let table = Table::new(
+        rows.iter()
+            .enumerate()
+            .skip(*scroll as usize)
+            .map(|(i, row)| {
+                let row = Row::new(row.iter().map(String::as_str));
+                if Some(i) == selected {
+                    row.style(highlight)
+                } else {
+                    row
+                }
+            }),
+        widths,
+    )
+    .header(
+        Row::new(header.iter().copied()).style(
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD),
+        ),
+    )
+    .column_spacing(2);
+    frame.render_widget(table, table_area);
+}
+
+/// `(name, value)` lines with the names lined up.
+fn summary_lines<'a>(
+    theme: &Theme,
+    summary: &'a [(String, String)],
+) -> Vec<Line<'a>> {
+    let name_width = summary
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    summary
+        .iter()
+        .map(|(name, value)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:width$}  ", name, width = name_width),
+                    Style::default().fg(theme.accent),
+                ),
+                Span::raw(value.as_str()),
+            ])
+        })
+        .collect()
+}
+
+/// A table of two databases side by side, one line per key.
+pub struct Comparison<'a> {
+    /// Titles of the left and right pane.
+    pub titles: [&'a str; 2],
+    /// The entry in each database, empty where it lacks the key, and
+    /// whether the two differ.
+    pub rows: &'a [([String; 2], bool)],
+    pub selected: usize,
+}
+
+/// The rows of `comparison` in two panes that scroll together, those that
+/// differ emphasized. `scroll` is kept around the selected row.
+pub fn render_comparison(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    comparison: &Comparison,
+    scroll: &mut u16,
+) {
+    let panes: [Rect; 2] =
+        Layout::horizontal([Constraint::Percentage(50); 2]).areas(area);
+    let height = panes[0].height.saturating_sub(2).max(1) as usize;
+    let selected = comparison.selected;
+    let first = selected.saturating_sub(height - 1);
+    *scroll = (*scroll as usize).clamp(first, selected) as u16;
+    let highlight = Style::default().bg(theme.selected_bg).fg(theme.selected_fg);
+    let differs = Style::default()
+        .fg(theme.accent)
+        .add_modifier(Modifier::BOLD);
+    for (side, pane) in panes.into_iter().enumerate() {
+        let lines: Vec<Line> = comparison
+            .rows
+            .iter()
+            .enumerate()
+            .skip(*scroll as usize)
+            .take(height)
+            .map(|(i, (cells, differ))| {
+                let style = match (i == selected, differ) {
+                    (true, _) => highlight,
+                    (false, true) => differs,
+                    (false, false) => Style::default(),
+                };
+                Line::styled(cells[side].clone(), style)
+            })
+            .collect();
+        let block = bordered(theme).title(comparison.titles[side]);
+        frame.render_widget(Paragraph::new(lines).block(block), pane);
+    }
+}
+
+/// The end of `lines`, scrolled up by `scroll` lines, which is kept short
+/// of the start.
+pub fn render_log(
+    frame: &mut Frame,
+    theme: &Theme,
+    area: Rect,
+    title: &str,
+    lines: &[String],
+    scroll: &mut u16,
+) {
+    let height = area.height.saturating_sub(2) as usize;
+    let first = lines.len().saturating_sub(height);
+    *scroll = (*scroll).min(first as u16);
+    let first = first - *scroll as usize;
+    let text: Vec<Line> = lines[first..]
+        .iter()
+        .take(height)
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    let paragraph = Paragraph::new(text).block(bordered(theme).title(title));
+    frame.render_widget(paragraph, area);
+}
+
+/// Full-screen list of `items` to pick one from, with `hint` on the line
+/// below it, covering whatever was drawn before.
+pub fn render_picker(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    items: &[String],
+    list_state: &mut ListState,
+    hint: &str,
+) {
+    let [list_area, hint_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(1)])
+            .areas(frame.area());
+    frame.render_widget(Clear, frame.area());
+    render_table_list(frame, theme, list_area, title, items, list_state);
+    let hint = Paragraph::new(hint).style(Style::default().fg(theme.accent));
+    frame.render_widget(hint, hint_area);
+}
+
+/// A popup of figures above a bar chart, one bar per line.
+pub struct Histogram<'a> {
+    pub title: &'a str,
+    /// Lines as `(name, value)` above the bars.
+    pub summary: &'a [(String, String)],
+    /// Label and height of each bar, top down.
+    pub bars: &'a [(String, u64)],
+    /// Shown when there are no bars.
+    pub empty: &'a str,
+}
+
+/// Width of the histogram popup, unless the screen is narrower.
+const HISTOGRAM_WIDTH: u16 = 72;
+
+pub fn render_histogram_popup(
+    frame: &mut Frame,
+    theme: &Theme,
+    histogram: &Histogram,
+) {
+    let Histogram {
+        title,
+        summary,
+        bars,
+        empty,
+    } = *histogram;
+    let width = HISTOGRAM_WIDTH.max(title.chars().count() as u16 + 4);
+    let summary_height = match summary.len() {
+        0 => 0,
+        n => n as u16 + 1,
+    };
+    let height = summary_height + bars.len().max(1) as u16 + 2;
+    let area = centered_rect(frame.area(), width, height);
+    frame.render_widget(Clear, area);
+    let block = bordered(theme).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let [summary_area, bars_area] = Layout::vertical([
+        Constraint::Length(summary_height),
+        Constraint::Min(0),
+    ])
+    .areas(inner);
+    frame.render_widget(
+        Paragraph::new(summary_lines(theme, summary)),
+        summary_area,
+    );
+    if bars.is_empty() {
+        frame.render_widget(Paragraph::new(empty), bars_area);
+        return;
+    }
+    let bars: Vec<Bar> = bars
+        .iter()
+        .map(|(label, value)| {
+            Bar::default()
+                .label(Line::raw(label.as_str()))
+                .value(*value)
+                .text_value(group_thousands(*value))
+        })
+        .collect();
+    let chart = BarChart::default()
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .bar_style(Style::default().fg(theme.accent))
+        .value_style(Style::default().fg(theme.text).add_modifier(Modifier::BOLD))
+        .data(BarGroup::default().bars(&bars));
+    frame.render_widget(chart, bars_area);
+}
+
+/// Popup listing `items` with the one at `selected` highlighted, or
+/// `empty` when there are none.
+pub fn render_list_popup(
+    frame: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    items: &[String],
+    selected: usize,
+    empty: &str,
+) {
+    let width = items
+        .iter()
+        .map(|item| item.chars().count())
+        .chain([title.chars().count(), empty.chars().count()])
+        .max()
+        .unwrap_or(0) as u16
+        + 4;
+    let height = (items.len().max(1) as u16 + 2).min(frame.area().height);
+    let area = centered_rect(frame.area(), width, height);
+    frame.render_widget(Clear, area);
+    if items.is_empty() {
+        let paragraph = Paragraph::new(empty).block(bordered(theme).title(title));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+    let mut list_state = ListState::default().with_selected(Some(selected));
+    render_table_list(frame, theme, area, title, items, &mut list_state);
+}
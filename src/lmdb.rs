@@ -0,0 +1,302 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::{AppError, Result};
+use std::{fs::File, io, path::Path};
+
+/// Nesting limit guarding against b-trees that loop back on themselves.
+const MAX_DEPTH: usize = 64;
+
+const MAGIC: u32 = 0xBEEF_C0DE;
+const DATA_VERSION: u32 = 1;
+/// Size of a page header; the meta and node pointers follow it.
+const PAGE_HEADER: usize = 16;
+/// Size of a node header; the key follows it.
+const NODE_HEADER: usize = 8;
+/// Root of an empty database.
+const NO_PAGE: u64 = u64::MAX;
+
+const BRANCH_PAGE: u16 = 0x01;
+const LEAF_PAGE: u16 = 0x02;
+
+/// Node data is on overflow pages.
+const BIG_DATA: u16 = 0x01;
+/// Node data is the record of a named database.
+const SUB_DATA: u16 = 0x02;
+
+const DUP_SORT: u16 = 0x04;
+
+/// An LMDB environment, read page by page from its data file so big ones
+/// aren't loaded whole. Only 64-bit little-endian environments, as written
+/// on the usual platforms, are understood.
+pub struct Environment {
+    file: File,
+    page_size: usize,
+    main: Database,
+}
+
+/// The unnamed database of an environment, or one of the databases named
+/// in it.
+#[derive(Debug, Clone)]
+pub struct Database {
+    /// `None` for the unnamed database.
+    pub name: Option<String>,
+    pub entries: u64,
+    /// Databases with sorted duplicates keep several values per key.
+    pub dup_sort: bool,
+    root: u64,
+}
+
+impl Database {
+    fn parse(name: Option<String>, record: &[u8]) -> DecodeResult<Self> {
+        Ok(Self {
+            name,
+            dup_sort: le_u16(record, 4)? & DUP_SORT != 0,
+            entries: le_u64(record, 32)?,
+            root: le_u64(record, 40)?,
+        })
+    }
+}
+
+impl Environment {
+    /// Opens the `data.mdb` of an environment directory, or the data file
+    /// itself for environments made with `MDB_NOSUBDIR`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data_path = if path.is_dir() {
+            path.join("data.mdb")
+        } else {
+            path.to_path_buf()
+        };
+        let error = |e: DecodeError| {
+            AppError::ImportError(format!("{}: {}", data_path.display(), e))
+        };
+        let file = File::open(&data_path)?;
+        let mut env = Self {
+            file,
+            page_size: 0,
+            main: Database {
+                name: None,
+                entries: 0,
+                dup_sort: false,
+                root: NO_PAGE,
+            },
+        };
+        let Some((txn, page_size, main)) = env.meta(0).map_err(error)? else {
+            return Err(error(DecodeError::new("not an LMDB environment")));
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(error(DecodeError::new("invalid page size")));
+        }
+        env.page_size = page_size;
+        // The meta page of the last committed transaction wins.
+        env.main = match env.meta(page_size as u64).map_err(error)? {
+            Some((second_txn, _, second)) if second_txn > txn => second,
+            _ => main,
+        };
+        Ok(env)
+    }
+
+    /// Transaction id, page size and main database of the meta page at
+    /// `offset`, or `None` if there is none.
+    fn meta(&self, offset: u64) -> DecodeResult<Option<(u64, usize, Database)>> {
+        let mut meta = [0; PAGE_HEADER + 136];
+        if self.read_at(offset, &mut meta).is_err() {
+            return Ok(None);
+        }
+        let meta = &meta[PAGE_HEADER..];
+        if le_u32(meta, 0)? != MAGIC {
+            return Ok(None);
+        }
+        if le_u32(meta, 4)? != DATA_VERSION {
+            return Err(DecodeError::new(format!(
+                "unsupported data version {}",
+                le_u32(meta, 4)?
+            )));
+        }
+        Ok(Some((
+            le_u64(meta, 128)?,
+            le_u32(meta, 24)? as usize,
+            Database::parse(None, &meta[72..120])?,
+        )))
+    }
+
+    /// The named databases, in name order, then the unnamed one if it
+    /// holds entries of its own.
+    pub fn databases(&self) -> DecodeResult<Vec<Database>> {
+        let mut databases = Vec::new();
+        for entry in self.walk(&self.main, true) {
+            let (key, record) = entry?;
+            databases.push(Database::parse(
+                Some(String::from_utf8_lossy(&key).into_owned()),
+                &record,
+            )?);
+        }
+        let mut main = self.main.clone();
+        main.entries = main.entries.saturating_sub(databases.len() as u64);
+        if main.entries > 0 {
+            databases.push(main);
+        }
+        Ok(databases)
+    }
+
+    /// The entries of `db` in key order. The records of named databases
+    /// in the unnamed one are left out.
+    pub fn entries<'a>(&'a self, db: &Database) -> Entries<'a> {
+        self.walk(db, false)
+    }
+
+    fn walk<'a>(&'a self, db: &Database, named: bool) -> Entries<'a> {
+        Entries {
+            env: self,
+            named,
+            stack: Vec::new(),
+            root: (db.root != NO_PAGE).then_some(db.root),
+        }
+    }
+
+    fn page(&self, number: u64) -> DecodeResult<Vec<u8>> {
+        let mut page = vec![0; self.page_size];
+        self.read_at(number * self.page_size as u64, &mut page)
+            .map_err(|e| DecodeError::new(format!("page {}: {}", number, e)))?;
+        Ok(page)
+    }
+
+    /// Data stored on the overflow pages starting at page `number`.
+    fn overflow(&self, number: u64, size: usize) -> DecodeResult<Vec<u8>> {
+        let mut data = vec![0; size];
+        self.read_at(
+            number * self.page_size as u64 + PAGE_HEADER as u64,
+            &mut data,
+        )
+        .map_err(|e| {
+            DecodeError::new(format!("overflow page {}: {}", number, e))
+        })?;
+        Ok(data)
+    }
+
+    #[cfg(unix)]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        use std::os::unix::fs::FileExt;
+        self.file.read_exact_at(buf, offset)
+    }
+
+    #[cfg(windows)]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        use std::os::windows::fs::FileExt;
+        let mut done = 0;
+        while done < buf.len() {
+            match self
+                .file
+                .seek_read(&mut buf[done..], offset + done as u64)?
+            {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => done += n,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the entries of a database, walking its b-tree depth
+/// first.
+pub struct Entries<'a> {
+    env: &'a Environment,
+    /// Whether to yield the records of named databases instead of entries.
+    named: bool,
+    /// Pages being walked and the next node of each.
+    stack: Vec<(Vec<u8>, usize)>,
+    /// Root page, until the walk starts.
+    root: Option<u64>,
+}
+
+impl Entries<'_> {
+    fn next_entry(&mut self) -> DecodeResult<Option<(Vec<u8>, Vec<u8>)>> {
+        if let Some(root) = self.root.take() {
+            self.stack.push((self.env.page(root)?, 0));
+        }
+        while let Some((page, node)) = self.stack.last_mut() {
+            let flags = le_u16(page, 10)?;
+            let nodes = (le_u16(page, 12)? as usize)
+                .checked_sub(PAGE_HEADER)
+                .ok_or_else(|| DecodeError::new("invalid page header"))?
+                / 2;
+            if *node >= nodes {
+                self.stack.pop();
+                continue;
+            }
+            let offset = le_u16(page, PAGE_HEADER + 2 * *node)? as usize;
+            *node += 1;
+            let lo = le_u16(page, offset)? as u64;
+            let hi = le_u16(page, offset + 2)? as u64;
+            let node_flags = le_u16(page, offset + 4)?;
+            let key_size = le_u16(page, offset + 6)? as usize;
+            if flags & BRANCH_PAGE != 0 {
+                let child = lo | hi << 16 | (node_flags as u64) << 32;
+                let child = self.env.page(child)?;
+                if self.stack.len() == MAX_DEPTH {
+                    return Err(DecodeError::new("b-tree nested too deeply"));
+                }
+                self.stack.push((child, 0));
+                continue;
+            }
+            if flags & LEAF_PAGE == 0 {
+                return Err(DecodeError::new(format!(
+                    "page {} is not a b-tree page",
+                    le_u64(page, 0)?
+                )));
+            }
+            if (node_flags & SUB_DATA != 0) != self.named {
+                continue;
+            }
+            let key_start = offset + NODE_HEADER;
+            let data_start = key_start + key_size;
+            let key = bytes(page, key_start, key_size)?.to_vec();
+            let size = (lo | hi << 16) as usize;
+            let data = if node_flags & BIG_DATA != 0 {
+                self.env.overflow(le_u64(page, data_start)?, size)?
+            } else {
+                bytes(page, data_start, size)?.to_vec()
+            };
+            return Ok(Some((key, data)));
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = DecodeResult<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                self.stack.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn bytes(page: &[u8], pos: usize, len: usize) -> DecodeResult<&[u8]> {
+    page.get(pos..pos + len)
+        .ok_or_else(|| DecodeError::new("node runs past its page"))
+}
+
+fn le_u16(bytes: &[u8], pos: usize) -> DecodeResult<u16> {
+    bytes
+        .get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| DecodeError::new("page is cut off"))
+}
+
+fn le_u32(bytes: &[u8], pos: usize) -> DecodeResult<u32> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| DecodeError::new("page is cut off"))
+}
+
+fn le_u64(bytes: &[u8], pos: usize) -> DecodeResult<u64> {
+    bytes
+        .get(pos..pos + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+        .ok_or_else(|| DecodeError::new("page is cut off"))
+}
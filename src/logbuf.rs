@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Log lines kept in memory for the Logs tab.
+pub const LOG_LINES: usize = 5000;
+
+/// Log sink keeping the last `LOG_LINES` lines in memory, so the TUI can
+/// show them without going through the log file. Clones share the lines.
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<Inner>>,
+    /// Log file written alongside, if it could be created.
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    lines: VecDeque<String>,
+    /// Bytes of a line not finished yet.
+    partial: Vec<u8>,
+    /// Lines written so far, including dropped ones.
+    written: u64,
+}
+
+impl LogBuffer {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            inner: Arc::default(),
+            path,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lock().lines.iter().cloned().collect()
+    }
+
+    /// Number of lines written so far; it changes whenever a line comes in.
+    pub fn written(&self) -> u64 {
+        self.lock().written
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // A panic while holding the lock leaves at worst a cut-off line.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.lock();
+        inner.partial.extend_from_slice(buf);
+        while let Some(end) = inner.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = inner.partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            let line = line.trim_end_matches('\r').to_string();
+            if inner.lines.len() == LOG_LINES {
+                inner.lines.pop_front();
+            }
+            inner.lines.push_back(line);
+            inner.written += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
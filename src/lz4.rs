@@ -0,0 +1,203 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::inflate::check_limit;
+
+/// Leading bytes of an LZ4 frame.
+const MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+/// Decompresses LZ4 frames (the `lz4` tool's format), one after another.
+/// Checksums aren't verified. Output past `limit` bytes is refused.
+pub fn decode(bytes: &[u8], limit: usize) -> DecodeResult<Vec<u8>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let mut out = Vec::new();
+    while reader.pos < bytes.len() {
+        if reader.take(4)? != MAGIC {
+            return Err(DecodeError::new("not an LZ4 frame"));
+        }
+        frame(&mut reader, &mut out, limit)?;
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::new("unexpected end of LZ4 data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> DecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> DecodeResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn frame(
+    reader: &mut Reader,
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> DecodeResult<()> {
+    let flags = reader.byte()?;
+    if flags >> 6 != 1 {
+        return Err(DecodeError::new("unknown LZ4 frame version"));
+    }
+    if flags & 0x01 != 0 {
+        return Err(DecodeError::new("LZ4 dictionaries aren't supported"));
+    }
+    let block_checksum = flags & 0x10 != 0;
+    let content_size = flags & 0x08 != 0;
+    let content_checksum = flags & 0x04 != 0;
+    // Block size, content size and header checksum.
+    reader.take(1 + if content_size { 8 } else { 0 } + 1)?;
+    // Blocks may refer back into earlier ones, so the frame's output is
+    // the window.
+    let start = out.len();
+    loop {
+        let size = reader.u32()?;
+        if size == 0 {
+            break;
+        }
+        let data = reader.take((size & 0x7fff_ffff) as usize)?;
+        if size & 0x8000_0000 != 0 {
+            check_limit(out.len() + data.len(), limit)?;
+            out.extend_from_slice(data);
+        } else {
+            block(data, out, start, limit)?;
+        }
+        if block_checksum {
+            reader.take(4)?;
+        }
+    }
+    if content_checksum {
+        reader.take(4)?;
+    }
+    Ok(())
+}
+
+/// Decodes the sequences of a compressed block: literals, then a copy of
+/// earlier output, with the last sequence having literals only.
+fn block(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    start: usize,
+    limit: usize,
+) -> DecodeResult<()> {
+    let mut reader = Reader {
+        bytes: data,
+        pos: 0,
+    };
+    loop {
+        let token = reader.byte()?;
+        let literals = length(&mut reader, token >> 4)?;
+        let literals = reader.take(literals)?;
+        check_limit(out.len() + literals.len(), limit)?;
+        out.extend_from_slice(literals);
+        if reader.pos == data.len() {
+            return Ok(());
+        }
+        let offset =
+            u16::from_le_bytes(reader.take(2)?.try_into().unwrap()) as usize;
+        if offset == 0 || offset > out.len() - start {
+            return Err(DecodeError::new("LZ4 offset out of range"));
+        }
+        let len = length(&mut reader, token & 0x0f)? + 4;
+        check_limit(out.len() + len, limit)?;
+        // Copies may overlap what they produce.
+        let from = out.len() - offset;
+        for i in 0..len {
+            out.push(out[from + i]);
+        }
+    }
+}
+
+/// A length of a token's four bits, going on in bytes of 255 when they
+/// are all set.
+fn length(reader: &mut Reader, nibble: u8) -> DecodeResult<usize> {
+    let mut len = nibble as usize;
+    if nibble == 15 {
+        loop {
+            let byte = reader.byte()?;
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::parse_hex;
+
+    const LIMIT: usize = 1 << 20;
+
+    /// `repeated()` from the `lz4` tool: one compressed block with
+    /// overlapping copies and a content checksum.
+    const COMPRESSED: &str = "\
+        04224d186440a71f0000006868656c6c6f20060068776f726c640a1200020c000f18\
+        001e506f726c640a00000000f8ea9c18";
+
+    /// The same with `-BX --content-size`, adding a block checksum and the
+    /// content size.
+    const CHECKED: &str = "\
+        04224d187c406000000000000000f61f0000006868656c6c6f20060068776f726c64\
+        0a1200020c000f18001e506f726c640a1e6637f900000000f8ea9c18";
+
+    /// Seven bytes the tool stored as an uncompressed block.
+    const STORED: &str = "04224d186440a70700008000254a6f94b9de00000000460c4807";
+
+    fn repeated() -> Vec<u8> {
+        b"hello hello hello world\n".repeat(4)
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        parse_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn decodes_frames_from_the_lz4_tool() {
+        assert_eq!(decode(&bytes(COMPRESSED), LIMIT).unwrap(), repeated());
+        assert_eq!(decode(&bytes(CHECKED), LIMIT).unwrap(), repeated());
+        let stored = decode(&bytes(STORED), LIMIT).unwrap();
+        assert_eq!(stored, [0x00, 0x25, 0x4a, 0x6f, 0x94, 0xb9, 0xde]);
+        // Frames one after another decode to their outputs in turn.
+        let both = [bytes(STORED), bytes(COMPRESSED)].concat();
+        assert_eq!(decode(&both, LIMIT).unwrap(), [stored, repeated()].concat());
+    }
+
+    #[test]
+    fn refuses_bad_offsets_and_output_past_the_limit() {
+        assert!(decode(&bytes(COMPRESSED), 50).is_err());
+        // A copy from before the start of the frame's output.
+        let mut frame = bytes(STORED)[..7].to_vec();
+        frame.extend_from_slice(&[4, 0, 0, 0, 0x10, b'a', 2, 0, 0, 0, 0, 0]);
+        assert!(decode(&frame, LIMIT).is_err());
+        let mut version = bytes(COMPRESSED);
+        version[4] = 0x24;
+        assert!(decode(&version, LIMIT).is_err());
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        for hex in [COMPRESSED, CHECKED, STORED] {
+            let frame = bytes(hex);
+            for len in 1..frame.len() {
+                assert!(decode(&frame[..len], LIMIT).is_err(), "{}", len);
+            }
+        }
+    }
+}
@@ -1,20 +1,280 @@
-use std::{fs::File, path::PathBuf};
+use std::{fs::File, path::PathBuf, time::Duration};
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+use decoder::{Decoder, Schemas};
+use human_repr::HumanCount;
 use log::{error, info};
-use simplelog::{Config, LevelFilter, WriteLogger};
+use logbuf::LogBuffer;
+use simplelog::{CombinedLogger, Config, LevelFilter, SharedLogger, WriteLogger};
 use thiserror::Error;
 use tui::TuiWrapper;
 
+mod bincode;
+mod browser;
+mod btree;
+mod cbor;
+mod clipboard;
+mod cmdline;
+mod commands;
+mod config;
+mod csv;
 mod database;
+mod decoder;
+mod diff;
+mod duplicates;
+mod history;
+mod inflate;
+mod input;
+mod json;
+mod keymap;
+mod keyorder;
+mod keytree;
 mod layout;
+#[cfg(feature = "lmdb")]
+mod lmdb;
+mod logbuf;
+mod lz4;
+mod msgpack;
+mod node;
+mod protobuf;
+mod registry;
+mod search;
+mod sizes;
+mod sqlite;
+mod state;
+mod theme;
+mod timestamp;
+mod toml;
 mod tui;
+mod zstd;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Database to open; repeat to open several, each in a tab. Without
+    /// one, a start screen lists the recent databases; for a directory, the
+    /// databases in it
     #[arg(short, long)]
-    database_path: PathBuf,
+    database_path: Vec<PathBuf>,
+    /// Table to select at startup
+    #[arg(short, long)]
+    table: Option<String>,
+    /// Create a demo database at the path if it doesn't exist
+    #[arg(long)]
+    create_demo: bool,
+    /// Share of the screen taken by the table list, in percent
+    #[arg(long, value_parser = clap::value_parser!(u16).range(10..=90))]
+    split: Option<u16>,
+    /// Draw with plain ASCII instead of box-drawing characters
+    #[arg(long)]
+    ascii: bool,
+    /// Config file with key bindings and colors [default: ~/.config/redb-tui/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// TOML file describing bincode struct layouts per table
+    #[arg(long, global = true)]
+    bincode_schema: Option<PathBuf>,
+    /// Compiled protobuf FileDescriptorSet (protoc --descriptor_set_out)
+    #[arg(long, global = true)]
+    descriptors: Option<PathBuf>,
+    /// Protobuf message type of a table's values, as TABLE=MESSAGE
+    #[arg(long, global = true, value_parser = parse_proto_message)]
+    proto_message: Vec<(String, String)>,
+    /// Never take write transactions, not even for stats
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Refuse snapshots of files bigger than MIB, as they are read into
+    /// memory whole; 0 for no limit
+    #[arg(
+        long,
+        global = true,
+        value_name = "MIB",
+        default_value_t = database::DEFAULT_SNAPSHOT_LIMIT >> 20
+    )]
+    snapshot_limit: u64,
+    /// Browse snapshots of the database, taken again every SECONDS [default: 2]
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    watch: Option<Option<u64>>,
+}
+
+// Headless commands that print their result instead of starting the TUI.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the value stored under a key
+    Get {
+        #[arg(short, long)]
+        table: String,
+        /// Key, written as it is displayed for the table's key type
+        #[arg(short, long)]
+        key: String,
+        /// Read the key as hex-encoded bytes
+        #[arg(long)]
+        hex_key: bool,
+        /// Write the value bytes as stored instead of decoding them
+        #[arg(long, conflicts_with = "decoder")]
+        raw: bool,
+        /// Value decoder; defaults to the schema sidecar's, or auto
+        #[arg(long, value_parser = parse_decoder)]
+        decoder: Option<Decoder>,
+        database_path: PathBuf,
+    },
+    /// Dump a table, or all tables, as JSON, CSV, JSON Lines or SQLite SQL
+    Export {
+        /// Table to export; all tables when omitted
+        #[arg(short, long)]
+        table: Option<String>,
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: commands::ExportFormat,
+        /// How keys are written
+        #[arg(long, value_enum, default_value = "decoded")]
+        key_encoding: commands::Encoding,
+        /// How values are written
+        #[arg(long, value_enum, default_value = "decoded")]
+        value_encoding: commands::Encoding,
+        /// File to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        database_path: PathBuf,
+    },
+    /// List table names
+    #[command(visible_alias = "list-tables")]
+    Tables {
+        /// Also print each table's entry count
+        #[arg(short, long)]
+        count: bool,
+        /// Also print each table's stored key and value types
+        #[arg(long)]
+        types: bool,
+        database_path: PathBuf,
+    },
+    /// Print database and per-table storage stats as JSON
+    Stats { database_path: PathBuf },
+    /// Check the database file for damage, repairing it if needed
+    Check { database_path: PathBuf },
+    /// Shrink the database file by reclaiming the space of deleted data
+    Compact { database_path: PathBuf },
+    /// Print a shell completion script
+    Completions { shell: clap_complete::Shell },
+    /// Rename a table, copying its entries into a new table
+    Rename {
+        #[arg(short, long)]
+        table: String,
+        /// New name of the table
+        #[arg(long)]
+        to: String,
+        database_path: PathBuf,
+    },
+    /// Copy a table into another database, created if needed
+    CopyTable {
+        /// Database to copy from
+        #[arg(long)]
+        from: PathBuf,
+        /// Database to copy into
+        #[arg(long)]
+        to: PathBuf,
+        #[arg(short, long)]
+        table: String,
+        /// Name of the table in the target database; the same by default
+        #[arg(long = "as", value_name = "NAME")]
+        target_table: Option<String>,
+        /// Copy into a table the target already has, replacing the values
+        /// of keys both have; without it, such a table is refused
+        #[arg(long)]
+        overwrite: bool,
+        /// Entries copied per write transaction
+        #[arg(
+            long,
+            default_value_t = 10_000,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        batch_size: usize,
+    },
+    /// Copy every table of a database into another, created if needed
+    Merge {
+        /// Database to copy from
+        #[arg(long)]
+        from: PathBuf,
+        /// Database to merge into
+        #[arg(long)]
+        to: PathBuf,
+        /// What to do with keys both have: skip, overwrite or fail
+        #[arg(
+            long,
+            value_name = "POLICY",
+            default_value = "fail",
+            value_parser = parse_on_conflict
+        )]
+        on_conflict: database::OnConflict,
+        /// Entries copied per write transaction
+        #[arg(
+            long,
+            default_value_t = 10_000,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        batch_size: usize,
+    },
+    /// Compare two databases: tables and entries added, removed or changed
+    Diff {
+        /// Compare just this table
+        #[arg(short, long)]
+        table: Option<String>,
+        #[arg(short, long, value_enum, default_value = "text")]
+        format: commands::DiffFormat,
+        /// How keys are written
+        #[arg(long, value_enum, default_value = "decoded")]
+        key_encoding: commands::Encoding,
+        /// How values are written
+        #[arg(long, value_enum, default_value = "decoded")]
+        value_encoding: commands::Encoding,
+        /// Browse the differences in the TUI, opened on the second database
+        #[arg(short, long)]
+        interactive: bool,
+        /// Database compared against
+        old: PathBuf,
+        /// Database compared
+        database_path: PathBuf,
+    },
+    /// Insert entries from a file into a table
+    Import {
+        /// Table to import into; by default the one each record names
+        #[arg(short, long)]
+        table: Option<String>,
+        #[command(flatten)]
+        options: Box<commands::ImportOptions>,
+        /// Key type of the table if it has to be created, e.g. u64 or &str
+        #[arg(long, default_value = "&str", value_parser = parse_builtin_type)]
+        key_type: database::RawType,
+        /// Value type of the table if it has to be created
+        #[arg(long, default_value = "&[u8]", value_parser = parse_builtin_type)]
+        value_type: database::RawType,
+        /// File with the entries to insert
+        input: PathBuf,
+        database_path: PathBuf,
+    },
+}
+
+fn parse_proto_message(s: &str) -> std::result::Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(table, message)| (table.to_string(), message.to_string()))
+        .ok_or_else(|| format!("expected TABLE=MESSAGE, got '{}'", s))
+}
+
+fn parse_builtin_type(s: &str) -> std::result::Result<database::RawType, String> {
+    database::builtin_type(s).ok_or_else(|| format!("unknown redb type '{}'", s))
+}
+
+fn parse_on_conflict(
+    s: &str,
+) -> std::result::Result<database::OnConflict, String> {
+    database::OnConflict::from_name(s)
+        .ok_or_else(|| format!("expected skip, overwrite or fail, got '{}'", s))
+}
+
+fn parse_decoder(s: &str) -> std::result::Result<Decoder, String> {
+    Decoder::from_name(s).ok_or_else(|| format!("unknown decoder '{}'", s))
 }
 
 #[derive(Error, Debug)]
@@ -24,39 +284,400 @@ pub enum AppError {
     #[error("Storage error: {0}")]
     StorageError(#[from] redb::StorageError),
     #[error("Transaction error: {0}")]
-    TransactionError(#[from] redb::TransactionError),
+    TransactionError(Box<redb::TransactionError>),
     #[error("Table error: {0}")]
     TableError(#[from] redb::TableError),
     #[error("Commit error: {0}")]
     CommitError(#[from] redb::CommitError),
+    #[error("Compaction error: {0}")]
+    CompactionError(#[from] redb::CompactionError),
+    #[error("Savepoint error: {0}")]
+    SavepointError(#[from] redb::SavepointError),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Unsupported table: {0}")]
+    UnsupportedTable(String),
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+    #[error("Table already exists: {0}")]
+    TableExists(String),
+    #[error("Table types differ: {0}")]
+    TableTypesDiffer(String),
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    #[error("Can't write to a table of keys in an unknown order: {0}")]
+    UnorderedKeys(String),
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
+    #[error("Key already exists: {0}")]
+    KeyExists(String),
+    #[error("Savepoint not found: {0}")]
+    SavepointNotFound(u64),
+    #[error("Import error: {0}")]
+    ImportError(String),
+    #[error("Database is opened read-only")]
+    ReadOnly,
+    #[error("Database not found: {} (use --create-demo for a demo database)", .0.display())]
+    DatabaseNotFound(PathBuf),
+    #[error("Database is locked by another process: {}", .0.display())]
+    DatabaseLocked(PathBuf),
+    #[error("Database is corrupted and can't be repaired: {}: {1}", .0.display())]
+    DatabaseCorrupted(PathBuf, String),
+    #[error(
+        "{} is {}, more than the {} a snapshot may read into memory \
+         (raise it with --snapshot-limit)",
+        .0.display(),
+        .1.human_count_bytes(),
+        .2.human_count_bytes()
+    )]
+    SnapshotTooLarge(PathBuf, u64, u64),
+    #[error("Config error: {0}")]
+    ConfigError(String),
+}
+
+impl From<redb::TransactionError> for AppError {
+    fn from(e: redb::TransactionError) -> Self {
+        AppError::TransactionError(Box::new(e))
+    }
 }
+
 pub type Result<T> = std::result::Result<T, AppError>;
 
-fn setup_logger(log_path: &PathBuf) {
-    let log_file = File::create(log_path).expect("Failed to create log file");
-    WriteLogger::init(LevelFilter::Debug, Config::default(), log_file)
-        .expect("Failed to initialize logger");
+/// Logs to `log_path` and to memory for the Logs tab. Without a writable
+/// log file, only the latter is kept.
+fn setup_logger(log_path: &PathBuf) -> LogBuffer {
+    let (log_file, failure) = match File::create(log_path) {
+        Ok(log_file) => (Some(log_file), None),
+        Err(e) => (None, Some(e)),
+    };
+    let buffer = LogBuffer::new(log_file.is_some().then(|| log_path.clone()));
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![WriteLogger::new(
+        LevelFilter::Debug,
+        Config::default(),
+        buffer.clone(),
+    )];
+    if let Some(log_file) = log_file {
+        loggers.push(WriteLogger::new(
+            LevelFilter::Debug,
+            Config::default(),
+            log_file,
+        ));
+    }
+    CombinedLogger::init(loggers).expect("Failed to initialize logger");
+    if let Some(e) = failure {
+        error!("Failed to create log file {:?}: {}", log_path, e);
+    }
+    buffer
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    let log_path = args.database_path.with_extension("log");
-    setup_logger(&log_path);
+/// Reads the config file given or the default one, with the command-line
+/// options that override it.
+fn load_config(args: &Args) -> Result<config::Config> {
+    if let Some(path) = args.config.as_ref().filter(|path| !path.exists()) {
+        return Err(AppError::ConfigError(format!(
+            "{}: no such file",
+            path.display()
+        )));
+    }
+    let mut config =
+        match args.config.clone().or_else(config::Config::default_path) {
+            Some(path) => {
+                info!("Loading config from {:?}", path);
+                config::Config::load(&path)?
+            }
+            None => config::Config::default(),
+        };
+    config.theme.ascii |= args.ascii;
+    config.split = args.split.or(config.split);
+    config.watch = args
+        .watch
+        .map(|secs| secs.map_or(tui::WATCH_INTERVAL, Duration::from_secs));
+    Ok(config)
+}
+
+fn run() -> Result<()> {
+    database::install_panic_hook();
+    let mut args = Args::parse();
+    database::set_snapshot_limit(args.snapshot_limit.saturating_mul(1 << 20));
+    if let Some(Command::Completions { shell }) = args.command {
+        let mut command = Args::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(
+            shell,
+            &mut command,
+            name,
+            &mut std::io::stdout(),
+        );
+        return Ok(());
+    }
+    // Without a database, or with a directory of them, a start screen
+    // asks which one to open.
+    let mut config = None;
+    let picks_database = args.database_path.is_empty()
+        || args.database_path.iter().any(|path| path.is_dir());
+    if args.command.is_none() && picks_database {
+        let loaded = load_config(&args)?;
+        if args.database_path.is_empty() {
+            match tui::pick_database(&loaded)? {
+                Some(path) => args.database_path.push(path),
+                None => return Ok(()),
+            }
+        }
+        for path in &mut args.database_path {
+            if path.is_dir() {
+                match tui::pick_in_directory(&loaded, path)? {
+                    Some(picked) => *path = picked,
+                    None => return Ok(()),
+                }
+            }
+        }
+        config = Some(loaded);
+    }
+    let database_path = match &args.command {
+        Some(
+            Command::Get { database_path, .. }
+            | Command::Export { database_path, .. }
+            | Command::Import { database_path, .. }
+            | Command::Diff { database_path, .. }
+            | Command::Rename { database_path, .. }
+            | Command::Stats { database_path }
+            | Command::Check { database_path }
+            | Command::Compact { database_path }
+            | Command::Tables { database_path, .. }
+            | Command::CopyTable {
+                from: database_path,
+                ..
+            }
+            | Command::Merge {
+                from: database_path,
+                ..
+            },
+        ) => database_path,
+        Some(Command::Completions { .. }) => unreachable!("handled above"),
+        None => args.database_path.first().expect("given or picked"),
+    };
+    // Import seeds new databases; everything else needs an existing file.
+    let creates_database =
+        args.create_demo || matches!(args.command, Some(Command::Import { .. }));
+    if !database_path.exists() && !creates_database {
+        return Err(AppError::DatabaseNotFound(database_path.clone()));
+    }
+    if args.command.is_none() {
+        if let Some(path) =
+            args.database_path.iter().skip(1).find(|p| !p.exists())
+        {
+            return Err(AppError::DatabaseNotFound(path.clone()));
+        }
+    }
+    if let Some(Command::Diff { old, .. }) = &args.command {
+        if !old.exists() {
+            return Err(AppError::DatabaseNotFound(old.clone()));
+        }
+    }
+
+    let log_path = database_path.with_extension("log");
+    let log = setup_logger(&log_path);
 
     info!("Starting application");
-    info!("Database path: {:?}", args.database_path);
+    info!("Database path: {:?}", database_path);
     info!("Log file path: {:?}", log_path);
 
-    if !args.database_path.exists() {
-        info!("Database does not exist. Creating dummy database.");
-        database::create_dummy_database(&args.database_path)?;
-        info!("Created dummy database at {:?}", args.database_path);
+    let mut schemas = Schemas::default();
+    if let Some(path) = &args.bincode_schema {
+        info!("Loading bincode schema from {:?}", path);
+        schemas.bincode = Some(bincode::Schema::load(path)?);
+    }
+    if let Some(path) = &args.descriptors {
+        info!("Loading protobuf descriptors from {:?}", path);
+        schemas.protobuf = Some(protobuf::DescriptorPool::load(path)?);
+    }
+    schemas
+        .proto_messages
+        .extend(args.proto_message.iter().cloned());
+
+    match &args.command {
+        Some(Command::Get {
+            table,
+            key,
+            hex_key,
+            raw,
+            decoder,
+            ..
+        }) => {
+            let key = if *hex_key {
+                commands::KeyArg::Hex(key)
+            } else {
+                commands::KeyArg::Text(key)
+            };
+            let output = if *raw {
+                commands::Output::Raw
+            } else {
+                commands::Output::Decoded(*decoder)
+            };
+            return commands::get(database_path, schemas, table, key, output);
+        }
+        Some(Command::Export {
+            table,
+            format,
+            key_encoding,
+            value_encoding,
+            output,
+            ..
+        }) => {
+            let options = commands::ExportOptions {
+                format: *format,
+                key: *key_encoding,
+                value: *value_encoding,
+            };
+            return commands::export(
+                database_path,
+                schemas,
+                table.as_deref(),
+                options,
+                output.as_deref(),
+            );
+        }
+        Some(Command::Import {
+            table,
+            options,
+            key_type,
+            value_type,
+            input,
+            ..
+        }) => {
+            if args.read_only {
+                return Err(AppError::ReadOnly);
+            }
+            let types = database::TableTypes {
+                key: key_type.clone(),
+                value: value_type.clone(),
+            };
+            return commands::import(
+                database_path,
+                table.as_deref(),
+                options,
+                input,
+                &types,
+            );
+        }
+        Some(Command::CopyTable {
+            from,
+            to,
+            table,
+            target_table,
+            overwrite,
+            batch_size,
+        }) => {
+            if args.read_only {
+                return Err(AppError::ReadOnly);
+            }
+            return commands::copy_table(
+                from,
+                to,
+                table,
+                target_table.as_deref(),
+                *overwrite,
+                *batch_size,
+            );
+        }
+        Some(Command::Merge {
+            from,
+            to,
+            on_conflict,
+            batch_size,
+        }) => {
+            if args.read_only {
+                return Err(AppError::ReadOnly);
+            }
+            return commands::merge(from, to, *on_conflict, *batch_size);
+        }
+        Some(Command::Diff {
+            table,
+            format,
+            key_encoding,
+            value_encoding,
+            interactive: false,
+            old,
+            ..
+        }) => {
+            return commands::diff(
+                old,
+                database_path,
+                schemas,
+                table.as_deref(),
+                *format,
+                *key_encoding,
+                *value_encoding,
+            );
+        }
+        Some(Command::Diff { .. }) => {}
+        Some(Command::Rename { table, to, .. }) => {
+            if args.read_only {
+                return Err(AppError::ReadOnly);
+            }
+            return commands::rename(database_path, table, to);
+        }
+        Some(Command::Stats { .. }) => {
+            return commands::stats(database_path, args.read_only);
+        }
+        Some(Command::Check { .. }) => {
+            if args.read_only {
+                return Err(AppError::ReadOnly);
+            }
+            return commands::check(database_path);
+        }
+        Some(Command::Compact { .. }) => {
+            if args.read_only {
+                return Err(AppError::ReadOnly);
+            }
+            return commands::compact(database_path);
+        }
+        Some(Command::Completions { .. }) => unreachable!("handled above"),
+        Some(Command::Tables { count, types, .. }) => {
+            return commands::tables(database_path, *count, *types);
+        }
+        None => {}
+    }
+
+    if !database_path.exists() && args.create_demo {
+        info!("Database does not exist. Creating demo database.");
+        database::create_dummy_database(database_path)?;
+        info!("Created demo database at {:?}", database_path);
     }
 
-    match TuiWrapper::new(&args.database_path) {
+    let config = match config {
+        Some(config) => config,
+        None => load_config(&args)?,
+    };
+
+    // `diff --interactive` opens the TUI on the second database, with the
+    // differences from the first in view.
+    let (database_paths, table, diff_against) = match &args.command {
+        Some(Command::Diff { table, old, .. }) => (
+            std::slice::from_ref(database_path),
+            table,
+            Some(old.as_path()),
+        ),
+        _ => (args.database_path.as_slice(), &args.table, None),
+    };
+    match TuiWrapper::new(
+        database_paths,
+        table.as_deref(),
+        diff_against,
+        args.read_only,
+        schemas,
+        config,
+        log,
+    ) {
         Ok(mut tui) => {
             info!("TUI initialized successfully.");
             if let Err(e) = tui.run() {
@@ -0,0 +1,132 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::node::Node;
+
+/// Nesting limit guarding against maliciously deep payloads.
+const MAX_DEPTH: usize = 128;
+
+/// Decodes a single MessagePack object that spans the whole input.
+pub fn decode(bytes: &[u8]) -> DecodeResult<Node> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let node = reader.object(0)?;
+    if reader.pos != bytes.len() {
+        return Err(DecodeError::new(format!(
+            "{} trailing bytes",
+            bytes.len() - reader.pos
+        )));
+    }
+    Ok(node)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::new("unexpected end of input"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Reads a big-endian unsigned integer of `n` bytes.
+    fn uint(&mut self, n: usize) -> DecodeResult<u64> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    fn int(&mut self, n: usize) -> DecodeResult<i64> {
+        let shift = 64 - 8 * n as u32;
+        Ok(((self.uint(n)? << shift) as i64) >> shift)
+    }
+
+    fn len(&mut self, n: usize) -> DecodeResult<usize> {
+        let len = self.uint(n)? as usize;
+        if len > self.bytes.len() - self.pos {
+            return Err(DecodeError::new("length exceeds input"));
+        }
+        Ok(len)
+    }
+
+    fn text(&mut self, len: usize) -> DecodeResult<Node> {
+        let bytes = self.take(len)?;
+        std::str::from_utf8(bytes)
+            .map(|s| Node::Text(s.to_string()))
+            .map_err(|_| DecodeError::new("invalid UTF-8 in str"))
+    }
+
+    fn array(&mut self, len: usize, depth: usize) -> DecodeResult<Node> {
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(self.object(depth + 1)?);
+        }
+        Ok(Node::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> DecodeResult<Node> {
+        let mut entries = Vec::new();
+        for _ in 0..len {
+            let key = self.object(depth + 1)?;
+            entries.push((key, self.object(depth + 1)?));
+        }
+        Ok(Node::Map(entries))
+    }
+
+    fn ext(&mut self, len: usize) -> DecodeResult<Node> {
+        let kind = self.take(1)?[0];
+        let data = self.take(len)?.to_vec();
+        Ok(Node::Tagged(kind as u64, Box::new(Node::Bytes(data))))
+    }
+
+    fn object(&mut self, depth: usize) -> DecodeResult<Node> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::new("nesting too deep"));
+        }
+        let marker = self.take(1)?[0];
+        match marker {
+            0x00..=0x7f => Ok(Node::Int(marker as i128)),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth),
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, depth),
+            0xa0..=0xbf => self.text((marker & 0x1f) as usize),
+            0xc0 => Ok(Node::Null),
+            0xc2 => Ok(Node::Bool(false)),
+            0xc3 => Ok(Node::Bool(true)),
+            0xc4..=0xc6 => {
+                let len = self.len(1 << (marker - 0xc4))?;
+                Ok(Node::Bytes(self.take(len)?.to_vec()))
+            }
+            0xc7..=0xc9 => {
+                let len = self.len(1 << (marker - 0xc7))?;
+                self.ext(len)
+            }
+            0xca => Ok(Node::Float(f32::from_bits(self.uint(4)? as u32) as f64)),
+            0xcb => Ok(Node::Float(f64::from_bits(self.uint(8)?))),
+            0xcc..=0xcf => {
+                Ok(Node::Int(self.uint(1 << (marker - 0xcc))? as i128))
+            }
+            0xd0..=0xd3 => Ok(Node::Int(self.int(1 << (marker - 0xd0))? as i128)),
+            0xd4..=0xd8 => self.ext(1 << (marker - 0xd4)),
+            0xd9..=0xdb => {
+                let len = self.len(1 << (marker - 0xd9))?;
+                self.text(len)
+            }
+            0xdc | 0xdd => {
+                let len = self.len(2 << (marker - 0xdc))?;
+                self.array(len, depth)
+            }
+            0xde | 0xdf => {
+                let len = self.len(2 << (marker - 0xde))?;
+                self.map(len, depth)
+            }
+            0xe0..=0xff => Ok(Node::Int(marker as i8 as i128)),
+            0xc1 => Err(DecodeError::new("reserved marker 0xc1")),
+        }
+    }
+}
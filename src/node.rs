@@ -0,0 +1,88 @@
+use std::fmt::{self, Write};
+
+/// Structured value produced by the self-describing decoders.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Null,
+    Undefined,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    Tagged(u64, Box<Node>),
+}
+
+impl Node {
+    /// Looks up the entry with the text key `key` in a `Node::Map`.
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match self {
+            Node::Map(entries) => entries.iter().find_map(|(k, v)| match k {
+                Node::Text(k) if k == key => Some(v),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `s` as a double-quoted, escaped string literal.
+pub fn write_quoted(f: &mut impl Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Compact single-line rendering, close to CBOR diagnostic notation.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Null => f.write_str("null"),
+            Node::Undefined => f.write_str("undefined"),
+            Node::Bool(b) => write!(f, "{}", b),
+            Node::Int(i) => write!(f, "{}", i),
+            Node::Float(x) => write!(f, "{:?}", x),
+            Node::Text(s) => write_quoted(f, s),
+            Node::Bytes(bytes) => {
+                f.write_str("h'")?;
+                for b in bytes {
+                    write!(f, "{:02x}", b)?;
+                }
+                f.write_char('\'')
+            }
+            Node::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Node::Map(entries) => {
+                f.write_char('{')?;
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", k, v)?;
+                }
+                f.write_char('}')
+            }
+            Node::Tagged(tag, inner) => write!(f, "{}({})", tag, inner),
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::node::Node;
+use crate::{AppError, Result};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Nesting limit guarding against recursive messages.
+const MAX_DEPTH: usize = 64;
+
+const LABEL_REPEATED: u64 = 3;
+
+/// `FieldDescriptorProto.Type` values.
+mod field_type {
+    pub const DOUBLE: u64 = 1;
+    pub const FLOAT: u64 = 2;
+    pub const INT64: u64 = 3;
+    pub const UINT64: u64 = 4;
+    pub const INT32: u64 = 5;
+    pub const FIXED64: u64 = 6;
+    pub const FIXED32: u64 = 7;
+    pub const BOOL: u64 = 8;
+    pub const STRING: u64 = 9;
+    pub const GROUP: u64 = 10;
+    pub const MESSAGE: u64 = 11;
+    pub const BYTES: u64 = 12;
+    pub const UINT32: u64 = 13;
+    pub const ENUM: u64 = 14;
+    pub const SFIXED32: u64 = 15;
+    pub const SFIXED64: u64 = 16;
+    pub const SINT32: u64 = 17;
+    pub const SINT64: u64 = 18;
+}
+
+#[derive(Debug, Clone)]
+struct FieldDesc {
+    name: String,
+    kind: u64,
+    repeated: bool,
+    /// Fully qualified message or enum name, without the leading dot.
+    type_name: String,
+}
+
+/// Message and enum definitions from a compiled `FileDescriptorSet`, as
+/// produced by `protoc --descriptor_set_out`.
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorPool {
+    messages: HashMap<String, HashMap<u64, FieldDesc>>,
+    enums: HashMap<String, HashMap<i64, String>>,
+}
+
+impl DescriptorPool {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::parse(&bytes).map_err(|e| {
+            AppError::ConfigError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> DecodeResult<Self> {
+        let mut pool = Self::default();
+        for (number, value) in fields(bytes)? {
+            if let (1, Wire::Len(file)) = (number, value) {
+                pool.add_file(file)?;
+            }
+        }
+        Ok(pool)
+    }
+
+    /// Fully qualified names of all known messages, sorted.
+    pub fn message_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> =
+            self.messages.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn add_file(&mut self, file: &[u8]) -> DecodeResult<()> {
+        let mut package = String::new();
+        let mut messages = Vec::new();
+        let mut enums = Vec::new();
+        for (number, value) in fields(file)? {
+            match (number, value) {
+                (2, Wire::Len(name)) => package = utf8(name)?,
+                (4, Wire::Len(message)) => messages.push(message),
+                (5, Wire::Len(enum_type)) => enums.push(enum_type),
+                _ => {}
+            }
+        }
+        for message in messages {
+            self.add_message(&package, message)?;
+        }
+        for enum_type in enums {
+            self.add_enum(&package, enum_type)?;
+        }
+        Ok(())
+    }
+
+    fn add_message(&mut self, scope: &str, bytes: &[u8]) -> DecodeResult<()> {
+        let mut name = String::new();
+        let mut field_list = Vec::new();
+        let mut nested = Vec::new();
+        let mut enums = Vec::new();
+        for (number, value) in fields(bytes)? {
+            match (number, value) {
+                (1, Wire::Len(n)) => name = utf8(n)?,
+                (2, Wire::Len(field)) => field_list.push(field),
+                (3, Wire::Len(message)) => nested.push(message),
+                (4, Wire::Len(enum_type)) => enums.push(enum_type),
+                _ => {}
+            }
+        }
+        let full_name = qualify(scope, &name);
+        let mut message_fields = HashMap::new();
+        for field in field_list {
+            let mut desc = FieldDesc {
+                name: String::new(),
+                kind: 0,
+                repeated: false,
+                type_name: String::new(),
+            };
+            let mut number = 0;
+            for (tag, value) in fields(field)? {
+                match (tag, value) {
+                    (1, Wire::Len(n)) => desc.name = utf8(n)?,
+                    (3, Wire::Varint(n)) => number = n,
+                    (4, Wire::Varint(label)) => {
+                        desc.repeated = label == LABEL_REPEATED
+                    }
+                    (5, Wire::Varint(kind)) => desc.kind = kind,
+                    (6, Wire::Len(n)) => {
+                        desc.type_name =
+                            utf8(n)?.trim_start_matches('.').to_string()
+                    }
+                    _ => {}
+                }
+            }
+            message_fields.insert(number, desc);
+        }
+        self.messages.insert(full_name.clone(), message_fields);
+        for message in nested {
+            self.add_message(&full_name, message)?;
+        }
+        for enum_type in enums {
+            self.add_enum(&full_name, enum_type)?;
+        }
+        Ok(())
+    }
+
+    fn add_enum(&mut self, scope: &str, bytes: &[u8]) -> DecodeResult<()> {
+        let mut name = String::new();
+        let mut values = HashMap::new();
+        for (number, value) in fields(bytes)? {
+            match (number, value) {
+                (1, Wire::Len(n)) => name = utf8(n)?,
+                (2, Wire::Len(enum_value)) => {
+                    let mut value_name = String::new();
+                    let mut value_number = 0;
+                    for (tag, value) in fields(enum_value)? {
+                        match (tag, value) {
+                            (1, Wire::Len(n)) => value_name = utf8(n)?,
+                            (2, Wire::Varint(n)) => {
+                                value_number = n as i32 as i64
+                            }
+                            _ => {}
+                        }
+                    }
+                    values.insert(value_number, value_name);
+                }
+                _ => {}
+            }
+        }
+        self.enums.insert(qualify(scope, &name), values);
+        Ok(())
+    }
+
+    /// Decodes `bytes` as the message `message_name`.
+    pub fn decode(&self, message_name: &str, bytes: &[u8]) -> DecodeResult<Node> {
+        self.decode_message(message_name, bytes, 0)
+    }
+
+    fn decode_message(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+        depth: usize,
+    ) -> DecodeResult<Node> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::new("nesting too deep"));
+        }
+        let descriptors = self.messages.get(message_name).ok_or_else(|| {
+            DecodeError::new(format!("unknown message {}", message_name))
+        })?;
+        let mut entries: Vec<(Node, Node)> = Vec::new();
+        for (number, value) in fields(bytes)? {
+            let (key, decoded) = match descriptors.get(&number) {
+                Some(desc) => (
+                    Node::Text(desc.name.clone()),
+                    self.decode_field(desc, value, depth)?,
+                ),
+                None => {
+                    (Node::Int(number as i128), vec![raw_value(value, depth)?])
+                }
+            };
+            let repeated = descriptors.get(&number).is_some_and(|d| d.repeated);
+            match entries.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, Node::Array(items))) if repeated => {
+                    items.extend(decoded)
+                }
+                Some((_, existing)) => {
+                    if let Some(last) = decoded.into_iter().last() {
+                        *existing = last;
+                    }
+                }
+                None if repeated => entries.push((key, Node::Array(decoded))),
+                None => {
+                    if let Some(last) = decoded.into_iter().last() {
+                        entries.push((key, last));
+                    }
+                }
+            }
+        }
+        Ok(Node::Map(entries))
+    }
+
+    /// Decodes one occurrence of a field. Packed repeated scalars yield
+    /// several values.
+    fn decode_field(
+        &self,
+        desc: &FieldDesc,
+        value: Wire,
+        depth: usize,
+    ) -> DecodeResult<Vec<Node>> {
+        use field_type::*;
+        match (desc.kind, value) {
+            (STRING, Wire::Len(b)) => Ok(vec![Node::Text(utf8(b)?)]),
+            (BYTES, Wire::Len(b)) => Ok(vec![Node::Bytes(b.to_vec())]),
+            (MESSAGE, Wire::Len(b)) | (GROUP, Wire::Len(b)) => {
+                Ok(vec![self.decode_message(&desc.type_name, b, depth + 1)?])
+            }
+            (_, Wire::Len(packed)) => {
+                let mut reader = Reader::new(packed);
+                let mut items = Vec::new();
+                while !reader.done() {
+                    let value = match desc.kind {
+                        DOUBLE | FIXED64 | SFIXED64 => {
+                            Wire::Fixed64(reader.fixed64()?)
+                        }
+                        FLOAT | FIXED32 | SFIXED32 => {
+                            Wire::Fixed32(reader.fixed32()?)
+                        }
+                        _ => Wire::Varint(reader.varint()?),
+                    };
+                    items.push(self.scalar(desc, value)?);
+                }
+                Ok(items)
+            }
+            (_, value) => Ok(vec![self.scalar(desc, value)?]),
+        }
+    }
+
+    fn scalar(&self, desc: &FieldDesc, value: Wire) -> DecodeResult<Node> {
+        use field_type::*;
+        Ok(match (desc.kind, value) {
+            (DOUBLE, Wire::Fixed64(v)) => Node::Float(f64::from_bits(v)),
+            (FLOAT, Wire::Fixed32(v)) => Node::Float(f32::from_bits(v) as f64),
+            (FIXED64, Wire::Fixed64(v)) => Node::Int(v as i128),
+            (SFIXED64, Wire::Fixed64(v)) => Node::Int(v as i64 as i128),
+            (FIXED32, Wire::Fixed32(v)) => Node::Int(v as i128),
+            (SFIXED32, Wire::Fixed32(v)) => Node::Int(v as i32 as i128),
+            (INT64, Wire::Varint(v)) => Node::Int(v as i64 as i128),
+            (UINT64, Wire::Varint(v)) => Node::Int(v as i128),
+            (INT32, Wire::Varint(v)) => Node::Int(v as i32 as i128),
+            (UINT32, Wire::Varint(v)) => Node::Int(v as u32 as i128),
+            (SINT32 | SINT64, Wire::Varint(v)) => Node::Int(zigzag(v) as i128),
+            (BOOL, Wire::Varint(v)) => Node::Bool(v != 0),
+            (ENUM, Wire::Varint(v)) => {
+                let number = v as i32 as i64;
+                match self.enums.get(&desc.type_name).and_then(|e| e.get(&number))
+                {
+                    Some(name) => Node::Text(name.clone()),
+                    None => Node::Int(number as i128),
+                }
+            }
+            _ => {
+                return Err(DecodeError::new(format!(
+                    "wire type mismatch for field {}",
+                    desc.name
+                )))
+            }
+        })
+    }
+}
+
+/// Decodes a message without a descriptor, keyed by field number.
+/// Length-delimited fields are shown as text, nested messages or bytes,
+/// whichever fits first.
+pub fn decode_raw(bytes: &[u8]) -> DecodeResult<Node> {
+    decode_raw_message(bytes, 0)
+}
+
+fn decode_raw_message(bytes: &[u8], depth: usize) -> DecodeResult<Node> {
+    if depth > MAX_DEPTH {
+        return Err(DecodeError::new("nesting too deep"));
+    }
+    let mut entries = Vec::new();
+    for (number, value) in fields(bytes)? {
+        entries.push((Node::Int(number as i128), raw_value(value, depth)?));
+    }
+    Ok(Node::Map(entries))
+}
+
+fn raw_value(value: Wire, depth: usize) -> DecodeResult<Node> {
+    Ok(match value {
+        Wire::Varint(v) => Node::Int(v as i128),
+        Wire::Fixed64(v) => Node::Int(v as i128),
+        Wire::Fixed32(v) => Node::Int(v as i128),
+        Wire::Len(b) => match std::str::from_utf8(b) {
+            Ok(s) if !s.chars().any(|c| c.is_control() && !c.is_whitespace()) => {
+                Node::Text(s.to_string())
+            }
+            _ => decode_raw_message(b, depth + 1)
+                .ok()
+                .filter(|_| !b.is_empty())
+                .unwrap_or_else(|| Node::Bytes(b.to_vec())),
+        },
+    })
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn utf8(bytes: &[u8]) -> DecodeResult<String> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| DecodeError::new("invalid UTF-8 in string field"))
+}
+
+fn zigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+/// A field value as encoded on the wire.
+#[derive(Debug, Clone, Copy)]
+enum Wire<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Len(&'a [u8]),
+}
+
+/// Splits a message into `(field number, value)` pairs.
+fn fields(bytes: &[u8]) -> DecodeResult<Vec<(u64, Wire<'_>)>> {
+    let mut reader = Reader::new(bytes);
+    let mut out = Vec::new();
+    while !reader.done() {
+        let tag = reader.varint()?;
+        let number = tag >> 3;
+        if number == 0 {
+            return Err(DecodeError::new("invalid field number 0"));
+        }
+        let value = match tag & 7 {
+            0 => Wire::Varint(reader.varint()?),
+            1 => Wire::Fixed64(reader.fixed64()?),
+            2 => {
+                let len = usize::try_from(reader.varint()?)
+                    .map_err(|_| DecodeError::new("length overflow"))?;
+                Wire::Len(reader.take(len)?)
+            }
+            // Groups are deprecated; their contents are kept as fields of the
+            // enclosing message.
+            3 | 4 => continue,
+            5 => Wire::Fixed32(reader.fixed32()?),
+            wire => {
+                return Err(DecodeError::new(format!(
+                    "invalid wire type {}",
+                    wire
+                )))
+            }
+        };
+        out.push((number, value));
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::new("unexpected end of input"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> DecodeResult<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::new("varint too long"))
+    }
+
+    fn fixed64(&mut self) -> DecodeResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn fixed32(&mut self) -> DecodeResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
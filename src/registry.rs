@@ -0,0 +1,128 @@
+use crate::decoder::Decoder;
+use crate::keytree::Grouping;
+use crate::node::Node;
+use crate::{toml, AppError, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Decoders declared for one table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableSchema {
+    pub key: Option<Decoder>,
+    pub value: Option<Decoder>,
+    /// Protobuf message type of the values.
+    pub message: Option<String>,
+    /// How the key tree groups the keys.
+    pub group_by: Option<Grouping>,
+}
+
+/// Per-table decoders stored next to the database in `<db>.schema.toml`:
+///
+/// ```toml
+/// [tables.users]
+/// key = "str"
+/// value = "json"
+/// group_by = ":"
+///
+/// [tables.events]
+/// key = "u64be"
+/// value = "protobuf"
+/// message = "app.Event"
+/// ```
+///
+/// Types are decoder names as shown in the TUI, plus the aliases `str`,
+/// `u32`, `u64`, `i64` (little endian, like redb) and `timestamp`. Custom
+/// types are declared as `bincode` or `protobuf`, with their layout coming
+/// from `--bincode-schema` or `message` respectively. `group_by` is the
+/// separator the key tree groups keys by, or a number of bytes per group.
+#[derive(Debug, Default)]
+pub struct Registry {
+    pub tables: BTreeMap<String, TableSchema>,
+}
+
+impl Registry {
+    /// Location of the sidecar file for the database at `db_path`.
+    pub fn sidecar_path(db_path: &Path) -> PathBuf {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(".schema.toml");
+        PathBuf::from(path)
+    }
+
+    /// Reads the sidecar file; a missing file yields an empty registry.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| {
+            AppError::ConfigError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let doc = toml::parse(text).map_err(|e| e.to_string())?;
+        let mut tables = BTreeMap::new();
+        if let Some(section) = toml::get(&doc, "tables") {
+            for (table, spec) in toml::entries(section) {
+                let decoder = |field: &str| match toml::get_str(spec, field) {
+                    None => Ok(None),
+                    Some(name) => {
+                        Decoder::from_name(name).map(Some).ok_or_else(|| {
+                            format!(
+                                "tables.{}.{}: unknown type '{}'",
+                                table, field, name
+                            )
+                        })
+                    }
+                };
+                let group_by = match toml::get(spec, "group_by") {
+                    Some(node) => {
+                        Some(Grouping::from_node(node).map_err(|e| {
+                            format!("tables.{}.group_by: {}", table, e)
+                        })?)
+                    }
+                    None => None,
+                };
+                let schema = TableSchema {
+                    key: decoder("key")?,
+                    value: decoder("value")?,
+                    message: toml::get_str(spec, "message").map(str::to_string),
+                    group_by,
+                };
+                tables.insert(table.to_string(), schema);
+            }
+        }
+        Ok(Self { tables })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    fn to_toml(&self) -> String {
+        let text = |s: &str| Node::Text(s.to_string());
+        let tables = self
+            .tables
+            .iter()
+            .map(|(name, schema)| {
+                let mut fields = Vec::new();
+                if let Some(key) = schema.key {
+                    fields.push((text("key"), text(key.label())));
+                }
+                if let Some(value) = schema.value {
+                    fields.push((text("value"), text(value.label())));
+                }
+                if let Some(message) = &schema.message {
+                    fields.push((text("message"), text(message)));
+                }
+                if let Some(grouping) = schema.group_by {
+                    fields.push((text("group_by"), grouping.to_node()));
+                }
+                (text(name), Node::Map(fields))
+            })
+            .collect();
+        toml::to_string(&Node::Map(vec![(text("tables"), Node::Map(tables))]))
+    }
+}
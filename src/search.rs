@@ -0,0 +1,598 @@
+use std::ops::Range;
+
+/// Scores how well `pattern` fuzzy-matches `candidate`: all of its characters
+/// have to appear in order, ignoring ASCII case. Runs of consecutive
+/// characters and matches at word starts score higher, gaps lower. Returns
+/// `None` when `candidate` doesn't match.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+    for p in pattern.chars().map(|c| c.to_ascii_lowercase()) {
+        let i =
+            (pos..chars.len()).find(|&i| chars[i].to_ascii_lowercase() == p)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == i) {
+            score += 5;
+        }
+        if i == 0 || !chars[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (i - pos) as i64;
+        previous = Some(i);
+        pos = i + 1;
+    }
+    Some(score)
+}
+
+/// Substring search over decoded keys and values, or a regular expression
+/// search when the text is written between slashes, like `/^user-\d+$/`.
+/// Matching ignores ASCII case unless the query contains an uppercase
+/// letter, escapes like `\D` aside.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    text: String,
+    ignore_case: bool,
+    regex: Option<Regex>,
+}
+
+impl Query {
+    pub fn new(text: &str) -> Result<Self, String> {
+        let pattern = text
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty());
+        let mut chars = pattern.unwrap_or(text).chars();
+        let mut ignore_case = true;
+        while let Some(c) = chars.next() {
+            if c == '\\' && pattern.is_some() {
+                chars.next();
+            } else if c.is_uppercase() {
+                ignore_case = false;
+            }
+        }
+        Ok(Self {
+            text: text.to_string(),
+            ignore_case,
+            regex: pattern.map(Regex::new).transpose()?,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_regex(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    pub fn matches(&self, haystack: &str) -> bool {
+        !self.find(haystack).is_empty()
+    }
+
+    /// Byte ranges of the non-overlapping matches in `haystack`.
+    pub fn find(&self, haystack: &str) -> Vec<Range<usize>> {
+        if let Some(regex) = &self.regex {
+            return regex.find(haystack, self.ignore_case);
+        }
+        if self.text.is_empty() {
+            return Vec::new();
+        }
+        // ASCII lowercasing keeps byte offsets valid in the original text.
+        let (haystack, needle) = if self.ignore_case {
+            (
+                haystack.to_ascii_lowercase(),
+                self.text.to_ascii_lowercase(),
+            )
+        } else {
+            (haystack.to_string(), self.text.clone())
+        };
+        haystack
+            .match_indices(&needle)
+            .map(|(start, m)| start..start + m.len())
+            .collect()
+    }
+}
+
+/// A regular expression, matched by backtracking. Supports literals, `.`,
+/// classes like `[a-z_]` and `[^,]`, the escapes `\d`, `\w`, `\s` and their
+/// negations `\D`, `\W`, `\S`, the anchors `^` and `$`, groups with
+/// alternatives like `(get|put)`, and the greedy quantifiers `*`, `+`, `?`
+/// and `{n}`, `{n,}`, `{n,m}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regex {
+    node: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Empty,
+    Char(char),
+    /// `.`, any character but a newline.
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassItem {
+    Range(char, char),
+    Digit,
+    Word,
+    Space,
+}
+
+impl Class {
+    fn of(item: ClassItem, negated: bool) -> Self {
+        Self {
+            items: vec![item],
+            negated,
+        }
+    }
+
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        let has = |c: char| {
+            self.items.iter().any(|item| match *item {
+                ClassItem::Range(from, to) => (from..=to).contains(&c),
+                ClassItem::Digit => c.is_ascii_digit(),
+                ClassItem::Word => c.is_alphanumeric() || c == '_',
+                ClassItem::Space => c.is_whitespace(),
+            })
+        };
+        let found = has(c)
+            || ignore_case
+                && (has(c.to_ascii_lowercase()) || has(c.to_ascii_uppercase()));
+        found != self.negated
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.alternation()?;
+        match parser.peek() {
+            None => Ok(Self { node }),
+            Some(_) => Err("Unmatched ) in pattern".into()),
+        }
+    }
+
+    /// Byte ranges of the non-overlapping, non-empty matches in `haystack`,
+    /// each the leftmost one and as long as the quantifiers make it.
+    pub fn find(&self, haystack: &str, ignore_case: bool) -> Vec<Range<usize>> {
+        let (offsets, chars): (Vec<usize>, Vec<char>) =
+            haystack.char_indices().unzip();
+        let offset = |i: usize| offsets.get(i).copied().unwrap_or(haystack.len());
+        let matcher = Matcher {
+            chars: &chars,
+            ignore_case,
+        };
+        let mut found = Vec::new();
+        let mut start = 0;
+        while start <= chars.len() {
+            let mut end = None;
+            matcher.matches(&self.node, start, &mut |i| {
+                end = Some(i);
+                true
+            });
+            match end {
+                Some(end) if end > start => {
+                    found.push(offset(start)..offset(end));
+                    start = end;
+                }
+                _ => start += 1,
+            }
+        }
+        found
+    }
+}
+
+struct Matcher<'a> {
+    chars: &'a [char],
+    ignore_case: bool,
+}
+
+impl Matcher<'_> {
+    /// Whether `node` matches at `i` with `then` accepting where it ends,
+    /// trying the longer matches first.
+    fn matches(
+        &self,
+        node: &Node,
+        i: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        match node {
+            Node::Empty => then(i),
+            Node::Char(_) | Node::Any | Node::Class(_) => {
+                self.matches_one(node, i) && then(i + 1)
+            }
+            Node::Start => i == 0 && then(i),
+            Node::End => i == self.chars.len() && then(i),
+            Node::Concat(nodes) => self.matches_all(nodes, i, then),
+            Node::Alt(nodes) => {
+                nodes.iter().any(|node| self.matches(node, i, then))
+            }
+            Node::Repeat { node, min, max } => {
+                self.repeat(node, *min, *max, 0, i, then)
+            }
+        }
+    }
+
+    fn matches_all(
+        &self,
+        nodes: &[Node],
+        i: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        match nodes.split_first() {
+            None => then(i),
+            Some((first, rest)) => self.matches(first, i, &mut |j| {
+                self.matches_all(rest, j, &mut *then)
+            }),
+        }
+    }
+
+    /// Whether the single character at `i` matches `node`.
+    fn matches_one(&self, node: &Node, i: usize) -> bool {
+        let Some(&c) = self.chars.get(i) else {
+            return false;
+        };
+        match node {
+            Node::Char(expected) if self.ignore_case => {
+                c.eq_ignore_ascii_case(expected)
+            }
+            Node::Char(expected) => c == *expected,
+            Node::Any => c != '\n',
+            Node::Class(class) => class.matches(c, self.ignore_case),
+            _ => false,
+        }
+    }
+
+    fn repeat(
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        count: usize,
+        i: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        if matches!(node, Node::Char(_) | Node::Any | Node::Class(_)) {
+            // Single characters repeat without recursing once per match, so
+            // `.*` over a long value doesn't run out of stack.
+            let limit = max.unwrap_or(usize::MAX);
+            let mut end = i;
+            while end - i < limit && self.matches_one(node, end) {
+                end += 1;
+            }
+            return (i + min..=end).rev().any(then);
+        }
+        let more = max.is_none_or(|max| count < max)
+            && self.matches(node, i, &mut |j| {
+                // A repeat matching nothing would go round forever.
+                (j > i || count < min)
+                    && self.repeat(node, min, max, count + 1, j, &mut *then)
+            });
+        more || count >= min && then(i)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Alternatives separated by `|`, up to a `)` or the end.
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.sequence()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.remove(0),
+            _ => Node::Alt(alternatives),
+        })
+    }
+
+    fn sequence(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or("Unexpected end of pattern")?;
+        Ok(match c {
+            '(' => {
+                // Groups don't capture, so `(?:` is the same as `(`.
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    if !self.eat(':') {
+                        return Err("Unsupported group (? in pattern".into());
+                    }
+                }
+                let node = self.alternation()?;
+                if !self.eat(')') {
+                    return Err("Unclosed ( in pattern".into());
+                }
+                node
+            }
+            '[' => Node::Class(self.class()?),
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => self.escape()?,
+            '*' | '+' | '?' => {
+                return Err(format!("Nothing to repeat before {}", c));
+            }
+            c => Node::Char(c),
+        })
+    }
+
+    /// The character after a `\`, as a node.
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or("Pattern ends with \\")?;
+        Ok(match c {
+            'd' | 'D' => Node::Class(Class::of(ClassItem::Digit, c == 'D')),
+            'w' | 'W' => Node::Class(Class::of(ClassItem::Word, c == 'W')),
+            's' | 'S' => Node::Class(Class::of(ClassItem::Space, c == 'S')),
+            c => Node::Char(escaped_char(c)?),
+        })
+    }
+
+    /// A class after its `[`: a leading `]` and a trailing `-` are taken
+    /// literally.
+    fn class(&mut self) -> Result<Class, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("Unclosed [ in pattern")?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let from = match c {
+                '\\' => match self.next().ok_or("Unclosed [ in pattern")? {
+                    'd' => {
+                        items.push(ClassItem::Digit);
+                        continue;
+                    }
+                    'w' => {
+                        items.push(ClassItem::Word);
+                        continue;
+                    }
+                    's' => {
+                        items.push(ClassItem::Space);
+                        continue;
+                    }
+                    c => escaped_char(c)?,
+                },
+                c => c,
+            };
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']');
+            if !is_range {
+                items.push(ClassItem::Range(from, from));
+                continue;
+            }
+            self.pos += 1;
+            let to = match self.next().ok_or("Unclosed [ in pattern")? {
+                '\\' => {
+                    escaped_char(self.next().ok_or("Unclosed [ in pattern")?)?
+                }
+                c => c,
+            };
+            if to < from {
+                return Err(format!(
+                    "Backwards range {}-{} in pattern",
+                    from, to
+                ));
+            }
+            items.push(ClassItem::Range(from, to));
+        }
+        Ok(Class { items, negated })
+    }
+
+    /// `node` with the quantifier following it, if any. A `{` that doesn't
+    /// start a count is a literal.
+    fn quantified(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.counts() {
+                Some(counts) => counts,
+                None => return Ok(node),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(node),
+        };
+        if matches!(node, Node::Start | Node::End) {
+            return Err("Nothing to repeat before a quantifier".into());
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("Backwards count {{{},{:?}}}", min, max));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        })
+    }
+
+    /// The `{n}`, `{n,}` or `{n,m}` at the current position, moving past
+    /// it.
+    fn counts(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let end = rest.find('}')?;
+        let (min, max) = match rest[..end].split_once(',') {
+            None => {
+                let n = rest[..end].parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        self.pos += rest[..end].chars().count() + 2;
+        Some((min, max))
+    }
+}
+
+/// The character a `\` followed by `c` stands for.
+fn escaped_char(c: char) -> Result<char, String> {
+    Ok(match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c if c.is_alphanumeric() => {
+            return Err(format!("Unknown escape \\{} in pattern", c));
+        }
+        c => c,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(ranges: Vec<Range<usize>>) -> Vec<(usize, usize)> {
+        ranges.into_iter().map(|r| (r.start, r.end)).collect()
+    }
+
+    fn find(pattern: &str, haystack: &str) -> Vec<(usize, usize)> {
+        spans(Regex::new(pattern).unwrap().find(haystack, false))
+    }
+
+    fn matches(pattern: &str, haystack: &str) -> bool {
+        !find(pattern, haystack).is_empty()
+    }
+
+    #[test]
+    fn anchors_hold_to_the_ends() {
+        assert!(matches("^user-\\d+$", "user-42"));
+        assert!(!matches("^user-\\d+$", "user-42x"));
+        assert!(!matches("^user-\\d+$", "old user-42"));
+        // Empty matches aren't reported.
+        assert!(find("^$", "").is_empty());
+        assert_eq!(find("^\\s*$", "  "), [(0, 2)]);
+        assert_eq!(find("^a", "aaa"), [(0, 1)]);
+        assert_eq!(find("a$", "aaa"), [(2, 3)]);
+        assert_eq!(find("(^a|b$)", "abab"), [(0, 1), (3, 4)]);
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert_eq!(find("[a-c_]+", "xab_cz"), [(1, 5)]);
+        assert_eq!(find("[^,]+", "a,bc,"), [(0, 1), (2, 4)]);
+        assert_eq!(find("[]x]", "a]x"), [(1, 2), (2, 3)]);
+        assert_eq!(find("[a-]", "-b"), [(0, 1)]);
+        assert_eq!(find("\\d\\D\\w\\W\\s\\S", "1a_-x."), []);
+        assert_eq!(find("\\d\\D\\w\\W\\s\\S", "1a_- x"), [(0, 6)]);
+        assert_eq!(find("[\\d.]+", "v1.2a"), [(1, 4)]);
+        assert_eq!(find("a\\.b\\n", "axb\na.b\n"), [(4, 8)]);
+        assert!(!matches(".", "\n"));
+    }
+
+    #[test]
+    fn quantifiers_and_groups() {
+        assert_eq!(find("a{2}", "aaaaa"), [(0, 2), (2, 4)]);
+        assert_eq!(find("a{2,}", "aaaaa"), [(0, 5)]);
+        assert_eq!(find("a{1,2}b?", "aaab"), [(0, 2), (2, 4)]);
+        assert_eq!(find("x{,2}", "x{,2}"), [(0, 5)]);
+        assert_eq!(find("(get|put)s?", "puts get"), [(0, 4), (5, 8)]);
+        assert_eq!(find("(?:ab)+", "ababa"), [(0, 4)]);
+        assert_eq!(find("(a*)*b", "aaab"), [(0, 4)]);
+        assert_eq!(find("a*", "bab"), [(1, 2)]);
+    }
+
+    #[test]
+    fn offsets_are_bytes_and_case_folds_ascii() {
+        let regex = Regex::new("é.").unwrap();
+        assert_eq!(spans(regex.find("aéb€", false)), [(1, 4)]);
+        assert_eq!(
+            spans(Regex::new("[a-z]+").unwrap().find("ABC", true)),
+            [(0, 3)]
+        );
+        let query = Query::new("/^User/").unwrap();
+        assert!(query.is_regex() && !query.matches("user"));
+        let query = Query::new("/^user\\D/").unwrap();
+        assert!(query.matches("USER-1"));
+        assert_eq!(spans(Query::new("Dé").unwrap().find("dé Dé")), [(4, 7)]);
+    }
+
+    #[test]
+    fn invalid_and_truncated_patterns_are_errors() {
+        for pattern in [
+            "(a", "a)", "[a", "[a-", "[z-a]", "\\", "*a", "a|+", "^*", "a{3,1}",
+            "\\q", "(?=a)", "[\\",
+        ] {
+            assert!(Regex::new(pattern).is_err(), "{:?}", pattern);
+        }
+        assert!(Query::new("/(a/").is_err());
+        // Every prefix either parses or fails cleanly, and runs.
+        let pattern = "^(?:[a-z\\d_-]{2,8}|\\(x\\))+\\s*$";
+        for (end, _) in pattern.char_indices() {
+            if let Ok(regex) = Regex::new(&pattern[..end]) {
+                regex.find("ab_1 (x) \u{e9}", true);
+            }
+        }
+    }
+
+    #[test]
+    fn long_values_dont_exhaust_the_stack() {
+        let haystack = "a".repeat(100_000);
+        assert_eq!(find(".*", &haystack), [(0, 100_000)]);
+        assert_eq!(find("^[a]+$", &haystack), [(0, 100_000)]);
+    }
+}
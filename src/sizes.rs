@@ -0,0 +1,98 @@
+/// Lengths of the keys or values of a table, to sum up how they spread.
+#[derive(Debug, Default)]
+pub struct Sizes {
+    sizes: Vec<usize>,
+}
+
+/// How lengths spread, see `Sizes::summary`.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub count: u64,
+    pub min: usize,
+    pub max: usize,
+    pub total: u64,
+    /// Length 95% of them are no longer than.
+    pub p95: usize,
+    /// Counts of lengths in powers of two, from the shortest length to
+    /// the longest.
+    pub buckets: Vec<Bucket>,
+    sorted: Vec<usize>,
+}
+
+/// Lengths from `start` up to, but not including, `end`.
+#[derive(Debug, Clone, Copy)]
+pub struct Bucket {
+    pub start: usize,
+    pub end: usize,
+    pub count: u64,
+}
+
+impl Sizes {
+    pub fn add(&mut self, len: usize) {
+        self.sizes.push(len);
+    }
+
+    /// Lengths added so far.
+    pub fn count(&self) -> u64 {
+        self.sizes.len() as u64
+    }
+
+    /// `None` when nothing was added.
+    pub fn summary(mut self) -> Option<Summary> {
+        self.sizes.sort_unstable();
+        let sorted = self.sizes;
+        let (min, max) = (*sorted.first()?, *sorted.last()?);
+        let index = |len: usize| (usize::BITS - len.leading_zeros()) as usize;
+        let mut buckets: Vec<Bucket> = (index(min)..=index(max))
+            .map(|i| Bucket {
+                start: if i == 0 { 0 } else { 1 << (i - 1) },
+                end: if i == 0 { 1 } else { 1 << i },
+                count: 0,
+            })
+            .collect();
+        for len in &sorted {
+            buckets[index(*len) - index(min)].count += 1;
+        }
+        let p95 = sorted[(sorted.len() * 95).div_ceil(100) - 1];
+        Some(Summary {
+            count: sorted.len() as u64,
+            min,
+            max,
+            total: sorted.iter().map(|len| *len as u64).sum(),
+            p95,
+            buckets,
+            sorted,
+        })
+    }
+}
+
+impl Summary {
+    pub fn average(&self) -> f64 {
+        self.total as f64 / self.count as f64
+    }
+
+    /// How many are longer than `len`.
+    pub fn longer_than(&self, len: usize) -> u64 {
+        (self.sorted.len() - self.sorted.partition_point(|n| *n <= len)) as u64
+    }
+}
+
+impl Bucket {
+    /// The range in bytes, with powers of 1024 as K, M and G, e.g. `1K-2K`.
+    pub fn label(&self) -> String {
+        match self.end - self.start {
+            1 => short_size(self.start),
+            _ => format!("{}-{}", short_size(self.start), short_size(self.end)),
+        }
+    }
+}
+
+/// A power of two as a number of bytes, KiB, MiB or GiB.
+fn short_size(len: usize) -> String {
+    match len {
+        0..0x400 => len.to_string(),
+        0x400..0x10_0000 => format!("{}K", len >> 10),
+        0x10_0000..0x4000_0000 => format!("{}M", len >> 20),
+        _ => format!("{}G", len >> 30),
+    }
+}
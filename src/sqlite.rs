@@ -0,0 +1,542 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::node::Node;
+use crate::{AppError, Result};
+use std::{fs, path::Path};
+
+/// Nesting limit guarding against b-trees that loop back on themselves.
+const MAX_DEPTH: usize = 64;
+
+const LEAF_TABLE_PAGE: u8 = 13;
+const INTERIOR_TABLE_PAGE: u8 = 5;
+
+/// A SQLite database file, read whole. Only what importing tables needs is
+/// understood: the schema and the rows of ordinary (rowid) tables, in UTF-8
+/// databases.
+pub struct Database {
+    data: Vec<u8>,
+    page_size: usize,
+    /// Page size minus the bytes reserved at the end of each page.
+    usable: usize,
+}
+
+/// A table from the schema of a SQLite database.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    root: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    /// `INTEGER PRIMARY KEY` columns are stored as the rowid.
+    rowid: bool,
+}
+
+impl Database {
+    pub fn load(path: &Path) -> Result<Self> {
+        let wal = path.with_file_name(format!(
+            "{}-wal",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        if fs::metadata(&wal).is_ok_and(|m| m.len() > 0) {
+            return Err(AppError::ImportError(format!(
+                "{}: has a write-ahead log; checkpoint it first, e.g. with \
+                 sqlite3 '{}' 'PRAGMA wal_checkpoint(TRUNCATE)'",
+                path.display(),
+                path.display()
+            )));
+        }
+        Self::parse(fs::read(path)?).map_err(|e| {
+            AppError::ImportError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(data: Vec<u8>) -> DecodeResult<Self> {
+        if data.len() < 100 || !data.starts_with(b"SQLite format 3\0") {
+            return Err(DecodeError::new("not a SQLite database"));
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size => size as usize,
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(DecodeError::new("invalid page size"));
+        }
+        if u32::from_be_bytes([data[56], data[57], data[58], data[59]]) > 1 {
+            return Err(DecodeError::new("only UTF-8 databases are supported"));
+        }
+        let usable = page_size - data[20] as usize;
+        Ok(Self {
+            data,
+            page_size,
+            usable,
+        })
+    }
+
+    /// The ordinary tables of the database, in schema order. Internal
+    /// `sqlite_` tables and `WITHOUT ROWID` tables are left out.
+    pub fn tables(&self) -> DecodeResult<Vec<Table>> {
+        let schema = Table {
+            name: "sqlite_schema".to_string(),
+            columns: Vec::new(),
+            root: 1,
+        };
+        let mut tables = Vec::new();
+        for row in self.rows(&schema) {
+            let row = row?;
+            let text = |i: usize| match row.get(i) {
+                Some(Node::Text(text)) => Some(text.as_str()),
+                _ => None,
+            };
+            let (Some("table"), Some(name), Some(sql)) =
+                (text(0), text(1), text(4))
+            else {
+                continue;
+            };
+            let root = match row.get(3) {
+                Some(Node::Int(root)) if *root > 0 => *root as usize,
+                _ => continue,
+            };
+            if name.starts_with("sqlite_")
+                || sql.to_ascii_uppercase().contains("WITHOUT ROWID")
+            {
+                continue;
+            }
+            tables.push(Table {
+                name: name.to_string(),
+                columns: parse_columns(sql)?,
+                root,
+            });
+        }
+        Ok(tables)
+    }
+
+    /// The rows of `table` in rowid order, one value per column.
+    pub fn rows<'a>(&'a self, table: &'a Table) -> Rows<'a> {
+        Rows {
+            db: self,
+            table,
+            stack: vec![(table.root, 0)],
+        }
+    }
+
+    fn page(&self, number: usize) -> DecodeResult<&[u8]> {
+        let start = (number.checked_sub(1))
+            .ok_or_else(|| DecodeError::new("page 0 referenced"))?
+            * self.page_size;
+        self.data.get(start..start + self.page_size).ok_or_else(|| {
+            DecodeError::new(format!("page {} is missing", number))
+        })
+    }
+
+    /// Offset of the b-tree header in a page; page 1 starts with the file
+    /// header.
+    fn header_offset(number: usize) -> usize {
+        if number == 1 {
+            100
+        } else {
+            0
+        }
+    }
+
+    /// The payload of a leaf cell at `offset`, following overflow pages.
+    fn payload(&self, page: &[u8], offset: usize) -> DecodeResult<Vec<u8>> {
+        let mut pos = offset;
+        let size = varint(page, &mut pos)? as usize;
+        varint(page, &mut pos)?; // rowid
+        let max_local = self.usable - 35;
+        let min_local = (self.usable - 12) * 32 / 255 - 23;
+        let local = if size <= max_local {
+            size
+        } else {
+            let k = min_local + (size - min_local) % (self.usable - 4);
+            if k <= max_local {
+                k
+            } else {
+                min_local
+            }
+        };
+        let mut payload = page
+            .get(pos..pos + local)
+            .ok_or_else(|| DecodeError::new("cell runs past its page"))?
+            .to_vec();
+        let mut next = if local < size {
+            be_u32(page, pos + local)? as usize
+        } else {
+            0
+        };
+        while payload.len() < size {
+            if next == 0 {
+                return Err(DecodeError::new("overflow chain ends early"));
+            }
+            let overflow = self.page(next)?;
+            next = be_u32(overflow, 0)? as usize;
+            let take = (size - payload.len()).min(self.usable - 4);
+            payload.extend_from_slice(&overflow[4..4 + take]);
+        }
+        Ok(payload)
+    }
+}
+
+/// Iterator over the rows of a table, walking its b-tree depth first.
+pub struct Rows<'a> {
+    db: &'a Database,
+    table: &'a Table,
+    /// Pages being walked and the next cell of each.
+    stack: Vec<(usize, usize)>,
+}
+
+impl Rows<'_> {
+    fn next_row(&mut self) -> DecodeResult<Option<Vec<Node>>> {
+        while let Some((number, cell)) = self.stack.pop() {
+            if self.stack.len() > MAX_DEPTH {
+                return Err(DecodeError::new("b-tree nested too deeply"));
+            }
+            let page = self.db.page(number)?;
+            let header = Database::header_offset(number);
+            let kind = *page
+                .get(header)
+                .ok_or_else(|| DecodeError::new("page header missing"))?;
+            let cells = be_u16(page, header + 3)? as usize;
+            let pointers = header
+                + match kind {
+                    INTERIOR_TABLE_PAGE => 12,
+                    LEAF_TABLE_PAGE => 8,
+                    _ => {
+                        return Err(DecodeError::new(format!(
+                            "page {} is not a table page",
+                            number
+                        )))
+                    }
+                };
+            // Interior pages go on to the rightmost child after their cells.
+            if cell > cells || (cell == cells && kind == LEAF_TABLE_PAGE) {
+                continue;
+            }
+            self.stack.push((number, cell + 1));
+            if cell == cells {
+                let child = be_u32(page, header + 8)? as usize;
+                self.stack.push((child, 0));
+                continue;
+            }
+            let offset = be_u16(page, pointers + 2 * cell)? as usize;
+            if kind == INTERIOR_TABLE_PAGE {
+                let child = be_u32(page, offset)? as usize;
+                self.stack.push((child, 0));
+                continue;
+            }
+            let mut pos = offset;
+            varint(page, &mut pos)?;
+            let rowid = varint(page, &mut pos)? as i64;
+            let payload = self.db.payload(page, offset)?;
+            let mut row = record(&payload)?;
+            // Columns added later are missing from older rows.
+            row.resize(row.len().max(self.table.columns.len()), Node::Null);
+            for (value, column) in row.iter_mut().zip(&self.table.columns) {
+                if column.rowid && *value == Node::Null {
+                    *value = Node::Int(rowid as i128);
+                }
+            }
+            return Ok(Some(row));
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = DecodeResult<Vec<Node>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_row() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                self.stack.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Decodes a record: a header of serial types, then the values.
+fn record(payload: &[u8]) -> DecodeResult<Vec<Node>> {
+    let mut pos = 0;
+    let header_size = varint(payload, &mut pos)? as usize;
+    let mut types = Vec::new();
+    while pos < header_size {
+        types.push(varint(payload, &mut pos)?);
+    }
+    let mut pos = header_size;
+    let mut take = |n: usize| {
+        let bytes = payload
+            .get(pos..pos + n)
+            .ok_or_else(|| DecodeError::new("record runs past its payload"));
+        pos += n;
+        bytes
+    };
+    let int = |bytes: &[u8]| {
+        // Sign-extend from the first byte.
+        let first = (bytes[0] as i8) as i64;
+        let n = bytes[1..].iter().fold(first, |n, &b| (n << 8) | b as i64);
+        Node::Int(n as i128)
+    };
+    types
+        .into_iter()
+        .map(|serial| {
+            Ok(match serial {
+                0 => Node::Null,
+                1..=4 => int(take(serial as usize)?),
+                5 => int(take(6)?),
+                6 => int(take(8)?),
+                7 => {
+                    let bytes = take(8)?;
+                    Node::Float(f64::from_be_bytes(
+                        bytes.try_into().unwrap_or_default(),
+                    ))
+                }
+                8 => Node::Int(0),
+                9 => Node::Int(1),
+                n if n >= 12 && n % 2 == 0 => {
+                    Node::Bytes(take((n as usize - 12) / 2)?.to_vec())
+                }
+                n if n >= 13 => Node::Text(
+                    String::from_utf8_lossy(take((n as usize - 13) / 2)?)
+                        .into_owned(),
+                ),
+                n => return Err(DecodeError::new(format!("serial type {}", n))),
+            })
+        })
+        .collect()
+}
+
+/// Column names of a `CREATE TABLE` statement, in order.
+fn parse_columns(sql: &str) -> DecodeResult<Vec<Column>> {
+    let start = sql
+        .find('(')
+        .ok_or_else(|| DecodeError::new(format!("no columns in '{}'", sql)))?;
+    let end = sql.rfind(')').unwrap_or(sql.len());
+    let mut columns = Vec::new();
+    for definition in split_top_level(&sql[start + 1..end]) {
+        let definition = definition.trim();
+        let upper = definition.to_ascii_uppercase();
+        let first = upper.split_whitespace().next().unwrap_or_default();
+        if matches!(
+            first,
+            "CONSTRAINT" | "PRIMARY" | "UNIQUE" | "CHECK" | "FOREIGN"
+        ) {
+            continue;
+        }
+        let (name, rest) = identifier(definition);
+        let rest = rest.to_ascii_uppercase();
+        let words: Vec<&str> = rest
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let rowid = words.first() == Some(&"INTEGER")
+            && words.windows(2).any(|w| w == ["PRIMARY", "KEY"])
+            && !words.contains(&"DESC");
+        columns.push(Column { name, rowid });
+    }
+    Ok(columns)
+}
+
+/// Splits at commas outside parentheses and quotes.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match (c, quote) {
+            (q, Some(open)) if q == open => quote = None,
+            (_, Some(_)) => {}
+            ('\'' | '"' | '`', None) => quote = Some(c),
+            ('[', None) => quote = Some(']'),
+            ('(', None) => depth += 1,
+            (')', None) => depth -= 1,
+            (',', None) if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// The leading identifier of `text`, unquoted, and what follows it.
+fn identifier(text: &str) -> (String, &str) {
+    let close = match text.chars().next() {
+        Some('"') => '"',
+        Some('`') => '`',
+        Some('[') => ']',
+        Some('\'') => '\'',
+        _ => {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            return (text[..end].to_string(), &text[end..]);
+        }
+    };
+    let mut name = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == close {
+            // Doubled quotes stand for the quote itself.
+            if close != ']'
+                && chars.peek().is_some_and(|&(_, next)| next == close)
+            {
+                chars.next();
+            } else {
+                return (name, &text[i + 1..]);
+            }
+        }
+        name.push(c);
+    }
+    (name, "")
+}
+
+fn varint(bytes: &[u8], pos: &mut usize) -> DecodeResult<u64> {
+    let mut n = 0u64;
+    for i in 0..9 {
+        let b = *bytes
+            .get(*pos)
+            .ok_or_else(|| DecodeError::new("varint runs past its page"))?;
+        *pos += 1;
+        if i == 8 {
+            return Ok((n << 8) | b as u64);
+        }
+        n = (n << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(n)
+}
+
+fn be_u16(bytes: &[u8], pos: usize) -> DecodeResult<u16> {
+    bytes
+        .get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| DecodeError::new("page is cut off"))
+}
+
+fn be_u32(bytes: &[u8], pos: usize) -> DecodeResult<u32> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| DecodeError::new("page is cut off"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Made by sqlite3 with 512-byte pages: `odd "name"` has 40 rows over
+    /// an interior page, `blobs` a 2000-byte value over four overflow
+    /// pages and a column added by `ALTER TABLE`.
+    const FIXTURE: &[u8] = include_bytes!("../tests/data/import.sqlite");
+
+    fn rows(db: &Database, table: &Table) -> Vec<Vec<Node>> {
+        db.rows(table).collect::<DecodeResult<_>>().unwrap()
+    }
+
+    #[test]
+    fn reads_interior_and_overflow_pages() {
+        let db = Database::parse(FIXTURE.to_vec()).unwrap();
+        let tables = db.tables().unwrap();
+        let names: Vec<_> = tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["odd \"name\"", "blobs"]);
+        let columns: Vec<_> =
+            tables[0].columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(columns, ["id", "label", "score"]);
+
+        let odd = rows(&db, &tables[0]);
+        assert_eq!(odd.len(), 40);
+        for (i, row) in (1..).zip(&odd) {
+            assert_eq!(
+                row,
+                &[
+                    Node::Int(i),
+                    Node::Text(format!("row {}", i)),
+                    // SQLite stores whole REALs as integers.
+                    if i % 2 == 0 {
+                        Node::Int(i / 2)
+                    } else {
+                        Node::Float(i as f64 * 0.5)
+                    },
+                ]
+            );
+        }
+
+        let big: Vec<u8> = (0..2000).map(|i| b'A' + (i % 26) as u8).collect();
+        let text = |s: &str| Node::Text(s.to_string());
+        assert_eq!(
+            rows(&db, &tables[1]),
+            [
+                vec![text("big"), Node::Bytes(big), Node::Null],
+                vec![text("small"), Node::Bytes(vec![0, 0xff]), Node::Null],
+                vec![text("neg"), Node::Int(-129), Node::Int(5)],
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        // The last page holds only the skipped WITHOUT ROWID table.
+        for len in (0..FIXTURE.len() - 512).step_by(7) {
+            let Ok(db) = Database::parse(FIXTURE[..len].to_vec()) else {
+                continue;
+            };
+            let read = db.tables().and_then(|tables| {
+                tables.iter().try_for_each(|table| {
+                    db.rows(table).try_for_each(|row| row.map(drop))
+                })
+            });
+            assert!(read.is_err(), "{} bytes", len);
+        }
+        assert!(Database::parse(FIXTURE[..99].to_vec()).is_err());
+    }
+
+    #[test]
+    fn corrupt_pages_are_errors() {
+        // A child pointer back to the interior page itself.
+        let mut looped = FIXTURE.to_vec();
+        let interior = 512;
+        looped[interior + 8..interior + 12].copy_from_slice(&2u32.to_be_bytes());
+        let db = Database::parse(looped).unwrap();
+        let table = db.tables().unwrap().remove(0);
+        assert!(db.rows(&table).any(|row| row.is_err()));
+
+        // An overflow chain cut short.
+        let mut cut = FIXTURE.to_vec();
+        let overflow = 7 * 512;
+        cut[overflow..overflow + 4].copy_from_slice(&[0; 4]);
+        let db = Database::parse(cut).unwrap();
+        let table = db.tables().unwrap().remove(1);
+        assert!(db.rows(&table).next().unwrap().is_err());
+
+        assert!(record(&[3, 1, 2, 7]).is_err());
+        assert!(record(&[2, 10]).is_err());
+        assert!(record(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn decodes_varints_and_records() {
+        let decode = |bytes: &[u8]| varint(bytes, &mut 0);
+        assert_eq!(decode(&[0x7f]).unwrap(), 0x7f);
+        assert_eq!(decode(&[0x81, 0x00]).unwrap(), 0x80);
+        assert_eq!(decode(&[0xff; 9]).unwrap(), u64::MAX);
+        assert!(decode(&[0x81]).is_err());
+        assert_eq!(
+            record(&[5, 1, 8, 9, 0x17, 0xfe, b'h', b'i', b'!', b'?', b'.'])
+                .unwrap(),
+            [
+                Node::Int(-2),
+                Node::Int(0),
+                Node::Int(1),
+                Node::Text("hi!?.".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use crate::decoder::{self, Decoder};
+use crate::layout::{LayoutMode, SPLIT_RANGE};
+use crate::node::Node;
+use crate::{toml, AppError, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Databases kept in the list of recent ones.
+pub const MAX_RECENT: usize = 10;
+
+/// Preferences changed in the TUI and kept for the next session in
+/// `state.toml`, see `State::default_path`. Unlike the config file it is
+/// written by redb-tui, so comments in it are not kept.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct State {
+    /// Share of the screen taken by the table list, in percent.
+    pub split: Option<u16>,
+    pub layout: Option<LayoutMode>,
+    /// Databases opened lately, most recent first.
+    pub recent: Vec<PathBuf>,
+    /// Where each of the recent databases was left.
+    pub sessions: Vec<(PathBuf, Session)>,
+}
+
+/// Where a database was left when it was closed, to pick up from when it
+/// is opened again.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub table: Option<String>,
+    /// Index of the selected entry of that table.
+    pub entry: usize,
+    pub table_filter: Option<String>,
+    /// Key range of the selected table, as typed.
+    pub key_range: Option<String>,
+    /// Whether entries were listed from the last key down.
+    pub reverse: bool,
+    /// Whether compressed values were unpacked before decoding.
+    pub decompress: bool,
+    /// Decoders chosen per table.
+    pub key_decoders: BTreeMap<String, Decoder>,
+    pub value_decoders: BTreeMap<String, Decoder>,
+    /// Protobuf message types chosen per table.
+    pub proto_messages: BTreeMap<String, String>,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// A key bookmarked to jump back to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub table: String,
+    pub key: Vec<u8>,
+    /// The key as shown in the entries pane when it was bookmarked.
+    pub label: String,
+}
+
+impl State {
+    /// `$XDG_STATE_HOME/redb-tui/state.toml`, falling back to
+    /// `~/.local/state/redb-tui/state.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".local").join("state"))
+            })?;
+        Some(dir.join("redb-tui").join("state.toml"))
+    }
+
+    /// Reads the state file; a missing file yields an empty state.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| {
+            AppError::ConfigError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let doc = toml::parse(text).map_err(|e| e.to_string())?;
+        let split = match toml::get(&doc, "split") {
+            None => None,
+            Some(split) => {
+                Some(parse_split(split).map_err(|e| format!("split: {}", e))?)
+            }
+        };
+        let layout = match toml::get(&doc, "layout") {
+            None => None,
+            Some(mode) => {
+                Some(parse_layout(mode).map_err(|e| format!("layout: {}", e))?)
+            }
+        };
+        let recent = match toml::get(&doc, "recent") {
+            None => Vec::new(),
+            Some(Node::Array(paths)) => paths
+                .iter()
+                .map(|path| match path {
+                    Node::Text(path) => Ok(PathBuf::from(path)),
+                    _ => Err("recent: expected a list of paths".to_string()),
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            Some(_) => return Err("recent: expected a list of paths".to_string()),
+        };
+        let sessions = match toml::get(&doc, "sessions") {
+            None => Vec::new(),
+            Some(Node::Array(sessions)) => sessions
+                .iter()
+                .map(|session| {
+                    Session::parse(session)
+                        .map_err(|e| format!("sessions: {}", e))
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            Some(_) => return Err("sessions: expected a list".to_string()),
+        };
+        Ok(Self {
+            split,
+            layout,
+            recent,
+            sessions,
+        })
+    }
+
+    /// Reads the state file, changes it with `f` and writes it back, so
+    /// what other sessions saved meanwhile is kept.
+    pub fn update(path: &Path, f: impl FnOnce(&mut Self)) -> Result<()> {
+        let mut state = Self::load(path)?;
+        f(&mut state);
+        state.save(path)
+    }
+
+    /// Moves `db_path` to the top of the recent databases. The sessions of
+    /// databases dropped from the list go with them.
+    pub fn remember(&mut self, db_path: &Path) {
+        let db_path = canonical(db_path);
+        self.recent.retain(|path| *path != db_path);
+        self.recent.insert(0, db_path);
+        self.recent.truncate(MAX_RECENT);
+        let recent = &self.recent;
+        self.sessions.retain(|(path, _)| recent.contains(path));
+    }
+
+    /// Where the database at `db_path` was left, if it is a recent one.
+    pub fn session(&self, db_path: &Path) -> Option<&Session> {
+        let db_path = canonical(db_path);
+        self.sessions
+            .iter()
+            .find_map(|(path, session)| (*path == db_path).then_some(session))
+    }
+
+    /// Keeps `bookmarks` for the database at `db_path`, leaving the rest of
+    /// its session as it is.
+    pub fn set_bookmarks(&mut self, db_path: &Path, bookmarks: Vec<Bookmark>) {
+        let mut session = self.session(db_path).cloned().unwrap_or_default();
+        session.bookmarks = bookmarks;
+        self.set_session(db_path, session);
+    }
+
+    /// Keeps `session` for the database at `db_path`.
+    pub fn set_session(&mut self, db_path: &Path, session: Session) {
+        let db_path = canonical(db_path);
+        self.sessions.retain(|(path, _)| *path != db_path);
+        self.sessions.push((db_path, session));
+    }
+
+    /// Writes the state file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut doc = Vec::new();
+        if let Some(split) = self.split {
+            doc.push((Node::Text("split".into()), Node::Int(split.into())));
+        }
+        if let Some(layout) = self.layout {
+            doc.push((
+                Node::Text("layout".into()),
+                Node::Text(layout.name().into()),
+            ));
+        }
+        if !self.recent.is_empty() {
+            doc.push((
+                Node::Text("recent".into()),
+                Node::Array(
+                    self.recent
+                        .iter()
+                        .map(|path| Node::Text(path.display().to_string()))
+                        .collect(),
+                ),
+            ));
+        }
+        if !self.sessions.is_empty() {
+            doc.push((
+                Node::Text("sessions".into()),
+                Node::Array(
+                    self.sessions
+                        .iter()
+                        .map(|(path, session)| session.to_node(path))
+                        .collect(),
+                ),
+            ));
+        }
+        fs::write(path, toml::to_string(&Node::Map(doc)))?;
+        Ok(())
+    }
+}
+
+impl Session {
+    /// Reads a session and the path of its database from an inline table.
+    fn parse(node: &Node) -> std::result::Result<(PathBuf, Self), String> {
+        let path = toml::get_str(node, "path").ok_or("expected a path")?;
+        let text = |key: &str| match toml::get(node, key) {
+            None => Ok(None),
+            Some(Node::Text(text)) => Ok(Some(text.clone())),
+            Some(_) => Err(format!("{}: expected a string", key)),
+        };
+        let entry = match toml::get(node, "entry") {
+            None => 0,
+            Some(Node::Int(n)) => usize::try_from(*n)
+                .map_err(|_| format!("entry: expected a row, got {}", n))?,
+            Some(_) => return Err("entry: expected a number".to_string()),
+        };
+        let reverse = match toml::get(node, "reverse") {
+            None => false,
+            Some(Node::Bool(reverse)) => *reverse,
+            Some(_) => return Err("reverse: expected true or false".to_string()),
+        };
+        let decompress = match toml::get(node, "decompress") {
+            None => false,
+            Some(Node::Bool(decompress)) => *decompress,
+            Some(_) => {
+                return Err("decompress: expected true or false".to_string())
+            }
+        };
+        let names = |key: &str| match toml::get(node, key) {
+            None => Ok(BTreeMap::new()),
+            Some(map @ Node::Map(_)) => toml::entries(map)
+                .map(|(table, name)| match name {
+                    Node::Text(name) => Ok((table.to_string(), name.clone())),
+                    _ => Err(format!("{}.{}: expected a string", key, table)),
+                })
+                .collect(),
+            Some(_) => Err(format!("{}: expected a table", key)),
+        };
+        let decoders = |key: &str| {
+            names(key)?
+                .into_iter()
+                .map(|(table, name)| match Decoder::from_name(&name) {
+                    Some(decoder) => Ok((table, decoder)),
+                    None => Err(format!(
+                        "{}.{}: unknown decoder '{}'",
+                        key, table, name
+                    )),
+                })
+                .collect::<std::result::Result<_, String>>()
+        };
+        let bookmarks = match toml::get(node, "bookmarks") {
+            None => Vec::new(),
+            Some(Node::Array(bookmarks)) => bookmarks
+                .iter()
+                .map(|bookmark| {
+                    Bookmark::parse(bookmark)
+                        .map_err(|e| format!("bookmarks: {}", e))
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            Some(_) => return Err("bookmarks: expected a list".to_string()),
+        };
+        let session = Self {
+            table: text("table")?,
+            entry,
+            table_filter: text("table_filter")?,
+            key_range: text("key_range")?,
+            reverse,
+            decompress,
+            key_decoders: decoders("key_decoders")?,
+            value_decoders: decoders("value_decoders")?,
+            proto_messages: names("proto_messages")?,
+            bookmarks,
+        };
+        Ok((PathBuf::from(path), session))
+    }
+
+    /// The session as an inline table, along with the path of its database.
+    fn to_node(&self, path: &Path) -> Node {
+        let text = |s: &str| Node::Text(s.to_string());
+        let mut node = vec![(text("path"), text(&path.display().to_string()))];
+        let texts = [
+            ("table", &self.table),
+            ("table_filter", &self.table_filter),
+            ("key_range", &self.key_range),
+        ];
+        for (key, value) in texts {
+            if let Some(value) = value {
+                node.push((text(key), text(value)));
+            }
+        }
+        if self.entry > 0 {
+            node.push((text("entry"), Node::Int(self.entry as i128)));
+        }
+        if self.reverse {
+            node.push((text("reverse"), Node::Bool(true)));
+        }
+        if self.decompress {
+            node.push((text("decompress"), Node::Bool(true)));
+        }
+        for (key, decoders) in [
+            ("key_decoders", &self.key_decoders),
+            ("value_decoders", &self.value_decoders),
+        ] {
+            if !decoders.is_empty() {
+                let map = decoders
+                    .iter()
+                    .map(|(table, decoder)| (text(table), text(decoder.label())))
+                    .collect();
+                node.push((text(key), Node::Map(map)));
+            }
+        }
+        if !self.proto_messages.is_empty() {
+            let map = self
+                .proto_messages
+                .iter()
+                .map(|(table, message)| (text(table), text(message)))
+                .collect();
+            node.push((text("proto_messages"), Node::Map(map)));
+        }
+        if !self.bookmarks.is_empty() {
+            let bookmarks =
+                self.bookmarks.iter().map(Bookmark::to_node).collect();
+            node.push((text("bookmarks"), Node::Array(bookmarks)));
+        }
+        Node::Map(node)
+    }
+}
+
+impl Bookmark {
+    /// Reads a bookmark from an inline table, with its key in hex.
+    fn parse(node: &Node) -> std::result::Result<Self, String> {
+        let field = |key: &str| {
+            toml::get_str(node, key)
+                .ok_or_else(|| format!("{}: expected a string", key))
+        };
+        let key = decoder::parse_hex(field("key")?)
+            .map_err(|e| format!("key: {}", e))?;
+        Ok(Self {
+            table: field("table")?.to_string(),
+            key,
+            label: field("label")?.to_string(),
+        })
+    }
+
+    fn to_node(&self) -> Node {
+        let text = |s: &str| Node::Text(s.to_string());
+        Node::Map(vec![
+            (text("table"), text(&self.table)),
+            (text("key"), text(&decoder::hex(&self.key))),
+            (text("label"), text(&self.label)),
+        ])
+    }
+}
+
+/// `path` made absolute, so a database is known by one name wherever
+/// redb-tui is started from.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Reads a split percentage within `SPLIT_RANGE`.
+pub fn parse_split(node: &Node) -> std::result::Result<u16, String> {
+    match node {
+        Node::Int(n) => u16::try_from(*n)
+            .ok()
+            .filter(|n| SPLIT_RANGE.contains(n))
+            .ok_or_else(|| {
+                format!(
+                    "expected {} to {} percent, got {}",
+                    SPLIT_RANGE.start(),
+                    SPLIT_RANGE.end(),
+                    n
+                )
+            }),
+        _ => Err("expected a number".to_string()),
+    }
+}
+
+/// Reads a layout mode, `horizontal` or `vertical`.
+pub fn parse_layout(node: &Node) -> std::result::Result<LayoutMode, String> {
+    match node {
+        Node::Text(name) => LayoutMode::from_name(name).ok_or_else(|| {
+            format!("expected horizontal or vertical, got '{}'", name)
+        }),
+        _ => Err("expected a string".to_string()),
+    }
+}
@@ -0,0 +1,135 @@
+use crate::node::Node;
+use crate::toml;
+use ratatui::style::Color;
+use std::env;
+
+/// Colors of the TUI, from a preset with single colors overridden in the
+/// `[theme]` section of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Whether the colors are made for a light background.
+    pub light: bool,
+    /// Draw borders, scrollbars and gauges in plain ASCII, for limited
+    /// terminals and serial consoles; see `--ascii`.
+    pub ascii: bool,
+    /// Text of the table list.
+    pub text: Color,
+    /// Text and background of the selected table or entry.
+    pub selected_fg: Color,
+    pub selected_bg: Color,
+    /// Headings, prompts, search matches and the status bar.
+    pub accent: Color,
+    /// Errors under prompts.
+    pub error: Color,
+    /// Filled part of progress gauges.
+    pub gauge: Color,
+}
+
+impl Theme {
+    /// For terminals with a dark background.
+    pub const DARK: Theme = Theme {
+        light: false,
+        ascii: false,
+        text: Color::White,
+        selected_fg: Color::Black,
+        selected_bg: Color::LightGreen,
+        accent: Color::Yellow,
+        error: Color::Red,
+        gauge: Color::LightGreen,
+    };
+
+    /// For terminals with a light background.
+    pub const LIGHT: Theme = Theme {
+        light: true,
+        ascii: false,
+        text: Color::Black,
+        selected_fg: Color::White,
+        selected_bg: Color::Green,
+        accent: Color::Blue,
+        error: Color::Red,
+        gauge: Color::Green,
+    };
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::DARK),
+            "light" => Some(Self::LIGHT),
+            _ => None,
+        }
+    }
+
+    /// Preset matching the terminal background, as far as it is known from
+    /// `COLORFGBG` (`15;0` for white on black), which rxvt, Konsole and
+    /// some other terminals set.
+    pub fn detect() -> Option<Self> {
+        let colors = env::var("COLORFGBG").ok()?;
+        let background: u8 = colors.rsplit(';').next()?.parse().ok()?;
+        // Light gray, white and the bright colors but blue.
+        match background {
+            7 | 9..=15 => Some(Self::LIGHT),
+            _ => Some(Self::DARK),
+        }
+    }
+
+    /// The preset for the other kind of background, drawn the same way.
+    pub fn opposite(&self) -> Self {
+        let preset = if self.light { Self::DARK } else { Self::LIGHT };
+        Self {
+            ascii: self.ascii,
+            ..preset
+        }
+    }
+
+    /// Reads the `[theme]` section of the config file: a `preset`, detected
+    /// from the terminal or `dark` by default, and any of the colors by
+    /// field name. Colors are names (`yellow`, `lightgreen`), 256-color
+    /// indexes (`208`) or truecolor hex values (`#ff8800`). `ascii = true`
+    /// does what `--ascii` does:
+    ///
+    /// ```toml
+    /// [theme]
+    /// preset = "light"
+    /// accent = "#ff8800"
+    /// ```
+    pub fn parse(section: &Node) -> Result<Self, String> {
+        let mut theme = match toml::get_str(section, "preset") {
+            None => Self::default(),
+            Some(name) => Self::preset(name).ok_or_else(|| {
+                format!("theme.preset: unknown preset '{}'", name)
+            })?,
+        };
+        for (name, value) in toml::entries(section) {
+            let field = match (name, value) {
+                ("preset", _) => continue,
+                ("ascii", Node::Bool(ascii)) => {
+                    theme.ascii = *ascii;
+                    continue;
+                }
+                ("ascii", _) => {
+                    return Err("theme.ascii: expected true or false".into())
+                }
+                ("text", _) => &mut theme.text,
+                ("selected_fg", _) => &mut theme.selected_fg,
+                ("selected_bg", _) => &mut theme.selected_bg,
+                ("accent", _) => &mut theme.accent,
+                ("error", _) => &mut theme.error,
+                ("gauge", _) => &mut theme.gauge,
+                _ => return Err(format!("theme.{}: unknown color", name)),
+            };
+            *field = match value {
+                Node::Text(color) => color.parse().map_err(|_| {
+                    format!("theme.{}: unknown color '{}'", name, color)
+                })?,
+                _ => return Err(format!("theme.{}: expected a string", name)),
+            };
+        }
+        Ok(theme)
+    }
+}
+
+/// The detected preset, or `Theme::DARK`.
+impl Default for Theme {
+    fn default() -> Self {
+        Self::detect().unwrap_or(Self::DARK)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the epoch of 2100-01-01; later instants are assumed to be
+/// a misread unit or byte order rather than a real timestamp.
+const MAX_PLAUSIBLE_SECS: i128 = 4_102_444_800;
+
+/// Resolution of a unix timestamp, inferred from its magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl Unit {
+    fn infer(value: i128) -> Self {
+        match value.unsigned_abs() {
+            0..100_000_000_000 => Unit::Seconds,
+            100_000_000_000..100_000_000_000_000 => Unit::Millis,
+            100_000_000_000_000..100_000_000_000_000_000 => Unit::Micros,
+            _ => Unit::Nanos,
+        }
+    }
+
+    fn per_second(self) -> i128 {
+        match self {
+            Unit::Seconds => 1,
+            Unit::Millis => 1_000,
+            Unit::Micros => 1_000_000,
+            Unit::Nanos => 1_000_000_000,
+        }
+    }
+
+    fn digits(self) -> usize {
+        match self {
+            Unit::Seconds => 0,
+            Unit::Millis => 3,
+            Unit::Micros => 6,
+            Unit::Nanos => 9,
+        }
+    }
+}
+
+/// Renders a 4/8-byte integer as an RFC 3339 time with the distance from
+/// now, e.g. `2024-05-01T12:00:00Z (3d ago)`. Builtin integer types are read
+/// as stored; for opaque bytes the byte order giving a plausible date wins.
+pub fn decode(type_name: &str, bytes: &[u8]) -> DecodeResult<String> {
+    let candidates = candidates(type_name, bytes)?;
+    let value = candidates
+        .iter()
+        .copied()
+        .find(|v| plausible(*v))
+        .unwrap_or(candidates[0]);
+    Ok(format(value, now_secs()))
+}
+
+fn candidates(type_name: &str, bytes: &[u8]) -> DecodeResult<Vec<i128>> {
+    macro_rules! le {
+        ($t:ty) => {
+            bytes
+                .try_into()
+                .map(|b| vec![<$t>::from_le_bytes(b) as i128])
+                .map_err(|_| DecodeError::new("width mismatch"))
+        };
+    }
+    match type_name {
+        "u32" => le!(u32),
+        "i32" => le!(i32),
+        "u64" => le!(u64),
+        "i64" => le!(i64),
+        _ => match bytes.len() {
+            4 => {
+                let b = bytes.try_into().unwrap();
+                Ok(vec![
+                    u32::from_le_bytes(b) as i128,
+                    u32::from_be_bytes(b) as i128,
+                ])
+            }
+            8 => {
+                let b = bytes.try_into().unwrap();
+                Ok(vec![
+                    i64::from_le_bytes(b) as i128,
+                    i64::from_be_bytes(b) as i128,
+                ])
+            }
+            n => Err(DecodeError::new(format!("{} bytes", n))),
+        },
+    }
+}
+
+fn plausible(value: i128) -> bool {
+    let secs = value / Unit::infer(value).per_second();
+    (0..MAX_PLAUSIBLE_SECS).contains(&secs)
+}
+
+fn now_secs() -> i128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i128)
+        .unwrap_or_default()
+}
+
+fn format(value: i128, now: i128) -> String {
+    let unit = Unit::infer(value);
+    let secs = value.div_euclid(unit.per_second());
+    let fraction = value.rem_euclid(unit.per_second());
+    let mut out = rfc3339(secs);
+    if unit != Unit::Seconds {
+        // Insert the fraction before the trailing `Z`.
+        out.pop();
+        out.push_str(&format!(".{:0width$}Z", fraction, width = unit.digits()));
+    }
+    format!("{} ({})", out, relative(secs - now))
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` for seconds since the unix epoch.
+fn rfc3339(secs: i128) -> String {
+    let days = secs.div_euclid(86_400);
+    let time = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Gregorian date of a day count since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`).
+fn civil_from_days(days: i128) -> (i128, i128, i128) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Coarse distance from now, e.g. `5m ago` or `in 2d`.
+fn relative(delta: i128) -> String {
+    let abs = delta.unsigned_abs();
+    let amount = match abs {
+        0..60 => return "now".to_string(),
+        60..3600 => format!("{}m", abs / 60),
+        3600..86_400 => format!("{}h", abs / 3600),
+        86_400..31_536_000 => format!("{}d", abs / 86_400),
+        _ => format!("{}y", abs / 31_536_000),
+    };
+    if delta < 0 {
+        format!("{} ago", amount)
+    } else {
+        format!("in {}", amount)
+    }
+}
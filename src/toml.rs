@@ -0,0 +1,619 @@
+use crate::node::{write_quoted, Node};
+use thiserror::Error;
+
+/// Failure to parse a TOML document.
+#[derive(Error, Debug)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+/// Parses the subset of TOML used by redb-tui's configuration files:
+/// tables, arrays of tables, dotted keys, strings, numbers, booleans,
+/// arrays and inline tables. Dates are not supported.
+///
+/// The document is returned as a `Node::Map` with keys in file order.
+pub fn parse(input: &str) -> ParseResult<Node> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.document()
+}
+
+/// Looks up `key` in a `Node::Map`.
+pub fn get<'a>(node: &'a Node, key: &str) -> Option<&'a Node> {
+    node.get(key)
+}
+
+/// Looks up `key` in a `Node::Map` and returns it if it is a string.
+pub fn get_str<'a>(node: &'a Node, key: &str) -> Option<&'a str> {
+    match get(node, key) {
+        Some(Node::Text(s)) => Some(s),
+        _ => None,
+    }
+}
+
+/// Iterates over the `(name, value)` pairs of a `Node::Map`.
+pub fn entries(node: &Node) -> impl Iterator<Item = (&str, &Node)> {
+    let entries = match node {
+        Node::Map(entries) => entries.as_slice(),
+        _ => &[],
+    };
+    entries.iter().filter_map(|(k, v)| match k {
+        Node::Text(k) => Some((k.as_str(), v)),
+        _ => None,
+    })
+}
+
+/// Serializes a `Node::Map` as a TOML document. Nested maps become `[a.b]`
+/// tables; maps inside arrays are written inline. Values TOML can't
+/// represent (null, bytes, tags) are skipped.
+pub fn to_string(doc: &Node) -> String {
+    let mut out = String::new();
+    write_table(&mut out, &mut Vec::new(), doc);
+    out
+}
+
+fn write_table(out: &mut String, path: &mut Vec<String>, table: &Node) {
+    let is_table = |v: &Node| matches!(v, Node::Map(_));
+    let scalars: Vec<_> = entries(table)
+        .filter(|(_, v)| !is_table(v) && is_representable(v))
+        .collect();
+    if !path.is_empty() && (!scalars.is_empty() || entries(table).count() == 0) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let header: Vec<_> = path.iter().map(|k| toml_key(k)).collect();
+        out.push_str(&format!("[{}]\n", header.join(".")));
+    }
+    for (key, value) in scalars {
+        out.push_str(&format!("{} = {}\n", toml_key(key), toml_value(value)));
+    }
+    for (key, value) in entries(table).filter(|(_, v)| is_table(v)) {
+        path.push(key.to_string());
+        write_table(out, path, value);
+        path.pop();
+    }
+}
+
+fn is_representable(value: &Node) -> bool {
+    match value {
+        Node::Bool(_) | Node::Int(_) | Node::Float(_) | Node::Text(_) => true,
+        Node::Array(items) => items.iter().all(is_representable),
+        Node::Map(entries) => entries.iter().all(|(_, v)| is_representable(v)),
+        _ => false,
+    }
+}
+
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml_value(&Node::Text(key.to_string()))
+    }
+}
+
+fn toml_value(value: &Node) -> String {
+    match value {
+        Node::Text(s) => {
+            let mut out = String::new();
+            let _ = write_quoted(&mut out, s);
+            out
+        }
+        Node::Float(x) if x.is_nan() => "nan".to_string(),
+        Node::Float(x) if x.is_infinite() => {
+            if *x > 0.0 { "inf" } else { "-inf" }.to_string()
+        }
+        Node::Array(items) => {
+            let items: Vec<_> = items.iter().map(toml_value).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Node::Map(_) => {
+            let items: Vec<_> = entries(value)
+                .map(|(k, v)| format!("{} = {}", toml_key(k), toml_value(v)))
+                .collect();
+            format!("{{ {} }}", items.join(", "))
+        }
+        other => other.to_string(),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        Err(ParseError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> ParseResult<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", c))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => {
+                    self.bump();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> ParseResult<()> {
+        self.skip_spaces();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.error(format!("unexpected '{}'", c)),
+        }
+    }
+
+    fn document(&mut self) -> ParseResult<Node> {
+        let mut root = Node::Map(Vec::new());
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Ok(root),
+                Some('[') => {
+                    self.bump();
+                    let array = self.eat('[');
+                    self.skip_spaces();
+                    let path = self.key_path()?;
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                        let line = self.line;
+                        let items =
+                            table_at(&mut root, &path[..path.len() - 1], line)?;
+                        let last = &path[path.len() - 1];
+                        match entry(items, last) {
+                            Some(Node::Array(items)) => {
+                                items.push(Node::Map(Vec::new()))
+                            }
+                            Some(_) => {
+                                return self
+                                    .error(format!("'{}' is not an array", last))
+                            }
+                            None => insert(
+                                items,
+                                last,
+                                Node::Array(vec![Node::Map(Vec::new())]),
+                            ),
+                        }
+                    } else {
+                        let line = self.line;
+                        table_at(&mut root, &path, line)?;
+                    }
+                    current = path;
+                    self.end_of_line()?;
+                }
+                Some(_) => {
+                    let path = self.key_path()?;
+                    self.expect('=')?;
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    let line = self.line;
+                    let table = table_at(&mut root, &current, line)?;
+                    let table = table_at(table, &path[..path.len() - 1], line)?;
+                    let last = &path[path.len() - 1];
+                    if entry(table, last).is_some() {
+                        return self.error(format!("duplicate key '{}'", last));
+                    }
+                    insert(table, last, value);
+                    self.end_of_line()?;
+                }
+            }
+        }
+    }
+
+    fn key_path(&mut self) -> ParseResult<Vec<String>> {
+        let mut path = vec![self.key()?];
+        loop {
+            self.skip_spaces();
+            if !self.eat('.') {
+                return Ok(path);
+            }
+            self.skip_spaces();
+            path.push(self.key()?);
+        }
+    }
+
+    fn key(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.bump();
+                }
+                if start == self.pos {
+                    return self.error("expected a key");
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> ParseResult<Node> {
+        match self.peek() {
+            Some('"') => Ok(Node::Text(self.basic_string()?)),
+            Some('\'') => Ok(Node::Text(self.literal_string()?)),
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    if self.eat(']') {
+                        return Ok(Node::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_blank();
+                    if !self.eat(',') {
+                        self.skip_blank();
+                        self.expect(']')?;
+                        return Ok(Node::Array(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.bump();
+                let mut table = Node::Map(Vec::new());
+                self.skip_spaces();
+                if self.eat('}') {
+                    return Ok(table);
+                }
+                loop {
+                    self.skip_spaces();
+                    let path = self.key_path()?;
+                    self.expect('=')?;
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    let line = self.line;
+                    let inner =
+                        table_at(&mut table, &path[..path.len() - 1], line)?;
+                    insert(inner, &path[path.len() - 1], value);
+                    self.skip_spaces();
+                    if self.eat('}') {
+                        return Ok(table);
+                    }
+                    self.expect(',')?;
+                }
+            }
+            Some('t' | 'f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Node::Bool(true)),
+                    "false" => Ok(Node::Bool(false)),
+                    _ => self.error(format!("invalid value '{}'", word)),
+                }
+            }
+            Some(_) => {
+                let word = self.word();
+                parse_number(&word).map_or_else(
+                    || self.error(format!("invalid value '{}'", word)),
+                    Ok,
+                )
+            }
+            None => self.error("expected a value"),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-._".contains(c))
+        {
+            self.bump();
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn basic_string(&mut self) -> ParseResult<String> {
+        self.expect('"')?;
+        let multiline = self.starts_with("\"\"");
+        if multiline {
+            self.pos += 2;
+            self.eat('\n');
+        }
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('"') if !multiline => return Ok(out),
+                Some('"') if self.starts_with("\"\"") => {
+                    self.pos += 2;
+                    return Ok(out);
+                }
+                Some('\n') if !multiline => {
+                    return self.error("newline in string")
+                }
+                Some('\\') => match self.bump() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('u') => out.push(self.unicode_escape(4)?),
+                    Some('U') => out.push(self.unicode_escape(8)?),
+                    _ => return self.error("invalid escape"),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, len: usize) -> ParseResult<char> {
+        let digits: String = (0..len).filter_map(|_| self.bump()).collect();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map_or_else(|| self.error("invalid unicode escape"), Ok)
+    }
+
+    fn literal_string(&mut self) -> ParseResult<String> {
+        self.expect('\'')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return self.error("unterminated string"),
+                Some('\'') => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+}
+
+fn parse_number(word: &str) -> Option<Node> {
+    let clean = word.replace('_', "");
+    let (sign, digits) = match clean.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, clean.strip_prefix('+').unwrap_or(&clean)),
+    };
+    let radix =
+        [("0x", 16), ("0o", 8), ("0b", 2)]
+            .iter()
+            .find_map(|(prefix, radix)| {
+                digits.strip_prefix(prefix).map(|d| (d, *radix))
+            });
+    if let Some((digits, radix)) = radix {
+        return i128::from_str_radix(digits, radix)
+            .ok()
+            .map(|i| Node::Int(sign * i));
+    }
+    if let Ok(i) = clean.parse::<i128>() {
+        return Some(Node::Int(i));
+    }
+    match digits {
+        "inf" => Some(Node::Float(sign as f64 * f64::INFINITY)),
+        "nan" => Some(Node::Float(f64::NAN)),
+        _ => clean.parse::<f64>().ok().map(Node::Float),
+    }
+}
+
+fn entry<'a>(table: &'a mut Node, key: &str) -> Option<&'a mut Node> {
+    match table {
+        Node::Map(entries) => entries.iter_mut().find_map(|(k, v)| match k {
+            Node::Text(k) if k == key => Some(v),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn insert(table: &mut Node, key: &str, value: Node) {
+    if let Node::Map(entries) = table {
+        entries.push((Node::Text(key.to_string()), value));
+    }
+}
+
+/// Walks (creating as needed) nested tables along `path`. Arrays of tables
+/// resolve to their last element, as TOML prescribes.
+fn table_at<'a>(
+    mut table: &'a mut Node,
+    path: &[String],
+    line: usize,
+) -> ParseResult<&'a mut Node> {
+    for key in path {
+        if entry(table, key).is_none() {
+            insert(table, key, Node::Map(Vec::new()));
+        }
+        table = match entry(table, key) {
+            Some(Node::Array(items)) => match items.last_mut() {
+                Some(last @ Node::Map(_)) => last,
+                _ => {
+                    return Err(ParseError {
+                        line,
+                        message: format!("'{}' is not a table", key),
+                    })
+                }
+            },
+            Some(node @ Node::Map(_)) => node,
+            _ => {
+                return Err(ParseError {
+                    line,
+                    message: format!("'{}' is not a table", key),
+                })
+            }
+        };
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"# settings
+title = "redb \"tui\"\tv1"
+"dotted.key" = 'literal \n'
+limits.max = 0x_ff
+limits.ratio = -1.5e3
+
+[server]
+ports = [80, 443, ]
+opts = { tls = true, name = "edge" }
+
+[[user]]
+name = "ann"
+
+[[user]]
+name = "bob"
+"#;
+
+    fn text(s: &str) -> Node {
+        Node::Text(s.to_string())
+    }
+
+    #[test]
+    fn parses_tables_keys_and_values() {
+        let doc = parse(DOC).unwrap();
+        assert_eq!(get_str(&doc, "title"), Some("redb \"tui\"\tv1"));
+        assert_eq!(get_str(&doc, "dotted.key"), Some("literal \\n"));
+        let limits = get(&doc, "limits").unwrap();
+        assert_eq!(get(limits, "max"), Some(&Node::Int(255)));
+        assert_eq!(get(limits, "ratio"), Some(&Node::Float(-1500.0)));
+        let server = get(&doc, "server").unwrap();
+        assert_eq!(
+            get(server, "ports"),
+            Some(&Node::Array(vec![Node::Int(80), Node::Int(443)]))
+        );
+        assert_eq!(
+            get(server, "opts"),
+            Some(&Node::Map(vec![
+                (text("tls"), Node::Bool(true)),
+                (text("name"), text("edge")),
+            ]))
+        );
+        let users: Vec<_> = match get(&doc, "user") {
+            Some(Node::Array(users)) => {
+                users.iter().filter_map(|u| get_str(u, "name")).collect()
+            }
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(users, ["ann", "bob"]);
+        let keys: Vec<_> = entries(&doc).map(|(k, _)| k).collect();
+        assert_eq!(keys, ["title", "dotted.key", "limits", "server", "user"]);
+    }
+
+    #[test]
+    fn round_trips_through_to_string() {
+        // Arrays of tables are written inline, ahead of the `[tables]`.
+        let sorted = |doc: Node| match doc {
+            Node::Map(mut entries) => {
+                entries.sort_by_key(|(k, _)| k.to_string());
+                entries
+            }
+            _ => Vec::new(),
+        };
+        let doc = parse(DOC).unwrap();
+        let again = parse(&to_string(&doc)).unwrap();
+        assert_eq!(sorted(again), sorted(doc));
+        let odd = Node::Map(vec![
+            (text("a b"), text("line\nbreak \u{1}")),
+            (text("nested"), Node::Map(vec![(text("x"), Node::Int(-1))])),
+            (text("skipped"), Node::Bytes(vec![1])),
+        ]);
+        let expected = Node::Map(vec![
+            (text("a b"), text("line\nbreak \u{1}")),
+            (text("nested"), Node::Map(vec![(text("x"), Node::Int(-1))])),
+        ]);
+        assert_eq!(parse(&to_string(&odd)).unwrap(), expected);
+    }
+
+    #[test]
+    fn truncated_documents_are_errors() {
+        // Every prefix must parse or fail cleanly; none may panic.
+        for (end, _) in DOC.char_indices() {
+            let _ = parse(&DOC[..end]);
+        }
+        for broken in [
+            "a = \"open",
+            "a = 'open",
+            "a = [1, 2",
+            "a = { b = 1",
+            "[table",
+            "[[array]",
+            "a =",
+            "a",
+            "a = 1 2",
+            "a = 1\na = 2",
+        ] {
+            assert!(parse(broken).is_err(), "{:?}", broken);
+        }
+        let error = parse("ok = 1\n\nbad = [").unwrap_err();
+        assert_eq!(error.line, 3);
+    }
+}
@@ -1,9 +1,36 @@
+use crate::browser::{self, Browser, Outcome};
+use crate::btree;
+use crate::clipboard;
+use crate::cmdline::{self, Command};
+use crate::commands::{self, ExportOptions, ExportTable};
+use crate::config::Config;
 use crate::database;
-use crate::database::DbProperties;
-use crate::layout;
-use crate::Result;
+use crate::database::{CopyOptions, OnConflict};
+use crate::database::{
+    DbProperties, KeyRange, Packing, RawEntry, RawType, StorageSummary,
+    TableSummary, TableTypes, WriteDurability, WriteOp,
+};
+use crate::decoder::{self, Decoder, EditFormat, Schemas};
+use crate::diff::{self, EntryDiff, TableDiff};
+use crate::duplicates::{Duplicate, Duplicates, Report};
+use crate::history::History;
+use crate::input::Input;
+use crate::json;
+use crate::keymap::{Action, Key, KeyMap};
+use crate::keytree::{self, Grouping, KeyTree, RowKind};
+use crate::layout::{self, LayoutMode, SPLIT_RANGE};
+use crate::logbuf::LogBuffer;
+use crate::registry::Registry;
+use crate::search::{self, Query};
+use crate::sizes::{Sizes, Summary};
+use crate::state::{Bookmark, Session, State};
+use crate::theme::Theme;
+use crate::{AppError, Result};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
     terminal::{
         disable_raw_mode, enable_raw_mode, EnterAlternateScreen,
         LeaveAlternateScreen,
@@ -11,208 +38,7051 @@ use crossterm::{
     ExecutableCommand,
 };
 use human_repr::HumanCount;
-use log::{debug, info};
-use ratatui::{backend::CrosstermBackend, Terminal};
-use redb::Database;
-use std::{fs, io, path::Path};
+use log::{debug, error, info};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout, Position, Rect},
+    widgets::ListState,
+    Frame, Terminal,
+};
+use redb::{Database, Savepoint};
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fmt, fs, io,
+    ops::Bound,
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
 pub struct TuiWrapper {
-    tui: Tui,
+    /// A session per open database, in the order of the database tabs.
+    tuis: Vec<Tui>,
+    /// The session on screen.
+    current: usize,
+    // What sessions opened later start from.
+    read_only: bool,
+    schemas: Schemas,
+    config: Config,
+    log: LogBuffer,
 }
 
 impl TuiWrapper {
-    pub fn new(db_path: &Path) -> Result<Self> {
+    /// Opens the databases at `db_paths`, each in a tab of its own, and
+    /// shows the first.
+    pub fn new(
+        db_paths: &[PathBuf],
+        table: Option<&str>,
+        diff: Option<&Path>,
+        read_only: bool,
+        schemas: Schemas,
+        config: Config,
+        log: LogBuffer,
+    ) -> Result<Self> {
         info!("Initializing TuiWrapper, enter alternate screen and raw mode...");
+        // Unwinding past `run` would print the panic while the screen is
+        // still alternate and raw, where it is lost.
+        // Damage redb panics on is caught and reported, and the session
+        // goes on; see `database::install_panic_hook`.
+        let panic_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !database::panic_is_caught() {
+                error!("{}", info);
+                if let Err(e) = restore_terminal() {
+                    error!("Failed to restore the terminal: {}", e);
+                }
+            }
+            panic_hook(info);
+        }));
         enable_raw_mode()?;
         io::stdout().execute(EnterAlternateScreen)?;
-        let tui = Tui::new(db_path)?;
-        Ok(Self { tui })
+        io::stdout().execute(EnableMouseCapture)?;
+        let mut wrapper = Self {
+            tuis: Vec::new(),
+            current: 0,
+            read_only,
+            schemas,
+            config,
+            log,
+        };
+        // On failure, dropping the wrapper restores the terminal.
+        for (i, db_path) in db_paths.iter().enumerate() {
+            let first = i == 0;
+            let tui = Tui::new(
+                db_path,
+                table.filter(|_| first),
+                diff.filter(|_| first),
+                read_only,
+                wrapper.schemas.clone(),
+                wrapper.config.clone(),
+                wrapper.log.clone(),
+            )?;
+            wrapper.tuis.push(tui);
+        }
+        Ok(wrapper)
     }
 
     pub fn run(&mut self) -> Result<()> {
         info!("Starting TuiWrapper run loop");
-        self.tui.run()
+        loop {
+            let titles: Vec<String> =
+                self.tuis.iter().map(Tui::database_title).collect();
+            let tui = &mut self.tuis[self.current];
+            tui.databases = titles;
+            tui.database_index = self.current;
+            match tui.run()? {
+                Exit::Quit => {
+                    self.tuis.iter_mut().for_each(Tui::save_session);
+                    return Ok(());
+                }
+                Exit::Switch(step) => {
+                    let len = self.tuis.len() as isize;
+                    let next = (self.current as isize + step).rem_euclid(len);
+                    self.current = next as usize;
+                }
+                Exit::Open(path) => self.open(&path),
+                Exit::Close if self.tuis.len() == 1 => {
+                    self.tuis[0].save_session();
+                    return Ok(());
+                }
+                Exit::Close => {
+                    let mut tui = self.tuis.remove(self.current);
+                    tui.save_session();
+                    info!("Closed {:?}", tui.db_path);
+                    self.current = self.current.min(self.tuis.len() - 1);
+                }
+                Exit::CopyTable { table, to, name } => {
+                    self.copy_table(&table, to, &name)
+                }
+            }
+        }
+    }
+
+    /// Opens the database at `path` in a new tab and switches to it, or to
+    /// its tab if it is open already.
+    fn open(&mut self, path: &Path) {
+        let canonical = fs::canonicalize(path).ok();
+        if let Some(i) = self
+            .tuis
+            .iter()
+            .position(|tui| fs::canonicalize(&tui.db_path).ok() == canonical)
+        {
+            self.current = i;
+            return;
+        }
+        let opened = Tui::new(
+            path,
+            None,
+            None,
+            self.read_only,
+            self.schemas.clone(),
+            self.config.clone(),
+            self.log.clone(),
+        );
+        match opened {
+            Ok(tui) => {
+                self.tuis.push(tui);
+                self.current = self.tuis.len() - 1;
+            }
+            Err(e) => self.tuis[self.current].show_error(format!(
+                "Opening {} failed: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Copies `table` of the database on screen into the one in tab `to`
+    /// as `name`, then has that one read its tables again.
+    fn copy_table(&mut self, table: &str, to: usize, name: &str) {
+        let target = &self.tuis[to];
+        if target.read_only {
+            let message =
+                format!("{} is open read-only", target.database_title());
+            self.tuis[self.current].show_error(message);
+            return;
+        }
+        let target_db = Arc::clone(&target.db);
+        let target_title = target.database_title();
+        let copied = self.tuis[self.current].copy_table_into(
+            &target_db,
+            &target_title,
+            table,
+            name,
+        );
+        if copied && to != self.current {
+            if let Err(e) = self.tuis[to].reload() {
+                error!("Failed to reload {}: {}", target_title, e);
+            }
+        }
     }
 }
 
+/// Why `Tui::run` returned, for `TuiWrapper` to act on.
+enum Exit {
+    Quit,
+    /// Show the database this many tabs on, wrapping around.
+    Switch(isize),
+    /// Open the database at this path in a new tab.
+    Open(PathBuf),
+    /// Close the database on screen, quitting with the last one.
+    Close,
+    /// Copy `table` of the database on screen into the one in tab `to`,
+    /// named `name` there.
+    CopyTable {
+        table: String,
+        to: usize,
+        name: String,
+    },
+}
+
+/// Entries copied per write transaction by `:copy`.
+const COPY_BATCH_SIZE: usize = 10_000;
+
 impl Drop for TuiWrapper {
     fn drop(&mut self) {
         info!("Cleaning up TuiWrapper, exit alternate screen and raw mode...");
-        disable_raw_mode().expect("Could not disable raw mode");
-        io::stdout()
-            .execute(LeaveAlternateScreen)
-            .expect("Could not leave alternate screen");
+        // The panic hook may have restored it already, which does no harm.
+        if let Err(e) = restore_terminal() {
+            error!("Failed to restore the terminal: {}", e);
+        }
     }
 }
 
-pub struct Tui {
-    terminal: Terminal<CrosstermBackend<io::Stdout>>,
-    table_names: Vec<String>,
-    list_state: ratatui::widgets::ListState,
-    db_properties: DbProperties,
-    selected_table_content: Vec<(String, String)>,
-    db: Database,
+/// Tells that the database at `path` is locked by another process, trying
+/// to open it every `LOCK_RETRY_INTERVAL` and on `r` until it opens or `s`
+/// picks a snapshot instead; see `database::open_snapshot`. `q` or Esc give
+/// up. Returns the database and whether it is a snapshot.
+fn wait_for_lock(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    theme: &Theme,
+    path: &Path,
+) -> Result<(Database, bool)> {
+    let name = path.display().to_string();
+    loop {
+        terminal.draw(|frame| {
+            layout::render_notice(
+                frame,
+                theme,
+                "Database locked",
+                &[
+                    &name,
+                    "is locked by another process, which has to close it",
+                    "before redb-tui can open it.",
+                    "",
+                    "r: try again (also done every few seconds)",
+                    "s: browse a read-only snapshot instead",
+                    "q: quit",
+                ],
+            )
+        })?;
+        if event::poll(LOCK_RETRY_INTERVAL)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Char('s') => {
+                    let path = path.to_path_buf();
+                    let db = open_database(terminal, theme, move |on_repair| {
+                        database::open_snapshot(&path, on_repair)
+                    })?;
+                    return Ok((db, true));
+                }
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    return Err(AppError::DatabaseLocked(path.to_path_buf()))
+                }
+                KeyCode::Char('r') => {}
+                _ => continue,
+            }
+        }
+        let path = path.to_path_buf();
+        match open_database(terminal, theme, move |on_repair| {
+            database::open(&path, on_repair)
+        }) {
+            Err(AppError::DatabaseLocked(_)) => {}
+            result => return Ok((result?, false)),
+        }
+    }
 }
 
-impl Tui {
-    pub fn new(db_path: &Path) -> Result<Self> {
-        info!("Initializing Tui with database at {:?}", db_path);
-        let db = Database::open(db_path)?;
-        let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
-        let table_names = crate::database::get_table_names(&db)?;
-        let mut list_state = ratatui::widgets::ListState::default();
-        list_state.select(Some(0));
+/// When the file at `path` was last written, if that can be told.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
 
-        let db_properties = DbProperties {
-            file_size: fs::metadata(db_path)?.len(),
-            num_tables: table_names.len(),
-        };
+/// Calls `f` with the database at `path` to compare `db` with, which is
+/// `db` itself if that is its own file. One another process keeps locked,
+/// or any with `read_only`, is read from a copy.
+fn with_other_database<T>(
+    db: &Database,
+    path: &Path,
+    same_file: bool,
+    read_only: bool,
+    f: impl FnOnce(&Database) -> Result<T>,
+) -> Result<T> {
+    if same_file {
+        return f(db);
+    }
+    if read_only {
+        return f(&database::open_snapshot(path, |_| true)?);
+    }
+    let other = match database::open(path, |_| true) {
+        Err(AppError::DatabaseLocked(_)) => {
+            database::open_snapshot(path, |_| true)
+        }
+        result => result,
+    }?;
+    f(&other)
+}
 
-        info!("Tui initialized successfully");
-        debug!("Database properties: {:?}", db_properties);
+/// Compares `db` with the database `old`, table by table.
+fn compare_databases(old: &Database, db: &Database) -> Result<Vec<DiffTable>> {
+    let mut tables = Vec::new();
+    for table in diff::tables(old, db, None)? {
+        let mut entries = Vec::new();
+        let counts = diff::entries(old, db, &table, |entry| {
+            if entries.len() < MAX_DIFF_ENTRIES {
+                entries.push(entry);
+            }
+            Ok(true)
+        })?;
+        tables.push(DiffTable {
+            table,
+            counts,
+            entries,
+        });
+    }
+    Ok(tables)
+}
 
-        Ok(Self {
-            db,
-            terminal,
-            table_names,
-            list_state,
-            db_properties,
-            selected_table_content: Vec::new(),
-        })
+/// Up to `COMPARE_BATCH` rows of `table` in the databases `old` and `db`
+/// from `from` on.
+fn compare_rows(
+    old: &Database,
+    db: &Database,
+    table: &str,
+    from: Bound<Vec<u8>>,
+) -> Result<ComparedRows> {
+    let tables = diff::tables(old, db, Some(table))?;
+    let Some(table) = tables.first() else {
+        return Err(AppError::TableNotFound(table.to_string()));
+    };
+    let (Some(old_types), Some(types)) = (&table.old, &table.new) else {
+        return Err(AppError::TableNotFound(table.name.clone()));
+    };
+    if old_types != types {
+        return Err(AppError::TableTypesDiffer(format!(
+            "{} -> {}, was {} -> {}",
+            types.key.name,
+            types.value.name,
+            old_types.key.name,
+            old_types.value.name
+        )));
     }
+    let mut rows = Vec::new();
+    let mut complete = true;
+    diff::rows(old, db, table, from.as_ref().map(Vec::as_slice), |row| {
+        if rows.len() == COMPARE_BATCH {
+            complete = false;
+            return Ok(false);
+        }
+        rows.push(row);
+        Ok(true)
+    })?;
+    Ok((types.clone(), rows, complete))
+}
 
-    pub fn run(&mut self) -> Result<()> {
-        info!("Starting Tui run loop");
-        loop {
-            self.terminal.draw(|frame| {
-                let size = frame.area();
-                let (left, right, bottom) = layout::get_layout(size);
+/// Line of the duplicates popup for a value stored under `keys` and more.
+fn duplicate_line(duplicate: &Duplicate, value: &str, keys: &[String]) -> String {
+    let more = if duplicate.count > keys.len() as u64 {
+        ", …"
+    } else {
+        ""
+    };
+    clip(
+        &format!(
+            "{} × {} ({} wasted)  {}  under {}{}",
+            layout::group_thousands(duplicate.count),
+            (duplicate.size as u64).human_count_bytes(),
+            duplicate.wasted().human_count_bytes(),
+            clip(value, DUPLICATE_VALUE_WIDTH),
+            keys.join(", "),
+            more
+        ),
+        HIT_WIDTH,
+    )
+}
 
-                layout::render_table_list(
-                    frame,
-                    left,
-                    &self.table_names,
-                    &mut self.list_state,
-                );
+/// `text` with its lines joined into one.
+fn one_line(text: &str) -> String {
+    text.lines().map(str::trim).collect::<Vec<_>>().join(" ")
+}
 
-                let binding_no_table_selected = String::from("No table selected");
-                let selected_table = self
-                    .table_names
-                    .get(self.list_state.selected().unwrap_or(0))
-                    .unwrap_or(&binding_no_table_selected);
-                layout::render_key_value_pairs(
-                    frame,
-                    right,
-                    selected_table,
-                    &self.selected_table_content,
-                );
+/// `text` on one line, cut to `width` characters.
+fn clip(text: &str, width: usize) -> String {
+    let line = one_line(text);
+    if line.chars().count() <= width {
+        return line;
+    }
+    let mut clipped: String = line.chars().take(width - 3).collect();
+    clipped.push_str("...");
+    clipped
+}
 
-                let stats = database::get_database_stats(&self.db);
-
-                let status = format!(
-                    "Tables: {} | DB Size: {} Height: {} Pages: {} Stored: {} Meta: {} Frag: {}",
-                    self.db_properties.num_tables,
-                    self.db_properties.file_size.human_count_bytes(),
-                    stats.tree_height(),
-                    stats.allocated_pages(),
-                    stats.stored_bytes().human_count_bytes(),
-                    stats.metadata_bytes().human_count_bytes(),
-                    stats.fragmented_bytes().human_count_bytes(),
-                );
-                layout::render_bottom_status(frame, bottom, &status);
-            })?;
+/// Called by redb with the progress of a repair; see `database::open`.
+type RepairFn = Box<dyn Fn(f64) -> bool>;
 
+/// Opens a database with `open` on another thread, so that a repair, which
+/// redb runs on databases that were not closed cleanly, can show its
+/// progress. Esc stops the repair, failing the open.
+fn open_database(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    theme: &Theme,
+    open: impl FnOnce(RepairFn) -> Result<Database> + Send + 'static,
+) -> Result<Database> {
+    let (tx, rx) = mpsc::channel();
+    let abort = Arc::new(AtomicBool::new(false));
+    let handle = thread::spawn({
+        let abort = abort.clone();
+        move || {
+            open(Box::new(move |progress| {
+                // The receiver is gone only if the TUI exited meanwhile.
+                let _ = tx.send(progress);
+                !abort.load(Ordering::Relaxed)
+            }))
+        }
+    });
+    let mut progress = None;
+    while !handle.is_finished() {
+        progress = rx.try_iter().last().or(progress);
+        // Most opens need no repair; leave the keys typed meanwhile alone.
+        let Some(progress) = progress else {
+            thread::sleep(LOAD_TICK_RATE);
+            continue;
+        };
+        terminal.draw(|frame| {
+            layout::render_percent_popup(
+                frame,
+                theme,
+                "Repairing the database, Esc to stop",
+                progress * 100.0,
+            )
+        })?;
+        if event::poll(LOAD_TICK_RATE)? {
             if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => {
-                        info!("User requested exit");
-                        return Ok(());
-                    }
-                    KeyCode::Down => self.next(),
-                    KeyCode::Up => self.previous(),
-                    _ => {}
+                if key.code == KeyCode::Esc {
+                    abort.store(true, Ordering::Relaxed);
                 }
             }
         }
     }
+    handle.join().unwrap_or_else(|e| panic::resume_unwind(e))
+}
 
-    fn next(&mut self) {
-        debug!("Moving to next item");
-        let i = match self.list_state.selected() {
-            Some(i) => {
-                if i >= self.table_names.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
+/// Leaves raw mode, mouse capture and the alternate screen.
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    io::stdout()
+        .execute(DisableMouseCapture)?
+        .execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+/// Start screen for when redb-tui is started without a database: the
+/// recent ones, to pick one from, or a file browser when there are none.
+/// `None` if the user quits instead.
+pub fn pick_database(config: &Config) -> Result<Option<PathBuf>> {
+    let recent = match State::default_path() {
+        Some(path) => State::load(&path)?.recent,
+        None => Vec::new(),
+    };
+    // Files moved or deleted since are left out.
+    let recent: Vec<PathBuf> =
+        recent.into_iter().filter(|path| path.is_file()).collect();
+    let items: Vec<String> = recent
+        .iter()
+        .map(|path| match fs::metadata(path) {
+            Ok(metadata) => format!(
+                "{}  {}",
+                path.display(),
+                metadata.len().human_count_bytes()
+            ),
+            Err(_) => path.display().to_string(),
+        })
+        .collect();
+    on_start_screen(|terminal| {
+        run_start_screen(
+            terminal,
+            &config.theme,
+            "Recent databases",
+            &recent,
+            &items,
+            Path::new("."),
+        )
+    })
+}
+
+/// Start screen for a directory given as the database: the redb databases
+/// in it and below it, with their sizes and table counts, to pick one from.
+pub fn pick_in_directory(config: &Config, dir: &Path) -> Result<Option<PathBuf>> {
+    let theme = &config.theme;
+    on_start_screen(|terminal| {
+        let looking = format!("Looking for redb files in {}", dir.display());
+        terminal.draw(|frame| {
+            layout::render_notice(frame, theme, "Scanning", &[&looking])
+        })?;
+        let found = browser::scan(dir)?;
+        let paths: Vec<PathBuf> =
+            found.iter().map(|found| found.path.clone()).collect();
+        let items: Vec<String> = found
+            .iter()
+            .map(|found| {
+                let tables = match &found.tables {
+                    Ok(1) => "1 table".to_string(),
+                    Ok(n) => format!("{} tables", n),
+                    Err(AppError::DatabaseLocked(_)) => {
+                        "locked by another process".to_string()
+                    }
+                    Err(AppError::DatabaseError(
+                        redb::DatabaseError::RepairAborted,
+                    )) => "needs a repair, not closed cleanly".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                format!(
+                    "{}  {}  {}",
+                    found
+                        .path
+                        .strip_prefix(dir)
+                        .unwrap_or(&found.path)
+                        .display(),
+                    found.size.human_count_bytes(),
+                    tables
+                )
+            })
+            .collect();
+        run_start_screen(
+            terminal,
+            theme,
+            &format!("Databases in {}", dir.display()),
+            &paths,
+            &items,
+            dir,
+        )
+    })
+}
+
+/// Runs `f` on the alternate screen in raw mode, which it leaves again
+/// before the TUI proper takes over.
+fn on_start_screen<T>(
+    f: impl FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<T>,
+) -> Result<T> {
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    let result = Terminal::new(CrosstermBackend::new(io::stdout()))
+        .map_err(AppError::from)
+        .and_then(|mut terminal| f(&mut terminal));
+    restore_terminal()?;
+    result
+}
+
+/// Lets the user pick one of `paths`, listed as `items`, or browse for
+/// another starting in `dir`; just the browser when there are none.
+fn run_start_screen(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    theme: &Theme,
+    title: &str,
+    paths: &[PathBuf],
+    items: &[String],
+    dir: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut list_state = ListState::default().with_selected(Some(0));
+    let mut browser = paths.is_empty().then(|| Browser::new(dir));
+    loop {
+        terminal.draw(|frame| match &mut browser {
+            Some(browser) => render_browser(frame, theme, browser),
+            None => layout::render_picker(
+                frame,
+                theme,
+                title,
+                items,
+                &mut list_state,
+                "Enter: open  o: browse files  q: quit",
+            ),
+        })?;
+        let Event::Key(key) = event::read()? else {
+            continue;
         };
-        self.list_state.select(Some(i));
-        self.update_selected_table_content();
+        if let Some(open) = &mut browser {
+            match open.handle_key(key.code) {
+                Some(Outcome::Pick(path)) => return Ok(Some(path)),
+                Some(Outcome::Cancel) if paths.is_empty() => return Ok(None),
+                Some(Outcome::Cancel) => browser = None,
+                None => {}
+            }
+            continue;
+        }
+        let selected = list_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                list_state.select(Some(selected.saturating_sub(1)));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let last = items.len().saturating_sub(1);
+                list_state.select(Some((selected + 1).min(last)));
+            }
+            KeyCode::Enter => return Ok(paths.get(selected).cloned()),
+            KeyCode::Char('o') => browser = Some(Browser::new(dir)),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            _ => {}
+        }
     }
+}
 
-    fn previous(&mut self) {
-        debug!("Moving to previous item");
-        let i = match self.list_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.table_names.len() - 1
-                } else {
-                    i - 1
-                }
+/// The file browser, over the whole screen, titled with its directory.
+fn render_browser(frame: &mut Frame, theme: &Theme, browser: &mut Browser) {
+    let title = browser.dir.display().to_string();
+    let items = browser.items();
+    let hint = browser.hint();
+    layout::render_picker(
+        frame,
+        theme,
+        &title,
+        &items,
+        &mut browser.list_state,
+        &hint,
+    );
+}
+
+/// Pane that receives navigation keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Tables,
+    Entries,
+}
+
+/// Storage stats of the database and of each table.
+type Stats = (StorageSummary, Vec<TableSummary>);
+
+/// A new snapshot, with the time the file was last written before it was
+/// taken.
+type Snapshot = (Database, Option<SystemTime>);
+
+/// Top-level view, picked from the tab bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    /// The table list and entries pane.
+    Data,
+    Stats,
+    Schema,
+    Logs,
+    Savepoints,
+    /// Differences from another database.
+    Diff,
+}
+
+impl Tab {
+    const ALL: [Tab; 6] = [
+        Tab::Data,
+        Tab::Stats,
+        Tab::Schema,
+        Tab::Logs,
+        Tab::Savepoints,
+        Tab::Diff,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Data => "Data",
+            Tab::Stats => "Stats",
+            Tab::Schema => "Schema",
+            Tab::Logs => "Logs",
+            Tab::Savepoints => "Savepoints",
+            Tab::Diff => "Diff",
+        }
+    }
+}
+
+/// Column the tables of the stats view are sorted by, most first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum StatsSort {
+    #[default]
+    Stored,
+    Metadata,
+    Fragmented,
+    /// Bytes of the table's pages in all.
+    Size,
+    Entries,
+}
+
+impl StatsSort {
+    fn next(self) -> Self {
+        match self {
+            StatsSort::Stored => StatsSort::Metadata,
+            StatsSort::Metadata => StatsSort::Fragmented,
+            StatsSort::Fragmented => StatsSort::Size,
+            StatsSort::Size => StatsSort::Entries,
+            StatsSort::Entries => StatsSort::Stored,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            StatsSort::Stored => "stored bytes",
+            StatsSort::Metadata => "metadata bytes",
+            StatsSort::Fragmented => "fragmented bytes",
+            StatsSort::Size => "size",
+            StatsSort::Entries => "entries",
+        }
+    }
+
+    fn key(self, table: &TableSummary) -> u64 {
+        match self {
+            StatsSort::Stored => table.stats.stored_bytes(),
+            StatsSort::Metadata => table.stats.metadata_bytes(),
+            StatsSort::Fragmented => table.stats.fragmented_bytes(),
+            StatsSort::Size => table_size(table),
+            StatsSort::Entries => table.entries,
+        }
+    }
+}
+
+/// Bytes of the pages of `table`: what it stores, its metadata and the
+/// room left unused.
+fn table_size(table: &TableSummary) -> u64 {
+    table.stats.stored_bytes()
+        + table.stats.metadata_bytes()
+        + table.stats.fragmented_bytes()
+}
+
+/// A row of the savepoints view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SavepointId {
+    /// Stored in the database file under this id.
+    Persistent(u64),
+    /// Taken in this session, by number.
+    Ephemeral(usize),
+}
+
+impl fmt::Display for SavepointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SavepointId::Persistent(id) => write!(f, "{}", id),
+            SavepointId::Ephemeral(number) => write!(f, "#{}", number),
+        }
+    }
+}
+
+/// Savepoint taken in this session, gone when it ends.
+struct EphemeralSavepoint {
+    number: usize,
+    /// Newest persistent savepoint when this one was taken. Restoring that
+    /// one or an older one makes this one invalid.
+    after: Option<u64>,
+    savepoint: Savepoint,
+}
+
+/// Savepoint change waiting for confirmation.
+#[derive(Debug, Clone, Copy)]
+enum SavepointOp {
+    Restore(SavepointId),
+    Delete(SavepointId),
+}
+
+const STATS_HEADER: &[&str] = &[
+    "Table",
+    "Entries",
+    "Height",
+    "Leaf pages",
+    "Stored",
+    "Metadata",
+    "Fragmented",
+    "Size",
+    "Unused",
+    "Of file",
+];
+
+const SAVEPOINTS_HEADER: &[&str] = &["Savepoint", "Kept"];
+
+const DIFF_HEADER: &[&str] = &["Table", "Change", "Key", "Old", "New"];
+
+/// Differing entries kept per table for the diff view; the counts cover
+/// all of them.
+const MAX_DIFF_ENTRIES: usize = 1000;
+
+/// Widest key or value shown in the diff view, in characters.
+const DIFF_CELL_WIDTH: usize = 40;
+
+/// Rows of a side-by-side comparison read at a time; more are read when
+/// the selection gets near the end.
+const COMPARE_BATCH: usize = 500;
+
+/// A table as compared for the diff view.
+struct DiffTable {
+    table: TableDiff,
+    counts: diff::Counts,
+    /// The first `MAX_DIFF_ENTRIES` differing entries, in key order.
+    entries: Vec<EntryDiff>,
+}
+
+/// A table of both databases side by side in the diff view, keys aligned.
+struct Comparison {
+    table: String,
+    /// Key the rows start at, `None` for the start of the table.
+    start: Option<Vec<u8>>,
+    /// Types of the table, once the first rows are read.
+    types: Option<TableTypes>,
+    rows: Vec<diff::Row>,
+    /// Whether `rows` reach the end of the table.
+    complete: bool,
+    selected: usize,
+}
+
+/// Rows read for a comparison, with the types of the table and whether
+/// they reach its end.
+type ComparedRows = (TableTypes, Vec<diff::Row>, bool);
+
+/// Pane titles, rows and selected row of a comparison, as drawn.
+type ComparisonView = ([String; 2], Vec<([String; 2], bool)>, usize);
+
+/// Title, figures and bars of a histogram popup.
+type HistogramView = (String, Vec<(String, String)>, Vec<(String, u64)>);
+
+const SCHEMA_HEADER: &[&str] = &[
+    "Table",
+    "Entries",
+    "Key type",
+    "Value type",
+    "Key decoder",
+    "Value decoder",
+];
+
+/// Modal state on top of the panes.
+enum Mode {
+    Normal,
+    /// Editing the value of the entry stored under `key`.
+    Edit {
+        key: Vec<u8>,
+        format: EditFormat,
+        input: Input,
+        error: Option<String>,
+    },
+    /// Prompting for the name of a new table, then for its key and value
+    /// types.
+    NewTable {
+        name: Option<String>,
+        key: Option<RawType>,
+        input: Input,
+        error: Option<String>,
+    },
+    /// Typing the filter of the table list, kept in `Tui::table_filter`.
+    TableFilter,
+    /// Typing a search over the entries of the selected table.
+    Search {
+        input: Input,
+    },
+    /// Prompting for a key range of the selected table.
+    KeyRange {
+        input: Input,
+        error: Option<String>,
+    },
+    /// Prompting for a key of the selected table to jump to.
+    GotoKey {
+        input: Input,
+        error: Option<String>,
+    },
+    /// Prompting for the new name of `table`.
+    Rename {
+        table: String,
+        input: Input,
+        error: Option<String>,
+    },
+    /// Waiting for the user to type `expected` to confirm a destructive
+    /// write.
+    TypedConfirm {
+        message: String,
+        expected: String,
+        input: Input,
+        error: Option<String>,
+        op: WriteOp,
+    },
+    /// Typing a command after `:`.
+    Command {
+        input: Input,
+        error: Option<String>,
+    },
+    /// Showing the key bindings, scrolled down by `scroll` lines.
+    Help {
+        scroll: u16,
+    },
+    /// Showing the selected entry in full, scrolled down by `scroll` lines.
+    Detail {
+        scroll: u16,
+    },
+    /// Waiting for the user to confirm a write.
+    Confirm {
+        message: String,
+        op: WriteOp,
+    },
+    /// Waiting for the user to confirm restoring or deleting a savepoint.
+    ConfirmSavepoint {
+        message: String,
+        op: SavepointOp,
+    },
+    /// Showing an error that stopped an action, until any key is pressed.
+    Error {
+        message: String,
+    },
+    /// Browsing the file system for a database to open in a new tab.
+    Browse {
+        browser: Browser,
+    },
+    /// Listing the bookmarks, with the one at `selected` highlighted.
+    Bookmarks {
+        selected: usize,
+    },
+    /// Typing a search through every table.
+    SearchAll {
+        input: Input,
+        error: Option<String>,
+    },
+    /// Listing the hits of the search through every table, with the one at
+    /// `selected` highlighted.
+    SearchHits {
+        selected: usize,
+    },
+    /// Browsing the keys of the selected table grouped by prefix.
+    KeyTree,
+    /// Listing the values of a table stored under more than one key, with
+    /// the one at `selected` highlighted.
+    Duplicates {
+        selected: usize,
+    },
+    /// Showing how the value sizes of a table spread.
+    ValueSizes,
+    /// Showing the levels of the selected table's B-tree.
+    BTree,
+}
+
+/// Message from the thread reading the entries of the selected table.
+enum LoadEvent {
+    /// A batch of entries, in listing order.
+    Entries(TableTypes, Vec<RawEntry>),
+    /// The read finished, with the number of entries read.
+    Done(Result<usize>),
+}
+
+/// What to do once a read of entries finished.
+enum AfterLoad {
+    Nothing,
+    /// Select this row, or the last one if there are fewer.
+    Select(Option<usize>),
+    /// Continue a search that had this many matches before the read.
+    NextMatch(usize),
+    /// Report a jump to the key typed as this text that found nothing.
+    Goto(String),
+    /// Select the last entry if the selection was on it before the read,
+    /// as given; see `Tui::follow_tick`.
+    Follow(bool),
+    /// Select this row scrolled this far, going back to a `Location`.
+    Restore {
+        row: usize,
+        offset: usize,
+    },
+}
+
+/// Entries of a table written at once in this session, to undo and redo.
+#[derive(Debug, Clone)]
+struct Change {
+    table: String,
+    entries: Vec<ChangedEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct ChangedEntry {
+    key: Vec<u8>,
+    /// Value before the write, `None` if the key wasn't stored.
+    before: Option<Vec<u8>>,
+    /// Value after the write, `None` if the entry was removed.
+    after: Option<Vec<u8>>,
+}
+
+impl Change {
+    /// The change `op` made, if it wrote entries, given the values it
+    /// replaced.
+    fn new(op: &WriteOp, before: Vec<Option<Vec<u8>>>) -> Option<Self> {
+        let (table, written) = match op {
+            WriteOp::Insert { table, key, value } => {
+                (table, vec![(key.clone(), Some(value.clone()))])
             }
-            None => 0,
+            WriteOp::Remove { table, key } => (table, vec![(key.clone(), None)]),
+            WriteOp::Batch { table, entries } => (table, entries.clone()),
+            _ => return None,
         };
-        self.list_state.select(Some(i));
-        self.update_selected_table_content();
+        let entries = written
+            .into_iter()
+            .zip(before)
+            .map(|((key, after), before)| ChangedEntry { key, before, after })
+            .collect();
+        Some(Self {
+            table: table.clone(),
+            entries,
+        })
     }
 
-    fn update_selected_table_content(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(table_name) = self.table_names.get(selected) {
-                debug!("Updating content for selected table: {}", table_name);
-
-                // NOTE: Unable to read key/values from untyped table. Typed table required
-                // TableDefnition at compile time, see
-                // https://github.com/cberner/redb/issues/741
-                //
-                // self.selected_table_content = self.read_table_content(table_name);
-                // let txn = self.db.begin_read().unwrap();
-                // debug!("txn: {:?}", txn);
-                // let slices: TableDefinition<&[u8], &[u8]> =
-                //     TableDefinition::new(&table_name);
-                // debug!("slices: {:?}", slices.to_string());
-                // let table = txn.open_table(slices);
-                // debug!("Table: {:?}", table);
-                // let table = table.unwrap();
-
-                // // Iterate over keys; interpreting them is another challenge
-                // self.selected_table_content = vec![];
-                // let table_iter = table.iter();
-                // debug!("Have iterator? {}", table_iter.is_err());
-                // for result in table.iter().unwrap() {
-                //     let (key, value) = result.unwrap();
-                //     let key = String::from_utf8(key.value().to_vec())
-                //         .unwrap_or("key".to_string());
-                //     let value = String::from_utf8(value.value().to_vec())
-                //         .unwrap_or("value".to_string());
-                //     debug!("Key: {:?}, Value size: {}", key, value,);
-                //     self.selected_table_content.push((key, value));
-                // }
-
-                // Fill with dummy values for now
-                self.selected_table_content = vec![
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                    ("Key".to_string(), "Value".to_string()),
-                ];
-            }
+    /// The write putting the entries back as they were before, with `undo`,
+    /// or after the change.
+    fn op(&self, undo: bool) -> WriteOp {
+        let entries = self
+            .entries
+            .iter()
+            .map(|e| {
+                let value = if undo { &e.before } else { &e.after };
+                (e.key.clone(), value.clone())
+            })
+            .collect();
+        WriteOp::Batch {
+            table: self.table.clone(),
+            entries,
         }
     }
 }
+
+/// Where the entries pane was, to go back to.
+#[derive(Debug, Clone, PartialEq)]
+struct Location {
+    table: String,
+    /// Key the entries started at after a jump, `None` at the start of the
+    /// table.
+    start: Option<Vec<u8>>,
+    row: usize,
+    offset: usize,
+}
+
+/// Read of entries in flight, see `Tui::load_entries`. Dropping it cancels
+/// the read.
+struct Load {
+    rx: Receiver<LoadEvent>,
+    then: AfterLoad,
+    /// Replace the entries shown once the first ones arrive, rather than
+    /// adding to them; see `Tui::reload_entries`.
+    replace: bool,
+    started: Instant,
+    /// Checked by the reading thread before each batch.
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for Load {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Key read again on every tick and shown above the panes; see
+/// `Tui::toggle_pin`.
+struct Pin {
+    table: String,
+    key: Vec<u8>,
+    /// The key as shown in the entries pane.
+    label: String,
+    /// Stored types and value of the last read; `None` when the key is
+    /// gone.
+    value: std::result::Result<(TableTypes, Option<Vec<u8>>), String>,
+    read_at: Instant,
+    /// When a read last found a different value.
+    changed_at: Instant,
+}
+
+/// Matches of a search counted through the whole listing of `table` in the
+/// background, see `Tui::count_matches`. Dropping it stops the count.
+struct MatchCount {
+    table: String,
+    query: Query,
+    /// Running totals, the channel closing once the count is done.
+    rx: Receiver<usize>,
+    matches: usize,
+    done: bool,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for MatchCount {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Hits of a search kept by `Tui::search_all`.
+const MAX_HITS: usize = 1000;
+
+/// Characters of an entry shown for a hit.
+const HIT_WIDTH: usize = 100;
+
+/// A search through every table, see `Tui::search_all`. Dropping it stops
+/// the search.
+struct TableSearch {
+    query: Query,
+    /// Hits so far, in table and key order.
+    hits: Vec<Hit>,
+    rx: Receiver<HitEvent>,
+    /// Table being searched, `None` once all are.
+    searching: Option<String>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for TableSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// An entry whose decoded key or value matches a search of every table.
+struct Hit {
+    table: String,
+    key: Vec<u8>,
+    /// The table and entry as listed.
+    label: String,
+}
+
+/// Message from the thread searching every table.
+enum HitEvent {
+    /// Searching this table now.
+    Table(String),
+    Hit(Hit),
+}
+
+/// Values listed at most by `Tui::find_duplicates`.
+const MAX_DUPLICATES: usize = 100;
+
+/// Characters of a value shown for a duplicate.
+const DUPLICATE_VALUE_WIDTH: usize = 40;
+
+/// A read of `table` for values stored more than once, see
+/// `Tui::find_duplicates`. Dropping it stops the read.
+struct DuplicateScan {
+    table: String,
+    rx: Receiver<DuplicateEvent>,
+    /// Entries read so far.
+    read: u64,
+    /// The values found once the read is done, with their lines.
+    found: Option<(Report, Vec<String>)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for DuplicateScan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Message from the thread looking for duplicated values.
+enum DuplicateEvent {
+    /// Entries read so far.
+    Read(u64),
+    /// The values stored more than once, with a line for each of the top
+    /// ones.
+    Done(std::result::Result<(Report, Vec<String>), String>),
+}
+
+/// A read of the value sizes of `table`, see `Tui::read_value_sizes`.
+/// Dropping it stops the read.
+struct SizeScan {
+    table: String,
+    rx: Receiver<SizeEvent>,
+    /// Entries read so far.
+    read: u64,
+    /// The sizes once the read is done, `None` inside for an empty table.
+    found: Option<Option<Summary>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for SizeScan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A read of the keys of `table` for the stats view, see
+/// `Tui::read_key_stats`. Dropping it stops the read.
+struct KeyScan {
+    table: String,
+    rx: Receiver<KeyStatsEvent>,
+    /// Entries read so far.
+    read: u64,
+    /// The stats once the read is done, `None` inside for an empty table.
+    found: Option<Option<KeyStats>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for KeyScan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The first and last key of a table and how long its keys are.
+struct KeyStats {
+    min: Vec<u8>,
+    max: Vec<u8>,
+    lengths: Summary,
+}
+
+/// Message from the thread reading keys for their stats.
+enum KeyStatsEvent {
+    /// Entries read so far.
+    Read(u64),
+    Done(std::result::Result<Option<KeyStats>, String>),
+}
+
+/// Characters of the first and last key shown in the stats view.
+const KEY_STATS_WIDTH: usize = 60;
+
+/// Message from the thread reading value sizes.
+enum SizeEvent {
+    /// Entries read so far.
+    Read(u64),
+    Done(std::result::Result<Option<Summary>, String>),
+}
+
+/// Key range the entries of `table` are narrowed to.
+struct TableRange {
+    table: String,
+    /// The range as typed, for the pane title.
+    text: String,
+    range: KeyRange,
+}
+
+/// Entries of `table` marked for export, copying or deleting, by raw key.
+struct Marks {
+    table: String,
+    keys: BTreeSet<Vec<u8>>,
+    /// Key `V` was pressed on, to mark the entries up to where it is
+    /// pressed next.
+    start: Option<Vec<u8>>,
+}
+
+pub struct Tui {
+    terminal: Terminal<CrosstermBackend<io::Stdout>>,
+    table_names: Vec<String>,
+    /// Fuzzy filter narrowing down the table list.
+    table_filter: Option<Input>,
+    /// Selected row of the (filtered) table list.
+    list_state: ListState,
+    /// Selected entry of the selected table.
+    entry_state: ListState,
+    focus: Focus,
+    mode: Mode,
+    db_properties: DbProperties,
+    selected_table_content: Vec<RawEntry>,
+    selected_table_types: Option<TableTypes>,
+    tab: Tab,
+    /// Rows the stats and schema views are scrolled down by, or the log
+    /// view up by.
+    tab_scroll: u16,
+    /// Where the stats, schema or log view was at the last draw.
+    view_area: Rect,
+    /// Show only the entries pane or the detail view.
+    zoomed: bool,
+    /// Leave out the table list, giving its room to the entries pane.
+    tables_hidden: bool,
+    /// First key of a sequence such as `gg`, waiting for the second one.
+    pending_key: Option<Key>,
+    /// Actions of the keys pressed while no prompt or popup is open.
+    keymap: KeyMap,
+    theme: Theme,
+    /// Theme `T` switches to, swapped with `theme` each time.
+    other_theme: Theme,
+    /// Share of the screen taken by the table list, in percent.
+    split: u16,
+    layout_mode: LayoutMode,
+    /// Where preferences are kept for the next session, if there is a
+    /// place for them.
+    state_path: Option<PathBuf>,
+    /// Where the panes were at the last draw, for paging and mouse clicks.
+    tables_area: Rect,
+    entries_area: Rect,
+    /// Whether the entries start at a key jumped to with `J` rather than at
+    /// the start of the table.
+    jumped: bool,
+    /// Whether `selected_table_content` holds every entry of the table.
+    selected_table_complete: bool,
+    /// Entries of the selected table being read in the background.
+    load: Option<Load>,
+    /// Narrows the entries pane down to entries matching it.
+    search: Option<Query>,
+    /// When the search being typed last changed, until it is applied.
+    search_typed_at: Option<Instant>,
+    match_count: Option<MatchCount>,
+    /// The last search through every table, with its hits.
+    table_search: Option<TableSearch>,
+    /// The last read of a table for duplicated values.
+    duplicates: Option<DuplicateScan>,
+    /// The last read of a table for its value sizes.
+    value_sizes: Option<SizeScan>,
+    /// The last read of the selected table's keys for the stats view.
+    key_stats: Option<KeyScan>,
+    key_range: Option<TableRange>,
+    /// The keys of a table grouped by prefix, as last browsed.
+    key_tree: Option<KeyTree>,
+    marks: Option<Marks>,
+    pin: Option<Pin>,
+    /// Keys bookmarked to jump back to, kept with the session.
+    bookmarks: Vec<Bookmark>,
+    /// Places left by selecting another table or jumping to a key.
+    history: History<Location>,
+    /// Table of the entries shown; the table list selection moves first.
+    shown_table: Option<String>,
+    /// List entries from the last key down.
+    reverse: bool,
+    /// Read entries added at the end of the selected table as they come;
+    /// see `Tui::toggle_follow`.
+    follow: bool,
+    followed_at: Instant,
+    /// Value decoder chosen per table; tables not listed use `Decoder::Auto`.
+    table_decoders: HashMap<String, Decoder>,
+    /// Key decoder chosen per table; tables not listed use `Decoder::Auto`.
+    table_key_decoders: HashMap<String, Decoder>,
+    /// Key tree grouping chosen per table; tables not listed are grouped by
+    /// the separator their keys have.
+    table_groupings: HashMap<String, Grouping>,
+    schemas: Schemas,
+    registry: Registry,
+    registry_path: PathBuf,
+    db_path: PathBuf,
+    /// Never take write transactions; see `--read-only`.
+    read_only: bool,
+    /// How writes are committed, switched with `D`.
+    durability: WriteDurability,
+    /// Browsing an in-memory copy of the database, locked by another
+    /// process or watched.
+    snapshot: bool,
+    /// Interval at which everything is read again, taking a new snapshot
+    /// first when browsing one; see `--watch`.
+    watch: Option<Duration>,
+    /// Interval `W` turns watching on with.
+    watch_interval: Duration,
+    watched_at: Instant,
+    /// Receives the new snapshot being taken, if any.
+    snapshot_rx: Option<Receiver<Result<Snapshot>>>,
+    /// When the file was last written before the snapshot was taken.
+    snapshot_modified: Option<SystemTime>,
+    /// Whether the file was written since the snapshot was taken.
+    changed_on_disk: bool,
+    changes_checked_at: Instant,
+    /// One-off message shown under the database info, e.g. after saving.
+    status_message: Option<String>,
+    /// Set by `:quit` and the like to end the run loop.
+    exit: Option<Exit>,
+    /// Titles of the open databases, for the database tabs.
+    databases: Vec<String>,
+    /// Tab of this database among them.
+    database_index: usize,
+    /// Entries written in this session, most recent last, for `u` to undo.
+    undo: Vec<Change>,
+    /// Changes undone, most recent last, until something else is written.
+    redo: Vec<Change>,
+    /// Entry counts shown in the table list, filled in by `count_tables`.
+    table_counts: HashMap<String, u64>,
+    count_rx: Option<Receiver<(String, u64)>>,
+    /// Storage stats last computed by `refresh_stats`.
+    stats: Option<StorageSummary>,
+    /// Per-table stats and types last computed by `refresh_stats`.
+    table_summaries: Vec<TableSummary>,
+    /// Column the tables of the stats view are sorted by.
+    stats_sort: StatsSort,
+    /// Receives the stats being computed, if a refresh is in flight.
+    stats_rx: Option<Receiver<Result<Stats>>>,
+    /// Log lines shown in the log view.
+    log: LogBuffer,
+    /// `LogBuffer::written` as of the last check for new lines.
+    log_written: u64,
+    stats_refreshed_at: Instant,
+    /// Ids of the persistent savepoints, as of the last `reload_savepoints`.
+    persistent_savepoints: Vec<u64>,
+    /// Savepoints taken in this session, oldest first. They go before `db`
+    /// so they are dropped first.
+    ephemeral_savepoints: Vec<EphemeralSavepoint>,
+    /// Selected row of the savepoints view.
+    savepoint_selected: usize,
+    /// Database the diff view compares with.
+    diff_path: Option<PathBuf>,
+    /// Tables as last compared by `refresh_diff`, or why that failed.
+    diff: Option<std::result::Result<Vec<DiffTable>, String>>,
+    /// Receives the comparison being made, if one is in flight.
+    diff_rx: Option<Receiver<Result<Vec<DiffTable>>>>,
+    /// Selected row of the diff view.
+    diff_selected: usize,
+    /// Table shown side by side in the diff view, if one is.
+    comparison: Option<Comparison>,
+    /// Receives the rows of the comparison being read, if any.
+    comparison_rx: Option<Receiver<Result<ComparedRows>>>,
+    db: Arc<Database>,
+}
+
+/// How long the event loop waits for input before checking on background
+/// work.
+const TICK_RATE: Duration = Duration::from_millis(250);
+
+/// How often the database is tried again while another process locks it.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Input wait while entries of the selected table are being read.
+const LOAD_TICK_RATE: Duration = Duration::from_millis(25);
+
+/// Keys and groups listed at most under a group of the key tree.
+const MAX_TREE_CHILDREN: usize = 1000;
+
+/// Pause in typing a search after which it is applied.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// How long storage stats are reused before they are recomputed.
+const STATS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often a snapshot is checked against the file it was taken from.
+const CHANGE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often tail mode reads the entries added after the last one.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Entries read from the end of a table when tail mode starts.
+const TAIL_ENTRIES: usize = 200;
+
+/// How often watch mode re-reads the database unless `--watch` says
+/// otherwise.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Upper bound on entries read from the selected table.
+const MAX_LOADED_ENTRIES: usize = 1000;
+
+/// Percent of the screen `<` and `>` move the split by.
+const SPLIT_STEP: i16 = 5;
+
+/// Entries moved per step of the mouse wheel.
+const MOUSE_SCROLL_ROWS: isize = 3;
+
+/// Entries sent at a time by the thread reading the selected table.
+const LOAD_BATCH_SIZE: usize = 100;
+
+impl Tui {
+    /// Opens the database at `db_path` with `table`, or the one selected
+    /// when it was last closed, selected. When it is compared with the one
+    /// at `diff`, the diff view comes first.
+    pub fn new(
+        db_path: &Path,
+        table: Option<&str>,
+        diff: Option<&Path>,
+        read_only: bool,
+        schemas: Schemas,
+        config: Config,
+        log: LogBuffer,
+    ) -> Result<Self> {
+        info!("Initializing Tui with database at {:?}", db_path);
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        let path = db_path.to_path_buf();
+        // Watching leaves the file to the process writing it, reading
+        // snapshots of it instead. So does read-only mode: redb marks any
+        // file it opens as in use, and repairs one that wasn't closed
+        // cleanly, both of which write to it.
+        let snapshot = config.watch.is_some() || read_only;
+        // Taken first, so a commit during the copy shows up as a change.
+        let snapshot_modified = modified_time(db_path);
+        let opened =
+            open_database(&mut terminal, &config.theme, move |on_repair| {
+                match snapshot {
+                    true => database::open_snapshot(&path, on_repair),
+                    false => database::open(&path, on_repair),
+                }
+            });
+        let (db, snapshot) = match opened {
+            Ok(db) => (db, snapshot),
+            Err(AppError::DatabaseLocked(_)) => {
+                wait_for_lock(&mut terminal, &config.theme, db_path)?
+            }
+            Err(e) => return Err(e),
+        };
+        let read_only = read_only || snapshot;
+        let db = Arc::new(db);
+        let table_names = crate::database::get_table_names(&db)?;
+        let selected = match table {
+            Some(name) => table_names
+                .iter()
+                .position(|t| t == name)
+                .ok_or_else(|| AppError::TableNotFound(name.to_string()))?,
+            None => 0,
+        };
+        let mut list_state = ListState::default();
+        list_state.select(Some(selected));
+
+        let db_properties = DbProperties {
+            file_size: fs::metadata(db_path)?.len(),
+            num_tables: table_names.len(),
+        };
+
+        let registry_path = Registry::sidecar_path(db_path);
+        let registry = Registry::load(&registry_path)?;
+        info!(
+            "Loaded {} table schemas from {:?}",
+            registry.tables.len(),
+            registry_path
+        );
+
+        let state_path = State::default_path();
+        let state = match &state_path {
+            Some(path) => State::load(path)?,
+            None => State::default(),
+        };
+
+        info!("Tui initialized successfully");
+        debug!("Database properties: {:?}", db_properties);
+
+        let mut tui = Self {
+            db,
+            terminal,
+            table_names,
+            table_filter: None,
+            list_state,
+            entry_state: ListState::default(),
+            focus: Focus::Tables,
+            mode: Mode::Normal,
+            db_properties,
+            selected_table_content: Vec::new(),
+            selected_table_types: None,
+            tab: Tab::Data,
+            tab_scroll: 0,
+            view_area: Rect::default(),
+            zoomed: false,
+            tables_hidden: false,
+            pending_key: None,
+            keymap: config.keymap,
+            theme: config.theme,
+            other_theme: config.theme.opposite(),
+            split: config
+                .split
+                .or(state.split)
+                .unwrap_or(layout::DEFAULT_SPLIT),
+            layout_mode: config.layout.or(state.layout).unwrap_or_default(),
+            state_path,
+            tables_area: Rect::default(),
+            entries_area: Rect::default(),
+            jumped: false,
+            selected_table_complete: false,
+            load: None,
+            search: None,
+            search_typed_at: None,
+            match_count: None,
+            table_search: None,
+            duplicates: None,
+            value_sizes: None,
+            key_stats: None,
+            key_range: None,
+            key_tree: None,
+            marks: None,
+            pin: None,
+            bookmarks: Vec::new(),
+            history: History::default(),
+            shown_table: None,
+            reverse: false,
+            follow: false,
+            followed_at: Instant::now(),
+            table_decoders: HashMap::new(),
+            table_key_decoders: HashMap::new(),
+            table_groupings: HashMap::new(),
+            schemas,
+            registry,
+            registry_path,
+            db_path: db_path.to_path_buf(),
+            read_only,
+            durability: config.durability,
+            snapshot,
+            watch: config.watch,
+            watch_interval: config.watch.unwrap_or(WATCH_INTERVAL),
+            watched_at: Instant::now(),
+            snapshot_rx: None,
+            snapshot_modified,
+            changed_on_disk: false,
+            changes_checked_at: Instant::now(),
+            status_message: None,
+            exit: None,
+            databases: Vec::new(),
+            database_index: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            table_counts: HashMap::new(),
+            count_rx: None,
+            stats: None,
+            table_summaries: Vec::new(),
+            stats_sort: StatsSort::default(),
+            stats_rx: None,
+            // Where `main` sets up the logger.
+            log_written: log.written(),
+            log,
+            stats_refreshed_at: Instant::now(),
+            persistent_savepoints: Vec::new(),
+            ephemeral_savepoints: Vec::new(),
+            savepoint_selected: 0,
+            diff_path: diff.map(Path::to_path_buf),
+            diff: None,
+            diff_rx: None,
+            diff_selected: 0,
+            comparison: None,
+            comparison_rx: None,
+        };
+        tui.update_state(|state| state.remember(db_path));
+        tui.apply_registry();
+        let session = state.session(db_path).cloned().unwrap_or_default();
+        let entry = session.entry;
+        let restores_selection = table.is_none() && session.table.is_some();
+        tui.restore_session(session, table.is_none());
+        tui.update_selected_table_content();
+        if let Some(load) = tui.load.as_mut().filter(|_| restores_selection) {
+            load.then = AfterLoad::Select(Some(entry));
+        }
+        tui.count_tables();
+        tui.refresh_stats();
+        if diff.is_some() {
+            tui.show_tab(Tab::Diff);
+        }
+        Ok(tui)
+    }
+
+    /// Runs until the user quits or turns to another database.
+    fn run(&mut self) -> Result<Exit> {
+        info!("Starting Tui run loop");
+        // Another database may have been on screen.
+        self.terminal.clear()?;
+        let mut redraw = true;
+        loop {
+            redraw |= self.receive_stats();
+            redraw |= self.receive_diff();
+            redraw |= self.receive_compared_rows();
+            if self.stats_refreshed_at.elapsed() >= STATS_REFRESH_INTERVAL {
+                self.refresh_stats();
+            }
+            if let Some(interval) = self.watch {
+                if self.watched_at.elapsed() >= interval {
+                    self.watch_tick();
+                }
+            }
+            if self.snapshot
+                && self.watch.is_none()
+                && self.changes_checked_at.elapsed() >= CHANGE_CHECK_INTERVAL
+            {
+                redraw |= self.check_for_changes();
+            }
+            redraw |= self.receive_snapshot();
+            if self.follow
+                && self.load.is_none()
+                && self.followed_at.elapsed() >= FOLLOW_INTERVAL
+            {
+                self.follow_tick();
+            }
+            if self
+                .pin
+                .as_ref()
+                .is_some_and(|p| p.read_at.elapsed() >= TICK_RATE)
+            {
+                redraw |= self.read_pin();
+            }
+            redraw |= self.receive_counts();
+            redraw |= self.search_tick();
+            redraw |= self.receive_match_count();
+            redraw |= self.receive_hits();
+            redraw |= self.receive_duplicates();
+            redraw |= self.receive_value_sizes();
+            redraw |= self.receive_key_stats();
+            redraw |= self.receive_log();
+            // Keep the spinner turning while entries load.
+            redraw |= self.receive_entries() || self.load.is_some();
+            if redraw {
+                self.draw()?;
+                redraw = false;
+            }
+            // Wake up periodically to pick up background results, more often
+            // while entries stream in or a search waits to be applied.
+            let timeout = match (&self.load, self.search_typed_at) {
+                (None, None) => TICK_RATE,
+                _ => LOAD_TICK_RATE,
+            };
+            if !event::poll(timeout)? {
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Mouse(mouse) => {
+                    redraw = self.handle_mouse(mouse);
+                    continue;
+                }
+                // Resizes and the like only need a redraw.
+                _ => {
+                    redraw = true;
+                    continue;
+                }
+            };
+            redraw = true;
+            self.status_message = None;
+            if !matches!(self.mode, Mode::Normal) {
+                if let Err(e) = self.handle_modal_key(key) {
+                    self.show_error(e.to_string());
+                }
+                if let Some(exit) = self.exit.take() {
+                    return Ok(exit);
+                }
+                continue;
+            }
+            let key = Key::from_event(&key);
+            let action = match self.pending_key.take() {
+                Some(first) => self.keymap.action(&[first, key]),
+                None if self.keymap.is_prefix(key) => {
+                    self.pending_key = Some(key);
+                    None
+                }
+                None => self.keymap.action(&[key]),
+            };
+            if let Some(action) = action {
+                if let Err(e) = self.run_action(action) {
+                    self.show_error(e.to_string());
+                }
+            }
+            if let Some(exit) = self.exit.take() {
+                return Ok(exit);
+            }
+        }
+    }
+
+    /// Reports a failure in a popup; the session goes on.
+    fn show_error(&mut self, message: String) {
+        error!("{}", message);
+        self.mode = Mode::Error { message };
+    }
+
+    fn run_action(&mut self, action: Action) -> Result<()> {
+        debug!("Running action {:?}", action);
+        if self.tab != Tab::Data && !self.run_view_action(action) {
+            return Ok(());
+        }
+        let entries_focused = self.focus == Focus::Entries;
+        match action {
+            Action::HalfPageDown => {
+                self.move_entry_selection(self.page_size() / 2)
+            }
+            Action::HalfPageUp => {
+                self.move_entry_selection(-(self.page_size() / 2))
+            }
+            Action::Quit => {
+                info!("User requested exit");
+                self.exit = Some(Exit::Quit);
+            }
+            Action::NextDatabase if self.databases.len() > 1 => {
+                self.exit = Some(Exit::Switch(1));
+            }
+            Action::PreviousDatabase if self.databases.len() > 1 => {
+                self.exit = Some(Exit::Switch(-1));
+            }
+            Action::NextDatabase | Action::PreviousDatabase => {}
+            // A hidden table list can't take focus.
+            Action::ToggleFocus | Action::FocusTables if self.tables_hidden => {}
+            Action::ToggleFocus => {
+                self.focus = match self.focus {
+                    Focus::Tables => Focus::Entries,
+                    Focus::Entries => Focus::Tables,
+                };
+            }
+            Action::FocusTables => self.focus = Focus::Tables,
+            Action::ToggleTables => {
+                self.tables_hidden = !self.tables_hidden;
+                if self.tables_hidden {
+                    self.focus = Focus::Entries;
+                }
+            }
+            Action::FocusEntries => self.focus = Focus::Entries,
+            Action::Down if entries_focused => self.entry_state.select_next(),
+            Action::Up if entries_focused => self.entry_state.select_previous(),
+            Action::Down => self.next(),
+            Action::Up => self.previous(),
+            Action::First => self.select_edge(false),
+            Action::Last => self.select_edge(true),
+            Action::PageDown => self.move_entry_selection(self.page_size()),
+            Action::PageUp => self.move_entry_selection(-(self.page_size())),
+            Action::Help => self.mode = Mode::Help { scroll: 0 },
+            Action::CommandLine => {
+                self.mode = Mode::Command {
+                    input: Input::default(),
+                    error: None,
+                }
+            }
+            Action::ShowEntry => {
+                if entries_focused && self.selected_entry().is_some() {
+                    self.mode = Mode::Detail { scroll: 0 }
+                }
+            }
+            Action::Zoom => self.zoomed = !self.zoomed,
+            Action::Edit => self.start_edit(),
+            Action::EditExternally => self.edit_externally()?,
+            Action::CopyKey => self.copy_entry(false),
+            Action::CopyValue => self.copy_entry(true),
+            Action::FilterTables => {
+                self.tables_hidden = false;
+                self.table_filter.get_or_insert_with(Input::default);
+                self.mode = Mode::TableFilter;
+            }
+            Action::PinKey => self.toggle_pin(),
+            Action::Bookmark => self.toggle_bookmark(),
+            Action::Bookmarks => self.mode = Mode::Bookmarks { selected: 0 },
+            Action::KeyTree => self.show_key_tree(),
+            Action::Duplicates => self.show_duplicates(),
+            Action::ValueSizes => self.show_value_sizes(),
+            Action::BTree if self.selected_table_name().is_some() => {
+                self.mode = Mode::BTree
+            }
+            Action::BTree => {}
+            Action::Back => self.go_back(false),
+            Action::Forward => self.go_back(true),
+            Action::Mark => self.toggle_mark(),
+            Action::MarkRange => self.mark_range(),
+            Action::Follow => self.toggle_follow()?,
+            Action::KeyRange => {
+                let text = self
+                    .active_range()
+                    .map_or(String::new(), |r| r.text.clone());
+                self.mode = Mode::KeyRange {
+                    input: Input::new(text),
+                    error: None,
+                }
+            }
+            Action::Cancel if self.load.is_some() => self.cancel_load(),
+            Action::Cancel if self.active_marks().is_some() => {
+                self.marks = None;
+            }
+            Action::Cancel => {
+                if self.active_range().is_some() {
+                    self.key_range = None;
+                    self.update_selected_table_content();
+                }
+            }
+            Action::RefreshStats if self.changed_on_disk => self.take_snapshot(),
+            Action::RefreshStats => {
+                self.refresh_stats();
+                if self.tab == Tab::Stats {
+                    self.read_key_stats();
+                }
+            }
+            Action::Reverse => {
+                self.reverse = !self.reverse;
+                self.update_selected_table_content();
+            }
+            Action::GotoKey => {
+                self.mode = Mode::GotoKey {
+                    input: Input::default(),
+                    error: None,
+                }
+            }
+            Action::Search => {
+                self.mode = Mode::Search {
+                    input: Input::default(),
+                }
+            }
+            Action::SearchAll => {
+                let text = self.table_search.as_ref().map(|s| s.query.text());
+                self.mode = Mode::SearchAll {
+                    input: Input::new(text.unwrap_or_default()),
+                    error: None,
+                }
+            }
+            Action::Delete if entries_focused => self.start_delete(),
+            Action::Delete => self.start_drop_table(),
+            Action::NewTable => self.start_new_table(),
+            Action::Rename => self.start_rename(),
+            Action::Truncate => self.start_truncate(),
+            Action::Undo => self.undo_write(true)?,
+            Action::Redo => self.undo_write(false)?,
+            Action::NextValueDecoder => self
+                .set_value_decoder(self.value_decoder().next(&Decoder::VALUES)),
+            Action::NextKeyDecoder => {
+                self.set_key_decoder(self.key_decoder().next(&Decoder::KEYS))
+            }
+            Action::NextProtoMessage => self.next_proto_message(),
+            Action::Decompress => {
+                self.schemas.decompress = !self.schemas.decompress;
+                self.status_message = Some(if self.schemas.decompress {
+                    "Unpacking compressed values".into()
+                } else {
+                    "Showing compressed values as stored".into()
+                });
+            }
+            Action::SaveDecoders => self.save_registry(),
+            Action::DataTab => self.show_tab(Tab::Data),
+            Action::StatsTab => self.show_tab(Tab::Stats),
+            Action::SchemaTab => self.show_tab(Tab::Schema),
+            Action::LogsTab => self.show_tab(Tab::Logs),
+            Action::SavepointsTab => self.show_tab(Tab::Savepoints),
+            Action::DiffTab => self.show_tab(Tab::Diff),
+            Action::Savepoint => self.take_savepoint(false),
+            Action::PersistentSavepoint => self.take_savepoint(true),
+            Action::ShrinkTables => self.resize_tables(-SPLIT_STEP),
+            Action::GrowTables => self.resize_tables(SPLIT_STEP),
+            Action::ToggleWatch => {
+                self.watch = match self.watch {
+                    Some(_) => None,
+                    None => Some(self.watch_interval),
+                };
+                self.watched_at = Instant::now();
+                self.status_message = Some(match self.watch {
+                    Some(interval) => {
+                        format!("Watching every {}s", interval.as_secs())
+                    }
+                    None => "Stopped watching".into(),
+                });
+            }
+            Action::ToggleDurability => {
+                self.durability = self.durability.toggled();
+                info!("Write durability: {}", self.durability.name());
+                self.status_message = Some(format!(
+                    "Writes: {} durability",
+                    self.durability.name()
+                ));
+            }
+            Action::ToggleLayout => {
+                self.layout_mode = self.layout_mode.toggled();
+                self.status_message =
+                    Some(format!("Layout: {}", self.layout_mode.name()));
+                let layout = self.layout_mode;
+                self.update_state(|state| state.layout = Some(layout));
+            }
+            Action::CheckIntegrity => self.check_integrity()?,
+            Action::Compact => self.compact()?,
+            Action::ToggleTheme => {
+                std::mem::swap(&mut self.theme, &mut self.other_theme);
+                let name = if self.theme.light { "Light" } else { "Dark" };
+                self.status_message = Some(format!("{} theme", name));
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let entries = self.visible_entries();
+        let marks = self.active_marks();
+        let marked: Vec<bool> = entries
+            .iter()
+            .map(|(i, _, _)| {
+                marks.is_some_and(|m| {
+                    m.keys.contains(&self.selected_table_content[*i].0)
+                })
+            })
+            .collect();
+        let marked_count = marks.map_or(0, |m| m.keys.len());
+        // Values of builtin types are never packed.
+        let opaque = self
+            .selected_table_types
+            .as_ref()
+            .is_some_and(|t| database::is_opaque_type(&t.value.name));
+        let packings: Vec<Option<&str>> = entries
+            .iter()
+            .map(|(i, _, _)| {
+                let value = &self.selected_table_content[*i].1;
+                opaque
+                    .then(|| database::detect_packing(value))
+                    .flatten()
+                    .map(Packing::label)
+            })
+            .collect();
+        let key_value_pairs: Vec<_> = entries
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect();
+        let decoder_label = self.decoder_label();
+        let table_names: Vec<String> = self
+            .visible_tables()
+            .into_iter()
+            .map(|name| match self.table_counts.get(name) {
+                Some(1) => format!("{} (1 entry)", name),
+                Some(count) => format!(
+                    "{} ({} entries)",
+                    name,
+                    layout::group_thousands(*count)
+                ),
+                None => name.to_string(),
+            })
+            .collect();
+        let table_list_title = match self.table_filter {
+            Some(_) => format!(
+                "ReDB Tables ({}/{})",
+                table_names.len(),
+                self.table_names.len()
+            ),
+            None => "ReDB Tables".to_string(),
+        };
+        let key_range = self.active_range().map(|r| r.text.clone());
+        let selected_table = self
+            .selected_table_name()
+            .unwrap_or("No table selected")
+            .to_string();
+        // The loaded entries are the start of the table unless narrowed down
+        // or jumped into, so the scrollbar can account for the rest.
+        let listing_total = match (&self.search, key_range.is_some(), self.jumped)
+        {
+            (None, false, false) => self
+                .table_counts
+                .get(&selected_table)
+                .map(|&count| count as usize),
+            _ => None,
+        };
+        // Owned, as the detail view is drawn while the terminal is borrowed.
+        let detail_entry = match self.mode {
+            Mode::Detail { .. } => self.selected_entry().cloned().zip(
+                self.selected_table_types
+                    .as_ref()
+                    .map(|t| (t.key.name.clone(), t.value.name.clone())),
+            ),
+            _ => None,
+        };
+        let key_decoder_label = self.key_decoder().label();
+        let value_decoder_label = self.value_decoder_label();
+        let (help_state, help_bindings) = match self.mode {
+            Mode::Help { .. } => (self.session_summary(), self.keymap.help()),
+            _ => Default::default(),
+        };
+        let (hits_title, hit_items) = match self.mode {
+            Mode::SearchHits { .. } => self.hits_list(),
+            _ => Default::default(),
+        };
+        let (duplicates_title, duplicate_lines) = match self.mode {
+            Mode::Duplicates { .. } => self.duplicates_list(),
+            _ => Default::default(),
+        };
+        let (sizes_title, sizes_summary, size_bars) = match self.mode {
+            Mode::ValueSizes => self.value_sizes_view(),
+            _ => Default::default(),
+        };
+        let (btree_title, btree_summary, btree_bars) = match self.mode {
+            Mode::BTree => self.btree_view(),
+            _ => Default::default(),
+        };
+        let (tree_title, tree_lines) = match (&self.mode, &self.key_tree) {
+            (Mode::KeyTree, Some(tree)) => (
+                format!(
+                    "Keys of {} by {} (l/h: open/close, Enter: show, Esc: close)",
+                    tree.table,
+                    tree.grouping.label()
+                ),
+                tree.lines(),
+            ),
+            _ => Default::default(),
+        };
+        // A search reading on through the table shows how far it got.
+        let search_progress = match &self.load {
+            Some(Load {
+                then: AfterLoad::NextMatch(_),
+                ..
+            }) => self
+                .table_counts
+                .get(&selected_table)
+                .map(|total| (self.selected_table_content.len() as u64, *total)),
+            _ => None,
+        };
+        let comparison = match self.tab {
+            Tab::Diff => self.comparison_view(),
+            _ => None,
+        };
+        let allocation = match self.tab {
+            Tab::Stats => self.allocation_view(),
+            _ => None,
+        };
+        let (report_summary, report_rows) = match self.tab {
+            Tab::Stats => self.stats_report(),
+            Tab::Schema => (Vec::new(), self.schema_rows()),
+            Tab::Savepoints => self.savepoints_report(),
+            Tab::Diff if comparison.is_none() => self.diff_report(),
+            _ => Default::default(),
+        };
+        let log_lines = match self.tab {
+            Tab::Logs => self.log.lines(),
+            _ => Vec::new(),
+        };
+        let log_title = match self.log.path() {
+            Some(path) => format!("Log {}", path.display()),
+            None => "Log (not written to a file)".to_string(),
+        };
+        let pin = self.pin_view();
+        self.terminal.draw(|frame| {
+            let size = frame.area();
+            let pin_height = if pin.is_some() { 3 } else { 0 };
+            // With several databases open, their tabs go above the others.
+            let databases_height = u16::from(self.databases.len() > 1);
+            let [databases_area, tabs_area, pin_area, body] = Layout::vertical([
+                Constraint::Length(databases_height),
+                Constraint::Length(1),
+                Constraint::Length(pin_height),
+                Constraint::Min(0),
+            ])
+            .areas(size);
+            let (mut left, mut right, bottom) =
+                layout::get_layout(body, self.layout_mode, self.split);
+            let mut top = left.union(right);
+            if self.tables_hidden {
+                (left, right) = (Rect::default(), top);
+            }
+            // Zoomed in, the entries pane or the detail view take up the
+            // whole screen.
+            if self.zoomed {
+                (left, right, top) = (Rect::default(), size, size);
+            } else {
+                let titles = Tab::ALL.map(Tab::title);
+                let selected = Tab::ALL.iter().position(|t| *t == self.tab);
+                layout::render_tabs(
+                    frame,
+                    &self.theme,
+                    tabs_area,
+                    &titles,
+                    selected.unwrap_or(0),
+                );
+                let databases: Vec<&str> =
+                    self.databases.iter().map(String::as_str).collect();
+                layout::render_tabs(
+                    frame,
+                    &self.theme,
+                    databases_area,
+                    &databases,
+                    self.database_index,
+                );
+                if self.changed_on_disk {
+                    layout::render_banner(
+                        frame,
+                        &self.theme,
+                        tabs_area,
+                        "Database changed on disk, press r to reload",
+                    );
+                }
+                if let Some((title, value, note)) = &pin {
+                    layout::render_pin(
+                        frame,
+                        &self.theme,
+                        pin_area,
+                        title,
+                        value,
+                        note,
+                    );
+                }
+            }
+            // Outside the data browser, the view takes the room of both
+            // panes.
+            if self.tab != Tab::Data {
+                self.view_area = top;
+                (left, right) = (Rect::default(), Rect::default());
+            }
+            match (self.tab, &comparison) {
+                (Tab::Data, _) => {}
+                (Tab::Diff, Some((titles, rows, selected))) => {
+                    let comparison = layout::Comparison {
+                        titles: [&titles[0], &titles[1]],
+                        rows,
+                        selected: *selected,
+                    };
+                    layout::render_comparison(
+                        frame,
+                        &self.theme,
+                        top,
+                        &comparison,
+                        &mut self.tab_scroll,
+                    );
+                }
+                (Tab::Stats | Tab::Schema | Tab::Savepoints | Tab::Diff, _) => {
+                    let mut top = top;
+                    if let Some((title, parts)) = &allocation {
+                        let [allocation_area, rest] = Layout::vertical([
+                            Constraint::Length(layout::ALLOCATION_HEIGHT),
+                            Constraint::Min(0),
+                        ])
+                        .areas(top);
+                        layout::render_allocation(
+                            frame,
+                            &self.theme,
+                            allocation_area,
+                            title,
+                            parts,
+                        );
+                        top = rest;
+                    }
+                    let report = layout::Report {
+                        title: self.tab.title(),
+                        summary: &report_summary,
+                        header: match self.tab {
+                            Tab::Stats => STATS_HEADER,
+                            Tab::Schema => SCHEMA_HEADER,
+                            Tab::Diff => DIFF_HEADER,
+                            _ => SAVEPOINTS_HEADER,
+                        },
+                        rows: &report_rows,
+                        selected: match self.tab {
+                            Tab::Savepoints => Some(self.savepoint_selected),
+                            Tab::Diff => Some(self.diff_selected),
+                            _ => None,
+                        },
+                    };
+                    layout::render_report(
+                        frame,
+                        &self.theme,
+                        top,
+                        &report,
+                        &mut self.tab_scroll,
+                    );
+                }
+                (Tab::Logs, _) => layout::render_log(
+                    frame,
+                    &self.theme,
+                    top,
+                    &log_title,
+                    &log_lines,
+                    &mut self.tab_scroll,
+                ),
+            }
+
+            if let Some(filter) = &self.table_filter {
+                let editing = matches!(self.mode, Mode::TableFilter);
+                left = layout::render_filter_box(
+                    frame,
+                    &self.theme,
+                    left,
+                    filter,
+                    editing,
+                );
+            }
+            self.tables_area = left;
+            self.entries_area = right;
+            layout::render_table_list(
+                frame,
+                &self.theme,
+                left,
+                &table_list_title,
+                &table_names,
+                &mut self.list_state,
+            );
+
+            let pane = layout::EntriesPane {
+                table: &selected_table,
+                range: key_range.as_deref(),
+                reverse: self.reverse,
+                decoder: &decoder_label,
+                pairs: &key_value_pairs,
+                marked: &marked,
+                packings: &packings,
+                marked_count,
+                focused: self.focus == Focus::Entries,
+                search: self.search.as_ref(),
+                matches: self
+                    .match_count
+                    .as_ref()
+                    .filter(|c| Some(&c.query) == self.search.as_ref())
+                    .map(|c| (c.matches, c.done)),
+                total: listing_total,
+                // Reads of tail mode come too often to be worth showing.
+                loading: self
+                    .load
+                    .as_ref()
+                    .filter(|l| !matches!(l.then, AfterLoad::Follow(_)))
+                    .map(|l| l.started.elapsed()),
+                following: self.follow,
+            };
+            layout::render_key_value_pairs(
+                frame,
+                &self.theme,
+                right,
+                &pane,
+                &mut self.entry_state,
+            );
+
+            let mut status = format!(
+                "Tables: {} | DB Size: {}",
+                self.db_properties.num_tables,
+                self.db_properties.file_size.human_count_bytes(),
+            );
+            match &self.stats {
+                Some(stats) => status.push_str(&format!(
+                    " Height: {} Pages: {} Stored: {} Meta: {} Frag: {}",
+                    stats.tree_height,
+                    stats
+                        .allocated_pages
+                        .map_or("-".to_string(), |p| p.to_string()),
+                    stats.stored_bytes.human_count_bytes(),
+                    stats.metadata_bytes.human_count_bytes(),
+                    stats.fragmented_bytes.human_count_bytes(),
+                )),
+                None => status.push_str(" | loading stats"),
+            }
+            // Read-only sessions browse snapshots as well.
+            if self.snapshot {
+                status.push_str(" | read-only snapshot");
+            } else if self.durability == WriteDurability::Eventual {
+                status.push_str(" | eventual writes");
+            }
+            if let Some(interval) = self.watch {
+                status.push_str(&format!(
+                    " | watching every {}s",
+                    interval.as_secs()
+                ));
+            }
+            if let Some(message) = &self.status_message {
+                status.push('\n');
+                status.push_str(message);
+            }
+            if !self.zoomed {
+                layout::render_bottom_status(frame, &self.theme, bottom, &status);
+            }
+
+            match &mut self.mode {
+                Mode::Normal => {}
+                Mode::Browse { browser } => {
+                    render_browser(frame, &self.theme, browser)
+                }
+                Mode::Bookmarks { selected } => {
+                    let items: Vec<String> = self
+                        .bookmarks
+                        .iter()
+                        .map(|b| format!("{}  {}", b.table, b.label))
+                        .collect();
+                    layout::render_list_popup(
+                        frame,
+                        &self.theme,
+                        "Bookmarks (Enter: jump, d: delete, Esc: close)",
+                        &items,
+                        *selected,
+                        "No bookmarks yet; m bookmarks the selected key",
+                    );
+                }
+                Mode::Help { scroll } => layout::render_help(
+                    frame,
+                    &self.theme,
+                    &help_state,
+                    &help_bindings,
+                    cmdline::COMMANDS,
+                    scroll,
+                ),
+                Mode::Detail { scroll } => {
+                    let row = self.entry_state.selected().unwrap_or(0);
+                    if let (
+                        Some(((key_bytes, value_bytes), (key_type, value_type))),
+                        Some((key, value)),
+                    ) = (&detail_entry, key_value_pairs.get(row))
+                    {
+                        let detail = layout::EntryDetail {
+                            table: &selected_table,
+                            key_type,
+                            value_type,
+                            key_decoder: key_decoder_label,
+                            value_decoder: &value_decoder_label,
+                            key,
+                            value,
+                            key_bytes,
+                            value_bytes,
+                            value_packing: packings.get(row).copied().flatten(),
+                        };
+                        layout::render_entry_detail(
+                            frame,
+                            &self.theme,
+                            top,
+                            &detail,
+                            scroll,
+                        );
+                    }
+                }
+                Mode::Edit {
+                    format,
+                    input,
+                    error,
+                    ..
+                } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    &format!("Edit value [{}]", format.label()),
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::NewTable {
+                    name,
+                    key,
+                    input,
+                    error,
+                } => {
+                    let title = match (name, key) {
+                        (None, _) => "New table name".to_string(),
+                        (Some(name), None) => format!("Key type of {}", name),
+                        (Some(name), Some(_)) => {
+                            format!("Value type of {}", name)
+                        }
+                    };
+                    layout::render_input_popup(
+                        frame,
+                        &self.theme,
+                        &title,
+                        input,
+                        error.as_deref(),
+                    )
+                }
+                Mode::TableFilter => {}
+                Mode::Search { input } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    "Search (Enter for next match)",
+                    input,
+                    None,
+                ),
+                Mode::SearchAll { input, error } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    "Search all tables (TEXT or /PATTERN/)",
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::SearchHits { selected } => {
+                    layout::render_list_popup(
+                        frame,
+                        &self.theme,
+                        &hits_title,
+                        &hit_items,
+                        *selected,
+                        "No hits",
+                    );
+                }
+                Mode::Duplicates { selected } => {
+                    let empty = match &self.duplicates {
+                        Some(DuplicateScan { found: None, .. }) => "Reading…",
+                        _ => "No value is stored under more than one key",
+                    };
+                    layout::render_list_popup(
+                        frame,
+                        &self.theme,
+                        &duplicates_title,
+                        &duplicate_lines,
+                        *selected,
+                        empty,
+                    );
+                }
+                Mode::ValueSizes => {
+                    let empty = match &self.value_sizes {
+                        Some(SizeScan { found: None, .. }) => "Reading…",
+                        _ => "No entries",
+                    };
+                    let histogram = layout::Histogram {
+                        title: &sizes_title,
+                        summary: &sizes_summary,
+                        bars: &size_bars,
+                        empty,
+                    };
+                    layout::render_histogram_popup(
+                        frame,
+                        &self.theme,
+                        &histogram,
+                    );
+                }
+                Mode::BTree => {
+                    let histogram = layout::Histogram {
+                        title: &btree_title,
+                        summary: &btree_summary,
+                        bars: &btree_bars,
+                        empty: "The table has no pages",
+                    };
+                    layout::render_histogram_popup(
+                        frame,
+                        &self.theme,
+                        &histogram,
+                    );
+                }
+                Mode::KeyTree => layout::render_list_popup(
+                    frame,
+                    &self.theme,
+                    &tree_title,
+                    &tree_lines,
+                    self.key_tree.as_ref().map_or(0, |tree| tree.selected),
+                    "No keys",
+                ),
+                Mode::KeyRange { input, error } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    "Key range (PREFIX* or START..END)",
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::Command { input, error } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    "Command (:help lists them)",
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::GotoKey { input, error } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    "Go to key (0x prefix for hex)",
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::Rename {
+                    table,
+                    input,
+                    error,
+                } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    &format!("Rename {} to", table),
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::TypedConfirm {
+                    message,
+                    input,
+                    error,
+                    ..
+                } => layout::render_input_popup(
+                    frame,
+                    &self.theme,
+                    message,
+                    input,
+                    error.as_deref(),
+                ),
+                Mode::Confirm { message, .. }
+                | Mode::ConfirmSavepoint { message, .. } => {
+                    layout::render_confirm_popup(frame, &self.theme, message)
+                }
+                Mode::Error { message } => {
+                    layout::render_error_popup(frame, &self.theme, message)
+                }
+            }
+            if let Some((done, total)) = search_progress {
+                let title = format!("Searching {}, Esc to stop", selected_table);
+                layout::render_progress_bar(
+                    frame,
+                    &self.theme,
+                    right,
+                    &title,
+                    done,
+                    total,
+                );
+            }
+        })?;
+        Ok(())
+    }
+
+    fn next(&mut self) {
+        debug!("Moving to next item");
+        let len = self.visible_tables().len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.list_state.selected() {
+            Some(i) => {
+                if i >= len - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.list_state.select(Some(i));
+        self.update_selected_table_content();
+    }
+
+    fn previous(&mut self) {
+        debug!("Moving to previous item");
+        let len = self.visible_tables().len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.list_state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    len - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.list_state.select(Some(i));
+        self.update_selected_table_content();
+    }
+
+    fn update_selected_table_content(&mut self) {
+        let table = self.selected_table_name().map(str::to_string);
+        if table != self.shown_table {
+            if let Some(left) = self.location() {
+                self.history.visit(left);
+            }
+            self.shown_table = table;
+        }
+        self.list_entries(Bound::Unbounded, AfterLoad::Nothing);
+    }
+
+    /// Shows the entries of the selected table from `from` afresh.
+    fn list_entries(&mut self, from: Bound<Vec<u8>>, then: AfterLoad) {
+        self.follow = false;
+        self.selected_table_content.clear();
+        self.selected_table_types = None;
+        self.search = None;
+        self.jumped = !matches!(from, Bound::Unbounded);
+        self.entry_state = ListState::default().with_selected(Some(0));
+        self.load_entries(from, then);
+    }
+
+    /// Starts reading entries of the selected table on a background thread,
+    /// continuing from `from` in the listing order. Entries are appended to
+    /// `selected_table_content` by `receive_entries` as they arrive, so
+    /// large tables don't freeze drawing. A read still in flight is
+    /// cancelled.
+    fn load_entries(&mut self, from: Bound<Vec<u8>>, then: AfterLoad) {
+        self.load = None;
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            self.selected_table_complete = true;
+            return;
+        };
+        debug!("Loading entries of {} from {:?}", table, from);
+        let (start, end) = self.read_range(from.as_ref().map(Vec::as_slice));
+        let (start, end) = (start.map(<[u8]>::to_vec), end.map(<[u8]>::to_vec));
+        let (tx, rx) = mpsc::channel();
+        let db = Arc::clone(&self.db);
+        let reverse = self.reverse;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let result = database::scan_table_entries(
+                &db,
+                &table,
+                (
+                    start.as_ref().map(Vec::as_slice),
+                    end.as_ref().map(Vec::as_slice),
+                ),
+                reverse,
+                MAX_LOADED_ENTRIES,
+                LOAD_BATCH_SIZE,
+                |types, entries| {
+                    !token.load(Ordering::Relaxed)
+                        && tx
+                            .send(LoadEvent::Entries(types.clone(), entries))
+                            .is_ok()
+                },
+            );
+            if token.load(Ordering::Relaxed) {
+                debug!("Loading entries of {} cancelled", table);
+                return;
+            }
+            let _ = tx.send(LoadEvent::Done(result));
+        });
+        self.load = Some(Load {
+            rx,
+            then,
+            started: Instant::now(),
+            cancelled,
+            replace: false,
+        });
+    }
+
+    /// Reads the entries of the selected table again with the same row
+    /// selected. The entries shown stay until the new ones come in, so the
+    /// pane doesn't go blank each time watch mode re-reads it.
+    fn reload_entries(&mut self) {
+        let from = match (self.jumped, self.selected_table_content.first()) {
+            (true, Some((key, _))) => Bound::Included(key.clone()),
+            _ => Bound::Unbounded,
+        };
+        let then = if self.follow {
+            AfterLoad::Follow(self.at_last_entry())
+        } else {
+            AfterLoad::Select(self.entry_state.selected())
+        };
+        self.load_entries(from, then);
+        if let Some(load) = &mut self.load {
+            load.replace = true;
+        }
+    }
+
+    /// Stops the read of entries in flight, keeping what was read so far.
+    /// Whatever was waiting for the read, like a search for the next match,
+    /// is dropped with it.
+    fn cancel_load(&mut self) {
+        if self.load.take().is_some() {
+            info!("Cancelled loading entries");
+            self.selected_table_complete = false;
+            self.status_message = Some("Loading cancelled".into());
+        }
+    }
+
+    /// Takes the entries read so far by `load_entries`, finishing up once
+    /// the read is done. Returns whether anything arrived.
+    fn receive_entries(&mut self) -> bool {
+        let Some(load) = &mut self.load else {
+            return false;
+        };
+        let mut received = false;
+        let mut done = None;
+        for event in load.rx.try_iter() {
+            received = true;
+            if std::mem::take(&mut load.replace) {
+                self.selected_table_content.clear();
+            }
+            match event {
+                LoadEvent::Entries(types, entries) => {
+                    self.selected_table_types.get_or_insert(types);
+                    self.selected_table_content.extend(entries);
+                    // Drawing the empty pane dropped the selection.
+                    if self.entry_state.selected().is_none() {
+                        self.entry_state.select(Some(0));
+                    }
+                }
+                LoadEvent::Done(result) => done = Some(result),
+            }
+        }
+        // A search waiting for a match takes the first one read rather than
+        // waiting for the rest of the read.
+        if let Some(Load {
+            then: AfterLoad::NextMatch(matches),
+            ..
+        }) = self.load
+        {
+            if received && self.visible_entries().len() > matches {
+                self.entry_state.select(Some(matches));
+                if let Some(load) = &mut self.load {
+                    load.then = AfterLoad::Nothing;
+                }
+            }
+        }
+        let Some(result) = done else {
+            return received;
+        };
+        let Some(mut load) = self.load.take() else {
+            return received;
+        };
+        match result {
+            Ok(count) => {
+                debug!(
+                    "Loaded {} entries of {} in {:?}",
+                    count,
+                    self.selected_table_name().unwrap_or_default(),
+                    load.started.elapsed()
+                );
+                self.selected_table_complete = count < MAX_LOADED_ENTRIES;
+                let then = std::mem::replace(&mut load.then, AfterLoad::Nothing);
+                self.finish_load(then);
+            }
+            Err(e) => {
+                self.selected_table_complete = true;
+                self.show_error(format!("Failed to load entries: {}", e));
+            }
+        }
+        true
+    }
+
+    /// Carries out what was waiting for a read of entries to finish.
+    fn finish_load(&mut self, then: AfterLoad) {
+        match then {
+            AfterLoad::Nothing => {}
+            AfterLoad::Select(row) => {
+                let last = self.visible_entries().len().checked_sub(1);
+                self.entry_state.select(row.min(last));
+            }
+            AfterLoad::NextMatch(matches) => self.continue_search(matches),
+            AfterLoad::Follow(at_end) => {
+                // Entries read while following pile up; keep the newest.
+                let excess = self
+                    .selected_table_content
+                    .len()
+                    .saturating_sub(MAX_LOADED_ENTRIES);
+                if excess > 0 {
+                    self.selected_table_content.drain(..excess);
+                    self.jumped = true;
+                    let row = self.entry_state.selected().unwrap_or(0);
+                    self.entry_state.select(Some(row.saturating_sub(excess)));
+                }
+                if at_end {
+                    self.move_entry_selection(isize::MAX);
+                }
+            }
+            AfterLoad::Restore { row, offset } => {
+                let last = self.visible_entries().len().checked_sub(1);
+                self.entry_state.select(Some(row).min(last));
+                *self.entry_state.offset_mut() = offset;
+            }
+            AfterLoad::Goto(text) => {
+                if self.selected_table_content.is_empty() {
+                    let side = if self.reverse { "before" } else { "after" };
+                    self.status_message =
+                        Some(format!("No keys at or {} {}", side, text));
+                }
+            }
+        }
+    }
+
+    /// Opens the value editor on the selected entry.
+    fn start_edit(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        let (Some((key, value)), Some(types)) =
+            (self.selected_entry(), &self.selected_table_types)
+        else {
+            return;
+        };
+        let format =
+            EditFormat::for_value(self.value_decoder(), &types.value.name, value);
+        self.mode = Mode::Edit {
+            key: key.clone(),
+            format,
+            input: Input::new(format.render(&types.value.name, value)),
+            error: None,
+        };
+    }
+
+    /// Asks to confirm storing `value` under `key` in the selected table, or
+    /// says why the table can't take it.
+    fn confirm_value(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> std::result::Result<Mode, String> {
+        let table = self.selected_table_name().unwrap_or_default().to_string();
+        if let Some(types) = &self.selected_table_types {
+            database::check_writable(&table, types)
+                .and_then(|_| database::check_entry(types, &key, &value))
+                .map_err(|e| e.to_string())?;
+        }
+        let message = format!(
+            "Write {} bytes to {} under {}?",
+            value.len(),
+            table,
+            self.display_key(&key)
+        );
+        let op = WriteOp::Insert { table, key, value };
+        Ok(Mode::Confirm { message, op })
+    }
+
+    /// Opens the value of the selected entry in `$VISUAL` or `$EDITOR` with
+    /// the TUI suspended, then asks to write back what was saved.
+    fn edit_externally(&mut self) -> Result<()> {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return Ok(());
+        }
+        let (Some((key, value)), Some(types)) =
+            (self.selected_entry().cloned(), &self.selected_table_types)
+        else {
+            return Ok(());
+        };
+        // Found out before editing rather than after.
+        let table = self.selected_table_name().unwrap_or_default();
+        if let Err(e) = database::check_writable(table, types) {
+            self.show_error(format!("Can't edit: {}", e));
+            return Ok(());
+        }
+        let value_type = types.value.clone();
+        let type_name = value_type.name.clone();
+        let format =
+            EditFormat::for_value(self.value_decoder(), &type_name, &value);
+        let text = format.render(&type_name, &value);
+        let path = env::temp_dir().join(format!(
+            "redb-tui-{}.{}",
+            process::id(),
+            format.extension()
+        ));
+        fs::write(&path, &text)?;
+
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        // Allow editors that need arguments, like `code --wait`.
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        info!("Editing value in {}", editor);
+        let status = self.suspended(|| {
+            process::Command::new(program)
+                .args(words)
+                .arg(&path)
+                .status()
+        })?;
+        let edited = fs::read_to_string(&path);
+        if let Err(e) = fs::remove_file(&path) {
+            debug!("Failed to remove {:?}: {}", path, e);
+        }
+        let edited = match (status, edited) {
+            (Ok(status), _) if !status.success() => {
+                self.status_message = Some(format!(
+                    "{} exited with {}, nothing written",
+                    editor, status
+                ));
+                return Ok(());
+            }
+            (Ok(_), Ok(edited)) => edited,
+            (Err(e), _) | (_, Err(e)) => {
+                self.show_error(format!("Failed to run {}: {}", editor, e));
+                return Ok(());
+            }
+        };
+
+        // Editors tend to end the file with a newline the value didn't have.
+        let edited = match format {
+            EditFormat::Native | EditFormat::Hex => edited.trim(),
+            EditFormat::Text | EditFormat::Json if !text.ends_with('\n') => {
+                edited
+                    .strip_suffix("\r\n")
+                    .or_else(|| edited.strip_suffix('\n'))
+                    .unwrap_or(&edited)
+            }
+            EditFormat::Text | EditFormat::Json => &edited,
+        };
+        match format.encode(&value_type, edited) {
+            Ok(new_value) if new_value == value => {
+                self.status_message = Some("Value unchanged".into())
+            }
+            Ok(new_value) => match self.confirm_value(key, new_value) {
+                Ok(mode) => self.mode = mode,
+                Err(e) => self.show_error(format!("Not written: {}", e)),
+            },
+            Err(e) => self.show_error(format!("Not written: {}", e)),
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with the terminal handed back to the shell, e.g. for an
+    /// external editor, and redraws everything afterwards.
+    fn suspended<T>(&mut self, f: impl FnOnce() -> T) -> Result<T> {
+        disable_raw_mode()?;
+        io::stdout()
+            .execute(DisableMouseCapture)?
+            .execute(LeaveAlternateScreen)?;
+        let result = f();
+        enable_raw_mode()?;
+        io::stdout()
+            .execute(EnterAlternateScreen)?
+            .execute(EnableMouseCapture)?;
+        self.terminal.clear()?;
+        Ok(result)
+    }
+
+    /// Copies the key of the selected entry, or with `value` its value, to
+    /// the clipboard as decoded text. With entries marked, those of all of
+    /// them are copied instead, a line each.
+    fn copy_entry(&mut self, value: bool) {
+        let Some(types) = &self.selected_table_types else {
+            return;
+        };
+        let table = self.selected_table_name().unwrap_or_default();
+        let entries = match self.active_marks() {
+            Some(marks) => {
+                let keys = marks.keys.iter().map(Vec::as_slice);
+                match database::get_raw_entries(&self.db, table, keys) {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        self.show_error(format!("Failed to copy: {}", e));
+                        return;
+                    }
+                }
+            }
+            None => self.selected_entry().cloned().into_iter().collect(),
+        };
+        let (what, decoder, type_name) = if value {
+            ("value", self.value_decoder(), &types.value.name)
+        } else {
+            ("key", self.key_decoder(), &types.key.name)
+        };
+        let ctx = self.schemas.context(table);
+        let text = entries
+            .iter()
+            .map(|(key, value_bytes)| {
+                let bytes = if value { value_bytes } else { key };
+                json::to_text(&decoder.decode_node(&ctx, type_name, bytes))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let what = match entries.len() {
+            0 => return,
+            1 => what.to_string(),
+            n => format!("{} {}s", n, what),
+        };
+        self.status_message = Some(match clipboard::copy(&text) {
+            Ok(()) => format!("Copied {} to the clipboard", what),
+            Err(e) => format!("Failed to copy {}: {}", what, e),
+        });
+    }
+
+    /// Asks to confirm deleting the marked entries, all in one write
+    /// transaction, or else the selected entry.
+    fn start_delete(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        if let Some(marks) = self.active_marks() {
+            let message = format!(
+                "Delete {} marked entries from {}?",
+                marks.keys.len(),
+                marks.table
+            );
+            let op = WriteOp::Batch {
+                table: marks.table.clone(),
+                entries: marks.keys.iter().map(|k| (k.clone(), None)).collect(),
+            };
+            self.mode = Mode::Confirm { message, op };
+            return;
+        }
+        let (Some((key, _)), Some(table)) =
+            (self.selected_entry(), self.selected_table_name())
+        else {
+            return;
+        };
+        let message = format!("Delete {} from {}?", self.display_key(key), table);
+        let op = WriteOp::Remove {
+            table: table.to_string(),
+            key: key.clone(),
+        };
+        self.mode = Mode::Confirm { message, op };
+    }
+
+    /// Asks to confirm dropping the selected table.
+    fn start_drop_table(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        let Some(table) = self.selected_table_name() else {
+            return;
+        };
+        let count = match database::table_len(&self.db, table) {
+            Ok(count) => count,
+            Err(e) => {
+                self.show_error(e.to_string());
+                return;
+            }
+        };
+        let message = format!("Drop table {} with {} entries?", table, count);
+        let op = WriteOp::DropTable {
+            table: table.to_string(),
+        };
+        self.mode = Mode::Confirm { message, op };
+    }
+
+    /// Entries shown at once in the entries pane.
+    fn page_size(&self) -> isize {
+        self.entries_area.height.saturating_sub(2) as isize
+    }
+
+    /// Selects the clicked table or entry, and moves the selection of the
+    /// pane under the pointer with the scroll wheel. Returns whether
+    /// anything changed.
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> bool {
+        if !matches!(self.mode, Mode::Normal) {
+            return false;
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        let (pane, area) = if self.tables_area.contains(position) {
+            (Focus::Tables, self.tables_area)
+        } else if self.entries_area.contains(position) {
+            (Focus::Entries, self.entries_area)
+        } else {
+            return false;
+        };
+        match (mouse.kind, pane) {
+            (MouseEventKind::Down(MouseButton::Left), _) => {
+                self.focus = pane;
+                // Clicks on the borders only move the focus.
+                if mouse.row == area.y || mouse.row + 1 >= area.bottom() {
+                    return true;
+                }
+                let row = (mouse.row - area.y - 1) as usize;
+                if pane == Focus::Entries {
+                    let row = self.entry_state.offset() + row;
+                    if row < self.visible_entries().len() {
+                        self.entry_state.select(Some(row));
+                    }
+                    return true;
+                }
+                let row = self.list_state.offset() + row;
+                if row < self.visible_tables().len()
+                    && self.list_state.selected() != Some(row)
+                {
+                    self.list_state.select(Some(row));
+                    self.update_selected_table_content();
+                }
+            }
+            (MouseEventKind::ScrollDown, Focus::Entries) => {
+                self.move_entry_selection(MOUSE_SCROLL_ROWS)
+            }
+            (MouseEventKind::ScrollUp, Focus::Entries) => {
+                self.move_entry_selection(-MOUSE_SCROLL_ROWS)
+            }
+            // The table list doesn't wrap around when scrolled.
+            (MouseEventKind::ScrollDown, Focus::Tables) => {
+                let len = self.visible_tables().len();
+                if self.list_state.selected().is_some_and(|i| i + 1 < len) {
+                    self.next();
+                }
+            }
+            (MouseEventKind::ScrollUp, Focus::Tables) => {
+                if self.list_state.selected().is_some_and(|i| i > 0) {
+                    self.previous();
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Selects the first, or with `last` the last, row of the focused pane.
+    fn select_edge(&mut self, last: bool) {
+        match self.focus {
+            Focus::Entries => {
+                let rows = if last { isize::MAX } else { isize::MIN };
+                self.move_entry_selection(rows)
+            }
+            Focus::Tables => {
+                let len = self.visible_tables().len();
+                if len == 0 {
+                    return;
+                }
+                let row = if last { len - 1 } else { 0 };
+                if self.list_state.selected() != Some(row) {
+                    self.list_state.select(Some(row));
+                    self.update_selected_table_content();
+                }
+            }
+        }
+    }
+
+    /// Moves the entry selection `rows` down, or up when negative, stopping
+    /// at the first and last entry.
+    fn move_entry_selection(&mut self, rows: isize) {
+        let Some(last) = self.visible_entries().len().checked_sub(1) else {
+            return;
+        };
+        let current = self.entry_state.selected().unwrap_or(0);
+        let row = if rows < 0 {
+            current.saturating_sub(rows.unsigned_abs())
+        } else {
+            current.saturating_add(rows as usize).min(last)
+        };
+        self.entry_state.select(Some(row));
+    }
+
+    /// Sets the search to `text`, selecting the first match, and starts
+    /// counting its matches. A pattern that doesn't parse clears it.
+    fn set_search(&mut self, text: &str) -> std::result::Result<(), String> {
+        let query = (!text.is_empty()).then(|| Query::new(text)).transpose();
+        self.search = query.as_ref().ok().cloned().flatten();
+        self.entry_state.select(Some(0));
+        self.count_matches();
+        query.map(|_| ())
+    }
+
+    /// Applies the search being typed once typing paused for
+    /// `SEARCH_DEBOUNCE`. Returns whether it did.
+    fn search_tick(&mut self) -> bool {
+        let Mode::Search { input } = &self.mode else {
+            return false;
+        };
+        match self.search_typed_at {
+            Some(at) if at.elapsed() >= SEARCH_DEBOUNCE => {}
+            _ => return false,
+        }
+        self.search_typed_at = None;
+        let text = input.text().to_string();
+        self.apply_search(&text);
+        true
+    }
+
+    /// Sets the search to `text` and selects its first match, reading on
+    /// through the table in the background if the entries loaded have none.
+    /// Matches show up as they are read, see `receive_entries`.
+    fn apply_search(&mut self, text: &str) {
+        if let Err(e) = self.set_search(text) {
+            self.status_message = Some(e);
+            return;
+        }
+        match &mut self.load {
+            // A read already going looks for the match as it goes.
+            Some(load) => {
+                if matches!(
+                    load.then,
+                    AfterLoad::Nothing | AfterLoad::NextMatch(_)
+                ) {
+                    load.then = AfterLoad::NextMatch(0);
+                }
+            }
+            None => self.continue_search(0),
+        }
+    }
+
+    /// Counts the matches of the search through the whole listing of the
+    /// selected table, within the key range, on a background thread, as
+    /// the entries pane only holds part of a big table. The running total
+    /// goes to the pane title.
+    fn count_matches(&mut self) {
+        self.match_count = None;
+        let (Some(query), Some(table)) = (
+            self.search.clone(),
+            self.selected_table_name().map(str::to_string),
+        ) else {
+            return;
+        };
+        let (start, end) = self.read_range(Bound::Unbounded);
+        let (start, end) = (start.map(<[u8]>::to_vec), end.map(<[u8]>::to_vec));
+        let db = Arc::clone(&self.db);
+        let schemas = self.schemas.clone();
+        let (key_decoder, value_decoder) =
+            (self.key_decoder(), self.value_decoder());
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        let counted = query.clone();
+        let name = table.clone();
+        thread::spawn(move || {
+            let ctx = schemas.context(&name);
+            let mut matches = 0;
+            let result = database::scan_table_entries(
+                &db,
+                &name,
+                (
+                    start.as_ref().map(Vec::as_slice),
+                    end.as_ref().map(Vec::as_slice),
+                ),
+                false,
+                usize::MAX,
+                LOAD_BATCH_SIZE,
+                |types, entries| {
+                    for (key, value) in &entries {
+                        let key = key_decoder.decode(&ctx, &types.key.name, key);
+                        let value =
+                            value_decoder.decode(&ctx, &types.value.name, value);
+                        if counted.matches(&key) || counted.matches(&value) {
+                            matches += 1;
+                        }
+                    }
+                    !token.load(Ordering::Relaxed) && tx.send(matches).is_ok()
+                },
+            );
+            if let Err(e) = result {
+                debug!("Counting matches in {} failed: {}", name, e);
+            }
+        });
+        self.match_count = Some(MatchCount {
+            table,
+            query,
+            rx,
+            matches: 0,
+            done: false,
+            cancelled,
+        });
+    }
+
+    /// Searches the decoded keys and values of every table for `query` on
+    /// a background thread, each with its decoders, keeping the first
+    /// `MAX_HITS` hits. Hits come in through `receive_hits`.
+    fn search_all(&mut self, query: Query) {
+        info!("Searching all tables for {}", query.text());
+        let tables = self.table_names.clone();
+        let db = Arc::clone(&self.db);
+        let schemas = self.schemas.clone();
+        let key_decoders = self.table_key_decoders.clone();
+        let value_decoders = self.table_decoders.clone();
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        let searched = query.clone();
+        thread::spawn(move || {
+            let mut hits = 0;
+            for table in tables {
+                if token.load(Ordering::Relaxed)
+                    || hits == MAX_HITS
+                    || tx.send(HitEvent::Table(table.clone())).is_err()
+                {
+                    return;
+                }
+                let ctx = schemas.context(&table);
+                let decoder = |decoders: &HashMap<String, Decoder>| {
+                    decoders.get(&table).copied().unwrap_or(Decoder::Auto)
+                };
+                let key_decoder = decoder(&key_decoders);
+                let value_decoder = decoder(&value_decoders);
+                let result = database::scan_table_entries(
+                    &db,
+                    &table,
+                    (Bound::Unbounded, Bound::Unbounded),
+                    false,
+                    usize::MAX,
+                    LOAD_BATCH_SIZE,
+                    |types, entries| {
+                        for (key, value) in entries {
+                            let key_text =
+                                key_decoder.decode(&ctx, &types.key.name, &key);
+                            let value_text = value_decoder.decode(
+                                &ctx,
+                                &types.value.name,
+                                &value,
+                            );
+                            if !searched.matches(&key_text)
+                                && !searched.matches(&value_text)
+                            {
+                                continue;
+                            }
+                            let label = format!(
+                                "{}  {}: {}",
+                                table, key_text, value_text
+                            );
+                            let hit = Hit {
+                                table: table.clone(),
+                                key,
+                                label: label.chars().take(HIT_WIDTH).collect(),
+                            };
+                            hits += 1;
+                            if tx.send(HitEvent::Hit(hit)).is_err()
+                                || hits == MAX_HITS
+                            {
+                                return false;
+                            }
+                        }
+                        !token.load(Ordering::Relaxed)
+                    },
+                );
+                if let Err(e) = result {
+                    debug!("Searching {} failed: {}", table, e);
+                }
+            }
+        });
+        self.table_search = Some(TableSearch {
+            query,
+            hits: Vec::new(),
+            rx,
+            searching: None,
+            cancelled,
+        });
+    }
+
+    /// Lists the values of the selected table stored more than once, from
+    /// the last read of it if there is one.
+    fn show_duplicates(&mut self) {
+        let Some(table) = self.selected_table_name() else {
+            return;
+        };
+        if self
+            .duplicates
+            .as_ref()
+            .is_none_or(|scan| scan.table != table)
+        {
+            self.find_duplicates();
+        }
+        self.mode = Mode::Duplicates { selected: 0 };
+    }
+
+    /// Reads the selected table on a background thread, hashing the values
+    /// to find those stored under more than one key, and lists the
+    /// `MAX_DUPLICATES` wasting the most bytes. Progress and the result
+    /// come in through `receive_duplicates`.
+    fn find_duplicates(&mut self) {
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        info!("Looking for duplicated values in {}", table);
+        let db = Arc::clone(&self.db);
+        let schemas = self.schemas.clone();
+        let (key_decoder, value_decoder) =
+            (self.key_decoder(), self.value_decoder());
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        let name = table.clone();
+        thread::spawn(move || {
+            let mut duplicates = Duplicates::default();
+            let mut table_types = None;
+            let result = database::scan_table_entries(
+                &db,
+                &name,
+                (Bound::Unbounded, Bound::Unbounded),
+                false,
+                usize::MAX,
+                LOAD_BATCH_SIZE,
+                |types, entries| {
+                    table_types.get_or_insert_with(|| types.clone());
+                    for (key, value) in &entries {
+                        duplicates.add(key, value);
+                    }
+                    let read = DuplicateEvent::Read(duplicates.entries());
+                    !token.load(Ordering::Relaxed) && tx.send(read).is_ok()
+                },
+            );
+            if token.load(Ordering::Relaxed) {
+                return;
+            }
+            let found = result.map_err(|e| e.to_string()).map(|_| {
+                let report = duplicates.report(MAX_DUPLICATES);
+                let ctx = schemas.context(&name);
+                let lines = match &table_types {
+                    Some(types) => report
+                        .top
+                        .iter()
+                        .map(|duplicate| {
+                            // Values are read again for the few listed
+                            // rather than kept for every value.
+                            let value = database::get_raw_value(
+                                &db,
+                                &name,
+                                &duplicate.keys[0],
+                            )
+                            .ok()
+                            .and_then(|(_, value)| value)
+                            .map(|value| {
+                                value_decoder.decode(
+                                    &ctx,
+                                    &types.value.name,
+                                    &value,
+                                )
+                            })
+                            .unwrap_or_default();
+                            let keys: Vec<String> = duplicate
+                                .keys
+                                .iter()
+                                .map(|key| {
+                                    key_decoder.decode(&ctx, &types.key.name, key)
+                                })
+                                .collect();
+                            duplicate_line(duplicate, &value, &keys)
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
+                (report, lines)
+            });
+            let _ = tx.send(DuplicateEvent::Done(found));
+        });
+        self.duplicates = Some(DuplicateScan {
+            table,
+            rx,
+            read: 0,
+            found: None,
+            cancelled,
+        });
+    }
+
+    /// Takes the progress and result of `find_duplicates`. Returns whether
+    /// anything changed.
+    fn receive_duplicates(&mut self) -> bool {
+        let Some(scan) = &mut self.duplicates else {
+            return false;
+        };
+        let mut changed = false;
+        while let Ok(event) = scan.rx.try_recv() {
+            match event {
+                DuplicateEvent::Read(read) => scan.read = read,
+                DuplicateEvent::Done(Ok(found)) => scan.found = Some(found),
+                DuplicateEvent::Done(Err(e)) => {
+                    self.duplicates = None;
+                    self.show_error(format!("Failed to read values: {}", e));
+                    return true;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Title and lines of the duplicates popup.
+    fn duplicates_list(&self) -> (String, Vec<String>) {
+        let Some(scan) = &self.duplicates else {
+            return Default::default();
+        };
+        let Some((report, lines)) = &scan.found else {
+            let total = match self.table_counts.get(&scan.table) {
+                Some(total) => format!(" of {}", layout::group_thousands(*total)),
+                None => String::new(),
+            };
+            let title = format!(
+                "Duplicated values in {}: read {}{} entries (Esc: close)",
+                scan.table,
+                layout::group_thousands(scan.read),
+                total
+            );
+            return (title, Vec::new());
+        };
+        let values = match report.duplicated {
+            1 => "1 value".to_string(),
+            n => format!("{} values", layout::group_thousands(n as u64)),
+        };
+        let title = format!(
+            "{}: {} stored more than once, {} wasted, in {} entries \
+             (Enter: jump, r: read again, Esc: close)",
+            scan.table,
+            values,
+            report.wasted.human_count_bytes(),
+            layout::group_thousands(report.entries)
+        );
+        (title, lines.clone())
+    }
+
+    /// Shows how the value sizes of the selected table spread, from the
+    /// last read of it if there is one.
+    fn show_value_sizes(&mut self) {
+        let Some(table) = self.selected_table_name() else {
+            return;
+        };
+        if self
+            .value_sizes
+            .as_ref()
+            .is_none_or(|scan| scan.table != table)
+        {
+            self.read_value_sizes();
+        }
+        self.mode = Mode::ValueSizes;
+    }
+
+    /// Reads the value sizes of the selected table on a background thread.
+    /// Progress and the result come in through `receive_value_sizes`.
+    fn read_value_sizes(&mut self) {
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        info!("Reading the value sizes of {}", table);
+        let db = Arc::clone(&self.db);
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        let name = table.clone();
+        thread::spawn(move || {
+            let mut sizes = Sizes::default();
+            let result = database::scan_table_entries(
+                &db,
+                &name,
+                (Bound::Unbounded, Bound::Unbounded),
+                false,
+                usize::MAX,
+                LOAD_BATCH_SIZE,
+                |_, entries| {
+                    for (_, value) in &entries {
+                        sizes.add(value.len());
+                    }
+                    let read = SizeEvent::Read(sizes.count());
+                    !token.load(Ordering::Relaxed) && tx.send(read).is_ok()
+                },
+            );
+            if token.load(Ordering::Relaxed) {
+                return;
+            }
+            let found =
+                result.map_err(|e| e.to_string()).map(|_| sizes.summary());
+            let _ = tx.send(SizeEvent::Done(found));
+        });
+        self.value_sizes = Some(SizeScan {
+            table,
+            rx,
+            read: 0,
+            found: None,
+            cancelled,
+        });
+    }
+
+    /// Takes the progress and result of `read_value_sizes`. Returns whether
+    /// anything changed.
+    fn receive_value_sizes(&mut self) -> bool {
+        let Some(scan) = &mut self.value_sizes else {
+            return false;
+        };
+        let mut changed = false;
+        while let Ok(event) = scan.rx.try_recv() {
+            match event {
+                SizeEvent::Read(read) => scan.read = read,
+                SizeEvent::Done(Ok(found)) => scan.found = Some(found),
+                SizeEvent::Done(Err(e)) => {
+                    self.value_sizes = None;
+                    self.show_error(format!("Failed to read values: {}", e));
+                    return true;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Title, figures and bars of the value sizes popup. Values longer than
+    /// a page are counted, as their leaves take several pages.
+    fn value_sizes_view(&self) -> HistogramView {
+        let Some(scan) = &self.value_sizes else {
+            return Default::default();
+        };
+        let Some(found) = &scan.found else {
+            let total = match self.table_counts.get(&scan.table) {
+                Some(total) => format!(" of {}", layout::group_thousands(*total)),
+                None => String::new(),
+            };
+            let title = format!(
+                "Value sizes in {}: read {}{} entries (Esc: close)",
+                scan.table,
+                layout::group_thousands(scan.read),
+                total
+            );
+            return (title, Vec::new(), Vec::new());
+        };
+        let title =
+            format!("Value sizes in {} (r: read again, Esc: close)", scan.table);
+        let Some(summary) = found else {
+            return (title, Vec::new(), Vec::new());
+        };
+        let bytes = |n: usize| n.human_count_bytes().to_string();
+        let mut figures = vec![
+            (
+                "Entries".to_string(),
+                layout::group_thousands(summary.count),
+            ),
+            (
+                "Total".to_string(),
+                summary.total.human_count_bytes().to_string(),
+            ),
+            ("Min".to_string(), bytes(summary.min)),
+            (
+                "Average".to_string(),
+                summary.average().human_count_bytes().to_string(),
+            ),
+            ("95th percentile".to_string(), bytes(summary.p95)),
+            ("Max".to_string(), bytes(summary.max)),
+        ];
+        if let Some(page_size) = self.stats.as_ref().and_then(|s| s.page_size) {
+            let longer = summary.longer_than(page_size);
+            figures.push((
+                format!("Over a page of {} bytes", page_size),
+                format!(
+                    "{} ({:.1}%)",
+                    layout::group_thousands(longer),
+                    longer as f64 * 100.0 / summary.count as f64
+                ),
+            ));
+        }
+        let bars = summary
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.label(), bucket.count))
+            .collect();
+        (title, figures, bars)
+    }
+
+    /// Title, figures and bars of the B-tree popup: a bar for the pages of
+    /// each level, from the root down, and how full the pages are.
+    fn btree_view(&self) -> HistogramView {
+        let Some(table) = self.selected_table_name() else {
+            return Default::default();
+        };
+        let title = format!("B-tree of {} (r: refresh, Esc: close)", table);
+        let Some(summary) = self.table_summaries.iter().find(|t| t.name == table)
+        else {
+            let figures = vec![("Stats".to_string(), "loading".to_string())];
+            return (title, figures, Vec::new());
+        };
+        let stats = &summary.stats;
+        let bytes = |n: u64| n.human_count_bytes().to_string();
+        let mut figures = vec![
+            ("Height".to_string(), stats.tree_height().to_string()),
+            (
+                "Pages".to_string(),
+                format!(
+                    "{} branch, {} leaf",
+                    layout::group_thousands(stats.branch_pages()),
+                    layout::group_thousands(stats.leaf_pages())
+                ),
+            ),
+        ];
+        if let Some(fanout) = btree::fanout(stats) {
+            figures.push((
+                "Fan-out".to_string(),
+                format!("{:.1} children per branch page", fanout),
+            ));
+        }
+        let page_size = self.stats.as_ref().and_then(|s| s.page_size);
+        if let (Some(page_size), leaves @ 1..) = (page_size, stats.leaf_pages()) {
+            let per_leaf = stats.stored_bytes() / leaves;
+            figures.push((
+                "Leaf fill".to_string(),
+                format!(
+                    "{} of data per {} page ({:.0}%)",
+                    bytes(per_leaf),
+                    bytes(page_size as u64),
+                    per_leaf as f64 * 100.0 / page_size as f64
+                ),
+            ));
+        }
+        let size = table_size(summary);
+        if size > 0 {
+            figures.push((
+                "In use".to_string(),
+                format!(
+                    "{:.0}% of {} holds data or metadata",
+                    (size - stats.fragmented_bytes()) as f64 * 100.0
+                        / size as f64,
+                    bytes(size)
+                ),
+            ));
+        }
+        let levels = btree::levels(stats);
+        if levels.iter().any(|level| level.estimated) {
+            figures.push((
+                "~".to_string(),
+                "estimated from the fan-out".to_string(),
+            ));
+        }
+        let bars = levels
+            .iter()
+            .enumerate()
+            .map(|(depth, level)| {
+                let estimated = if level.estimated { "~" } else { "" };
+                let label =
+                    format!("{} {}{}", depth + 1, level.kind.label(), estimated);
+                (label, level.pages)
+            })
+            .collect();
+        (title, figures, bars)
+    }
+
+    /// Takes the hits `search_all` found since the last call. Returns
+    /// whether anything changed.
+    fn receive_hits(&mut self) -> bool {
+        let Some(search) = &mut self.table_search else {
+            return false;
+        };
+        let mut changed = false;
+        loop {
+            match search.rx.try_recv() {
+                Ok(HitEvent::Table(table)) => search.searching = Some(table),
+                Ok(HitEvent::Hit(hit)) => search.hits.push(hit),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    changed |= search.searching.take().is_some();
+                    break;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Title and lines of the hits popup.
+    fn hits_list(&self) -> (String, Vec<String>) {
+        let Some(search) = &self.table_search else {
+            return Default::default();
+        };
+        let count = match search.hits.len() {
+            1 => "1 hit".to_string(),
+            MAX_HITS => format!("first {} hits", MAX_HITS),
+            n => format!("{} hits", n),
+        };
+        let progress = match &search.searching {
+            Some(table) => format!(", searching {}", table),
+            None => String::new(),
+        };
+        let title = format!(
+            "{}: {}{} (Enter: jump, Esc: close)",
+            search.query.text(),
+            count,
+            progress
+        );
+        let items = search.hits.iter().map(|hit| hit.label.clone()).collect();
+        (title, items)
+    }
+
+    /// Takes the running total of `count_matches`, dropping a count whose
+    /// search or table is gone. Returns whether anything changed.
+    fn receive_match_count(&mut self) -> bool {
+        let table = self.selected_table_name().map(str::to_string);
+        let Some(count) = &mut self.match_count else {
+            return false;
+        };
+        if Some(&count.query) != self.search.as_ref()
+            || Some(&count.table) != table.as_ref()
+        {
+            self.match_count = None;
+            return true;
+        }
+        let mut changed = false;
+        loop {
+            match count.rx.try_recv() {
+                Ok(matches) => {
+                    count.matches = matches;
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    changed |= !count.done;
+                    count.done = true;
+                    break;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Moves to the next match, reading further into the table once the
+    /// loaded matches are exhausted, and wraps around at the end.
+    fn next_match(&mut self) {
+        // The search continues once the read in flight is done.
+        if self.load.is_some() {
+            return;
+        }
+        let matches = self.visible_entries().len();
+        let next = self.entry_state.selected().map_or(0, |i| i + 1);
+        if next < matches {
+            self.entry_state.select(Some(next));
+            return;
+        }
+        self.continue_search(matches);
+    }
+
+    /// Selects the first match after the `matches` seen so far, reading
+    /// the next batch of entries in the background if there is none yet.
+    fn continue_search(&mut self, matches: usize) {
+        if self.visible_entries().len() > matches {
+            self.entry_state.select(Some(matches));
+            return;
+        }
+        if !self.selected_table_complete {
+            let after =
+                self.selected_table_content.last().map(|(k, _)| k.clone());
+            self.load_entries(
+                after.map_or(Bound::Unbounded, Bound::Excluded),
+                AfterLoad::NextMatch(matches),
+            );
+            return;
+        }
+        self.status_message = Some(match matches {
+            0 => "No matches".to_string(),
+            n => format!("{} matches, search wrapped", n),
+        });
+        self.entry_state.select((matches > 0).then_some(0));
+    }
+
+    /// The key range of the selected table, if one is set.
+    fn active_range(&self) -> Option<&TableRange> {
+        self.key_range
+            .as_ref()
+            .filter(|r| Some(r.table.as_str()) == self.selected_table_name())
+    }
+
+    /// Bounds of a read continuing from `from` in the listing order, kept
+    /// within the active key range.
+    fn read_range<'a>(
+        &'a self,
+        from: Bound<&'a [u8]>,
+    ) -> (Bound<&'a [u8]>, Bound<&'a [u8]>) {
+        let (start, end) = self
+            .active_range()
+            .map_or((Bound::Unbounded, Bound::Unbounded), |r| r.range.bounds());
+        match (from, self.reverse) {
+            (Bound::Unbounded, _) => (start, end),
+            (from, false) => (from, end),
+            (from, true) => (start, from),
+        }
+    }
+
+    /// Narrows the selected table down to the keys in `text`, see
+    /// `KeyRange::parse`.
+    fn set_key_range(&mut self, text: &str) -> std::result::Result<(), String> {
+        let (Some(table), Some(types)) =
+            (self.selected_table_name(), &self.selected_table_types)
+        else {
+            return Ok(());
+        };
+        self.key_range = match text {
+            "" => None,
+            text => Some(TableRange {
+                table: table.to_string(),
+                text: text.to_string(),
+                range: KeyRange::parse(text, &types.key.name)?,
+            }),
+        };
+        self.update_selected_table_content();
+        Ok(())
+    }
+
+    /// Shows the entries of the selected table from the first key at or
+    /// after (before, when reversed) the one given as `text`: hex with a `0x`
+    /// prefix, otherwise the display form of the key type.
+    fn goto_key(&mut self, text: &str) -> std::result::Result<(), String> {
+        // Hex keys can be jumped to before the types of a table just
+        // selected are known.
+        let key = match (text.strip_prefix("0x"), &self.selected_table_types) {
+            (Some(hex), _) => decoder::parse_hex(hex)?,
+            (None, Some(types)) => decoder::encode_native(&types.key.name, text)?,
+            (None, None) => return Ok(()),
+        };
+        debug!(
+            "Jumping to {:?} in {}",
+            key,
+            self.selected_table_name().unwrap_or_default()
+        );
+        // A table selected to jump into was left for nothing shown yet.
+        let just_selected =
+            self.load.is_some() && self.selected_table_content.is_empty();
+        if let Some(left) = self.location().filter(|_| !just_selected) {
+            self.history.visit(left);
+        }
+        self.selected_table_content.clear();
+        self.search = None;
+        self.jumped = true;
+        self.entry_state.select(Some(0));
+        self.focus = Focus::Entries;
+        self.load_entries(
+            Bound::Included(key),
+            AfterLoad::Goto(text.to_string()),
+        );
+        Ok(())
+    }
+
+    /// Carries out a command typed at the `:` prompt.
+    fn run_command(
+        &mut self,
+        command: Command,
+    ) -> std::result::Result<(), String> {
+        debug!("Running command {:?}", command);
+        if matches!(
+            command,
+            Command::Table(_)
+                | Command::KeyRange(_)
+                | Command::Goto(_)
+                | Command::Search(_)
+        ) {
+            self.show_tab(Tab::Data);
+        }
+        match command {
+            Command::Table(name) => {
+                if !self.table_names.contains(&name) {
+                    return Err(format!("No table named '{}'", name));
+                }
+                if !self.visible_tables().contains(&name.as_str()) {
+                    self.table_filter = None;
+                }
+                self.select_table(Some(&name));
+            }
+            Command::Export { options, path } => self
+                .export_table(options, &path)
+                .map_err(|e| e.to_string())?,
+            Command::KeyRange(text) => self.set_key_range(&text)?,
+            Command::Goto(key) => self.goto_key(&key)?,
+            Command::Search(text) => {
+                self.focus = Focus::Entries;
+                self.set_search(&text)?;
+            }
+            Command::Decoder { key: true, decoder } => {
+                self.set_key_decoder(decoder)
+            }
+            Command::Decoder {
+                key: false,
+                decoder,
+            } => self.set_value_decoder(decoder),
+            Command::Diff(path) => {
+                if !path.exists() {
+                    return Err(format!("{} not found", path.display()));
+                }
+                self.diff_path = Some(path);
+                self.diff = None;
+                self.diff_selected = 0;
+                self.comparison = None;
+                self.comparison_rx = None;
+                self.show_tab(Tab::Diff);
+            }
+            Command::Compare(table) => {
+                if self.diff_path.is_none() {
+                    return Err("No database to compare with, open one with \
+                                :diff PATH"
+                        .into());
+                }
+                let Some(table) = table
+                    .or_else(|| self.selected_table_name().map(str::to_string))
+                else {
+                    return Err("No table selected".into());
+                };
+                self.show_tab(Tab::Diff);
+                self.open_comparison(table, None);
+            }
+            Command::Open(None) => {
+                // A bare file name has an empty parent.
+                let dir = match self.db_path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                let browser = Browser::new(dir);
+                self.mode = Mode::Browse { browser };
+            }
+            Command::Open(Some(path)) if path.is_dir() => {
+                let browser = Browser::new(&path);
+                self.mode = Mode::Browse { browser };
+            }
+            Command::Open(Some(path)) => {
+                if !path.exists() {
+                    return Err(format!("{} not found", path.display()));
+                }
+                self.exit = Some(Exit::Open(path));
+            }
+            Command::Close => self.exit = Some(Exit::Close),
+            Command::CopyTable { database, name } => {
+                let Some(table) = self.selected_table_name() else {
+                    return Err("No table selected".into());
+                };
+                let to = database
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .filter(|&i| i < self.databases.len())
+                    .or_else(|| {
+                        self.databases.iter().position(|t| *t == database)
+                    })
+                    .ok_or_else(|| format!("No open database '{}'", database))?;
+                let name = name.unwrap_or_else(|| table.to_string());
+                if to == self.database_index && name == table {
+                    return Err(format!("Table {} already exists", name));
+                }
+                self.exit = Some(Exit::CopyTable {
+                    table: table.to_string(),
+                    to,
+                    name,
+                });
+            }
+            Command::Help => self.mode = Mode::Help { scroll: 0 },
+            Command::Quit => {
+                info!("User requested exit");
+                self.exit = Some(Exit::Quit);
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the database file, for the database tabs.
+    fn database_title(&self) -> String {
+        self.db_path.file_name().map_or_else(
+            || self.db_path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+
+    /// Copies `table` into `target`, titled `target_title`, as `name`,
+    /// replacing the values of keys both have and showing progress along
+    /// the way. Returns whether it went through; failures are shown.
+    fn copy_table_into(
+        &mut self,
+        target: &Database,
+        target_title: &str,
+        table: &str,
+        name: &str,
+    ) -> bool {
+        let options = CopyOptions {
+            on_conflict: OnConflict::Overwrite,
+            batch_size: COPY_BATCH_SIZE,
+            durability: self.durability,
+        };
+        let title = format!("Copying {} to {}", table, target_title);
+        let terminal = &mut self.terminal;
+        let theme = &self.theme;
+        let result = database::copy_table(
+            &self.db,
+            target,
+            table,
+            name,
+            &options,
+            |done, total| {
+                let draw = terminal.draw(|frame| {
+                    layout::render_progress_popup(
+                        frame, theme, &title, done, total,
+                    )
+                });
+                if let Err(e) = draw {
+                    debug!("Failed to draw copy progress: {}", e);
+                }
+            },
+        );
+        // The progress popup replaced the screen contents.
+        if let Err(e) = self.terminal.clear() {
+            debug!("Failed to clear the screen: {}", e);
+        }
+        match result {
+            Ok(copied) => {
+                info!(
+                    "Copied {} entries of {} to {} of {}",
+                    copied.copied, table, name, target_title
+                );
+                self.status_message = Some(format!(
+                    "Copied {} {} of {} to {} in {}",
+                    layout::group_thousands(copied.copied),
+                    if copied.copied == 1 {
+                        "entry"
+                    } else {
+                        "entries"
+                    },
+                    table,
+                    name,
+                    target_title
+                ));
+                // Copies within this database show up in its table list.
+                if std::ptr::eq(target, &*self.db) {
+                    if let Err(e) = self.reload() {
+                        self.show_error(e.to_string());
+                    }
+                }
+                true
+            }
+            Err(e) => {
+                self.show_error(format!("Copying {} failed: {}", table, e));
+                false
+            }
+        }
+    }
+
+    /// Exports the marked entries of the selected table, or else those in
+    /// view, within the key range and matching the search, to `path` with
+    /// the decoders of the session, showing progress while the entries are
+    /// written.
+    fn export_table(
+        &mut self,
+        options: ExportOptions,
+        path: &Path,
+    ) -> Result<()> {
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return Ok(());
+        };
+        let marked = self
+            .active_marks()
+            .map(|m| m.keys.iter().cloned().collect::<Vec<_>>());
+        let filtered = marked.is_none();
+        let tables = [ExportTable {
+            range: self
+                .active_range()
+                .filter(|_| filtered)
+                .map(|r| r.range.clone()),
+            search: self.search.clone().filter(|_| filtered),
+            keys: marked,
+            ..ExportTable::new(
+                table.clone(),
+                self.key_decoder(),
+                self.value_decoder(),
+            )
+        }];
+        let title = format!("Exporting {} to {}", table, path.display());
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        let terminal = &mut self.terminal;
+        let result = commands::write_export(
+            &self.db,
+            &self.schemas,
+            &tables,
+            options,
+            &mut out,
+            |_, done, total| {
+                let draw = terminal.draw(|frame| {
+                    layout::render_progress_popup(
+                        frame,
+                        &self.theme,
+                        &title,
+                        done,
+                        total,
+                    )
+                });
+                if let Err(e) = draw {
+                    debug!("Failed to draw export progress: {}", e);
+                }
+            },
+        )
+        .and_then(|written| {
+            io::Write::flush(&mut out)?;
+            Ok(written)
+        });
+        // The progress popup replaced the screen contents.
+        self.terminal.clear()?;
+        let written = result?;
+        info!("Exported {} entries of {} to {:?}", written, table, path);
+        self.status_message = Some(format!(
+            "Exported {} {} of {} to {}",
+            layout::group_thousands(written),
+            if written == 1 { "entry" } else { "entries" },
+            table,
+            path.display()
+        ));
+        Ok(())
+    }
+
+    /// Asks to confirm clearing the selected table by typing its name.
+    fn start_truncate(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        let Some(table) = self.selected_table_name() else {
+            return;
+        };
+        let count = match database::table_len(&self.db, table) {
+            Ok(count) => count,
+            Err(e) => {
+                self.show_error(e.to_string());
+                return;
+            }
+        };
+        self.mode = Mode::TypedConfirm {
+            message: format!(
+                "Type {} to delete all {} entries of it",
+                table, count
+            ),
+            expected: table.to_string(),
+            input: Input::default(),
+            error: None,
+            op: WriteOp::Truncate {
+                table: table.to_string(),
+            },
+        };
+    }
+
+    /// Starts prompting for the new name of the selected table.
+    fn start_rename(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        self.mode = Mode::Rename {
+            input: Input::new(table.clone()),
+            table,
+            error: None,
+        };
+    }
+
+    /// Renames `from`, showing progress while the entries are copied.
+    fn rename_table(&mut self, from: &str, to: &str) -> Result<()> {
+        let title = format!("Renaming {} to {}", from, to);
+        let terminal = &mut self.terminal;
+        let result = database::rename_table(
+            &self.db,
+            from,
+            to,
+            self.durability,
+            |done, total| {
+                let draw = terminal.draw(|frame| {
+                    layout::render_progress_popup(
+                        frame,
+                        &self.theme,
+                        &title,
+                        done,
+                        total,
+                    )
+                });
+                if let Err(e) = draw {
+                    debug!("Failed to draw rename progress: {}", e);
+                }
+            },
+        );
+        match result {
+            Ok(count) => {
+                info!("Renamed {} to {} ({} entries)", from, to, count);
+                self.status_message = Some(format!("Renamed {} to {}", from, to));
+                // Keep the decoders chosen for the table under its new name.
+                if let Some(decoder) = self.table_decoders.remove(from) {
+                    self.table_decoders.insert(to.to_string(), decoder);
+                }
+                if let Some(decoder) = self.table_key_decoders.remove(from) {
+                    self.table_key_decoders.insert(to.to_string(), decoder);
+                }
+                if let Some(message) = self.schemas.proto_messages.remove(from) {
+                    self.schemas.proto_messages.insert(to.to_string(), message);
+                }
+                self.db_properties.file_size = fs::metadata(&self.db_path)?.len();
+            }
+            Err(e) => self.show_error(format!("Rename failed: {}", e)),
+        }
+        // The progress popup replaced the screen contents.
+        self.terminal.clear()?;
+        self.reload_table_names(Some(to))?;
+        self.count_tables();
+        self.refresh_stats();
+        self.update_selected_table_content();
+        Ok(())
+    }
+
+    /// Starts prompting for a table to create.
+    fn start_new_table(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        self.mode = Mode::NewTable {
+            name: None,
+            key: None,
+            input: Input::default(),
+            error: None,
+        };
+    }
+
+    /// Puts the entry written last back as it was, or with `undo` false
+    /// writes again the entry undone last, in a new write transaction. The
+    /// entry is shown if it is in another table.
+    fn undo_write(&mut self, undo: bool) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let Some(change) = self.changes(undo).pop() else {
+            let word = if undo { "undo" } else { "redo" };
+            self.status_message = Some(format!("Nothing to {}", word));
+            return Ok(());
+        };
+        if self.write(&change.op(undo))?.is_none() {
+            // It stays to try again.
+            self.changes(undo).push(change);
+            return Ok(());
+        }
+        self.status_message = Some(match undo {
+            true => format!("Undid the write to {}", change.table),
+            false => format!("Redid the write to {}", change.table),
+        });
+        if self.selected_table_name() != Some(change.table.as_str()) {
+            let key = change.entries.first().map(|e| e.key.clone());
+            self.show_in_data_tab(change.table.clone(), key);
+        }
+        self.changes(!undo).push(change);
+        Ok(())
+    }
+
+    /// Changes to undo, or with `undo` false to redo.
+    fn changes(&mut self, undo: bool) -> &mut Vec<Change> {
+        if undo {
+            &mut self.undo
+        } else {
+            &mut self.redo
+        }
+    }
+
+    fn handle_modal_key(&mut self, key: KeyEvent) -> Result<()> {
+        // Take the mode out so handlers can use `self`; whatever isn't
+        // replaced below stays closed.
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => {}
+            Mode::Edit {
+                key: entry_key,
+                format,
+                mut input,
+                error,
+            } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    let Some(types) = &self.selected_table_types else {
+                        return Ok(());
+                    };
+                    let confirm =
+                        format.encode(&types.value, input.text()).and_then(
+                            |value| self.confirm_value(entry_key.clone(), value),
+                        );
+                    match confirm {
+                        Ok(mode) => self.mode = mode,
+                        Err(e) => {
+                            self.mode = Mode::Edit {
+                                key: entry_key,
+                                format,
+                                input,
+                                error: Some(e),
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Edit {
+                        key: entry_key,
+                        format,
+                        input,
+                        error,
+                    };
+                }
+            },
+            Mode::NewTable {
+                name,
+                key: key_type,
+                mut input,
+                error,
+            } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    self.mode = self.new_table_step(name, key_type, input)
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::NewTable {
+                        name,
+                        key: key_type,
+                        input,
+                        error,
+                    };
+                }
+            },
+            Mode::TableFilter => {
+                let selected = self.selected_table_name().map(str::to_string);
+                let mut filter = self.table_filter.take().unwrap_or_default();
+                match key.code {
+                    // Esc drops the filter, Enter keeps it applied.
+                    KeyCode::Esc => {}
+                    KeyCode::Enter => self.table_filter = Some(filter),
+                    KeyCode::Down => {
+                        self.table_filter = Some(filter);
+                        self.next();
+                        self.mode = Mode::TableFilter;
+                        return Ok(());
+                    }
+                    KeyCode::Up => {
+                        self.table_filter = Some(filter);
+                        self.previous();
+                        self.mode = Mode::TableFilter;
+                        return Ok(());
+                    }
+                    _ => {
+                        filter.handle_key(key);
+                        self.table_filter = Some(filter);
+                        self.mode = Mode::TableFilter;
+                    }
+                }
+                self.select_table(selected.as_deref());
+            }
+            Mode::Search { mut input } => {
+                match key.code {
+                    // The first Esc stops a search still reading the table.
+                    KeyCode::Esc if self.load.is_some() => self.cancel_load(),
+                    KeyCode::Esc => {
+                        // Leave the search on the matched entry.
+                        let selected = self.selected_index();
+                        self.search = None;
+                        self.search_typed_at = None;
+                        self.entry_state.select(selected);
+                        return Ok(());
+                    }
+                    // Enter right after typing applies the search at once.
+                    KeyCode::Enter if self.search_typed_at.take().is_some() => {
+                        self.apply_search(input.text())
+                    }
+                    KeyCode::Enter => self.next_match(),
+                    KeyCode::Down => self.entry_state.select_next(),
+                    KeyCode::Up => self.entry_state.select_previous(),
+                    _ => {
+                        if input.handle_key(key) {
+                            self.search_typed_at = Some(Instant::now());
+                        }
+                    }
+                }
+                self.mode = Mode::Search { input };
+            }
+            Mode::KeyRange { mut input, error } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    let text = input.text().trim().to_string();
+                    if let Err(e) = self.set_key_range(&text) {
+                        self.mode = Mode::KeyRange {
+                            input,
+                            error: Some(e),
+                        };
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::KeyRange { input, error };
+                }
+            },
+            Mode::Command { mut input, error } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    let result = Command::parse(input.text())
+                        .and_then(|command| self.run_command(command));
+                    if let Err(e) = result {
+                        self.mode = Mode::Command {
+                            input,
+                            error: Some(e),
+                        };
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Command { input, error };
+                }
+            },
+            Mode::GotoKey { mut input, error } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    let text = input.text().trim().to_string();
+                    if let Err(e) = self.goto_key(&text) {
+                        self.mode = Mode::GotoKey {
+                            input,
+                            error: Some(e),
+                        };
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::GotoKey { input, error };
+                }
+            },
+            Mode::Rename {
+                table,
+                mut input,
+                error,
+            } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    let to = input.text().trim().to_string();
+                    if to.is_empty() || to == table {
+                        self.mode = Mode::Rename {
+                            table,
+                            input,
+                            error: Some("Enter a different name".into()),
+                        };
+                    } else {
+                        self.rename_table(&table, &to)?;
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Rename {
+                        table,
+                        input,
+                        error,
+                    };
+                }
+            },
+            Mode::TypedConfirm {
+                message,
+                expected,
+                mut input,
+                error,
+                op,
+            } => match key.code {
+                KeyCode::Esc => {
+                    self.status_message = Some("Cancelled".into());
+                }
+                KeyCode::Enter if input.text() == expected => {
+                    self.apply_write(&op)?
+                }
+                KeyCode::Enter => {
+                    self.mode = Mode::TypedConfirm {
+                        error: Some(format!("Type {} to confirm", expected)),
+                        message,
+                        expected,
+                        input,
+                        op,
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::TypedConfirm {
+                        message,
+                        expected,
+                        input,
+                        error,
+                        op,
+                    };
+                }
+            },
+            Mode::Browse { mut browser } => match browser.handle_key(key.code) {
+                Some(Outcome::Pick(path)) => self.exit = Some(Exit::Open(path)),
+                Some(Outcome::Cancel) => {}
+                None => self.mode = Mode::Browse { browser },
+            },
+            Mode::SearchAll { mut input, error } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    // The same search again shows its hits rather than
+                    // starting over.
+                    let same = self
+                        .table_search
+                        .as_ref()
+                        .is_some_and(|s| s.query.text() == input.text());
+                    if same {
+                        self.mode = Mode::SearchHits { selected: 0 };
+                    } else {
+                        match Query::new(input.text()) {
+                            _ if input.text().is_empty() => {}
+                            Ok(query) => {
+                                self.search_all(query);
+                                self.mode = Mode::SearchHits { selected: 0 };
+                            }
+                            Err(e) => {
+                                self.mode = Mode::SearchAll {
+                                    input,
+                                    error: Some(e),
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::SearchAll { input, error };
+                }
+            },
+            Mode::SearchHits { selected } => {
+                let hits = self.table_search.as_ref().map_or(0, |s| s.hits.len());
+                let last = hits.saturating_sub(1);
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {}
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.mode = Mode::SearchHits {
+                            selected: (selected + 1).min(last),
+                        }
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.mode = Mode::SearchHits {
+                            selected: selected.saturating_sub(1),
+                        }
+                    }
+                    KeyCode::Enter => {
+                        let hit = self
+                            .table_search
+                            .as_ref()
+                            .and_then(|s| s.hits.get(selected))
+                            .map(|hit| (hit.table.clone(), hit.key.clone()));
+                        if let Some((table, key)) = hit {
+                            // A key range may leave the key out.
+                            self.key_range = None;
+                            self.show_in_data_tab(table, Some(key));
+                        }
+                    }
+                    _ => self.mode = Mode::SearchHits { selected },
+                }
+            }
+            Mode::KeyTree => {
+                self.handle_key_tree_key(key);
+            }
+            Mode::Duplicates { selected } => {
+                let found = self
+                    .duplicates
+                    .as_ref()
+                    .and_then(|scan| scan.found.as_ref())
+                    .map(|(report, _)| report);
+                let last = found.map_or(0, |r| r.top.len().saturating_sub(1));
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {}
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.mode = Mode::Duplicates {
+                            selected: (selected + 1).min(last),
+                        }
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.mode = Mode::Duplicates {
+                            selected: selected.saturating_sub(1),
+                        }
+                    }
+                    KeyCode::Enter => {
+                        let key = found
+                            .and_then(|r| r.top.get(selected))
+                            .and_then(|duplicate| duplicate.keys.first());
+                        if let (Some(scan), Some(key)) = (&self.duplicates, key) {
+                            let (table, key) = (scan.table.clone(), key.clone());
+                            // A key range may leave the key out.
+                            self.key_range = None;
+                            self.show_in_data_tab(table, Some(key));
+                        }
+                    }
+                    KeyCode::Char('r') => {
+                        self.find_duplicates();
+                        self.mode = Mode::Duplicates { selected: 0 };
+                    }
+                    _ => self.mode = Mode::Duplicates { selected },
+                }
+            }
+            Mode::BTree => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => {}
+                KeyCode::Char('r') => {
+                    self.refresh_stats();
+                    self.mode = Mode::BTree;
+                }
+                _ => self.mode = Mode::BTree,
+            },
+            Mode::ValueSizes => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => {}
+                KeyCode::Char('r') => {
+                    self.read_value_sizes();
+                    self.mode = Mode::ValueSizes;
+                }
+                _ => self.mode = Mode::ValueSizes,
+            },
+            Mode::Bookmarks { selected } => {
+                let last = self.bookmarks.len().saturating_sub(1);
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q' | '\'') => {}
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.mode = Mode::Bookmarks {
+                            selected: (selected + 1).min(last),
+                        }
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.mode = Mode::Bookmarks {
+                            selected: selected.saturating_sub(1),
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if let Some(bookmark) = self.bookmarks.get(selected) {
+                            let Bookmark { table, key, .. } = bookmark.clone();
+                            // A key range may leave the key out.
+                            self.key_range = None;
+                            self.show_in_data_tab(table, Some(key));
+                        }
+                    }
+                    KeyCode::Char('d') | KeyCode::Delete => {
+                        if selected < self.bookmarks.len() {
+                            self.bookmarks.remove(selected);
+                            self.save_bookmarks();
+                        }
+                        self.mode = Mode::Bookmarks {
+                            selected: selected.min(last.saturating_sub(1)),
+                        }
+                    }
+                    _ => self.mode = Mode::Bookmarks { selected },
+                }
+            }
+            Mode::Help { scroll } => match key.code {
+                KeyCode::Esc | KeyCode::Char('?' | 'q') => {}
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.mode = Mode::Help {
+                        scroll: scroll.saturating_add(1),
+                    }
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.mode = Mode::Help {
+                        scroll: scroll.saturating_sub(1),
+                    }
+                }
+                KeyCode::PageDown => {
+                    self.mode = Mode::Help {
+                        scroll: scroll.saturating_add(self.page_size() as u16),
+                    }
+                }
+                KeyCode::PageUp => {
+                    self.mode = Mode::Help {
+                        scroll: scroll.saturating_sub(self.page_size() as u16),
+                    }
+                }
+                _ => self.mode = Mode::Help { scroll },
+            },
+            Mode::Detail { scroll } => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {}
+                KeyCode::Char('z') => {
+                    self.zoomed = !self.zoomed;
+                    self.mode = Mode::Detail { scroll };
+                }
+                KeyCode::Char(c @ ('y' | 'Y')) => {
+                    self.copy_entry(c == 'Y');
+                    self.mode = Mode::Detail { scroll };
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.mode = Mode::Detail {
+                        scroll: scroll.saturating_add(1),
+                    }
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.mode = Mode::Detail {
+                        scroll: scroll.saturating_sub(1),
+                    }
+                }
+                KeyCode::PageDown => {
+                    self.mode = Mode::Detail {
+                        scroll: scroll.saturating_add(self.page_size() as u16),
+                    }
+                }
+                KeyCode::PageUp => {
+                    self.mode = Mode::Detail {
+                        scroll: scroll.saturating_sub(self.page_size() as u16),
+                    }
+                }
+                KeyCode::Home => self.mode = Mode::Detail { scroll: 0 },
+                KeyCode::End => self.mode = Mode::Detail { scroll: u16::MAX },
+                _ => self.mode = Mode::Detail { scroll },
+            },
+            Mode::Confirm { message, op } => match key.code {
+                KeyCode::Char('y') => self.apply_write(&op)?,
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.status_message = Some("Cancelled".into());
+                }
+                _ => self.mode = Mode::Confirm { message, op },
+            },
+            Mode::ConfirmSavepoint { message, op } => match key.code {
+                KeyCode::Char('y') => self.apply_savepoint_op(op)?,
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.status_message = Some("Cancelled".into());
+                }
+                _ => self.mode = Mode::ConfirmSavepoint { message, op },
+            },
+            Mode::Error { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Accepts the current answer of the new table prompt and moves on to
+    /// the next question, or to confirming the table.
+    fn new_table_step(
+        &self,
+        name: Option<String>,
+        key: Option<RawType>,
+        input: Input,
+    ) -> Mode {
+        let text = input.text().trim();
+        let retry = |name, key, input, error: String| Mode::NewTable {
+            name,
+            key,
+            input,
+            error: Some(error),
+        };
+        let Some(name) = name else {
+            if text.is_empty() {
+                return retry(None, None, input, "Name is empty".into());
+            }
+            if self.table_names.iter().any(|t| t == text) {
+                let error = format!("Table {} already exists", text);
+                return retry(None, None, input, error);
+            }
+            return Mode::NewTable {
+                name: Some(text.to_string()),
+                key: None,
+                input: Input::new("&str"),
+                error: None,
+            };
+        };
+        let Some(raw) = database::builtin_type(text) else {
+            let error = format!(
+                "Unknown type '{}'; use a builtin type such as u64, &str or &[u8]",
+                text
+            );
+            return retry(Some(name), key, input, error);
+        };
+        let Some(key) = key else {
+            return Mode::NewTable {
+                name: Some(name),
+                key: Some(raw),
+                input: Input::new("&[u8]"),
+                error: None,
+            };
+        };
+        let message =
+            format!("Create table {}: Table<{}, {}>?", name, key.name, raw.name);
+        let op = WriteOp::CreateTable {
+            table: name,
+            types: TableTypes { key, value: raw },
+        };
+        Mode::Confirm { message, op }
+    }
+
+    /// Commits `op` like `write`, keeping the entry it wrote to undo.
+    fn apply_write(&mut self, op: &WriteOp) -> Result<()> {
+        if let Some(Some(change)) = self.write(op)? {
+            let removed =
+                change.entries.iter().filter(|e| e.after.is_none()).count();
+            self.status_message = Some(match (removed, change.entries.len()) {
+                (0, _) => "Saved, u to undo".into(),
+                (1, 1) => "Deleted, u to undo".into(),
+                (n, _) => format!("Deleted {} entries, u to undo", n),
+            });
+            self.undo.push(change);
+            self.redo.clear();
+        }
+        Ok(())
+    }
+
+    /// Commits `op` and reloads the selected table, keeping the selection
+    /// unless the set of tables changed. Returns `None` if the write failed,
+    /// else the entry it changed if it wrote one.
+    fn write(&mut self, op: &WriteOp) -> Result<Option<Option<Change>>> {
+        info!("Applying {:?}", op);
+        let selected = self.entry_state.selected();
+        let written = match database::apply_write(&self.db, op, self.durability) {
+            Ok(previous) => {
+                self.status_message = Some("Saved".into());
+                self.db_properties.file_size = fs::metadata(&self.db_path)?.len();
+                self.count_tables();
+                self.refresh_stats();
+                self.key_tree = None;
+                let change = Change::new(op, previous);
+                if let Some(change) = &change {
+                    self.unmark_removed(change);
+                }
+                Some(change)
+            }
+            Err(e) => {
+                self.show_error(format!("Write failed: {}", e));
+                None
+            }
+        };
+        if op.changes_tables() {
+            let created = match op {
+                WriteOp::CreateTable { table, .. } => Some(table.as_str()),
+                _ => None,
+            };
+            self.reload_table_names(created)?;
+            self.update_selected_table_content();
+            return Ok(written);
+        }
+        self.update_selected_table_content();
+        if let Some(load) = &mut self.load {
+            load.then = AfterLoad::Select(selected);
+        }
+        Ok(written)
+    }
+
+    /// Checks the database file for damage and repairs it, reporting which
+    /// it was in the status bar.
+    fn check_integrity(&mut self) -> Result<()> {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return Ok(());
+        }
+        let path = self.db_path.clone();
+        let intact = self.run_exclusive("Checking integrity", |db| {
+            database::check_integrity(db, &path)
+        })??;
+        self.status_message = Some(match intact {
+            true => "Integrity check passed".into(),
+            false => "Integrity check found damage and repaired it".into(),
+        });
+        Ok(())
+    }
+
+    /// Compacts the database file, reporting how much it shrank in the
+    /// status bar.
+    fn compact(&mut self) -> Result<()> {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return Ok(());
+        }
+        let path = self.db_path.clone();
+        let compaction =
+            self.run_exclusive("Compacting", |db| database::compact(db, &path))??;
+        self.status_message = Some(format!("Compacted: {}", compaction));
+        Ok(())
+    }
+
+    /// Lists the persistent savepoints again. Listing takes a write
+    /// transaction, so a read-only database lists none.
+    fn reload_savepoints(&mut self) {
+        if self.read_only {
+            return;
+        }
+        match database::list_savepoints(&self.db) {
+            Ok(ids) => self.persistent_savepoints = ids,
+            Err(e) => {
+                self.show_error(format!("Listing savepoints failed: {}", e))
+            }
+        }
+        let last = self.savepoint_rows().len().saturating_sub(1);
+        self.savepoint_selected = self.savepoint_selected.min(last);
+    }
+
+    /// Savepoints of the savepoints view, oldest first.
+    fn savepoint_rows(&self) -> Vec<SavepointId> {
+        let persistent = self
+            .persistent_savepoints
+            .iter()
+            .map(|&id| ((Some(id), 0), SavepointId::Persistent(id)));
+        // Ephemeral numbers start at 1, so they sort after the persistent
+        // savepoint they were taken after.
+        let ephemeral = self
+            .ephemeral_savepoints
+            .iter()
+            .map(|e| ((e.after, e.number), SavepointId::Ephemeral(e.number)));
+        let mut rows: Vec<_> = persistent.chain(ephemeral).collect();
+        rows.sort_by_key(|(order, _)| *order);
+        rows.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn savepoints_report(&self) -> (Vec<(String, String)>, Vec<Vec<String>>) {
+        if self.read_only {
+            let summary = vec![(
+                "Savepoints".to_string(),
+                "not listed, the database is opened read-only".to_string(),
+            )];
+            return (summary, Vec::new());
+        }
+        let summary = vec![
+            (
+                "Persistent".to_string(),
+                format!(
+                    "{}, stored in the database file",
+                    self.persistent_savepoints.len()
+                ),
+            ),
+            (
+                "Ephemeral".to_string(),
+                format!(
+                    "{}, dropped when you quit",
+                    self.ephemeral_savepoints.len()
+                ),
+            ),
+        ];
+        let rows = self
+            .savepoint_rows()
+            .into_iter()
+            .map(|id| {
+                let kept = match id {
+                    SavepointId::Persistent(_) => "in the file",
+                    SavepointId::Ephemeral(_) => "until you quit",
+                };
+                vec![id.to_string(), kept.to_string()]
+            })
+            .collect();
+        (summary, rows)
+    }
+
+    /// Takes a savepoint of the database as it is now, stored in the file
+    /// if `persistent`.
+    fn take_savepoint(&mut self, persistent: bool) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        let taken = if persistent {
+            database::create_persistent_savepoint(&self.db)
+                .map(SavepointId::Persistent)
+        } else {
+            let after = self.persistent_savepoints.last().copied();
+            let number =
+                self.ephemeral_savepoints.last().map_or(1, |e| e.number + 1);
+            database::create_ephemeral_savepoint(&self.db).map(|savepoint| {
+                self.ephemeral_savepoints.push(EphemeralSavepoint {
+                    number,
+                    after,
+                    savepoint,
+                });
+                SavepointId::Ephemeral(number)
+            })
+        };
+        match taken {
+            Ok(id) => {
+                self.reload_savepoints();
+                self.status_message = Some(format!("Took savepoint {}", id));
+            }
+            Err(e) => self.show_error(format!("Savepoint failed: {}", e)),
+        }
+    }
+
+    /// Asks to confirm restoring, or deleting, the selected savepoint.
+    fn start_savepoint_op(&mut self, delete: bool) {
+        if self.read_only {
+            self.status_message = Some("Database is opened read-only".into());
+            return;
+        }
+        let Some(&id) = self.savepoint_rows().get(self.savepoint_selected) else {
+            return;
+        };
+        let (message, op) = if delete {
+            (format!("Delete savepoint {}?", id), SavepointOp::Delete(id))
+        } else {
+            let message =
+                format!("Restore savepoint {}? Changes made since are lost.", id);
+            (message, SavepointOp::Restore(id))
+        };
+        self.mode = Mode::ConfirmSavepoint { message, op };
+    }
+
+    fn apply_savepoint_op(&mut self, op: SavepointOp) -> Result<()> {
+        info!("Applying {:?}", op);
+        let ephemeral = |number| {
+            self.ephemeral_savepoints
+                .iter()
+                .position(|e| e.number == number)
+        };
+        let result = match op {
+            SavepointOp::Delete(SavepointId::Persistent(id)) => {
+                database::delete_savepoint(&self.db, id)
+            }
+            SavepointOp::Delete(SavepointId::Ephemeral(number)) => {
+                if let Some(i) = ephemeral(number) {
+                    self.ephemeral_savepoints.remove(i);
+                }
+                Ok(())
+            }
+            SavepointOp::Restore(SavepointId::Persistent(id)) => {
+                database::restore_persistent_savepoint(&self.db, id).map(|()| {
+                    // Ephemeral savepoints taken while it existed are newer.
+                    self.ephemeral_savepoints
+                        .retain(|e| e.after.is_none_or(|after| after < id));
+                })
+            }
+            SavepointOp::Restore(SavepointId::Ephemeral(number)) => {
+                match ephemeral(number) {
+                    Some(i) => database::restore_savepoint(
+                        &self.db,
+                        &self.ephemeral_savepoints[i].savepoint,
+                    )
+                    .map(|()| self.ephemeral_savepoints.truncate(i + 1)),
+                    None => Ok(()),
+                }
+            }
+        };
+        if let Err(e) = result {
+            self.show_error(format!("Savepoint failed: {}", e));
+            return Ok(());
+        }
+        self.reload_savepoints();
+        self.status_message = Some(match op {
+            SavepointOp::Restore(id) => format!("Restored savepoint {}", id),
+            SavepointOp::Delete(id) => format!("Deleted savepoint {}", id),
+        });
+        if let SavepointOp::Restore(_) = op {
+            self.db_properties.file_size = fs::metadata(&self.db_path)?.len();
+            self.reload_table_names(None)?;
+            self.count_tables();
+            self.refresh_stats();
+            self.update_selected_table_content();
+        }
+        Ok(())
+    }
+
+    /// Gives `op` the database to itself, which redb requires for checks
+    /// and compaction, and runs it on another thread with a popup titled
+    /// `title` meanwhile. Background reads are stopped first, and everything
+    /// is read again afterwards.
+    fn run_exclusive<T: Send>(
+        &mut self,
+        title: &str,
+        op: impl FnOnce(&mut Database) -> T + Send,
+    ) -> Result<T> {
+        info!("{}", title);
+        self.load = None;
+        self.count_rx = None;
+        self.stats_rx = None;
+        let started = Instant::now();
+        let theme = self.theme;
+        let draw = |terminal: &mut Terminal<CrosstermBackend<io::Stdout>>| {
+            terminal
+                .draw(|frame| {
+                    let elapsed = started.elapsed();
+                    layout::render_busy_popup(frame, &theme, title, elapsed)
+                })
+                .map(drop)
+        };
+        // The threads stopped above let go of it once they notice.
+        while Arc::get_mut(&mut self.db).is_none() {
+            draw(&mut self.terminal)?;
+            thread::sleep(LOAD_TICK_RATE);
+        }
+        let db = Arc::get_mut(&mut self.db).expect("checked above");
+        let terminal = &mut self.terminal;
+        let result = thread::scope(|scope| -> Result<T> {
+            let handle = scope.spawn(|| op(db));
+            while !handle.is_finished() {
+                draw(terminal)?;
+                thread::sleep(LOAD_TICK_RATE);
+            }
+            Ok(handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+        })?;
+        self.db_properties.file_size = fs::metadata(&self.db_path)?.len();
+        self.terminal.clear()?;
+        self.count_tables();
+        self.refresh_stats();
+        self.update_selected_table_content();
+        Ok(result)
+    }
+
+    /// Counts the entries of every table on a background thread, so large
+    /// databases don't hold up startup. Counts arrive through `count_rx`.
+    fn count_tables(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let db = Arc::clone(&self.db);
+        let names = self.table_names.clone();
+        thread::spawn(move || {
+            for name in names {
+                match database::table_len(&db, &name) {
+                    Ok(count) => {
+                        if tx.send((name, count)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Failed to count entries of {}: {}", name, e)
+                    }
+                }
+            }
+        });
+        self.count_rx = Some(rx);
+    }
+
+    /// Recomputes the storage stats on a background thread. Outside of
+    /// read-only mode they need a write transaction, which must not hold up
+    /// drawing or fail it when another process is writing. A refresh still
+    /// in flight is superseded.
+    fn refresh_stats(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let db = Arc::clone(&self.db);
+        let read_only = self.read_only;
+        thread::spawn(move || {
+            let result = database::get_storage_summary(&db, read_only).and_then(
+                |summary| Ok((summary, database::get_table_summaries(&db)?)),
+            );
+            // The receiver is gone only if the TUI exited meanwhile.
+            let _ = tx.send(result);
+        });
+        self.stats_rx = Some(rx);
+        self.stats_refreshed_at = Instant::now();
+    }
+
+    /// Takes the stats computed by `refresh_stats`, once they are ready.
+    /// Returns whether anything arrived.
+    fn receive_stats(&mut self) -> bool {
+        let Some(rx) = &self.stats_rx else {
+            return false;
+        };
+        match rx.try_recv() {
+            Ok(Ok((stats, tables))) => {
+                self.stats = Some(stats);
+                self.table_summaries = tables;
+            }
+            Ok(Err(e)) => {
+                info!("Failed to compute stats: {}", e);
+                self.status_message = Some(format!("Stats failed: {}", e));
+            }
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.stats_rx = None;
+        true
+    }
+
+    /// Compares the database with the one at `diff_path` on a background
+    /// thread, as that reads both through. A comparison still in flight is
+    /// superseded.
+    fn refresh_diff(&mut self) {
+        let Some(path) = self.diff_path.clone() else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let db = Arc::clone(&self.db);
+        // redb opens a file only once, so it is compared with itself.
+        let same_file =
+            fs::canonicalize(&path).ok() == fs::canonicalize(&self.db_path).ok();
+        let read_only = self.read_only;
+        thread::spawn(move || {
+            let result =
+                with_other_database(&db, &path, same_file, read_only, |old| {
+                    compare_databases(old, &db)
+                });
+            // The receiver is gone only if the TUI exited meanwhile.
+            let _ = tx.send(result);
+        });
+        self.diff_rx = Some(rx);
+    }
+
+    /// Takes the comparison made by `refresh_diff`, once it is done.
+    /// Returns whether anything arrived.
+    fn receive_diff(&mut self) -> bool {
+        let Some(rx) = &self.diff_rx else {
+            return false;
+        };
+        match rx.try_recv() {
+            Ok(result) => {
+                if let Err(e) = &result {
+                    info!("Failed to compare the databases: {}", e);
+                }
+                self.diff = Some(result.map_err(|e| e.to_string()));
+                let last = self.diff_rows().len().saturating_sub(1);
+                self.diff_selected = self.diff_selected.min(last);
+            }
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.diff_rx = None;
+        true
+    }
+
+    /// Rows of the diff view: the tables added, removed or stored with
+    /// other types, and the entries that differ in the others.
+    fn diff_rows(&self) -> Vec<(&DiffTable, Option<&EntryDiff>)> {
+        let Some(Ok(tables)) = &self.diff else {
+            return Vec::new();
+        };
+        tables
+            .iter()
+            .flat_map(|table| {
+                let row = table.table.change().map(|_| (table, None));
+                row.into_iter()
+                    .chain(table.entries.iter().map(move |e| (table, Some(e))))
+            })
+            .collect()
+    }
+
+    /// Totals and rows of the diff view.
+    fn diff_report(&self) -> (Vec<(String, String)>, Vec<Vec<String>>) {
+        let Some(path) = &self.diff_path else {
+            let summary = vec![(
+                "Compared with".to_string(),
+                "nothing yet, open a database with :diff PATH".to_string(),
+            )];
+            return (summary, Vec::new());
+        };
+        let mut summary =
+            vec![("Compared with".to_string(), path.display().to_string())];
+        let tables = match &self.diff {
+            Some(Ok(tables)) => tables,
+            Some(Err(e)) => {
+                summary
+                    .push(("Differences".to_string(), format!("failed: {}", e)));
+                return (summary, Vec::new());
+            }
+            None => {
+                summary.push(("Differences".to_string(), "loading".to_string()));
+                return (summary, Vec::new());
+            }
+        };
+        let mut changed_tables = diff::Counts::default();
+        let mut entries = diff::Counts::default();
+        for table in tables {
+            if let Some(change) = table.table.change() {
+                changed_tables.add(change);
+            }
+            entries.added += table.counts.added;
+            entries.removed += table.counts.removed;
+            entries.changed += table.counts.changed;
+        }
+        summary.extend([
+            (
+                "Tables".to_string(),
+                format!(
+                    "{} added, {} removed, {} with other types",
+                    changed_tables.added,
+                    changed_tables.removed,
+                    changed_tables.changed
+                ),
+            ),
+            (
+                "Entries".to_string(),
+                format!(
+                    "{} added, {} removed, {} changed",
+                    layout::group_thousands(entries.added),
+                    layout::group_thousands(entries.removed),
+                    layout::group_thousands(entries.changed)
+                ),
+            ),
+        ]);
+        if tables
+            .iter()
+            .any(|t| t.counts.total() > t.entries.len() as u64)
+        {
+            summary.push((
+                "Shown".to_string(),
+                format!("the first {} of each table", MAX_DIFF_ENTRIES),
+            ));
+        }
+        let types = |types: &Option<TableTypes>, len: u64| {
+            types.as_ref().map_or(String::new(), |t| {
+                format!(
+                    "{} -> {}, {} entries",
+                    t.key.name,
+                    t.value.name,
+                    layout::group_thousands(len)
+                )
+            })
+        };
+        let rows = self
+            .diff_rows()
+            .into_iter()
+            .map(|(table, entry)| {
+                let TableDiff { name, .. } = &table.table;
+                let Some(entry) = entry else {
+                    let change = match table.table.change() {
+                        Some(diff::Change::Added) => "table added",
+                        Some(diff::Change::Removed) => "table removed",
+                        _ => "types changed",
+                    };
+                    return vec![
+                        name.clone(),
+                        change.to_string(),
+                        String::new(),
+                        types(&table.table.old, table.table.old_len),
+                        types(&table.table.new, table.table.new_len),
+                    ];
+                };
+                let Some(types) = &table.table.new else {
+                    return Vec::new();
+                };
+                let ctx = self.schemas.context(name);
+                let key_decoder = self
+                    .table_key_decoders
+                    .get(name)
+                    .copied()
+                    .unwrap_or(Decoder::Auto);
+                let value_decoder = self
+                    .table_decoders
+                    .get(name)
+                    .copied()
+                    .unwrap_or(Decoder::Auto);
+                let value = |bytes: &Option<Vec<u8>>| {
+                    bytes.as_ref().map_or(String::new(), |bytes| {
+                        let text =
+                            value_decoder.decode(&ctx, &types.value.name, bytes);
+                        clip(&text, DIFF_CELL_WIDTH)
+                    })
+                };
+                let key = key_decoder.decode(&ctx, &types.key.name, &entry.key);
+                vec![
+                    name.clone(),
+                    entry.change.name().to_string(),
+                    clip(&key, DIFF_CELL_WIDTH),
+                    value(&entry.old),
+                    value(&entry.new),
+                ]
+            })
+            .collect();
+        (summary, rows)
+    }
+
+    /// Shows the table of the selected row of the diff view side by side,
+    /// from the entry if the row is one. Tables one database lacks or
+    /// stores with other types are shown in the Data tab instead.
+    fn open_diff_row(&mut self) {
+        let rows = self.diff_rows();
+        let Some((table, key, aligned)) =
+            rows.get(self.diff_selected).map(|(t, e)| {
+                (
+                    t.table.name.clone(),
+                    e.map(|e| e.key.clone()),
+                    t.table.change().is_none(),
+                )
+            })
+        else {
+            return;
+        };
+        if aligned {
+            self.open_comparison(table, key);
+        } else {
+            self.show_in_data_tab(table, None);
+        }
+    }
+
+    /// Shows the selected key of the comparison in the Data tab.
+    fn show_compared_row(&mut self) {
+        let Some(c) = &self.comparison else {
+            return;
+        };
+        let table = c.table.clone();
+        let key = c.rows.get(c.selected).map(|row| row.key.clone());
+        self.show_in_data_tab(table, key);
+    }
+
+    /// Shows `table` of both databases side by side from `start`, or from
+    /// the start of the table.
+    fn open_comparison(&mut self, table: String, start: Option<Vec<u8>>) {
+        let from = start.clone().map_or(Bound::Unbounded, Bound::Included);
+        self.comparison = Some(Comparison {
+            table,
+            start,
+            types: None,
+            rows: Vec::new(),
+            complete: false,
+            selected: 0,
+        });
+        self.tab_scroll = 0;
+        self.read_compared_rows(from);
+    }
+
+    /// Reads the next `COMPARE_BATCH` rows of the comparison from `from`
+    /// on a background thread.
+    fn read_compared_rows(&mut self, from: Bound<Vec<u8>>) {
+        let (Some(c), Some(path)) = (&self.comparison, self.diff_path.clone())
+        else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let db = Arc::clone(&self.db);
+        let table = c.table.clone();
+        let same_file =
+            fs::canonicalize(&path).ok() == fs::canonicalize(&self.db_path).ok();
+        let read_only = self.read_only;
+        thread::spawn(move || {
+            let result =
+                with_other_database(&db, &path, same_file, read_only, |old| {
+                    compare_rows(old, &db, &table, from)
+                });
+            // The receiver is gone only if the TUI exited meanwhile.
+            let _ = tx.send(result);
+        });
+        self.comparison_rx = Some(rx);
+    }
+
+    /// Adds the rows read by `read_compared_rows` to the comparison, once
+    /// they are ready. Returns whether anything arrived.
+    fn receive_compared_rows(&mut self) -> bool {
+        let Some(rx) = &self.comparison_rx else {
+            return false;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.comparison_rx = None;
+                return true;
+            }
+        };
+        self.comparison_rx = None;
+        let Some(c) = &mut self.comparison else {
+            return true;
+        };
+        match result {
+            Ok((types, rows, complete)) => {
+                c.types = Some(types);
+                c.rows.extend(rows);
+                c.complete = complete;
+            }
+            Err(e) => {
+                let message = format!("Comparing {} failed: {}", c.table, e);
+                self.comparison = None;
+                self.show_error(message);
+            }
+        }
+        true
+    }
+
+    /// Moves the selection of the comparison by `rows`, reading more rows
+    /// when it gets within a page of the end.
+    fn move_compared_row(&mut self, rows: i32, page: i32) {
+        let Some(c) = &mut self.comparison else {
+            return;
+        };
+        let last = c.rows.len().saturating_sub(1);
+        let row = c.selected as i64 + i64::from(rows);
+        c.selected = row.clamp(0, last as i64) as usize;
+        if !c.complete
+            && self.comparison_rx.is_none()
+            && c.selected + page as usize >= c.rows.len()
+        {
+            let from = c
+                .rows
+                .last()
+                .map_or(Bound::Unbounded, |row| Bound::Excluded(row.key.clone()));
+            self.read_compared_rows(from);
+        }
+    }
+
+    /// Titles and rows of the comparison, with the selected row.
+    fn comparison_view(&self) -> Option<ComparisonView> {
+        let c = self.comparison.as_ref()?;
+        let loading = if self.comparison_rx.is_some() {
+            " loading"
+        } else {
+            ""
+        };
+        let title =
+            |path: &Path| format!("{}: {}{}", path.display(), c.table, loading);
+        let titles = [
+            title(self.diff_path.as_deref().unwrap_or(Path::new(""))),
+            title(&self.db_path),
+        ];
+        let Some(types) = &c.types else {
+            return Some((titles, Vec::new(), 0));
+        };
+        let ctx = self.schemas.context(&c.table);
+        let key_decoder = self
+            .table_key_decoders
+            .get(&c.table)
+            .copied()
+            .unwrap_or(Decoder::Auto);
+        let value_decoder = self
+            .table_decoders
+            .get(&c.table)
+            .copied()
+            .unwrap_or(Decoder::Auto);
+        let rows = c
+            .rows
+            .iter()
+            .map(|row| {
+                let change = row.change();
+                let sign = change.map_or(" ", diff::Change::sign);
+                let key = key_decoder.decode(&ctx, &types.key.name, &row.key);
+                let cell = |value: &Option<Vec<u8>>| {
+                    value.as_ref().map_or(String::new(), |value| {
+                        let value =
+                            value_decoder.decode(&ctx, &types.value.name, value);
+                        one_line(&format!("{} {}: {}", sign, key, value))
+                    })
+                };
+                ([cell(&row.old), cell(&row.new)], change.is_some())
+            })
+            .collect();
+        Some((titles, rows, c.selected))
+    }
+
+    /// Where the entries pane is, if a table is shown.
+    fn location(&self) -> Option<Location> {
+        Some(Location {
+            table: self.shown_table.clone()?,
+            start: self
+                .selected_table_content
+                .first()
+                .filter(|_| self.jumped)
+                .map(|(key, _)| key.clone()),
+            row: self.selected_index().unwrap_or_default(),
+            offset: self.entry_state.offset(),
+        })
+    }
+
+    /// Goes back to the place left last, or with `forward` to the place
+    /// going back came from. Places in tables dropped or renamed since are
+    /// passed over.
+    fn go_back(&mut self, forward: bool) {
+        let Some(here) = self.location() else {
+            return;
+        };
+        let names = &self.table_names;
+        self.history.retain(|place| names.contains(&place.table));
+        let place = if forward {
+            self.history.forward(here)
+        } else {
+            self.history.back(here)
+        };
+        let Some(place) = place else {
+            let way = if forward { "forward" } else { "back" };
+            self.status_message = Some(format!("Nowhere to go {}", way));
+            return;
+        };
+        debug!(
+            "Going {} to {:?}",
+            if forward { "forward" } else { "back" },
+            place
+        );
+        self.show_tab(Tab::Data);
+        if !self.visible_tables().contains(&place.table.as_str()) {
+            self.table_filter = None;
+        }
+        let row = self.visible_tables().iter().position(|t| *t == place.table);
+        self.list_state.select(row);
+        self.shown_table = Some(place.table);
+        self.list_entries(
+            place.start.map_or(Bound::Unbounded, Bound::Included),
+            AfterLoad::Restore {
+                row: place.row,
+                offset: place.offset,
+            },
+        );
+    }
+
+    /// Opens the key tree of the selected table, kept from the last time
+    /// it was browsed if that was the same table.
+    fn show_key_tree(&mut self) {
+        let Some(table) = self.selected_table_name() else {
+            return;
+        };
+        if self
+            .key_tree
+            .as_ref()
+            .is_some_and(|tree| tree.table == table)
+        {
+            self.mode = Mode::KeyTree;
+        } else {
+            self.build_key_tree(None);
+        }
+    }
+
+    /// Reads the top level of the key tree of the selected table, grouping
+    /// keys by `grouping`, which is kept for the table. Otherwise the
+    /// grouping kept or saved for it is used, or else the separator most
+    /// keys shown have.
+    fn build_key_tree(&mut self, grouping: Option<Grouping>) {
+        let (Some(table), Some(types)) =
+            (self.selected_table_name(), &self.selected_table_types)
+        else {
+            return;
+        };
+        if !database::is_byte_ordered(&types.key.name) {
+            self.status_message = Some(format!(
+                "{} keys aren't ordered by bytes; they can't be grouped",
+                types.key.name
+            ));
+            return;
+        }
+        let table = table.to_string();
+        if let Some(grouping) = grouping {
+            self.table_groupings.insert(table.clone(), grouping);
+        }
+        let grouping = match self.table_groupings.get(&table) {
+            Some(grouping) => *grouping,
+            None => Grouping::Separator(keytree::detect_separator(
+                self.selected_table_content
+                    .iter()
+                    .map(|(k, _)| k.as_slice()),
+            )),
+        };
+        match database::key_children(
+            &self.db,
+            &table,
+            &[],
+            |rest| grouping.group_len(rest),
+            MAX_TREE_CHILDREN,
+        ) {
+            Ok((children, more)) => {
+                self.key_tree =
+                    Some(KeyTree::new(table, grouping, children, more));
+                self.mode = Mode::KeyTree;
+            }
+            Err(e) => self.show_error(format!("Failed to read keys: {}", e)),
+        }
+    }
+
+    /// Moves through the key tree, opening and closing groups, or leaves
+    /// it for the keys of the selected group or key.
+    fn handle_key_tree_key(&mut self, key: KeyEvent) {
+        let Some(tree) = &mut self.key_tree else {
+            return;
+        };
+        let selected = tree.selected;
+        let kind = tree.selected_row().map(|row| row.kind);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q' | 't') => return,
+            KeyCode::Down | KeyCode::Char('j') => {
+                tree.selected = (selected + 1).min(tree.rows.len().max(1) - 1)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                tree.selected = selected.saturating_sub(1)
+            }
+            KeyCode::Right | KeyCode::Char('l' | ' ')
+                if kind == Some(RowKind::Group { open: false }) =>
+            {
+                self.open_tree_group(selected)
+            }
+            KeyCode::Left | KeyCode::Char('h' | ' ')
+                if kind == Some(RowKind::Group { open: true }) =>
+            {
+                tree.close(selected)
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(parent) = tree.parent(selected) {
+                    tree.selected = parent;
+                }
+            }
+            KeyCode::Enter => {
+                let Some(row) = tree.selected_row() else {
+                    return;
+                };
+                let (table, key) = (tree.table.clone(), row.key.clone());
+                match row.kind {
+                    RowKind::Group { .. } => {
+                        self.show_key_prefix(&key);
+                        return;
+                    }
+                    RowKind::Key => {
+                        // A key range may leave the key out.
+                        self.key_range = None;
+                        self.show_in_data_tab(table, Some(key));
+                        return;
+                    }
+                    RowKind::More => {}
+                }
+            }
+            KeyCode::Char(c)
+                if c.is_ascii() && keytree::SEPARATORS.contains(&(c as u8)) =>
+            {
+                self.build_key_tree(Some(Grouping::Separator(c as u8)))
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let bytes = c.to_digit(10).unwrap_or(1) as usize;
+                self.build_key_tree(Some(Grouping::Bytes(bytes)))
+            }
+            _ => {}
+        }
+        // An error reading keys is shown instead.
+        if matches!(self.mode, Mode::Normal) {
+            self.mode = Mode::KeyTree;
+        }
+    }
+
+    /// Reads the keys and groups under the group at `row` of the key tree
+    /// and shows them.
+    fn open_tree_group(&mut self, row: usize) {
+        let Some(tree) = &mut self.key_tree else {
+            return;
+        };
+        let Some(group) = tree.rows.get(row) else {
+            return;
+        };
+        match database::key_children(
+            &self.db,
+            &tree.table,
+            &group.key,
+            |rest| tree.grouping.group_len(rest),
+            MAX_TREE_CHILDREN,
+        ) {
+            Ok((children, more)) => tree.open(row, children, more),
+            Err(e) => self.show_error(format!("Failed to read keys: {}", e)),
+        }
+    }
+
+    /// Narrows the selected table down to the keys starting with `prefix`.
+    fn show_key_prefix(&mut self, prefix: &[u8]) {
+        let is_text = self
+            .selected_table_types
+            .as_ref()
+            .is_some_and(|t| matches!(t.key.name.as_str(), "&str" | "String"));
+        let text = match std::str::from_utf8(prefix) {
+            Ok(text) if is_text && !text.starts_with("0x") => text.to_string(),
+            _ => format!("0x{}", decoder::hex(prefix)),
+        };
+        if let Err(e) = self.set_key_range(&format!("{}*", text)) {
+            self.status_message = Some(e);
+        }
+        self.focus = Focus::Entries;
+    }
+
+    /// Shows `table` in the Data tab, at `key` if given or else the entry
+    /// after it, which is where a removed entry would be.
+    fn show_in_data_tab(&mut self, table: String, key: Option<Vec<u8>>) {
+        if !self.table_names.contains(&table) {
+            self.status_message = Some(format!("No table named '{}'", table));
+            return;
+        }
+        self.show_tab(Tab::Data);
+        if !self.visible_tables().contains(&table.as_str()) {
+            self.table_filter = None;
+        }
+        self.select_table(Some(&table));
+        if let Some(key) = key {
+            if let Err(e) = self.goto_key(&format!("0x{}", decoder::hex(&key))) {
+                self.status_message = Some(e);
+            }
+        }
+    }
+
+    /// Re-reads the database for watch mode. A snapshot is taken again
+    /// on a background thread first, as the file may be big; a file opened
+    /// directly is read again right away.
+    fn watch_tick(&mut self) {
+        self.watched_at = Instant::now();
+        if !self.snapshot {
+            if let Err(e) = self.reload() {
+                self.status_message = Some(format!("Watch failed: {}", e));
+            }
+            return;
+        }
+        self.take_snapshot();
+    }
+
+    /// Starts taking a new snapshot of the file on a background thread,
+    /// unless one is being taken already.
+    fn take_snapshot(&mut self) {
+        if self.snapshot_rx.is_some() {
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        let path = self.db_path.clone();
+        thread::spawn(move || {
+            let modified = modified_time(&path);
+            // A copy taken mid-commit needs a repair, which is quick.
+            let result =
+                database::open_snapshot(&path, |_| true).map(|db| (db, modified));
+            // The receiver is gone only if the TUI exited meanwhile.
+            let _ = tx.send(result);
+        });
+        self.snapshot_rx = Some(rx);
+    }
+
+    /// Checks whether another process wrote to the file since the snapshot
+    /// was taken. Returns whether that was just found out.
+    fn check_for_changes(&mut self) -> bool {
+        self.changes_checked_at = Instant::now();
+        if self.changed_on_disk || self.snapshot_rx.is_some() {
+            return false;
+        }
+        self.changed_on_disk =
+            modified_time(&self.db_path) != self.snapshot_modified;
+        if self.changed_on_disk {
+            info!("Database changed on disk");
+        }
+        self.changed_on_disk
+    }
+
+    /// Switches to the snapshot taken by `take_snapshot`, if it's ready.
+    /// Returns whether anything changed.
+    fn receive_snapshot(&mut self) -> bool {
+        let Some(rx) = &self.snapshot_rx else {
+            return false;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.snapshot_rx = None;
+                return false;
+            }
+        };
+        self.snapshot_rx = None;
+        let result = result.and_then(|(db, modified)| {
+            self.db = Arc::new(db);
+            self.snapshot_modified = modified;
+            self.changed_on_disk = false;
+            self.reload()
+        });
+        if let Err(e) = result {
+            info!("Failed to take a snapshot: {}", e);
+            self.status_message = Some(format!("Watch failed: {}", e));
+        }
+        true
+    }
+
+    /// Starts tail mode on the selected table, showing its last entries
+    /// with the last one selected, or stops it. Entries are listed in key
+    /// order while following.
+    fn toggle_follow(&mut self) -> Result<()> {
+        if self.follow {
+            self.follow = false;
+            self.status_message = Some("Stopped following".into());
+            return Ok(());
+        }
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return Ok(());
+        };
+        self.reverse = false;
+        let start = database::tail_start(
+            &self.db,
+            &table,
+            self.read_range(Bound::Unbounded),
+            TAIL_ENTRIES,
+        )?;
+        info!("Following {}", table);
+        self.selected_table_content.clear();
+        self.search = None;
+        self.jumped = start.is_some();
+        self.focus = Focus::Entries;
+        self.load_entries(
+            start.map_or(Bound::Unbounded, Bound::Included),
+            AfterLoad::Follow(true),
+        );
+        self.follow = true;
+        self.followed_at = Instant::now();
+        Ok(())
+    }
+
+    /// Whether the selection is on the last entry, or there is none.
+    fn at_last_entry(&self) -> bool {
+        let rows = self.visible_entries().len();
+        self.entry_state
+            .selected()
+            .is_none_or(|row| row + 1 >= rows)
+    }
+
+    /// Reads the entries added after the last one shown, moving the
+    /// selection along if it is on the last entry.
+    fn follow_tick(&mut self) {
+        self.followed_at = Instant::now();
+        let at_end = self.at_last_entry();
+        let last = self
+            .selected_table_content
+            .last()
+            .map(|(key, _)| key.clone());
+        self.load_entries(
+            last.map_or(Bound::Unbounded, Bound::Excluded),
+            AfterLoad::Follow(at_end),
+        );
+    }
+
+    /// Marks of the selected table, if it has any.
+    fn active_marks(&self) -> Option<&Marks> {
+        self.marks
+            .as_ref()
+            .filter(|m| Some(m.table.as_str()) == self.selected_table_name())
+    }
+
+    /// Marks the selected entry for export, or unmarks it, and moves on to
+    /// the next one. Marking in another table drops the marks made so far.
+    fn toggle_mark(&mut self) {
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        let Some((key, _)) = self.selected_entry() else {
+            return;
+        };
+        let key = key.clone();
+        if self.active_marks().is_none() {
+            self.marks = Some(Marks {
+                table,
+                keys: BTreeSet::new(),
+                start: None,
+            });
+        }
+        if let Some(marks) = &mut self.marks {
+            if !marks.keys.remove(&key) {
+                marks.keys.insert(key);
+            }
+            if marks.keys.is_empty() {
+                self.marks = None;
+            }
+        }
+        self.move_entry_selection(1);
+    }
+
+    /// Marks the selected entry as the start of a range or, once one is
+    /// started, every entry in view from there to the selected one.
+    fn mark_range(&mut self) {
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        let rows: Vec<usize> =
+            self.visible_entries().iter().map(|(i, ..)| *i).collect();
+        let Some(row) = self.entry_state.selected().filter(|&r| r < rows.len())
+        else {
+            return;
+        };
+        let key_at =
+            |row: usize| self.selected_table_content[rows[row]].0.clone();
+        let start = self
+            .active_marks()
+            .and_then(|marks| marks.start.as_ref())
+            .and_then(|start| (0..rows.len()).position(|r| key_at(r) == *start));
+        let keys: Vec<Vec<u8>> = match start {
+            Some(start) => {
+                (start.min(row)..=start.max(row)).map(key_at).collect()
+            }
+            None => vec![key_at(row)],
+        };
+        if self.active_marks().is_none() {
+            self.marks = Some(Marks {
+                table,
+                keys: BTreeSet::new(),
+                start: None,
+            });
+        }
+        let Some(marks) = &mut self.marks else {
+            return;
+        };
+        marks.start = match start {
+            Some(_) => None,
+            None => keys.first().cloned(),
+        };
+        let count = keys.len();
+        marks.keys.extend(keys);
+        self.status_message = Some(match start {
+            Some(_) => format!("Marked {} entries", count),
+            None => "Marking a range; V again at its other end".into(),
+        });
+    }
+
+    /// Drops the marks of entries `change` removed.
+    fn unmark_removed(&mut self, change: &Change) {
+        let Some(marks) = self.marks.as_mut().filter(|m| m.table == change.table)
+        else {
+            return;
+        };
+        for entry in change.entries.iter().filter(|e| e.after.is_none()) {
+            marks.keys.remove(&entry.key);
+        }
+        if marks.keys.is_empty() {
+            self.marks = None;
+        }
+    }
+
+    /// Pins the selected entry's key, or unpins the pinned one when it is
+    /// selected or nothing is.
+    fn toggle_pin(&mut self) {
+        let selected = self
+            .selected_table_name()
+            .zip(self.selected_entry())
+            .map(|(table, (key, _))| (table.to_string(), key.clone()));
+        let same = matches!(
+            (&self.pin, &selected),
+            (Some(pin), Some((table, key))) if pin.table == *table && pin.key == *key
+        );
+        if same || selected.is_none() {
+            if self.pin.take().is_some() {
+                self.status_message = Some("Unpinned".into());
+            }
+            return;
+        }
+        let Some((table, key)) = selected else {
+            return;
+        };
+        let label = self.display_key(&key);
+        info!("Pinned {} in {}", label, table);
+        self.status_message = Some(format!("Pinned {}", label));
+        self.pin = Some(Pin {
+            table,
+            key,
+            label,
+            value: Err(String::new()),
+            read_at: Instant::now(),
+            changed_at: Instant::now(),
+        });
+        self.read_pin();
+    }
+
+    /// Bookmarks the selected key, or removes its bookmark.
+    fn toggle_bookmark(&mut self) {
+        let Some((table, key)) = self
+            .selected_table_name()
+            .zip(self.selected_entry())
+            .map(|(table, (key, _))| (table.to_string(), key.clone()))
+        else {
+            return;
+        };
+        let label = self.display_key(&key);
+        match self
+            .bookmarks
+            .iter()
+            .position(|b| b.table == table && b.key == key)
+        {
+            Some(i) => {
+                self.bookmarks.remove(i);
+                self.status_message = Some(format!("Unbookmarked {}", label));
+            }
+            None => {
+                self.status_message = Some(format!("Bookmarked {}", label));
+                self.bookmarks.push(Bookmark { table, key, label });
+            }
+        }
+        self.save_bookmarks();
+    }
+
+    /// Writes the bookmarks to the state file right away, rather than when
+    /// the database is closed.
+    fn save_bookmarks(&mut self) {
+        let db_path = self.db_path.clone();
+        let bookmarks = self.bookmarks.clone();
+        self.update_state(|state| state.set_bookmarks(&db_path, bookmarks));
+    }
+
+    /// Reads the pinned key again. Returns whether it needs a redraw, which
+    /// is always, as it shows how long ago its value changed.
+    fn read_pin(&mut self) -> bool {
+        let Some(pin) = &mut self.pin else {
+            return false;
+        };
+        pin.read_at = Instant::now();
+        let value = database::get_raw_value(&self.db, &pin.table, &pin.key)
+            .map_err(|e| e.to_string());
+        let changed = match (&value, &pin.value) {
+            (Ok((_, new)), Ok((_, old))) => new != old,
+            _ => true,
+        };
+        if changed {
+            pin.changed_at = Instant::now();
+        }
+        pin.value = value;
+        true
+    }
+
+    /// Title, value and note of the pin, as shown above the panes.
+    fn pin_view(&self) -> Option<(String, String, String)> {
+        let pin = self.pin.as_ref()?;
+        let title = format!("Pinned {}: {}", pin.table, pin.label);
+        let value = match &pin.value {
+            Ok((types, Some(value))) => {
+                let decoder = self
+                    .table_decoders
+                    .get(&pin.table)
+                    .copied()
+                    .unwrap_or(Decoder::Auto);
+                let ctx = self.schemas.context(&pin.table);
+                let text = decoder.decode(&ctx, &types.value.name, value);
+                // Values spanning lines, like JSON, go on one.
+                text.lines().map(str::trim).collect::<Vec<_>>().join(" ")
+            }
+            Ok((_, None)) => "(key not found)".to_string(),
+            Err(e) => format!("(read failed: {})", e),
+        };
+        let note =
+            format!("changed {} ago", layout::age(pin.changed_at.elapsed()));
+        Some((title, value, note))
+    }
+
+    /// Reads the tables, stats and entries of the selected table again,
+    /// keeping the selection where it was.
+    fn reload(&mut self) -> Result<()> {
+        self.db_properties.file_size = fs::metadata(&self.db_path)?.len();
+        let table = self.selected_table_name().map(str::to_string);
+        self.reload_table_names(table.as_deref())?;
+        self.count_tables();
+        self.refresh_stats();
+        self.key_tree = None;
+        if self.selected_table_name() != table.as_deref() {
+            self.update_selected_table_content();
+        } else if self.load.is_none() {
+            // A read still going, like a search, is left to finish.
+            self.reload_entries();
+        }
+        Ok(())
+    }
+
+    /// Checks for new log lines. Returns whether the log view needs a redraw.
+    fn receive_log(&mut self) -> bool {
+        let written = self.log.written();
+        let new = written - self.log_written;
+        self.log_written = written;
+        if new == 0 || self.tab != Tab::Logs {
+            return false;
+        }
+        // Keep the lines in view when scrolled up; at the end, follow the log.
+        if self.tab_scroll > 0 {
+            let scroll = u64::from(self.tab_scroll) + new;
+            self.tab_scroll = scroll.min(u64::from(u16::MAX)) as u16;
+        }
+        true
+    }
+
+    /// Takes the entry counts sent so far by the counting thread. Returns
+    /// whether any arrived.
+    fn receive_counts(&mut self) -> bool {
+        let Some(rx) = &self.count_rx else {
+            return false;
+        };
+        let mut received = false;
+        for (name, count) in rx.try_iter() {
+            self.table_counts.insert(name, count);
+            received = true;
+        }
+        received
+    }
+
+    /// Re-reads the table list after tables were created or removed,
+    /// selecting `select` if it exists.
+    fn reload_table_names(&mut self, select: Option<&str>) -> Result<()> {
+        self.table_names = database::get_table_names(&self.db)?;
+        self.db_properties.num_tables = self.table_names.len();
+        let visible = self.visible_tables();
+        let wanted =
+            select.and_then(|name| visible.iter().position(|t| *t == name));
+        let last = visible.len().checked_sub(1);
+        let selected = wanted.or(self.list_state.selected()).min(last);
+        self.list_state.select(selected);
+        Ok(())
+    }
+
+    /// Selects `table` in the table list, or the first listed table if it
+    /// isn't listed, reloading the entries when the selection changed.
+    fn select_table(&mut self, table: Option<&str>) {
+        let visible = self.visible_tables();
+        let row = table
+            .and_then(|name| visible.iter().position(|t| *t == name))
+            .or((!visible.is_empty()).then_some(0));
+        let changed = row.and_then(|i| visible.get(i)).copied() != table;
+        self.list_state.select(row);
+        if changed {
+            self.update_selected_table_content();
+        }
+    }
+
+    /// Names in the table list: all tables, or those matching the filter
+    /// from best to worst match.
+    fn visible_tables(&self) -> Vec<&str> {
+        let pattern = self.table_filter.as_ref().map_or("", |f| f.text());
+        if pattern.is_empty() {
+            return self.table_names.iter().map(String::as_str).collect();
+        }
+        let mut scored: Vec<(i64, &str)> = self
+            .table_names
+            .iter()
+            .filter_map(|name| {
+                search::fuzzy_score(pattern, name)
+                    .map(|score| (score, name.as_str()))
+            })
+            .collect();
+        // Stable, so equally good matches stay in name order.
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// Index into `selected_table_content` of the selected entry.
+    fn selected_index(&self) -> Option<usize> {
+        let row = self.entry_state.selected()?;
+        match self.search {
+            None => Some(row),
+            Some(_) => self.visible_entries().get(row).map(|(i, ..)| *i),
+        }
+    }
+
+    fn selected_entry(&self) -> Option<&RawEntry> {
+        self.selected_index()
+            .and_then(|i| self.selected_table_content.get(i))
+    }
+
+    /// A key as shown in the entries pane.
+    fn display_key(&self, key: &[u8]) -> String {
+        let Some(types) = &self.selected_table_types else {
+            return String::new();
+        };
+        let ctx = self
+            .schemas
+            .context(self.selected_table_name().unwrap_or_default());
+        self.key_decoder().decode(&ctx, &types.key.name, key)
+    }
+
+    /// Applies the decoders declared in the schema sidecar. Protobuf messages
+    /// given on the command line take precedence.
+    fn apply_registry(&mut self) {
+        for (table, schema) in &self.registry.tables {
+            if let Some(key) = schema.key {
+                self.table_key_decoders.insert(table.clone(), key);
+            }
+            if let Some(value) = schema.value {
+                self.table_decoders.insert(table.clone(), value);
+            }
+            if let Some(message) = &schema.message {
+                self.schemas
+                    .proto_messages
+                    .entry(table.clone())
+                    .or_insert_with(|| message.clone());
+            }
+            if let Some(grouping) = schema.group_by {
+                self.table_groupings.insert(table.clone(), grouping);
+            }
+        }
+    }
+
+    /// Writes the decoders and key tree groupings chosen in this session to
+    /// the schema sidecar, keeping the entries of tables that weren't
+    /// touched.
+    fn save_registry(&mut self) {
+        for (table, decoder) in &self.table_key_decoders {
+            let schema = self.registry.tables.entry(table.clone()).or_default();
+            schema.key = Some(*decoder);
+        }
+        for (table, decoder) in &self.table_decoders {
+            let schema = self.registry.tables.entry(table.clone()).or_default();
+            schema.value = Some(*decoder);
+        }
+        for (table, message) in &self.schemas.proto_messages {
+            let schema = self.registry.tables.entry(table.clone()).or_default();
+            schema.message = Some(message.clone());
+        }
+        for (table, grouping) in &self.table_groupings {
+            let schema = self.registry.tables.entry(table.clone()).or_default();
+            schema.group_by = Some(*grouping);
+        }
+        let message = match self.registry.save(&self.registry_path) {
+            Ok(()) => {
+                format!("Saved decoders to {}", self.registry_path.display())
+            }
+            Err(e) => format!("Failed to save decoders: {}", e),
+        };
+        info!("{}", message);
+        self.status_message = Some(message);
+    }
+
+    fn show_tab(&mut self, tab: Tab) {
+        match tab {
+            Tab::Stats => {
+                let stale = self.key_stats.as_ref().map(|scan| &scan.table);
+                if self.selected_table_name() != stale.map(String::as_str) {
+                    self.read_key_stats();
+                }
+            }
+            Tab::Savepoints => self.reload_savepoints(),
+            Tab::Diff => self.refresh_diff(),
+            _ => {}
+        }
+        if tab != self.tab {
+            self.tab = tab;
+            self.tab_scroll = 0;
+        }
+    }
+
+    /// Handles `action` in the stats, schema, log, savepoints or diff view,
+    /// where navigation scrolls the view and actions on the selected table
+    /// or entry don't apply. Returns whether the action is left to
+    /// `run_action`.
+    fn run_view_action(&mut self, action: Action) -> bool {
+        let page = self.view_area.height.saturating_sub(3).max(1) as i32;
+        if self.tab == Tab::Stats && action == Action::Reverse {
+            self.stats_sort = self.stats_sort.next();
+            return false;
+        }
+        if self.tab == Tab::Diff {
+            let rows = match action {
+                Action::Down => 1,
+                Action::Up => -1,
+                Action::PageDown => page,
+                Action::PageUp => -page,
+                Action::HalfPageDown => page / 2,
+                Action::HalfPageUp => -page / 2,
+                Action::First => i32::MIN / 2,
+                Action::Last => i32::MAX / 2,
+                Action::ShowEntry if self.comparison.is_some() => {
+                    self.show_compared_row();
+                    return false;
+                }
+                Action::ShowEntry => {
+                    self.open_diff_row();
+                    return false;
+                }
+                Action::Cancel if self.comparison.is_some() => {
+                    self.comparison = None;
+                    self.comparison_rx = None;
+                    self.tab_scroll = 0;
+                    return false;
+                }
+                Action::RefreshStats => {
+                    self.refresh_diff();
+                    if let Some(c) = &self.comparison {
+                        self.open_comparison(c.table.clone(), c.start.clone());
+                    }
+                    return false;
+                }
+                _ => 0,
+            };
+            if rows != 0 {
+                match &self.comparison {
+                    // The rows start at a key; the start of the table is
+                    // read again.
+                    Some(c) if action == Action::First && c.start.is_some() => {
+                        self.open_comparison(c.table.clone(), None);
+                    }
+                    Some(_) => self.move_compared_row(rows, page),
+                    None => {
+                        let last = self.diff_rows().len().saturating_sub(1);
+                        let row = self.diff_selected as i64 + i64::from(rows);
+                        self.diff_selected = row.clamp(0, last as i64) as usize;
+                    }
+                }
+                return false;
+            }
+        }
+        if self.tab == Tab::Savepoints {
+            let rows = match action {
+                Action::Down => 1,
+                Action::Up => -1,
+                Action::PageDown => page,
+                Action::PageUp => -page,
+                Action::HalfPageDown => page / 2,
+                Action::HalfPageUp => -page / 2,
+                Action::First => i32::MIN / 2,
+                Action::Last => i32::MAX / 2,
+                Action::ShowEntry | Action::Delete => {
+                    self.start_savepoint_op(action == Action::Delete);
+                    return false;
+                }
+                _ => 0,
+            };
+            if rows != 0 {
+                let last = self.savepoint_rows().len().saturating_sub(1);
+                let row = self.savepoint_selected as i64 + i64::from(rows);
+                self.savepoint_selected = row.clamp(0, last as i64) as usize;
+                return false;
+            }
+        }
+        // The log view is scrolled up from its end.
+        let down = if self.tab == Tab::Logs { -1 } else { 1 };
+        let rows = match action {
+            Action::Down => down,
+            Action::Up => -down,
+            Action::PageDown => down * page,
+            Action::PageUp => -down * page,
+            Action::HalfPageDown => down * page / 2,
+            Action::HalfPageUp => -down * page / 2,
+            // The renderers keep the scroll short of the end.
+            Action::First => -down * i32::from(u16::MAX),
+            Action::Last => down * i32::from(u16::MAX),
+            Action::DataTab
+            | Action::StatsTab
+            | Action::SchemaTab
+            | Action::LogsTab
+            | Action::SavepointsTab
+            | Action::DiffTab
+            | Action::NextDatabase
+            | Action::PreviousDatabase
+            | Action::Bookmarks
+            | Action::SearchAll
+            | Action::Back
+            | Action::Forward
+            | Action::Savepoint
+            | Action::PersistentSavepoint
+            | Action::RefreshStats
+            | Action::CheckIntegrity
+            | Action::Compact
+            | Action::ToggleTheme
+            | Action::ToggleLayout
+            | Action::ToggleDurability
+            | Action::ToggleWatch
+            | Action::Zoom
+            | Action::CommandLine
+            | Action::Help
+            | Action::Quit => return true,
+            _ => return false,
+        };
+        self.scroll_view(rows);
+        false
+    }
+
+    fn scroll_view(&mut self, rows: i32) {
+        let scroll = i32::from(self.tab_scroll) + rows;
+        self.tab_scroll = scroll.clamp(0, i32::from(u16::MAX)) as u16;
+    }
+
+    /// Database-wide figures and per-table rows of the stats view.
+    fn stats_report(&self) -> (Vec<(String, String)>, Vec<Vec<String>>) {
+        let bytes = |n: u64| n.human_count_bytes().to_string();
+        let mut summary = vec![
+            ("File size".to_string(), bytes(self.db_properties.file_size)),
+            (
+                "Tables".to_string(),
+                self.db_properties.num_tables.to_string(),
+            ),
+        ];
+        let Some(stats) = &self.stats else {
+            summary.push(("Stats".to_string(), "loading".to_string()));
+            return (summary, Vec::new());
+        };
+        summary.push(("Tree height".to_string(), stats.tree_height.to_string()));
+        if let Some(page_size) = stats.page_size {
+            summary.push(("Page size".to_string(), bytes(page_size as u64)));
+        }
+        if let Some(pages) = stats.allocated_pages {
+            summary.push((
+                "Allocated pages".to_string(),
+                layout::group_thousands(pages),
+            ));
+        }
+        summary.extend([
+            (
+                "Leaf pages".to_string(),
+                layout::group_thousands(stats.leaf_pages),
+            ),
+            (
+                "Branch pages".to_string(),
+                layout::group_thousands(stats.branch_pages),
+            ),
+            ("Stored".to_string(), bytes(stats.stored_bytes)),
+            ("Metadata".to_string(), bytes(stats.metadata_bytes)),
+        ]);
+        let fragmented = match self.db_properties.file_size {
+            0 => bytes(stats.fragmented_bytes),
+            size => format!(
+                "{} ({:.0}% of the file)",
+                bytes(stats.fragmented_bytes),
+                stats.fragmented_bytes as f64 * 100.0 / size as f64
+            ),
+        };
+        summary.push(("Fragmented".to_string(), fragmented));
+
+        let mut tables: Vec<&TableSummary> =
+            self.table_summaries.iter().collect();
+        let sort = self.stats_sort;
+        tables.sort_by_key(|t| std::cmp::Reverse(sort.key(t)));
+        let percent = |part: u64, whole: u64| match whole {
+            0 => "-".to_string(),
+            whole => format!("{:.1}%", part as f64 * 100.0 / whole as f64),
+        };
+        let file_size = self.db_properties.file_size;
+        let mut rows: Vec<Vec<String>> = tables
+            .iter()
+            .map(|t| {
+                vec![
+                    t.name.clone(),
+                    layout::group_thousands(t.entries),
+                    t.stats.tree_height().to_string(),
+                    layout::group_thousands(t.stats.leaf_pages()),
+                    bytes(t.stats.stored_bytes()),
+                    bytes(t.stats.metadata_bytes()),
+                    bytes(t.stats.fragmented_bytes()),
+                    bytes(table_size(t)),
+                    percent(t.stats.fragmented_bytes(), table_size(t)),
+                    percent(table_size(t), file_size),
+                ]
+            })
+            .collect();
+        if tables.len() > 1 {
+            let total =
+                |f: fn(&TableSummary) -> u64| tables.iter().map(|t| f(t)).sum();
+            let size = total(table_size);
+            let fragmented = total(|t| t.stats.fragmented_bytes());
+            rows.push(vec![
+                "Total".to_string(),
+                layout::group_thousands(total(|t| t.entries)),
+                String::new(),
+                layout::group_thousands(total(|t| t.stats.leaf_pages())),
+                bytes(total(|t| t.stats.stored_bytes())),
+                bytes(total(|t| t.stats.metadata_bytes())),
+                bytes(fragmented),
+                bytes(size),
+                percent(fragmented, size),
+                percent(size, file_size),
+            ]);
+        }
+        summary.extend(self.key_stats_summary());
+        summary.push((
+            "Tables sorted by".to_string(),
+            format!(
+                "{}, most first (o: by {})",
+                sort.label(),
+                sort.next().label()
+            ),
+        ));
+        (summary, rows)
+    }
+
+    /// Title and parts of the bar over the stats view: the allocated pages
+    /// of the file, split into the bytes stored, taken by metadata and left
+    /// unused, then the free pages. Pages are only counted with a write
+    /// transaction; read-only, the unused bytes are those of the tables and
+    /// the rest of the file makes the last part.
+    fn allocation_view(&self) -> Option<(String, Vec<(String, u64)>)> {
+        let stats = self.stats.as_ref()?;
+        let file_size = self.db_properties.file_size;
+        let bytes = |n: u64| n.human_count_bytes().to_string();
+        let share = |n: u64| match file_size {
+            0 => bytes(n),
+            size => {
+                format!("{} {:.0}%", bytes(n), n as f64 * 100.0 / size as f64)
+            }
+        };
+        let (stored, metadata) = (stats.stored_bytes, stats.metadata_bytes);
+        let part = |label: &str, n: u64| (format!("{} {}", label, share(n)), n);
+        let mut parts = vec![part("stored", stored), part("metadata", metadata)];
+        let title = match (stats.allocated_pages, stats.page_size) {
+            (Some(allocated), Some(page_size)) => {
+                let allocated_bytes = allocated * page_size as u64;
+                let unused = allocated_bytes.saturating_sub(stored + metadata);
+                let free = file_size.saturating_sub(allocated_bytes);
+                parts.extend([part("unused", unused), part("free", free)]);
+                let pages = file_size / page_size as u64;
+                format!(
+                    "Pages: {} of {} allocated, {} free",
+                    layout::group_thousands(allocated),
+                    layout::group_thousands(pages),
+                    layout::group_thousands(pages.saturating_sub(allocated))
+                )
+            }
+            _ => {
+                let unused = stats.fragmented_bytes;
+                let rest = file_size.saturating_sub(stored + metadata + unused);
+                parts.extend([
+                    part("unused", unused),
+                    part("free or other", rest),
+                ]);
+                "Pages (allocation is only counted when writable)".to_string()
+            }
+        };
+        Some((title, parts))
+    }
+
+    /// Reads the keys of the selected table on a background thread, for the
+    /// first and last of them and how long they are. Progress and the
+    /// result come in through `receive_key_stats`.
+    fn read_key_stats(&mut self) {
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        let db = Arc::clone(&self.db);
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        let name = table.clone();
+        thread::spawn(move || {
+            let mut lengths = Sizes::default();
+            let mut bounds: Option<(Vec<u8>, Vec<u8>)> = None;
+            let result = database::scan_table_entries(
+                &db,
+                &name,
+                (Bound::Unbounded, Bound::Unbounded),
+                false,
+                usize::MAX,
+                LOAD_BATCH_SIZE,
+                |_, entries| {
+                    for (key, _) in &entries {
+                        lengths.add(key.len());
+                    }
+                    if let (Some((first, _)), Some((last, _))) =
+                        (entries.first(), entries.last())
+                    {
+                        let first = bounds.take().map_or(first.clone(), |b| b.0);
+                        bounds = Some((first, last.clone()));
+                    }
+                    let read = KeyStatsEvent::Read(lengths.count());
+                    !token.load(Ordering::Relaxed) && tx.send(read).is_ok()
+                },
+            );
+            if token.load(Ordering::Relaxed) {
+                return;
+            }
+            let found = result.map_err(|e| e.to_string()).map(|_| {
+                let (min, max) = bounds?;
+                Some(KeyStats {
+                    min,
+                    max,
+                    lengths: lengths.summary()?,
+                })
+            });
+            let _ = tx.send(KeyStatsEvent::Done(found));
+        });
+        self.key_stats = Some(KeyScan {
+            table,
+            rx,
+            read: 0,
+            found: None,
+            cancelled,
+        });
+    }
+
+    /// Takes the progress and result of `read_key_stats`. Returns whether
+    /// anything changed.
+    fn receive_key_stats(&mut self) -> bool {
+        let Some(scan) = &mut self.key_stats else {
+            return false;
+        };
+        let mut changed = false;
+        while let Ok(event) = scan.rx.try_recv() {
+            match event {
+                KeyStatsEvent::Read(read) => scan.read = read,
+                KeyStatsEvent::Done(Ok(found)) => scan.found = Some(found),
+                KeyStatsEvent::Done(Err(e)) => {
+                    let table = scan.table.clone();
+                    self.key_stats = None;
+                    info!("Failed to read the keys of {}: {}", table, e);
+                    self.status_message =
+                        Some(format!("Key stats failed: {}", e));
+                    return true;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Lines of the stats view about the keys of the selected table.
+    fn key_stats_summary(&self) -> Vec<(String, String)> {
+        let (Some(scan), Some(types)) =
+            (&self.key_stats, &self.selected_table_types)
+        else {
+            return Vec::new();
+        };
+        if self.selected_table_name() != Some(scan.table.as_str()) {
+            return Vec::new();
+        }
+        let mut summary =
+            vec![("Selected table".to_string(), scan.table.clone())];
+        let stats = match &scan.found {
+            None => {
+                let total = match self.table_counts.get(&scan.table) {
+                    Some(total) => {
+                        format!(" of {}", layout::group_thousands(*total))
+                    }
+                    None => String::new(),
+                };
+                let reading = format!(
+                    "reading {}{} entries",
+                    layout::group_thousands(scan.read),
+                    total
+                );
+                summary.push(("Keys".to_string(), reading));
+                return summary;
+            }
+            Some(None) => {
+                summary.push(("Keys".to_string(), "none".to_string()));
+                return summary;
+            }
+            Some(Some(stats)) => stats,
+        };
+        let ctx = self.schemas.context(&scan.table);
+        let key = |key: &[u8]| {
+            let text = self.key_decoder().decode(&ctx, &types.key.name, key);
+            clip(&text, KEY_STATS_WIDTH)
+        };
+        let lengths = &stats.lengths;
+        let bytes = |n: usize| n.human_count_bytes().to_string();
+        let buckets: Vec<String> = lengths
+            .buckets
+            .iter()
+            .map(|bucket| {
+                format!(
+                    "{}: {}",
+                    bucket.label(),
+                    layout::group_thousands(bucket.count)
+                )
+            })
+            .collect();
+        summary.extend([
+            ("Min key".to_string(), key(&stats.min)),
+            ("Max key".to_string(), key(&stats.max)),
+            (
+                "Key length".to_string(),
+                format!(
+                    "{} average, {} to {}, 95% up to {}",
+                    lengths.average().human_count_bytes(),
+                    bytes(lengths.min),
+                    bytes(lengths.max),
+                    bytes(lengths.p95)
+                ),
+            ),
+            ("Key lengths".to_string(), buckets.join(", ")),
+        ]);
+        summary
+    }
+
+    /// Rows of the schema view: every table with its stored types and the
+    /// decoders in use.
+    fn schema_rows(&self) -> Vec<Vec<String>> {
+        self.table_names
+            .iter()
+            .map(|name| {
+                let summary =
+                    self.table_summaries.iter().find(|t| &t.name == name);
+                let key_decoder = self
+                    .table_key_decoders
+                    .get(name)
+                    .copied()
+                    .unwrap_or(Decoder::Auto);
+                vec![
+                    name.clone(),
+                    summary.map_or("-".to_string(), |t| {
+                        layout::group_thousands(t.entries)
+                    }),
+                    summary.map_or("-".to_string(), |t| t.types.key.name.clone()),
+                    summary
+                        .map_or("-".to_string(), |t| t.types.value.name.clone()),
+                    key_decoder.label().to_string(),
+                    self.table_value_decoder_label(name),
+                ]
+            })
+            .collect()
+    }
+
+    /// Moves the split between the table list and the entries pane by
+    /// `step` percent, remembering it for the next session.
+    fn resize_tables(&mut self, step: i16) {
+        let split = self.split.saturating_add_signed(step);
+        self.split = split.clamp(*SPLIT_RANGE.start(), *SPLIT_RANGE.end());
+        self.status_message = Some(format!("Table list: {}%", self.split));
+        let split = self.split;
+        self.update_state(|state| state.split = Some(split));
+    }
+
+    /// Picks up where `session` left off: its decoders and listing order,
+    /// and unless `with_table` is false its table filter, table and key
+    /// range. Entries are read afterwards.
+    fn restore_session(&mut self, session: Session, with_table: bool) {
+        self.table_key_decoders.extend(session.key_decoders);
+        self.table_decoders.extend(session.value_decoders);
+        for (table, message) in session.proto_messages {
+            // Messages given on the command line take precedence.
+            self.schemas.proto_messages.entry(table).or_insert(message);
+        }
+        self.reverse = session.reverse;
+        self.schemas.decompress = session.decompress;
+        self.bookmarks = session.bookmarks;
+        if !with_table {
+            return;
+        }
+        self.table_filter = session.table_filter.map(Input::new);
+        let visible = self.visible_tables();
+        // Tables dropped since leave the first one selected.
+        let row = session
+            .table
+            .and_then(|table| visible.iter().position(|t| *t == table))
+            .or((!visible.is_empty()).then_some(0));
+        self.list_state.select(row);
+        let (Some(table), Some(text)) =
+            (self.selected_table_name(), session.key_range)
+        else {
+            return;
+        };
+        let types =
+            self.db
+                .begin_read()
+                .map_err(AppError::from)
+                .and_then(|txn| {
+                    database::open_raw_table(&txn, table).map(|(_, types)| types)
+                });
+        let range = types
+            .map_err(|e| e.to_string())
+            .and_then(|types| KeyRange::parse(&text, &types.key.name));
+        match range {
+            Ok(range) => {
+                self.key_range = Some(TableRange {
+                    table: table.to_string(),
+                    text,
+                    range,
+                })
+            }
+            Err(e) => debug!("Not restoring the key range {}: {}", text, e),
+        }
+    }
+
+    /// Keeps where this database was left for the next time it is opened,
+    /// see `restore_session`.
+    fn save_session(&mut self) {
+        let key_range = self.active_range().map(|range| range.text.clone());
+        let session = Session {
+            table: self.selected_table_name().map(str::to_string),
+            // A row after a jump is not where it is in the whole table.
+            entry: self
+                .selected_index()
+                .filter(|_| !self.jumped)
+                .unwrap_or_default(),
+            table_filter: self
+                .table_filter
+                .as_ref()
+                .map(|filter| filter.text().to_string())
+                .filter(|text| !text.is_empty()),
+            key_range,
+            reverse: self.reverse,
+            decompress: self.schemas.decompress,
+            key_decoders: self.table_key_decoders.clone().into_iter().collect(),
+            value_decoders: self.table_decoders.clone().into_iter().collect(),
+            proto_messages: self
+                .schemas
+                .proto_messages
+                .clone()
+                .into_iter()
+                .collect(),
+            bookmarks: self.bookmarks.clone(),
+        };
+        let db_path = self.db_path.clone();
+        self.update_state(|state| state.set_session(&db_path, session));
+    }
+
+    /// Changes the preferences kept for the next session with `f`.
+    fn update_state(&mut self, f: impl FnOnce(&mut State)) {
+        if let Some(path) = &self.state_path {
+            if let Err(e) = State::update(path, f) {
+                self.status_message =
+                    Some(format!("Failed to save {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    /// Cycles the protobuf message type used for the selected table,
+    /// including the schemaless mode.
+    fn next_proto_message(&mut self) {
+        let Some(pool) = &self.schemas.protobuf else {
+            return;
+        };
+        let Some(table) = self.selected_table_name().map(str::to_string) else {
+            return;
+        };
+        let names = pool.message_names();
+        let current = self.schemas.proto_messages.get(&table);
+        let next =
+            match current.and_then(|c| names.iter().position(|n| n == c)) {
+                Some(i) => names.get(i + 1),
+                None => names.first(),
+            }
+            .map(|n| n.to_string());
+        debug!("Protobuf message for {}: {:?}", table, next);
+        match next {
+            Some(message) => self.schemas.proto_messages.insert(table, message),
+            None => self.schemas.proto_messages.remove(&table),
+        };
+        self.set_value_decoder(Decoder::Protobuf);
+    }
+
+    fn value_decoder(&self) -> Decoder {
+        self.selected_table_name()
+            .and_then(|table| self.table_decoders.get(table))
+            .copied()
+            .unwrap_or(Decoder::Auto)
+    }
+
+    fn set_value_decoder(&mut self, decoder: Decoder) {
+        if let Some(table) = self.selected_table_name().map(str::to_string) {
+            debug!("Value decoder for {}: {}", table, decoder.label());
+            self.table_decoders.insert(table, decoder);
+        }
+    }
+
+    fn key_decoder(&self) -> Decoder {
+        self.selected_table_name()
+            .and_then(|table| self.table_key_decoders.get(table))
+            .copied()
+            .unwrap_or(Decoder::Auto)
+    }
+
+    fn set_key_decoder(&mut self, decoder: Decoder) {
+        if let Some(table) = self.selected_table_name().map(str::to_string) {
+            debug!("Key decoder for {}: {}", table, decoder.label());
+            self.table_key_decoders.insert(table, decoder);
+        }
+    }
+
+    fn decoder_label(&self) -> String {
+        let mut value = self.value_decoder_label();
+        if self.schemas.decompress {
+            value = format!("unpacked {}", value);
+        }
+        match self.key_decoder() {
+            Decoder::Auto => value,
+            key => format!("key:{} {}", key.label(), value),
+        }
+    }
+
+    /// What the help shows about the session: the selected table, its
+    /// decoders, and the filters in effect.
+    fn session_summary(&self) -> Vec<(String, String)> {
+        let mut rows = vec![
+            (
+                "Table".to_string(),
+                self.selected_table_name().unwrap_or("-").to_string(),
+            ),
+            (
+                "Key decoder".to_string(),
+                self.key_decoder().label().to_string(),
+            ),
+            ("Value decoder".to_string(), self.value_decoder_label()),
+        ];
+        if let Some(filter) = &self.table_filter {
+            rows.push(("Table filter".to_string(), filter.text().to_string()));
+        }
+        if let Some(query) = &self.search {
+            rows.push(("Search".to_string(), query.text().to_string()));
+        }
+        if let Some(range) = self.active_range() {
+            rows.push(("Key range".to_string(), range.text.clone()));
+        }
+        if self.schemas.decompress {
+            rows.push(("Values".to_string(), "unpacked".to_string()));
+        }
+        if self.reverse {
+            rows.push(("Order".to_string(), "reversed".to_string()));
+        }
+        if self.read_only {
+            rows.push(("Mode".to_string(), "read-only".to_string()));
+        }
+        rows
+    }
+
+    /// Label of the value decoder, with the protobuf message type if any.
+    fn value_decoder_label(&self) -> String {
+        self.selected_table_name()
+            .map_or(Decoder::Auto.label().to_string(), |table| {
+                self.table_value_decoder_label(table)
+            })
+    }
+
+    /// Label of the value decoder of `table`, with the protobuf message
+    /// type if any.
+    fn table_value_decoder_label(&self, table: &str) -> String {
+        let decoder = self
+            .table_decoders
+            .get(table)
+            .copied()
+            .unwrap_or(Decoder::Auto);
+        match (decoder, self.schemas.proto_messages.get(table)) {
+            (Decoder::Protobuf, Some(message)) => format!("protobuf:{}", message),
+            (decoder, _) => decoder.label().to_string(),
+        }
+    }
+
+    fn selected_table_name(&self) -> Option<&str> {
+        let row = self.list_state.selected()?;
+        self.visible_tables().get(row).copied()
+    }
+
+    /// Loaded entries shown in the entries pane as `(index, key, value)`,
+    /// decoded and narrowed down to the search matches.
+    fn visible_entries(&self) -> Vec<(usize, String, String)> {
+        let Some(types) = &self.selected_table_types else {
+            return Vec::new();
+        };
+        let ctx = self
+            .schemas
+            .context(self.selected_table_name().unwrap_or_default());
+        let key_decoder = self.key_decoder();
+        let value_decoder = self.value_decoder();
+        self.selected_table_content
+            .iter()
+            .enumerate()
+            .map(|(i, (k, v))| {
+                (
+                    i,
+                    key_decoder.decode(&ctx, &types.key.name, k),
+                    value_decoder.decode(&ctx, &types.value.name, v),
+                )
+            })
+            .filter(|(_, key, value)| match &self.search {
+                Some(query) => query.matches(key) || query.matches(value),
+                None => true,
+            })
+            .collect()
+    }
+}
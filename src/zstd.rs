@@ -0,0 +1,827 @@
+use crate::decoder::{DecodeError, DecodeResult};
+use crate::inflate::check_limit;
+
+/// Leading bytes of a zstd frame.
+const MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Largest accuracy logs of the literal length, match length and offset
+/// codes, and of Huffman weights.
+const MAX_LITERAL_LOG: u32 = 9;
+const MAX_MATCH_LOG: u32 = 9;
+const MAX_OFFSET_LOG: u32 = 8;
+const MAX_WEIGHT_LOG: u32 = 6;
+
+/// Longest Huffman code of literals.
+const MAX_HUFFMAN_BITS: u32 = 11;
+
+/// Distributions the codes use when a block picks the predefined mode.
+const LITERAL_DEFAULT: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3,
+    2, 1, 1, 1, 1, 1, -1, -1, -1, -1,
+];
+const MATCH_DEFAULT: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1,
+    -1, -1, -1,
+];
+const OFFSET_DEFAULT: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1,
+    -1, -1, -1, -1,
+];
+
+/// Base lengths and extra bits of literal length codes 16 to 35; lower
+/// codes are their own length.
+const LITERAL_BASE: [u32; 20] = [
+    16, 18, 20, 22, 24, 28, 32, 40, 48, 64, 128, 256, 512, 1024, 2048, 4096,
+    8192, 16384, 32768, 65536,
+];
+const LITERAL_EXTRA: [u32; 20] = [
+    1, 1, 1, 1, 2, 2, 3, 3, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+];
+
+/// Base lengths and extra bits of match length codes 32 to 52; lower codes
+/// are their length less 3.
+const MATCH_BASE: [u32; 21] = [
+    35, 37, 39, 41, 43, 47, 51, 59, 67, 83, 99, 131, 259, 515, 1027, 2051, 4099,
+    8195, 16387, 32771, 65539,
+];
+const MATCH_EXTRA: [u32; 21] = [
+    1, 1, 1, 1, 2, 2, 3, 3, 4, 4, 5, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+];
+
+/// Decompresses zstd frames (RFC 8878), one after another, skipping
+/// skippable frames. Checksums aren't verified and dictionaries aren't
+/// supported. Output past `limit` bytes is refused.
+pub fn decode(bytes: &[u8], limit: usize) -> DecodeResult<Vec<u8>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let mut out = Vec::new();
+    while reader.pos < bytes.len() {
+        let magic = reader.take(4)?;
+        if magic[1..] == [0x2a, 0x4d, 0x18] && magic[0] & 0xf0 == 0x50 {
+            let size = reader.u32()?;
+            reader.take(size as usize)?;
+        } else if magic == MAGIC {
+            Frame::default().decode(&mut reader, &mut out, limit)?;
+        } else {
+            return Err(DecodeError::new("not a zstd frame"));
+        }
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError::new("unexpected end of zstd data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> DecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> DecodeResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A little-endian number of `n` bytes.
+    fn uint(&mut self, n: usize) -> DecodeResult<u64> {
+        let bytes = self.take(n)?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | u64::from(*byte)))
+    }
+}
+
+/// Reads a bitstream the way zstd writes its entropy-coded parts: from the
+/// end back, past the zero bits and the one bit closing the last byte.
+struct BackBits<'a> {
+    bytes: &'a [u8],
+    left: isize,
+}
+
+impl<'a> BackBits<'a> {
+    fn new(bytes: &'a [u8]) -> DecodeResult<Self> {
+        let last = *bytes.last().filter(|byte| **byte != 0).ok_or_else(|| {
+            DecodeError::new("zstd bitstream without an end mark")
+        })?;
+        let padding = last.leading_zeros() as isize + 1;
+        Ok(Self {
+            bytes,
+            left: bytes.len() as isize * 8 - padding,
+        })
+    }
+
+    /// The `n` bits below `end`, with bits before the start reading as
+    /// zeros.
+    fn get(&self, end: isize, n: u32) -> u64 {
+        let mut value = 0;
+        for i in 0..n as isize {
+            let pos = end - n as isize + i;
+            if pos >= 0 && self.bytes[pos as usize / 8] >> (pos % 8) & 1 == 1 {
+                value |= 1 << i;
+            }
+        }
+        value
+    }
+
+    fn peek(&self, n: u32) -> u64 {
+        self.get(self.left, n)
+    }
+
+    fn bits(&mut self, n: u32) -> u64 {
+        let value = self.peek(n);
+        self.left -= n as isize;
+        value
+    }
+
+    /// Whether reads have gone past the start of the stream.
+    fn overflowed(&self) -> bool {
+        self.left < 0
+    }
+}
+
+/// Reads a bitstream forward, least significant bits first, as the table
+/// descriptions are written.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> DecodeResult<u32> {
+        let value = self.peek(n)?;
+        self.pos += n as usize;
+        Ok(value)
+    }
+
+    fn peek(&self, n: u32) -> DecodeResult<u32> {
+        let mut value = 0;
+        for i in 0..n as usize {
+            let pos = self.pos + i;
+            let byte = self
+                .bytes
+                .get(pos / 8)
+                .ok_or_else(|| DecodeError::new("truncated zstd table"))?;
+            value |= u32::from(byte >> (pos % 8) & 1) << i;
+        }
+        Ok(value)
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.pos.div_ceil(8)
+    }
+}
+
+/// A finite state entropy decoding table: each state's symbol, and how to
+/// get to the next state.
+#[derive(Clone, Default)]
+struct Fse {
+    log: u32,
+    states: Vec<FseState>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct FseState {
+    symbol: u8,
+    bits: u32,
+    base: u32,
+}
+
+impl Fse {
+    /// A table of the normalized symbol counts `counts` summing to
+    /// `1 << log`, -1 counts standing for "less than one".
+    fn new(counts: &[i16], log: u32) -> DecodeResult<Self> {
+        let size = 1usize << log;
+        let mut states = vec![FseState::default(); size];
+        let mut next = vec![0u32; counts.len()];
+        let mut high = size;
+        for (symbol, count) in counts.iter().enumerate() {
+            if *count == -1 {
+                high -= 1;
+                states[high].symbol = symbol as u8;
+                next[symbol] = 1;
+            } else {
+                next[symbol] = *count as u32;
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut pos = 0;
+        for (symbol, count) in counts.iter().enumerate() {
+            for _ in 0..(*count).max(0) {
+                states[pos].symbol = symbol as u8;
+                pos = (pos + step) & (size - 1);
+                while pos >= high {
+                    pos = (pos + step) & (size - 1);
+                }
+            }
+        }
+        if pos != 0 {
+            return Err(DecodeError::new("invalid zstd FSE table"));
+        }
+        for state in &mut states {
+            let symbol = state.symbol as usize;
+            let value = next[symbol];
+            next[symbol] += 1;
+            state.bits = log - (31 - value.leading_zeros());
+            state.base = (value << state.bits) - size as u32;
+        }
+        Ok(Self { log, states })
+    }
+
+    /// A table always decoding `symbol`, reading no bits.
+    fn rle(symbol: u8) -> Self {
+        Self {
+            log: 0,
+            states: vec![FseState {
+                symbol,
+                bits: 0,
+                base: 0,
+            }],
+        }
+    }
+
+    /// Reads a table description: its accuracy log, then each symbol's
+    /// count.
+    fn read(
+        bits: &mut Bits,
+        max_log: u32,
+        max_symbol: usize,
+    ) -> DecodeResult<Self> {
+        let log = bits.bits(4)? + 5;
+        if log > max_log {
+            return Err(DecodeError::new("zstd FSE accuracy log too large"));
+        }
+        let mut counts = Vec::new();
+        let mut remaining = (1i32 << log) + 1;
+        let mut threshold = 1i32 << log;
+        let mut width = log + 1;
+        while remaining > 1 {
+            if counts.len() > max_symbol {
+                return Err(DecodeError::new("too many zstd FSE symbols"));
+            }
+            let max = 2 * threshold - 1 - remaining;
+            let low = bits.peek(width - 1)? as i32;
+            let count = if low < max {
+                bits.bits(width - 1)?;
+                low
+            } else {
+                let value = bits.bits(width)? as i32;
+                if value >= threshold {
+                    value - max
+                } else {
+                    value
+                }
+            } - 1;
+            remaining -= count.abs();
+            counts.push(count as i16);
+            // A zero count is followed by how many more zeros follow it,
+            // in two-bit steps.
+            if count == 0 {
+                loop {
+                    let repeat = bits.bits(2)?;
+                    counts.extend(std::iter::repeat_n(0, repeat as usize));
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+            while remaining < threshold {
+                width -= 1;
+                threshold >>= 1;
+            }
+        }
+        if remaining != 1 || counts.len() > max_symbol + 1 {
+            return Err(DecodeError::new("invalid zstd FSE table"));
+        }
+        Self::new(&counts, log)
+    }
+
+    fn init(&self, bits: &mut BackBits) -> usize {
+        bits.bits(self.log) as usize
+    }
+
+    fn symbol(&self, state: usize) -> u8 {
+        self.states[state].symbol
+    }
+
+    fn update(&self, state: &mut usize, bits: &mut BackBits) {
+        let entry = self.states[*state];
+        *state = (entry.base + bits.bits(entry.bits) as u32) as usize;
+    }
+}
+
+/// A Huffman decoding table of literals, indexed by the next `bits` bits.
+#[derive(Default)]
+struct Huffman {
+    bits: u32,
+    entries: Vec<(u8, u32)>,
+}
+
+impl Huffman {
+    /// Reads a tree description: the weight of each symbol but the last,
+    /// whose weight is implied.
+    fn read(reader: &mut Reader) -> DecodeResult<Self> {
+        let header = reader.byte()?;
+        let mut weights = Vec::new();
+        if header < 128 {
+            let data = reader.take(header as usize)?;
+            let mut bits = Bits {
+                bytes: data,
+                pos: 0,
+            };
+            let table = Fse::read(&mut bits, MAX_WEIGHT_LOG, 255)?;
+            let mut stream = BackBits::new(&data[bits.bytes_read()..])?;
+            let mut states = [table.init(&mut stream), table.init(&mut stream)];
+            // The two states take turns until the stream runs out, when
+            // the other one gives the last weight.
+            'weights: loop {
+                for turn in [0, 1] {
+                    weights.push(table.symbol(states[turn]));
+                    table.update(&mut states[turn], &mut stream);
+                    if stream.overflowed() {
+                        weights.push(table.symbol(states[1 - turn]));
+                        break 'weights;
+                    }
+                    if weights.len() > 255 {
+                        return Err(DecodeError::new("too many zstd weights"));
+                    }
+                }
+            }
+        } else {
+            let count = header as usize - 127;
+            let data = reader.take(count.div_ceil(2))?;
+            for i in 0..count {
+                let byte = data[i / 2];
+                weights.push(if i % 2 == 0 { byte >> 4 } else { byte & 0x0f });
+            }
+        }
+        Self::new(weights)
+    }
+
+    fn new(mut weights: Vec<u8>) -> DecodeResult<Self> {
+        let invalid = || DecodeError::new("invalid zstd Huffman weights");
+        if weights
+            .iter()
+            .any(|weight| u32::from(*weight) > MAX_HUFFMAN_BITS)
+        {
+            return Err(invalid());
+        }
+        let total: u32 = weights
+            .iter()
+            .filter(|weight| **weight > 0)
+            .map(|weight| 1 << (weight - 1))
+            .sum();
+        if total == 0 {
+            return Err(invalid());
+        }
+        let bits = 32 - total.leading_zeros();
+        let left = (1 << bits) - total;
+        if bits > MAX_HUFFMAN_BITS || !left.is_power_of_two() {
+            return Err(invalid());
+        }
+        weights.push(left.trailing_zeros() as u8 + 1);
+        // Each symbol takes the entries of its code, lowest weights
+        // (longest codes) first.
+        let mut entries = Vec::with_capacity(1 << bits);
+        for weight in 1..=bits as u8 {
+            for (symbol, _) in
+                weights.iter().enumerate().filter(|(_, w)| **w == weight)
+            {
+                let len = bits + 1 - u32::from(weight);
+                let count = 1 << (weight - 1);
+                entries.extend(std::iter::repeat_n((symbol as u8, len), count));
+            }
+        }
+        Ok(Self { bits, entries })
+    }
+
+    /// Decodes `count` literals of a stream.
+    fn decode(
+        &self,
+        data: &[u8],
+        count: usize,
+        out: &mut Vec<u8>,
+    ) -> DecodeResult<()> {
+        let mut stream = BackBits::new(data)?;
+        for _ in 0..count {
+            let (symbol, len) = self.entries[stream.peek(self.bits) as usize];
+            stream.bits(len);
+            out.push(symbol);
+        }
+        if stream.left != 0 {
+            return Err(DecodeError::new("zstd literal stream size mismatch"));
+        }
+        Ok(())
+    }
+}
+
+/// What a frame's blocks carry over to the next: the literal code, the
+/// sequence tables and the last offsets.
+struct Frame {
+    huffman: Option<Huffman>,
+    literals: Option<Fse>,
+    offsets: Option<Fse>,
+    matches: Option<Fse>,
+    repeats: [usize; 3],
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            huffman: None,
+            literals: None,
+            offsets: None,
+            matches: None,
+            repeats: [1, 4, 8],
+        }
+    }
+}
+
+/// A sequence: literals to copy, then a match at an offset back.
+struct Sequence {
+    literals: usize,
+    offset: usize,
+    len: usize,
+}
+
+impl Frame {
+    fn decode(
+        &mut self,
+        reader: &mut Reader,
+        out: &mut Vec<u8>,
+        limit: usize,
+    ) -> DecodeResult<()> {
+        let descriptor = reader.byte()?;
+        let single_segment = descriptor & 0x20 != 0;
+        let checksum = descriptor & 0x04 != 0;
+        if !single_segment {
+            // Window descriptor; the whole output is kept anyway.
+            reader.byte()?;
+        }
+        let dictionary = reader.uint([0, 1, 2, 4][descriptor as usize & 3])?;
+        if dictionary != 0 {
+            return Err(DecodeError::new("zstd dictionaries aren't supported"));
+        }
+        let content_size = match descriptor >> 6 {
+            0 if single_segment => 1,
+            0 => 0,
+            flag => 1 << flag,
+        };
+        reader.take(content_size)?;
+        let start = out.len();
+        loop {
+            let header = reader.uint(3)? as usize;
+            let size = header >> 3;
+            match header >> 1 & 3 {
+                0 => {
+                    let data = reader.take(size)?;
+                    check_limit(out.len() + size, limit)?;
+                    out.extend_from_slice(data);
+                }
+                1 => {
+                    let byte = reader.byte()?;
+                    check_limit(out.len() + size, limit)?;
+                    out.extend(std::iter::repeat_n(byte, size));
+                }
+                2 => {
+                    let mut block = Reader {
+                        bytes: reader.take(size)?,
+                        pos: 0,
+                    };
+                    self.block(&mut block, out, start, limit)?;
+                }
+                _ => return Err(DecodeError::new("reserved zstd block type")),
+            }
+            if header & 1 == 1 {
+                break;
+            }
+        }
+        if checksum {
+            reader.take(4)?;
+        }
+        Ok(())
+    }
+
+    fn block(
+        &mut self,
+        reader: &mut Reader,
+        out: &mut Vec<u8>,
+        start: usize,
+        limit: usize,
+    ) -> DecodeResult<()> {
+        let literals = self.literals(reader)?;
+        let sequences = self.sequences(reader)?;
+        let mut literal = 0;
+        for sequence in sequences {
+            let end = literal + sequence.literals;
+            let copied = literals.get(literal..end).ok_or_else(|| {
+                DecodeError::new("zstd sequence past its literals")
+            })?;
+            check_limit(out.len() + copied.len() + sequence.len, limit)?;
+            out.extend_from_slice(copied);
+            literal = end;
+            if sequence.offset == 0 || sequence.offset > out.len() - start {
+                return Err(DecodeError::new("zstd offset out of range"));
+            }
+            // Copies may overlap what they produce.
+            let from = out.len() - sequence.offset;
+            for i in 0..sequence.len {
+                out.push(out[from + i]);
+            }
+        }
+        check_limit(out.len() + literals.len() - literal, limit)?;
+        out.extend_from_slice(&literals[literal..]);
+        Ok(())
+    }
+
+    fn literals(&mut self, reader: &mut Reader) -> DecodeResult<Vec<u8>> {
+        let first = reader.byte()?;
+        let kind = first & 3;
+        let format = first >> 2 & 3;
+        if kind < 2 {
+            let size = match format {
+                0 | 2 => first as usize >> 3,
+                1 => (first as usize >> 4) + ((reader.byte()? as usize) << 4),
+                _ => (first as usize >> 4) + ((reader.uint(2)? as usize) << 4),
+            };
+            return Ok(if kind == 0 {
+                reader.take(size)?.to_vec()
+            } else {
+                vec![reader.byte()?; size]
+            });
+        }
+        let (streams, width, rest) = match format {
+            0 => (1, 10, 2),
+            1 => (4, 10, 2),
+            2 => (4, 14, 3),
+            _ => (4, 18, 4),
+        };
+        let header = (reader.uint(rest)? << 4) | u64::from(first >> 4);
+        let mask = (1 << width) - 1;
+        let size = (header & mask) as usize;
+        let compressed = (header >> width & mask) as usize;
+        let mut data = Reader {
+            bytes: reader.take(compressed)?,
+            pos: 0,
+        };
+        if kind == 2 {
+            self.huffman = Some(Huffman::read(&mut data)?);
+        }
+        let huffman = self.huffman.as_ref().ok_or_else(|| {
+            DecodeError::new("zstd literals reuse a missing table")
+        })?;
+        let mut out = Vec::with_capacity(size);
+        if streams == 1 {
+            huffman.decode(&data.bytes[data.pos..], size, &mut out)?;
+        } else {
+            let jumps = [data.uint(2)?, data.uint(2)?, data.uint(2)?];
+            let each = size.div_ceil(4);
+            let last = size
+                .checked_sub(3 * each)
+                .ok_or_else(|| DecodeError::new("too few zstd literals"))?;
+            for jump in jumps {
+                let stream = data.take(jump as usize)?;
+                huffman.decode(stream, each, &mut out)?;
+            }
+            huffman.decode(&data.bytes[data.pos..], last, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    fn sequences(&mut self, reader: &mut Reader) -> DecodeResult<Vec<Sequence>> {
+        let first = reader.byte()? as usize;
+        let count = match first {
+            0 => return Ok(Vec::new()),
+            1..=127 => first,
+            128..=254 => ((first - 128) << 8) + reader.byte()? as usize,
+            _ => reader.uint(2)? as usize + 0x7f00,
+        };
+        let modes = reader.byte()?;
+        let literals = Self::table(
+            reader,
+            modes >> 6,
+            &mut self.literals,
+            (&LITERAL_DEFAULT, 6),
+            (MAX_LITERAL_LOG, 35),
+        )?;
+        let offsets = Self::table(
+            reader,
+            modes >> 4 & 3,
+            &mut self.offsets,
+            (&OFFSET_DEFAULT, 5),
+            (MAX_OFFSET_LOG, 31),
+        )?;
+        let matches = Self::table(
+            reader,
+            modes >> 2 & 3,
+            &mut self.matches,
+            (&MATCH_DEFAULT, 6),
+            (MAX_MATCH_LOG, 52),
+        )?;
+        let mut stream = BackBits::new(&reader.bytes[reader.pos..])?;
+        let mut literal_state = literals.init(&mut stream);
+        let mut offset_state = offsets.init(&mut stream);
+        let mut match_state = matches.init(&mut stream);
+        let mut sequences = Vec::with_capacity(count);
+        for i in 0..count {
+            let offset_code = u32::from(offsets.symbol(offset_state));
+            let literal_code = literals.symbol(literal_state) as usize;
+            let match_code = matches.symbol(match_state) as usize;
+            if offset_code > 31 {
+                return Err(DecodeError::new("invalid zstd offset code"));
+            }
+            let offset = (1u64 << offset_code) + stream.bits(offset_code);
+            let len = match match_code {
+                0..=31 => match_code + 3,
+                code => {
+                    let index = code - 32;
+                    MATCH_BASE[index] as usize
+                        + stream.bits(MATCH_EXTRA[index]) as usize
+                }
+            };
+            let literal_len = match literal_code {
+                0..=15 => literal_code,
+                code => {
+                    let index = code - 16;
+                    LITERAL_BASE[index] as usize
+                        + stream.bits(LITERAL_EXTRA[index]) as usize
+                }
+            };
+            let offset = self.offset(offset as usize, literal_len);
+            sequences.push(Sequence {
+                literals: literal_len,
+                offset,
+                len,
+            });
+            if i + 1 < count {
+                literals.update(&mut literal_state, &mut stream);
+                matches.update(&mut match_state, &mut stream);
+                offsets.update(&mut offset_state, &mut stream);
+            }
+        }
+        if stream.left != 0 {
+            return Err(DecodeError::new("zstd sequence stream size mismatch"));
+        }
+        Ok(sequences)
+    }
+
+    /// Reads or reuses a sequence code's table, by its mode in the block.
+    fn table(
+        reader: &mut Reader,
+        mode: u8,
+        previous: &mut Option<Fse>,
+        (default, default_log): (&[i16], u32),
+        (max_log, max_symbol): (u32, usize),
+    ) -> DecodeResult<Fse> {
+        let table = match mode {
+            0 => Fse::new(default, default_log)?,
+            1 => {
+                let symbol = reader.byte()?;
+                if symbol as usize > max_symbol {
+                    return Err(DecodeError::new("invalid zstd RLE symbol"));
+                }
+                Fse::rle(symbol)
+            }
+            2 => {
+                let mut bits = Bits {
+                    bytes: &reader.bytes[reader.pos..],
+                    pos: 0,
+                };
+                let table = Fse::read(&mut bits, max_log, max_symbol)?;
+                reader.take(bits.bytes_read())?;
+                table
+            }
+            _ => previous.clone().ok_or_else(|| {
+                DecodeError::new("zstd block reuses a missing table")
+            })?,
+        };
+        *previous = Some(table.clone());
+        Ok(table)
+    }
+
+    /// The offset an offset value stands for: a new offset past 3, else
+    /// one of the last three, shifted by one when there are no literals.
+    fn offset(&mut self, value: usize, literals: usize) -> usize {
+        if value > 3 {
+            let offset = value - 3;
+            self.repeats = [offset, self.repeats[0], self.repeats[1]];
+            return offset;
+        }
+        let index = value - 1 + usize::from(literals == 0);
+        let offset = match index {
+            3 => self.repeats[0].saturating_sub(1),
+            index => self.repeats[index],
+        };
+        match index {
+            0 => {}
+            1 => self.repeats.swap(0, 1),
+            _ => self.repeats = [offset, self.repeats[0], self.repeats[1]],
+        }
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::parse_hex;
+
+    const LIMIT: usize = 1 << 20;
+
+    /// `b"zstd!"` in a raw block, from the `zstd` tool.
+    const RAW: &str = "28b52ffd00682900007a73746421";
+
+    /// A hundred `a`s in an RLE block, written by hand: a single-segment
+    /// header with a one-byte content size, then the block.
+    const RLE: &str = "28b52ffd206423030061";
+
+    /// A hundred `a`s from the `zstd` tool: a compressed block with RLE
+    /// literals.
+    const RLE_LITERALS: &str = "28b52ffd006845000010616101003f012c";
+
+    /// `numbers()` from `zstd -19`: Huffman-coded literals and FSE-coded
+    /// sequences.
+    const COMPRESSED: &str = "\
+        28b52ffd00688d040016522309b0eb8448b60927c37920001f002000c26f20069b9a\
+        c49b8675d1c471774b9daddacc6821ab41da4524261b5f5c0d012193082f83be38bb\
+        c0bbe2f8316c8a74463c64b7d9706a75eacdbceb40e317349486bb3838f709651bc6\
+        56d385a086e17c645ceee6f66a6ad5b5996f8c6e0447ab8bd93476453b89d86479c5\
+        d903604f9c4cec7c21332a73ec9ad1d10a00";
+
+    /// `b"hello hello hello world\n"` four times, with a content checksum.
+    const CHECKSUMMED: &str = "\
+        28b52ffd2460bd00006068656c6c6f20776f726c640a03003b71706759c29522009126ed";
+
+    fn numbers() -> Vec<u8> {
+        (0..60u64)
+            .flat_map(|i| format!("{},", i * i * 7919 % 10007).into_bytes())
+            .collect()
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        parse_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn decodes_raw_rle_and_compressed_frames() {
+        assert_eq!(decode(&bytes(RAW), LIMIT).unwrap(), b"zstd!");
+        assert_eq!(decode(&bytes(RLE), LIMIT).unwrap(), [b'a'; 100]);
+        assert_eq!(decode(&bytes(RLE_LITERALS), LIMIT).unwrap(), [b'a'; 100]);
+        assert_eq!(decode(&bytes(COMPRESSED), LIMIT).unwrap(), numbers());
+        assert_eq!(
+            decode(&bytes(CHECKSUMMED), LIMIT).unwrap(),
+            b"hello hello hello world\n".repeat(4)
+        );
+    }
+
+    #[test]
+    fn skips_skippable_frames_between_frames() {
+        let skippable = [0x5a, 0x2a, 0x4d, 0x18, 3, 0, 0, 0, 1, 2, 3];
+        let frames = [bytes(RAW), skippable.to_vec(), bytes(RAW)].concat();
+        assert_eq!(decode(&frames, LIMIT).unwrap(), b"zstd!zstd!");
+    }
+
+    #[test]
+    fn refuses_bad_frames_and_output_past_the_limit() {
+        assert!(decode(&bytes(RLE), 99).is_err());
+        assert!(decode(&bytes(COMPRESSED), 100).is_err());
+        assert!(decode(b"not zstd", LIMIT).is_err());
+        // The reserved block type.
+        let mut reserved = bytes(RAW);
+        reserved[6] |= 0x06;
+        assert!(decode(&reserved, LIMIT).is_err());
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        for hex in [RAW, RLE, RLE_LITERALS, COMPRESSED, CHECKSUMMED] {
+            let frame = bytes(hex);
+            for len in 1..frame.len() {
+                assert!(decode(&frame[..len], LIMIT).is_err(), "{}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_compressed_blocks_dont_panic() {
+        // Flipping any one bit must not panic, whatever it decodes to.
+        let frame = bytes(COMPRESSED);
+        for bit in 0..frame.len() * 8 {
+            let mut corrupt = frame.clone();
+            corrupt[bit / 8] ^= 1 << (bit % 8);
+            let _ = decode(&corrupt, LIMIT);
+        }
+    }
+}